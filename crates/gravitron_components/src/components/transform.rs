@@ -1,7 +1,7 @@
 use gravitron_ecs::Component;
 use gravitron_hierarchy::propagation::PropagationUpdate;
 
-#[derive(Component)]
+#[derive(Component, Clone, Debug)]
 pub struct Transform {
   position: glam::Vec3,
   rotation: glam::Quat,
@@ -17,7 +17,58 @@ pub struct GlobalTransform {
   inverse_position_matrix: glam::Mat4,
 }
 
+/// The euler order used by [`Transform::set_rotation`] and [`Transform::rotation_euler`]
+const EULER_ORDER: glam::EulerRot = glam::EulerRot::ZXYEx;
+
 impl Transform {
+  #[inline]
+  pub fn new(position: glam::Vec3, rotation: glam::Quat, scaling: glam::Vec3) -> Self {
+    Self {
+      position,
+      rotation,
+      scaling,
+    }
+  }
+
+  /// Decomposes an affine matrix into scale, rotation and translation.
+  /// Shear or perspective components are lost.
+  pub fn from_matrix(matrix: glam::Mat4) -> Self {
+    let (scaling, rotation, position) = matrix.to_scale_rotation_translation();
+    Self {
+      position,
+      rotation,
+      scaling,
+    }
+  }
+
+  #[inline]
+  pub fn with_position(mut self, position: glam::Vec3) -> Self {
+    self.position = position;
+    self
+  }
+
+  #[inline]
+  pub fn with_rotation(mut self, rotation: glam::Quat) -> Self {
+    self.rotation = rotation;
+    self
+  }
+
+  #[inline]
+  pub fn with_scale(mut self, scaling: glam::Vec3) -> Self {
+    self.scaling = scaling;
+    self
+  }
+
+  #[inline]
+  pub fn looking_at(mut self, target: glam::Vec3, up: glam::Vec3) -> Self {
+    self.look_at(target, up);
+    self
+  }
+
+  pub fn compute_matrix(&self) -> glam::Mat4 {
+    glam::Mat4::from_scale_rotation_translation(self.scaling, self.rotation, self.position)
+  }
+
   pub fn position(&self) -> glam::Vec3 {
     self.position
   }
@@ -26,10 +77,32 @@ impl Transform {
     self.rotation
   }
 
+  /// Returns the rotation as euler angles in the same order [`Transform::set_rotation`] expects them
+  pub fn rotation_euler(&self) -> glam::Vec3 {
+    let (z, x, y) = self.rotation.to_euler(EULER_ORDER);
+    glam::Vec3::new(x, y, z)
+  }
+
   pub fn scale(&self) -> glam::Vec3 {
     self.scaling
   }
 
+  /// The local X axis, which is the direction cameras and lights point to
+  #[inline]
+  pub fn forward(&self) -> glam::Vec3 {
+    self.rotation * glam::Vec3::X
+  }
+
+  #[inline]
+  pub fn up(&self) -> glam::Vec3 {
+    self.rotation * glam::Vec3::Y
+  }
+
+  #[inline]
+  pub fn right(&self) -> glam::Vec3 {
+    self.rotation * glam::Vec3::Z
+  }
+
   pub fn set_position(&mut self, position: glam::Vec3) {
     self.position = position;
   }
@@ -39,7 +112,53 @@ impl Transform {
   }
 
   pub fn set_rotation(&mut self, x: f32, y: f32, z: f32) {
-    self.rotation = glam::Quat::from_euler(glam::EulerRot::ZXYEx, z, x, y);
+    self.rotation = glam::Quat::from_euler(EULER_ORDER, z, x, y);
+  }
+
+  pub fn set_rotation_quat(&mut self, rotation: glam::Quat) {
+    self.rotation = rotation.normalize();
+  }
+
+  #[inline]
+  pub fn translate(&mut self, offset: glam::Vec3) {
+    self.position += offset;
+  }
+
+  /// Moves the transform relative to its own orientation
+  #[inline]
+  pub fn translate_local(&mut self, offset: glam::Vec3) {
+    self.position += self.rotation * offset;
+  }
+
+  /// Applies `rotation` in world space on top of the current rotation
+  #[inline]
+  pub fn rotate(&mut self, rotation: glam::Quat) {
+    self.rotation = (rotation * self.rotation).normalize();
+  }
+
+  #[inline]
+  pub fn rotate_axis(&mut self, axis: glam::Vec3, angle: f32) {
+    self.rotate(glam::Quat::from_axis_angle(axis.normalize(), angle));
+  }
+
+  /// Rotates the transform around `point`, changing both its position and its rotation
+  pub fn rotate_around(&mut self, point: glam::Vec3, rotation: glam::Quat) {
+    self.position = point + rotation * (self.position - point);
+    self.rotate(rotation);
+  }
+
+  /// Rotates the transform so that [`Transform::forward`] points at `target` and
+  /// [`Transform::up`] is as close to `up` as possible.
+  /// Does nothing if `target` is the current position or `up` is parallel to the view direction.
+  pub fn look_at(&mut self, target: glam::Vec3, up: glam::Vec3) {
+    let forward = (target - self.position).normalize_or_zero();
+    let right = forward.cross(up).normalize_or_zero();
+    if forward == glam::Vec3::ZERO || right == glam::Vec3::ZERO {
+      return;
+    }
+    let up = right.cross(forward);
+
+    self.rotation = glam::Quat::from_mat3(&glam::Mat3::from_cols(forward, up, right));
   }
 }
 
@@ -108,3 +227,145 @@ impl PropagationUpdate for GlobalTransform {
     self.update_position_matrix();
   }
 }
+
+#[cfg(test)]
+mod test {
+  use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+
+  use super::Transform;
+
+  const EPSILON: f32 = 1e-5;
+
+  fn assert_vec3_eq(a: glam::Vec3, b: glam::Vec3) {
+    assert!(a.abs_diff_eq(b, EPSILON), "{a} != {b}");
+  }
+
+  #[test]
+  fn compute_matrix() {
+    let transform = Transform::default()
+      .with_position(glam::Vec3::new(1.0, 2.0, 3.0))
+      .with_rotation(glam::Quat::from_rotation_y(FRAC_PI_4))
+      .with_scale(glam::Vec3::new(2.0, 1.0, 0.5));
+
+    let expected = glam::Mat4::from_scale_rotation_translation(
+      glam::Vec3::new(2.0, 1.0, 0.5),
+      glam::Quat::from_rotation_y(FRAC_PI_4),
+      glam::Vec3::new(1.0, 2.0, 3.0),
+    );
+    assert!(transform.compute_matrix().abs_diff_eq(expected, EPSILON));
+  }
+
+  #[test]
+  fn from_matrix() {
+    let rotation = glam::Quat::from_euler(glam::EulerRot::XYZ, 0.3, -1.2, 2.0);
+    let matrix = glam::Mat4::from_scale_rotation_translation(
+      glam::Vec3::new(1.0, 3.0, 2.0),
+      rotation,
+      glam::Vec3::new(-4.0, 0.5, 8.0),
+    );
+
+    let transform = Transform::from_matrix(matrix);
+    assert_vec3_eq(transform.position(), glam::Vec3::new(-4.0, 0.5, 8.0));
+    assert_vec3_eq(transform.scale(), glam::Vec3::new(1.0, 3.0, 2.0));
+    assert!(transform.rotation().abs_diff_eq(rotation, EPSILON));
+    assert!(transform.compute_matrix().abs_diff_eq(matrix, EPSILON));
+  }
+
+  #[test]
+  fn euler_round_trip() {
+    let angles = [
+      glam::Vec3::new(0.0, 0.0, 0.0),
+      glam::Vec3::new(0.5, -1.0, 2.0),
+      glam::Vec3::new(-1.2, 3.0, -0.7),
+      glam::Vec3::new(0.0, FRAC_PI_4 * 3.0, -FRAC_PI_4),
+    ];
+
+    for angle in angles {
+      let mut transform = Transform::default();
+      transform.set_rotation(angle.x, angle.y, angle.z);
+      assert_vec3_eq(transform.rotation_euler(), angle);
+
+      let expected = glam::Quat::from_euler(glam::EulerRot::ZXYEx, angle.z, angle.x, angle.y);
+      assert!(transform.rotation().abs_diff_eq(expected, EPSILON));
+    }
+  }
+
+  #[test]
+  fn set_rotation_quat() {
+    let mut transform = Transform::default();
+    transform.set_rotation_quat(glam::Quat::from_xyzw(0.0, 2.0, 0.0, 2.0));
+
+    assert!(transform
+      .rotation()
+      .abs_diff_eq(glam::Quat::from_rotation_y(FRAC_PI_2), EPSILON));
+  }
+
+  #[test]
+  fn look_at() {
+    let eye = glam::Vec3::new(10.0, 10.0, 10.0);
+    let transform = Transform::default()
+      .with_position(eye)
+      .looking_at(glam::Vec3::ZERO, glam::Vec3::Y);
+
+    assert_vec3_eq(transform.forward(), (-eye).normalize());
+    assert_vec3_eq(
+      transform.right(),
+      transform.forward().cross(glam::Vec3::Y).normalize(),
+    );
+    assert!(transform.up().dot(glam::Vec3::Y) > 0.0);
+
+    let view = glam::Mat4::look_at_rh(eye, glam::Vec3::ZERO, glam::Vec3::Y);
+    assert_vec3_eq(
+      view.transform_vector3(transform.forward()),
+      glam::Vec3::NEG_Z,
+    );
+    assert_vec3_eq(view.transform_vector3(transform.up()), glam::Vec3::Y);
+  }
+
+  #[test]
+  fn look_at_degenerate() {
+    let mut transform = Transform::default().with_rotation(glam::Quat::from_rotation_z(1.0));
+    let rotation = transform.rotation();
+
+    transform.look_at(glam::Vec3::ZERO, glam::Vec3::Y);
+    assert_eq!(transform.rotation(), rotation);
+
+    transform.look_at(glam::Vec3::Y * 5.0, glam::Vec3::Y);
+    assert_eq!(transform.rotation(), rotation);
+  }
+
+  #[test]
+  fn rotate_axis() {
+    let mut transform = Transform::default().with_rotation(glam::Quat::from_rotation_x(0.4));
+    transform.rotate_axis(glam::Vec3::new(0.0, 2.0, 0.0), FRAC_PI_2);
+
+    let expected = glam::Quat::from_rotation_y(FRAC_PI_2) * glam::Quat::from_rotation_x(0.4);
+    assert!(transform.rotation().abs_diff_eq(expected, EPSILON));
+  }
+
+  #[test]
+  fn rotate_around() {
+    let mut transform = Transform::default().with_position(glam::Vec3::new(2.0, 0.0, 0.0));
+    transform.rotate_around(
+      glam::Vec3::new(1.0, 0.0, 0.0),
+      glam::Quat::from_rotation_y(FRAC_PI_2),
+    );
+
+    assert_vec3_eq(transform.position(), glam::Vec3::new(1.0, 0.0, -1.0));
+    assert!(transform
+      .rotation()
+      .abs_diff_eq(glam::Quat::from_rotation_y(FRAC_PI_2), EPSILON));
+  }
+
+  #[test]
+  fn translate_local() {
+    let mut transform = Transform::default()
+      .with_position(glam::Vec3::ONE)
+      .with_rotation(glam::Quat::from_rotation_z(FRAC_PI_2));
+    transform.translate_local(glam::Vec3::new(2.0, 0.0, 0.0));
+    assert_vec3_eq(transform.position(), glam::Vec3::new(1.0, 3.0, 1.0));
+
+    transform.translate(glam::Vec3::new(2.0, 0.0, 0.0));
+    assert_vec3_eq(transform.position(), glam::Vec3::new(3.0, 3.0, 1.0));
+  }
+}
@@ -2,7 +2,7 @@ use gravitron_macro_utils::Manifest;
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
 use quote::{format_ident, quote};
-use syn::{parse::Parse, parse_macro_input, token::Comma, Ident, ItemStruct, LitInt};
+use syn::{parse::Parse, parse_macro_input, token::Comma, DeriveInput, Ident, LitInt};
 
 pub(crate) fn ecs_path() -> syn::Path {
   Manifest::default().get_path("gravitron_ecs")
@@ -10,7 +10,7 @@ pub(crate) fn ecs_path() -> syn::Path {
 
 #[proc_macro_derive(Component)]
 pub fn component(input: TokenStream) -> TokenStream {
-  let input = parse_macro_input!(input as ItemStruct);
+  let input = parse_macro_input!(input as DeriveInput);
 
   let ecs_path = ecs_path();
 
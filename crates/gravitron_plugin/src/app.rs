@@ -79,30 +79,36 @@ impl App<Running> {
       let elapsed = last_frame.elapsed();
 
       if elapsed > frame_time {
-        self.set_resource(EngineInfo {
-          delta_time: elapsed.as_secs_f32(),
-        });
-
         last_frame = Instant::now();
 
-        self.main_scheduler.run(&mut self.world);
-
-        let cmds = self
-          .get_resource::<EngineCommands>()
-          .expect("Failed to get Engine Commands");
-        if cmds.is_shutdown() {
+        if !self.run_frame(elapsed.as_secs_f32()) {
           debug!("Exiting game loop");
           break;
         }
 
-        self.world.next_tick();
-
         #[cfg(feature = "debug")]
         trace!("Frame took {:?}", last_frame.elapsed());
       }
     }
   }
 
+  /// Runs the main systems once, returns false if a shutdown was requested
+  pub fn run_frame(&mut self, delta_time: f32) -> bool {
+    self.set_resource(EngineInfo { delta_time });
+
+    self.main_scheduler.run(&mut self.world);
+
+    let cmds = self
+      .get_resource::<EngineCommands>()
+      .expect("Failed to get Engine Commands");
+    if cmds.is_shutdown() {
+      return false;
+    }
+
+    self.world.next_tick();
+    true
+  }
+
   #[inline]
   pub fn run_cleanup(mut self) -> App<Cleanup> {
     self.cleanup_scheduler.run(&mut self.world);
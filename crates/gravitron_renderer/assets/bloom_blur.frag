@@ -0,0 +1,42 @@
+#version 450
+
+layout (location=0) out vec4 color_out;
+
+layout (location=0) in vec2 uv;
+
+layout(push_constant) uniform PushConstants {
+  // 0 blurs along x, 1 along y
+  uint direction;
+} pc;
+
+// matches PostProcessInfo on the cpu side
+layout (set=0, binding=0) uniform PostProcessInfo {
+  float exposure;
+  uint tonemapping;
+  float bloom_threshold;
+  float bloom_intensity;
+  float bloom_radius;
+  float gamma;
+  uint encode_srgb;
+} info;
+
+// the output of the previous pass
+layout (set=0, binding=1) uniform sampler2D source;
+// the hdr scene
+layout (set=0, binding=2) uniform sampler2D scene;
+
+const float WEIGHTS[5] = float[](0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+// separable gaussian blur, the taps are bloom_radius texels apart
+void main() {
+  vec2 texel = 1.0 / vec2(textureSize(source, 0));
+  vec2 offset = (pc.direction == 0 ? vec2(texel.x, 0.0) : vec2(0.0, texel.y)) * info.bloom_radius;
+
+  vec3 ret = texture(source, uv).rgb * WEIGHTS[0];
+  for (int i = 1; i < 5; i++) {
+    ret += texture(source, uv + offset * i).rgb * WEIGHTS[i];
+    ret += texture(source, uv - offset * i).rgb * WEIGHTS[i];
+  }
+
+  color_out = vec4(ret, 1.0);
+}
@@ -0,0 +1,29 @@
+#version 450
+
+layout (location=0) out vec4 color_out;
+
+layout (location=0) in vec2 uv;
+
+// matches PostProcessInfo on the cpu side
+layout (set=0, binding=0) uniform PostProcessInfo {
+  float exposure;
+  uint tonemapping;
+  float bloom_threshold;
+  float bloom_intensity;
+  float bloom_radius;
+  float gamma;
+  uint encode_srgb;
+} info;
+
+// the output of the previous pass
+layout (set=0, binding=1) uniform sampler2D source;
+// the hdr scene
+layout (set=0, binding=2) uniform sampler2D scene;
+
+// adds the blurred bright parts onto the scene
+void main() {
+  vec4 color = texture(scene, uv);
+  vec3 bloom = texture(source, uv).rgb;
+
+  color_out = vec4(color.rgb + bloom * info.bloom_intensity, color.a);
+}
@@ -0,0 +1,30 @@
+#version 450
+
+layout (location=0) out vec4 color_out;
+
+layout (location=0) in vec2 uv;
+
+// matches PostProcessInfo on the cpu side
+layout (set=0, binding=0) uniform PostProcessInfo {
+  float exposure;
+  uint tonemapping;
+  float bloom_threshold;
+  float bloom_intensity;
+  float bloom_radius;
+  float gamma;
+  uint encode_srgb;
+} info;
+
+// the output of the previous pass
+layout (set=0, binding=1) uniform sampler2D source;
+// the hdr scene
+layout (set=0, binding=2) uniform sampler2D scene;
+
+// keeps the part of the color above the threshold, scaled by its brightness
+void main() {
+  vec3 color = texture(source, uv).rgb;
+  float brightness = max(max(color.r, color.g), color.b);
+  float contribution = max(brightness - info.bloom_threshold, 0.0) / max(brightness, 1e-4);
+
+  color_out = vec4(color * contribution, 1.0);
+}
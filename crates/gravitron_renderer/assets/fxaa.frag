@@ -0,0 +1,141 @@
+#version 450
+
+layout (location=0) out vec4 color_out;
+
+layout (location=0) in vec2 uv;
+
+// matches PostProcessInfo on the cpu side
+layout (set=0, binding=0) uniform PostProcessInfo {
+  float exposure;
+  uint tonemapping;
+  float bloom_threshold;
+  float bloom_intensity;
+  float bloom_radius;
+  float gamma;
+  uint encode_srgb;
+} info;
+
+// the output of the previous pass
+layout (set=0, binding=1) uniform sampler2D source;
+// the hdr scene
+layout (set=0, binding=2) uniform sampler2D scene;
+
+const float EDGE_THRESHOLD_MIN = 0.0312;
+const float EDGE_THRESHOLD_MAX = 0.125;
+const float SUBPIXEL_QUALITY = 0.75;
+const int ITERATIONS = 12;
+const float QUALITY[ITERATIONS] = float[](1.0, 1.0, 1.0, 1.0, 1.0, 1.5, 2.0, 2.0, 2.0, 2.0, 4.0, 8.0);
+
+// perceived brightness, the colors are still linear here
+float luma(vec2 position) {
+  return sqrt(dot(texture(source, position).rgb, vec3(0.299, 0.587, 0.114)));
+}
+
+// fast approximate anti-aliasing, blends along the edges found by the contrast of the neighbours
+void main() {
+  vec4 color = texture(source, uv);
+  vec2 texel = 1.0 / vec2(textureSize(source, 0));
+
+  float center = sqrt(dot(color.rgb, vec3(0.299, 0.587, 0.114)));
+  float down = luma(uv + vec2(0.0, -texel.y));
+  float up = luma(uv + vec2(0.0, texel.y));
+  float left = luma(uv + vec2(-texel.x, 0.0));
+  float right = luma(uv + vec2(texel.x, 0.0));
+
+  float luma_min = min(center, min(min(down, up), min(left, right)));
+  float luma_max = max(center, max(max(down, up), max(left, right)));
+  float range = luma_max - luma_min;
+  if (range < max(EDGE_THRESHOLD_MIN, luma_max * EDGE_THRESHOLD_MAX)) {
+    color_out = color;
+    return;
+  }
+
+  float down_left = luma(uv - texel);
+  float up_right = luma(uv + texel);
+  float up_left = luma(uv + vec2(-texel.x, texel.y));
+  float down_right = luma(uv + vec2(texel.x, -texel.y));
+
+  float down_up = down + up;
+  float left_right = left + right;
+  float left_corners = down_left + up_left;
+  float down_corners = down_left + down_right;
+  float right_corners = down_right + up_right;
+  float up_corners = up_right + up_left;
+
+  float horizontal_edge = abs(-2.0 * left + left_corners) + abs(-2.0 * center + down_up) * 2.0 + abs(-2.0 * right + right_corners);
+  float vertical_edge = abs(-2.0 * up + up_corners) + abs(-2.0 * center + left_right) * 2.0 + abs(-2.0 * down + down_corners);
+  bool horizontal = horizontal_edge >= vertical_edge;
+
+  // the side of the pixel the edge is on
+  float luma_1 = horizontal ? down : left;
+  float luma_2 = horizontal ? up : right;
+  float gradient_1 = luma_1 - center;
+  float gradient_2 = luma_2 - center;
+  bool steepest_1 = abs(gradient_1) >= abs(gradient_2);
+  float gradient_scaled = 0.25 * max(abs(gradient_1), abs(gradient_2));
+
+  float step_length = horizontal ? texel.y : texel.x;
+  float local_average;
+  if (steepest_1) {
+    step_length = -step_length;
+    local_average = 0.5 * (luma_1 + center);
+  } else {
+    local_average = 0.5 * (luma_2 + center);
+  }
+
+  vec2 current_uv = uv;
+  if (horizontal) {
+    current_uv.y += step_length * 0.5;
+  } else {
+    current_uv.x += step_length * 0.5;
+  }
+
+  // walks along the edge in both directions until its end
+  vec2 offset = horizontal ? vec2(texel.x, 0.0) : vec2(0.0, texel.y);
+  vec2 uv_1 = current_uv - offset;
+  vec2 uv_2 = current_uv + offset;
+  float luma_end_1 = luma(uv_1) - local_average;
+  float luma_end_2 = luma(uv_2) - local_average;
+  bool reached_1 = abs(luma_end_1) >= gradient_scaled;
+  bool reached_2 = abs(luma_end_2) >= gradient_scaled;
+
+  for (int i = 1; i < ITERATIONS && !(reached_1 && reached_2); i++) {
+    if (!reached_1) {
+      uv_1 -= offset * QUALITY[i];
+      luma_end_1 = luma(uv_1) - local_average;
+      reached_1 = abs(luma_end_1) >= gradient_scaled;
+    }
+    if (!reached_2) {
+      uv_2 += offset * QUALITY[i];
+      luma_end_2 = luma(uv_2) - local_average;
+      reached_2 = abs(luma_end_2) >= gradient_scaled;
+    }
+  }
+
+  float distance_1 = horizontal ? uv.x - uv_1.x : uv.y - uv_1.y;
+  float distance_2 = horizontal ? uv_2.x - uv.x : uv_2.y - uv.y;
+  bool closer_1 = distance_1 < distance_2;
+  float distance_final = min(distance_1, distance_2);
+  float edge_length = distance_1 + distance_2;
+
+  // only blend if the end of the edge is on the same side as the pixel
+  bool center_smaller = center < local_average;
+  bool correct_variation = ((closer_1 ? luma_end_1 : luma_end_2) < 0.0) != center_smaller;
+  float edge_offset = correct_variation ? -distance_final / edge_length + 0.5 : 0.0;
+
+  // subpixel aliasing, e.g. thin lines
+  float average = (1.0 / 12.0) * (2.0 * (down_up + left_right) + left_corners + right_corners);
+  float subpixel = clamp(abs(average - center) / range, 0.0, 1.0);
+  subpixel = (-2.0 * subpixel + 3.0) * subpixel * subpixel;
+  float subpixel_offset = subpixel * subpixel * SUBPIXEL_QUALITY;
+
+  vec2 final_uv = uv;
+  float final_offset = max(edge_offset, subpixel_offset);
+  if (horizontal) {
+    final_uv.y += final_offset * step_length;
+  } else {
+    final_uv.x += final_offset * step_length;
+  }
+
+  color_out = vec4(texture(source, final_uv).rgb, color.a);
+}
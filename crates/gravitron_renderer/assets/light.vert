@@ -1,20 +1,32 @@
 #version 450
 
-layout(set=0, binding=0) uniform UniformBufferObject {
+struct Camera {
   mat4 view_matrix;
   mat4 projection_matrix;
-} ubo;
+  uint render_layers;
+};
+
+layout(set=0, binding=0) buffer readonly Cameras {
+  Camera cameras[];
+} cameras;
+
+layout(push_constant) uniform PushConstants {
+  uint camera;
+} pc;
 
 out gl_PerVertex {
 	vec4 gl_Position;
 };
 layout (location=0) out vec3 cam_pos;
+layout (location=1) out vec2 ndc;
 
 void main() {
+  Camera camera = cameras.cameras[pc.camera];
   gl_Position = vec4(vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2) * 2.0f - 1.0f, 0.0f, 1.0f);
+  ndc = gl_Position.xy;
 
   cam_pos =
-    - ubo.view_matrix[3][0] * vec3(ubo.view_matrix[0][0], ubo.view_matrix[1][0], ubo.view_matrix[2][0])
-    - ubo.view_matrix[3][1] * vec3(ubo.view_matrix[0][1], ubo.view_matrix[1][1], ubo.view_matrix[2][1])
-    - ubo.view_matrix[3][2] * vec3(ubo.view_matrix[0][2], ubo.view_matrix[1][2], ubo.view_matrix[2][2]);
+    - camera.view_matrix[3][0] * vec3(camera.view_matrix[0][0], camera.view_matrix[1][0], camera.view_matrix[2][0])
+    - camera.view_matrix[3][1] * vec3(camera.view_matrix[0][1], camera.view_matrix[1][1], camera.view_matrix[2][1])
+    - camera.view_matrix[3][2] * vec3(camera.view_matrix[0][2], camera.view_matrix[1][2], camera.view_matrix[2][2]);
 }
\ No newline at end of file
@@ -0,0 +1,272 @@
+// lighting shared by the light pass and the forward pass of transparent materials,
+// the including shader declares its own inputs and set 1
+
+struct DirectionalLight {
+  vec3 direction;
+  vec3 color;
+  float intensity;
+  vec3 ambient_color;
+  float ambient_intensity;
+};
+
+struct PointLight {
+  vec3 position;
+  vec3 color;
+  float intensity;
+  float range;
+};
+
+struct Camera {
+  mat4 view_matrix;
+  mat4 projection_matrix;
+  uint render_layers;
+};
+
+struct ClusterCamera {
+  float near;
+  float scale;
+};
+
+struct Cluster {
+  uint offset;
+  uint point_lights;
+  uint spot_lights;
+};
+
+struct SpotLight {
+  vec3 position;
+  vec3 direction;
+  vec3 color;
+  float intensity;
+  float range;
+  float angle;
+  uint shadow;
+};
+
+layout(push_constant) uniform PushConstants {
+  uint camera;
+} pc;
+
+layout (set=0, binding=0) buffer readonly Cameras {
+  Camera cameras[];
+} cameras;
+
+layout (set=0, binding=1) uniform LightInfo {
+  uint num_pls;
+  uint num_sls;
+  DirectionalLight dl;
+} light_info;
+
+layout (set=0, binding=2) buffer readonly PointLights {
+  PointLight pls[];
+} pls;
+
+layout (set=0, binding=3) buffer readonly SpotLights {
+  SpotLight sls[];
+} sls;
+
+layout (set=0, binding=5) buffer readonly ClusterCameras {
+  ClusterCamera cameras[];
+} cluster_cameras;
+
+layout (set=0, binding=6) buffer readonly Clusters {
+  Cluster clusters[];
+} clusters;
+
+// point light indices of a cluster followed by its spot light indices
+layout (set=0, binding=7) buffer readonly LightIndices {
+  uint indices[];
+} light_indices;
+
+layout (set=2, binding=0) uniform ShadowInfo {
+  uint num_cascades;
+  uint pcf_radius;
+  float texel_size;
+  float normal_bias;
+} shadow_info;
+
+layout (set=2, binding=1) buffer readonly ShadowMatrices {
+  mat4 matrices[];
+} shadow_matrices;
+
+layout (set=2, binding=2) uniform sampler2DArrayShadow shadow_map;
+
+// matches EnvironmentInfo on the cpu side
+layout (set=3, binding=0) uniform EnvironmentInfo {
+  float intensity;
+  float max_lod;
+  uint enabled;
+} environment;
+
+layout (set=3, binding=1) uniform samplerCube skybox;
+layout (set=3, binding=2) uniform samplerCube irradiance_map;
+layout (set=3, binding=3) uniform samplerCube prefiltered_map;
+layout (set=3, binding=4) uniform sampler2D brdf_lut;
+
+const float PI = 3.14159265359;
+// matches NO_SHADOW on the cpu side
+const uint NO_SHADOW = 0xFFFFFFFF;
+// matches the cluster grid on the cpu side
+const uint CLUSTER_X = 16;
+const uint CLUSTER_Y = 9;
+const uint CLUSTER_Z = 24;
+const uint CLUSTER_COUNT = CLUSTER_X * CLUSTER_Y * CLUSTER_Z;
+
+// the lights of the cluster containing the position, in the view of the current camera
+Cluster find_cluster(vec3 position) {
+  Camera camera = cameras.cameras[pc.camera];
+  ClusterCamera cluster_camera = cluster_cameras.cameras[pc.camera];
+
+  vec4 view_pos = camera.view_matrix * vec4(position, 1.0);
+  vec4 clip_pos = camera.projection_matrix * view_pos;
+  vec2 ndc = clip_pos.xy / clip_pos.w;
+
+  uvec2 tile = min(uvec2(clamp(ndc * 0.5 + 0.5, 0.0, 1.0) * vec2(CLUSTER_X, CLUSTER_Y)), uvec2(CLUSTER_X - 1, CLUSTER_Y - 1));
+  float depth = max(-view_pos.z, cluster_camera.near);
+  uint slice = min(uint(floor(log(depth / cluster_camera.near) * cluster_camera.scale)), CLUSTER_Z - 1);
+
+  return clusters.clusters[pc.camera * CLUSTER_COUNT + (slice * CLUSTER_Y + tile.y) * CLUSTER_X + tile.x];
+}
+
+// fraction of the light reaching the position, filtered over the neighbouring texels
+float sample_shadow(uint layer, vec3 position) {
+  vec4 light_pos = shadow_matrices.matrices[layer] * vec4(position, 1.0);
+  if (light_pos.w <= 0.0) {
+    return 1.0;
+  }
+  vec3 projected = light_pos.xyz / light_pos.w;
+  if (projected.z > 1.0) {
+    return 1.0;
+  }
+
+  vec2 shadow_uv = projected.xy * 0.5 + 0.5;
+  int radius = int(shadow_info.pcf_radius);
+  float lit = 0.0;
+  for (int x = -radius; x <= radius; x++) {
+    for (int y = -radius; y <= radius; y++) {
+      vec2 offset = vec2(x, y) * shadow_info.texel_size;
+      lit += texture(shadow_map, vec4(shadow_uv + offset, float(layer), projected.z));
+    }
+  }
+  return lit / float((2 * radius + 1) * (2 * radius + 1));
+}
+
+// the first cascade containing the position has the highest resolution
+float directional_shadow(vec3 position) {
+  for (uint i = 0; i < shadow_info.num_cascades; i++) {
+    vec3 projected = (shadow_matrices.matrices[i] * vec4(position, 1.0)).xyz;
+    if (all(lessThanEqual(abs(projected.xy), vec2(1.0))) && projected.z >= 0.0 && projected.z <= 1.0) {
+      return sample_shadow(i, position);
+    }
+  }
+  return 1.0;
+}
+
+float geometry(vec3 light_direction, vec3 cam_direction, float roughness, vec3 normal) {
+  float n_dot_l = abs(dot(normal, light_direction));
+  float n_dot_c = abs(dot(normal, cam_direction));
+  return 0.5 / max(0.01, mix(2 * n_dot_l * n_dot_c, n_dot_l + n_dot_c, roughness));
+}
+
+float distribution(vec3 half_vector, float roughness, vec3 normal) {
+  float n_dot_h = dot(half_vector, normal);
+  if(n_dot_h > 0) {
+    float r = roughness * roughness;
+    return r / (PI * pow(1 + n_dot_h * n_dot_h * (r - 1), 2));
+  } else {
+    return 0.0;
+  }
+}
+
+vec3 compute_light(vec3 light_color, vec3 light_direction, vec3 color, vec3 cam_direction, vec3 normal, float metallic, float roughness_in) {
+  float n_dot_d = max(dot(normal, light_direction), 0);
+
+  vec3 color_surface = light_color * n_dot_d;
+
+  float roughness = roughness_in * roughness_in;
+  vec3 f0 = mix(vec3(0.03), color, vec3(metallic));
+
+  vec3 reflected_color = (f0 + (1 - f0) * pow(1 - n_dot_d, 5)) * color_surface;
+  vec3 refracted_color = color_surface - reflected_color;
+  vec3 refracted_not_absorbed_color = refracted_color * (1 - metallic);
+
+  vec3 half_vec = normalize(0.5 * (cam_direction + light_direction));
+  float n_dot_h = max(dot(normal, half_vec), 0);
+  vec3 f = f0 + (1 - f0) * pow(1 - n_dot_h, 5);
+  vec3 relevant_reflection = reflected_color * f * geometry(light_direction, cam_direction, roughness, normal) * distribution(half_vec, roughness, normal);
+
+  return refracted_not_absorbed_color * color / PI + relevant_reflection;
+}
+
+// light from the surroundings, sampled from the skybox maps if there is one and a flat color otherwise
+vec3 ambient_light(vec3 flat_ambient, vec3 color, vec3 cam_direction, vec3 normal, float metallic, float roughness) {
+  if (environment.enabled == 0) {
+    return flat_ambient * color;
+  }
+
+  float n_dot_c = max(dot(normal, cam_direction), 0.0);
+  vec3 f0 = mix(vec3(0.03), color, vec3(metallic));
+  // rough surfaces reflect less of the surroundings at grazing angles
+  vec3 f = f0 + (max(vec3(1.0 - roughness), f0) - f0) * pow(1.0 - n_dot_c, 5.0);
+
+  vec3 diffuse = texture(irradiance_map, normal).rgb * color * (1.0 - f) * (1.0 - metallic);
+  vec3 reflected = textureLod(prefiltered_map, reflect(-cam_direction, normal), roughness * environment.max_lod).rgb;
+  vec2 brdf = texture(brdf_lut, vec2(n_dot_c, roughness)).rg;
+
+  return (diffuse + reflected * (f0 * brdf.x + brdf.y)) * environment.intensity;
+}
+
+// ambient, directional, point and spot light reaching the surface, without emission
+vec3 shade(vec3 world_pos, vec3 cam_pos, vec3 color, vec3 normal, float metallic, float roughness, float occlusion, bool receives_shadows) {
+  // moved along the normal, so surfaces do not shadow themselves
+  vec3 shadow_pos = world_pos + normal * shadow_info.normal_bias;
+
+  vec3 direction_to_cam = normalize(cam_pos - world_pos);
+
+  DirectionalLight dl = light_info.dl;
+
+  // occlusion only affects the indirect ambient light
+  vec3 ret = ambient_light(dl.ambient_color * dl.ambient_intensity, color, direction_to_cam, normal, metallic, roughness) * occlusion;
+
+  float dl_shadow = receives_shadows ? directional_shadow(shadow_pos) : 1.0;
+  ret += compute_light(dl.color, -dl.direction, color, direction_to_cam, normal, metallic, roughness) * dl.intensity * dl_shadow;
+
+  Cluster cluster = find_cluster(world_pos);
+
+  for(uint i = 0; i < cluster.point_lights; i++) {
+    PointLight pl = pls.pls[light_indices.indices[cluster.offset + i]];
+    vec3 direction = normalize(pl.position - world_pos);
+    float d = length(world_pos - pl.position);
+
+    if(d > pl.range) {
+      continue;
+    }
+
+    vec3 light_color = pl.color / (4 * PI * d * d);
+
+    ret += compute_light(light_color, direction, color, direction_to_cam, normal, metallic, roughness) * pl.intensity;
+  }
+
+  uint spot_offset = cluster.offset + cluster.point_lights;
+  for(uint i = 0; i < cluster.spot_lights; i++) {
+    SpotLight sl = sls.sls[light_indices.indices[spot_offset + i]];
+    vec3 direction = normalize(sl.position - world_pos);
+    float d = length(world_pos - sl.position);
+
+    if(d > sl.range) {
+      continue;
+    }
+
+    float angle = acos(dot(-direction, sl.direction));
+    if(angle > sl.angle) {
+      continue;
+    }
+
+    vec3 light_color = sl.color / (4 * PI * d * d);
+    float shadow = receives_shadows && sl.shadow != NO_SHADOW ? sample_shadow(sl.shadow, shadow_pos) : 1.0;
+
+    ret += compute_light(light_color, direction, color, direction_to_cam, normal, metallic, roughness) * sl.intensity * shadow;
+  }
+
+  return ret;
+}
@@ -0,0 +1,38 @@
+#version 450
+
+layout (location=0) out vec4 color_out;
+
+layout (location=0) in vec2 uv;
+
+// matches PostProcessInfo on the cpu side
+layout (set=0, binding=0) uniform PostProcessInfo {
+  float exposure;
+  uint tonemapping;
+  float bloom_threshold;
+  float bloom_intensity;
+  float bloom_radius;
+  float gamma;
+  uint encode_srgb;
+} info;
+
+// the output of the previous pass
+layout (set=0, binding=1) uniform sampler2D source;
+// the hdr scene
+layout (set=0, binding=2) uniform sampler2D scene;
+
+vec3 linear_to_srgb(vec3 color) {
+  vec3 low = color * 12.92;
+  vec3 high = 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055;
+  return mix(high, low, lessThanEqual(color, vec3(0.0031308)));
+}
+
+// applies the gamma correction, srgb targets encode the colors themselves
+void main() {
+  vec4 color = texture(source, uv);
+  vec3 ret = pow(max(color.rgb, vec3(0.0)), vec3(2.2 / info.gamma));
+  if (info.encode_srgb != 0) {
+    ret = linear_to_srgb(clamp(ret, 0.0, 1.0));
+  }
+
+  color_out = vec4(ret, color.a);
+}
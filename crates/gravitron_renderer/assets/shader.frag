@@ -6,17 +6,60 @@ layout (set=1, binding=0) uniform sampler2D textures[];
 layout (location = 0) out vec4 color_out;
 layout (location = 1) out vec4 normal_out;
 layout (location = 2) out vec4 pos_out;
+layout (location = 3) out vec4 emissive_out;
 
 layout (location = 0) in vec4 color_in;
-layout (location = 1) in vec3 normal;
+layout (location = 1) in vec3 normal_in;
 layout (location = 2) in vec2 uv;
 layout (location = 3) in vec3 world_pos;
-layout (location = 5) in float metallic;
-layout (location = 6) in float roughness;
-layout (location = 7) flat in uint texture_id;
+layout (location = 4) in vec4 tangent_in;
+// metallic, roughness, normal scale, occlusion strength
+layout (location = 5) in vec4 material;
+// base color, normal, metallic-roughness, occlusion
+layout (location = 6) flat in uvec4 texture_ids;
+layout (location = 7) in vec3 emissive;
+layout (location = 8) flat in uint emissive_texture;
+layout (location = 9) flat in uint flags;
+layout (location = 10) flat in float alpha_cutoff;
+
+// matches NO_TEXTURE on the cpu side
+const uint NO_TEXTURE = 0xFFFFFFFF;
+// matches INSTANCE_NOT_SHADOW_RECEIVER on the cpu side
+const uint NOT_SHADOW_RECEIVER = 2;
+
+vec4 sample_texture(uint id, vec4 fallback) {
+  if (id == NO_TEXTURE) {
+    return fallback;
+  }
+  return texture(textures[nonuniformEXT(id)], uv);
+}
 
 void main() {
-  color_out = texture(textures[texture_id], uv) + color_in;
+  vec4 base_color = texture(textures[nonuniformEXT(texture_ids.x)], uv);
+  if (base_color.a * color_in.a < alpha_cutoff) {
+    discard;
+  }
+
+  vec3 normal = normalize(normal_in);
+  if (texture_ids.y != NO_TEXTURE) {
+    // the interpolated tangent is re-orthogonalized against the normal
+    vec3 tangent = normalize(tangent_in.xyz - normal * dot(normal, tangent_in.xyz));
+    vec3 bitangent = cross(normal, tangent) * tangent_in.w;
+
+    vec3 mapped = texture(textures[nonuniformEXT(texture_ids.y)], uv).xyz * 2.0 - 1.0;
+    mapped.xy *= material.z;
+    normal = normalize(mat3(tangent, bitangent, normal) * mapped);
+  }
+
+  vec4 metallic_roughness = sample_texture(texture_ids.z, vec4(1.0));
+  float metallic = material.x * metallic_roughness.b;
+  float roughness = material.y * metallic_roughness.g;
+  float occlusion = mix(1.0, sample_texture(texture_ids.w, vec4(1.0)).r, material.w);
+
+  color_out = base_color + color_in;
   normal_out = vec4(normal, metallic);
   pos_out = vec4(world_pos, roughness);
+  // the light pass skips shadows for occlusion values offset by 2
+  float receiver_offset = (flags & NOT_SHADOW_RECEIVER) != 0 ? 2.0 : 0.0;
+  emissive_out = vec4(emissive * sample_texture(emissive_texture, vec4(1.0)).rgb, occlusion + receiver_offset);
 }
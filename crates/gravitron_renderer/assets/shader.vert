@@ -3,35 +3,74 @@
 layout (location=0) in vec3 position;
 layout (location=1) in vec3 normal;
 layout (location=2) in vec2 uv;
-layout (location=3) in mat4 model_matrix;
-layout (location=7) in mat4 inverse_model_matrix;
-layout (location=11) in vec4 color;
-layout (location=12) in float metallic;
-layout (location=13) in float roughness;
-layout (location=14) in uint texture_id;
-
-layout(set=0, binding=0) uniform UniformBufferObject {
+layout (location=3) in vec4 tangent;
+layout (location=4) in mat4 model_matrix;
+layout (location=8) in uint material_index;
+layout (location=9) in uint render_layers;
+layout (location=10) in uint flags;
+
+struct Camera {
   mat4 view_matrix;
   mat4 projection_matrix;
-} ubo;
+  uint render_layers;
+};
+
+layout(set=0, binding=0) buffer readonly Cameras {
+  Camera cameras[];
+} cameras;
+
+struct Material {
+  vec4 color;
+  // metallic, roughness, normal scale, occlusion strength
+  vec4 factors;
+  vec3 emissive;
+  uint emissive_texture;
+  // base color, normal, metallic-roughness, occlusion
+  uvec4 textures;
+  float alpha_cutoff;
+};
+
+layout(set=0, binding=4) buffer readonly Materials {
+  Material materials[];
+} materials;
+
+layout(push_constant) uniform PushConstants {
+  uint camera;
+} pc;
 
 layout (location=0) out vec4 color_out;
 layout (location=1) out vec3 normal_out;
 layout (location=2) out vec2 uv_out;
 layout (location=3) out vec3 world_pos;
-layout (location=5) out float metallic_out;
-layout (location=6) out float roughness_out;
-layout (location=7) out uint texture_id_out;
+layout (location=4) out vec4 tangent_out;
+layout (location=5) out vec4 material_out;
+layout (location=6) flat out uvec4 textures_out;
+layout (location=7) out vec3 emissive_out;
+layout (location=8) flat out uint emissive_texture_out;
+layout (location=9) flat out uint flags_out;
+layout (location=10) flat out float alpha_cutoff_out;
 
 void main() {
+  Camera camera = cameras.cameras[pc.camera];
+  if ((render_layers & camera.render_layers) == 0) {
+    // outside of the depth range, so the whole instance is clipped
+    gl_Position = vec4(0.0, 0.0, 2.0, 1.0);
+    return;
+  }
+
   vec4 world_pos_temp = model_matrix * vec4(position,1.0);
-  gl_Position = ubo.projection_matrix * ubo.view_matrix * world_pos_temp;
-  color_out = color;
-  normal_out = transpose(mat3(inverse_model_matrix)) * normal;
+  gl_Position = camera.projection_matrix * camera.view_matrix * world_pos_temp;
+  Material material = materials.materials[material_index];
+  color_out = material.color;
+  normal_out = transpose(inverse(mat3(model_matrix))) * normal;
+  tangent_out = vec4(mat3(model_matrix) * tangent.xyz, tangent.w);
   uv_out = uv;
-  metallic_out = metallic;
-  roughness_out = roughness;
-  texture_id_out = texture_id;
+  material_out = material.factors;
+  textures_out = material.textures;
+  emissive_out = material.emissive;
+  emissive_texture_out = material.emissive_texture;
+  flags_out = flags;
+  alpha_cutoff_out = material.alpha_cutoff;
 
   world_pos = world_pos_temp.xyz;
 }
\ No newline at end of file
@@ -0,0 +1,4 @@
+#version 450
+
+// only the depth is written
+void main() {}
@@ -0,0 +1,27 @@
+#version 450
+
+layout (location=0) in vec3 position;
+layout (location=4) in mat4 model_matrix;
+layout (location=10) in uint flags;
+
+layout(set=0, binding=1) buffer readonly ShadowMatrices {
+  mat4 matrices[];
+} shadow_matrices;
+
+// the shadow map layer rendered
+layout(push_constant) uniform PushConstants {
+  uint layer;
+} pc;
+
+// matches INSTANCE_NOT_SHADOW_CASTER on the cpu side
+const uint NOT_SHADOW_CASTER = 1;
+
+void main() {
+  if ((flags & NOT_SHADOW_CASTER) != 0) {
+    // outside of the depth range, so the whole instance is clipped
+    gl_Position = vec4(0.0, 0.0, 2.0, 1.0);
+    return;
+  }
+
+  gl_Position = shadow_matrices.matrices[pc.layer] * model_matrix * vec4(position, 1.0);
+}
@@ -0,0 +1,78 @@
+#version 450
+
+layout (location=0) out vec4 color_out;
+
+layout (location=0) in vec2 uv;
+
+// matches PostProcessInfo on the cpu side
+layout (set=0, binding=0) uniform PostProcessInfo {
+  float exposure;
+  uint tonemapping;
+  float bloom_threshold;
+  float bloom_intensity;
+  float bloom_radius;
+  float gamma;
+  uint encode_srgb;
+} info;
+
+// the output of the previous pass
+layout (set=0, binding=1) uniform sampler2D source;
+// the hdr scene
+layout (set=0, binding=2) uniform sampler2D scene;
+
+// matches the TONEMAP constants on the cpu side
+const uint TONEMAP_NONE = 0;
+const uint TONEMAP_REINHARD = 1;
+const uint TONEMAP_ACES = 2;
+const uint TONEMAP_AGX = 3;
+
+// fitted curve by Krzysztof Narkowicz
+vec3 aces(vec3 x) {
+  return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+// polynomial approximation of the AgX base curve by Benjamin Wrensch
+vec3 agx_curve(vec3 x) {
+  vec3 x2 = x * x;
+  vec3 x4 = x2 * x2;
+  return 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+}
+
+vec3 agx(vec3 color) {
+  const mat3 inset = mat3(
+    0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+    0.0784335999999992, 0.878468636469772, 0.0784336,
+    0.0792237451477643, 0.0791661274605434, 0.879142973793104
+  );
+  const mat3 outset = mat3(
+    1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+    -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+    -0.0990297440797205, -0.0989611768448433, 1.15107367264116
+  );
+  const float min_ev = -12.47393;
+  const float max_ev = 4.026069;
+
+  vec3 x = inset * max(color, vec3(1e-10));
+  x = clamp((log2(x) - min_ev) / (max_ev - min_ev), 0.0, 1.0);
+  x = outset * agx_curve(x);
+  // the curve returns display encoded colors, the output pass expects linear ones
+  return pow(max(x, vec3(0.0)), vec3(2.2));
+}
+
+void main() {
+  vec4 color = texture(source, uv);
+  vec3 hdr = color.rgb * info.exposure;
+
+  vec3 ret;
+  if (info.tonemapping == TONEMAP_REINHARD) {
+    ret = hdr / (1.0 + hdr);
+  } else if (info.tonemapping == TONEMAP_ACES) {
+    ret = aces(hdr);
+  } else if (info.tonemapping == TONEMAP_AGX) {
+    ret = agx(hdr);
+  } else {
+    ret = clamp(hdr, 0.0, 1.0);
+  }
+
+  color_out = vec4(ret, color.a);
+}
@@ -0,0 +1,139 @@
+use std::{
+  fmt::Debug,
+  hash::Hash,
+  marker::PhantomData,
+  sync::{mpsc::Sender, Arc, Weak},
+};
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
+pub struct AssetId(pub(crate) u64);
+
+/// Notifies the `AssetServer` once the last strong handle of an asset is dropped
+pub(crate) struct HandleRef {
+  id: AssetId,
+  dropped: Sender<AssetId>,
+}
+
+/// Keeps the asset alive, it is freed once every strong handle is dropped
+pub struct Handle<T> {
+  id: AssetId,
+  inner: Arc<HandleRef>,
+  marker: PhantomData<fn() -> T>,
+}
+
+/// Refers to an asset without keeping it alive
+pub struct WeakHandle<T> {
+  id: AssetId,
+  inner: Weak<HandleRef>,
+  marker: PhantomData<fn() -> T>,
+}
+
+impl HandleRef {
+  pub(crate) fn new(id: AssetId, dropped: Sender<AssetId>) -> Arc<Self> {
+    Arc::new(Self { id, dropped })
+  }
+}
+
+impl Drop for HandleRef {
+  fn drop(&mut self) {
+    // the server is already gone if this fails, so there is nothing left to free
+    let _ = self.dropped.send(self.id);
+  }
+}
+
+impl<T> Handle<T> {
+  pub(crate) fn new(inner: Arc<HandleRef>) -> Self {
+    Self {
+      id: inner.id,
+      inner,
+      marker: PhantomData,
+    }
+  }
+
+  #[inline]
+  pub fn id(&self) -> AssetId {
+    self.id
+  }
+
+  #[inline]
+  pub fn downgrade(&self) -> WeakHandle<T> {
+    WeakHandle {
+      id: self.id,
+      inner: Arc::downgrade(&self.inner),
+      marker: PhantomData,
+    }
+  }
+}
+
+impl<T> WeakHandle<T> {
+  #[inline]
+  pub fn id(&self) -> AssetId {
+    self.id
+  }
+
+  /// Returns `None` if the asset was already freed
+  #[inline]
+  pub fn upgrade(&self) -> Option<Handle<T>> {
+    self.inner.upgrade().map(Handle::new)
+  }
+}
+
+impl<T> Clone for Handle<T> {
+  fn clone(&self) -> Self {
+    Self {
+      id: self.id,
+      inner: self.inner.clone(),
+      marker: PhantomData,
+    }
+  }
+}
+
+impl<T> Clone for WeakHandle<T> {
+  fn clone(&self) -> Self {
+    Self {
+      id: self.id,
+      inner: self.inner.clone(),
+      marker: PhantomData,
+    }
+  }
+}
+
+impl<T> PartialEq for Handle<T> {
+  fn eq(&self, other: &Self) -> bool {
+    self.id == other.id
+  }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+  fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+    self.id.hash(state);
+  }
+}
+
+impl<T> Debug for Handle<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_tuple("Handle").field(&self.id).finish()
+  }
+}
+
+impl<T> PartialEq for WeakHandle<T> {
+  fn eq(&self, other: &Self) -> bool {
+    self.id == other.id
+  }
+}
+
+impl<T> Eq for WeakHandle<T> {}
+
+impl<T> Hash for WeakHandle<T> {
+  fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+    self.id.hash(state);
+  }
+}
+
+impl<T> Debug for WeakHandle<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_tuple("WeakHandle").field(&self.id).finish()
+  }
+}
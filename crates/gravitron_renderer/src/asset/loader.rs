@@ -0,0 +1,36 @@
+use std::{any::Any, path::Path};
+
+use anyhow::Error;
+
+/// Types that can be stored in the `AssetServer`
+pub trait Asset: Send + 'static {}
+
+/// Loads assets on a background thread
+pub trait AssetLoader: Send + Sync + 'static {
+  type Asset: Asset;
+
+  /// File extensions without the leading dot
+  fn extensions(&self) -> &[&str];
+
+  fn load(&self, data: Vec<u8>, path: &Path) -> Result<Self::Asset, Error>;
+}
+
+pub(crate) trait ErasedAssetLoader: Send + Sync {
+  fn supports(&self, asset: std::any::TypeId, extension: &str) -> bool;
+
+  fn load(&self, data: Vec<u8>, path: &Path) -> Result<Box<dyn Any + Send>, Error>;
+}
+
+impl<L: AssetLoader> ErasedAssetLoader for L {
+  fn supports(&self, asset: std::any::TypeId, extension: &str) -> bool {
+    asset == std::any::TypeId::of::<L::Asset>()
+      && self
+        .extensions()
+        .iter()
+        .any(|supported| supported.eq_ignore_ascii_case(extension))
+  }
+
+  fn load(&self, data: Vec<u8>, path: &Path) -> Result<Box<dyn Any + Send>, Error> {
+    AssetLoader::load(self, data, path).map(|asset| Box::new(asset) as Box<dyn Any + Send>)
+  }
+}
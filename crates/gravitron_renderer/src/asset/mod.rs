@@ -0,0 +1,8 @@
+pub mod handle;
+pub mod loader;
+pub mod server;
+pub(crate) mod watcher;
+
+pub use handle::{AssetId, Handle, WeakHandle};
+pub use loader::{Asset, AssetLoader};
+pub use server::{AssetServer, LoadState};
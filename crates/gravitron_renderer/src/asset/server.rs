@@ -0,0 +1,467 @@
+use std::{
+  any::{Any, TypeId},
+  collections::HashMap,
+  path::{Path, PathBuf},
+  sync::{
+    mpsc::{self, Receiver, Sender},
+    Arc, Weak,
+  },
+};
+
+use anyhow::Error;
+use log::error;
+
+use super::{
+  handle::{AssetId, Handle, HandleRef},
+  loader::{Asset, AssetLoader, ErasedAssetLoader},
+  watcher::FileWatcher,
+};
+
+type LoadResult = (AssetId, Result<Box<dyn Any + Send>, Error>);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LoadState {
+  /// The asset is unknown or was already freed
+  NotLoaded,
+  Loading,
+  Loaded,
+  Failed(String),
+}
+
+struct AssetEntry {
+  state: LoadState,
+  asset: Option<Box<dyn Any + Send>>,
+  handle: Weak<HandleRef>,
+  path: Option<(TypeId, PathBuf)>,
+}
+
+/// Loads assets in the background, the results are applied by [`AssetServer::update`] which runs every frame
+pub struct AssetServer {
+  root: PathBuf,
+  loaders: Vec<Arc<dyn ErasedAssetLoader>>,
+  assets: HashMap<AssetId, AssetEntry>,
+  paths: HashMap<(TypeId, PathBuf), AssetId>,
+  last_id: u64,
+  loaded: (Sender<LoadResult>, Receiver<LoadResult>),
+  dropped: (Sender<AssetId>, Receiver<AssetId>),
+  removed: Vec<Box<dyn Any + Send>>,
+  replaced: Vec<(AssetId, Box<dyn Any + Send>)>,
+  watcher: Option<FileWatcher<(TypeId, PathBuf)>>,
+}
+
+impl AssetServer {
+  /// Paths passed to [`AssetServer::load`] are relative to `root`
+  pub fn new(root: impl Into<PathBuf>) -> Self {
+    Self {
+      root: root.into(),
+      loaders: Vec::new(),
+      assets: HashMap::new(),
+      paths: HashMap::new(),
+      last_id: 0,
+      loaded: mpsc::channel(),
+      dropped: mpsc::channel(),
+      removed: Vec::new(),
+      replaced: Vec::new(),
+      watcher: None,
+    }
+  }
+
+  #[inline]
+  pub fn add_loader(&mut self, loader: impl AssetLoader) {
+    self.loaders.push(Arc::new(loader));
+  }
+
+  /// Returns immediately, the asset is available once its state is `LoadState::Loaded`.
+  /// Loading the same path again returns the existing handle as long as the asset is alive
+  pub fn load<T: Asset>(&mut self, path: impl AsRef<Path>) -> Handle<T> {
+    let path = path.as_ref().to_path_buf();
+    let key = (TypeId::of::<T>(), path.clone());
+    if let Some(inner) = self
+      .paths
+      .get(&key)
+      .and_then(|id| self.assets.get(id))
+      .and_then(|entry| entry.handle.upgrade())
+    {
+      return Handle::new(inner);
+    }
+
+    let handle = self.create_entry(None, LoadState::Loading, Some(key.clone()));
+    if let Some(watcher) = &mut self.watcher {
+      watcher.watch(self.root.join(&path), key.clone());
+    }
+    if let Err(message) = self.spawn_load(handle.id(), &key) {
+      error!(
+        "Failed to load {} as {}: {message}",
+        path.display(),
+        std::any::type_name::<T>()
+      );
+      self.assets.get_mut(&handle.id()).unwrap().state = LoadState::Failed(message);
+    }
+
+    handle
+  }
+
+  /// Reloads loaded files once they change on disk, handles stay valid and keep pointing to the new asset
+  pub fn watch_for_changes(&mut self) {
+    let mut watcher = FileWatcher::new();
+    for key in self.paths.keys() {
+      watcher.watch(self.root.join(&key.1), key.clone());
+    }
+    self.watcher = Some(watcher);
+  }
+
+  fn spawn_load(&self, id: AssetId, (type_id, path): &(TypeId, PathBuf)) -> Result<(), String> {
+    let extension = path
+      .extension()
+      .and_then(|extension| extension.to_str())
+      .unwrap_or_default();
+    let loader = self
+      .loaders
+      .iter()
+      .find(|loader| loader.supports(*type_id, extension))
+      .cloned()
+      .ok_or_else(|| format!("No loader for extension `{extension}`"))?;
+
+    let path = self.root.join(path);
+    let sender = self.loaded.0.clone();
+    std::thread::Builder::new()
+      .name("asset loader".into())
+      .spawn(move || {
+        let result = std::fs::read(&path)
+          .map_err(Error::from)
+          .and_then(|data| loader.load(data, &path));
+        let _ = sender.send((id, result));
+      })
+      .map(|_| ())
+      .map_err(|err| err.to_string())
+  }
+
+  /// Adds an already loaded asset
+  pub fn add<T: Asset>(&mut self, asset: T) -> Handle<T> {
+    self.create_entry(Some(Box::new(asset)), LoadState::Loaded, None)
+  }
+
+  #[inline]
+  pub fn load_state(&self, id: AssetId) -> LoadState {
+    self
+      .assets
+      .get(&id)
+      .map(|entry| entry.state.clone())
+      .unwrap_or(LoadState::NotLoaded)
+  }
+
+  #[inline]
+  pub fn is_loaded(&self, id: AssetId) -> bool {
+    self.load_state(id) == LoadState::Loaded
+  }
+
+  pub fn get<T: Asset>(&self, handle: &Handle<T>) -> Option<&T> {
+    self
+      .assets
+      .get(&handle.id())?
+      .asset
+      .as_ref()?
+      .downcast_ref()
+  }
+
+  pub fn get_mut<T: Asset>(&mut self, handle: &Handle<T>) -> Option<&mut T> {
+    self
+      .assets
+      .get_mut(&handle.id())?
+      .asset
+      .as_mut()?
+      .downcast_mut()
+  }
+
+  /// All loaded assets of type `T`
+  pub fn iter_mut<T: Asset>(&mut self) -> impl Iterator<Item = (AssetId, &mut T)> {
+    self.assets.iter_mut().filter_map(|(id, entry)| {
+      entry
+        .asset
+        .as_mut()?
+        .downcast_mut()
+        .map(|asset| (*id, asset))
+    })
+  }
+
+  /// Takes the assets of type `T` freed in the last update, used to release their gpu resources
+  pub fn drain_removed<T: Asset>(&mut self) -> Vec<T> {
+    let (removed, rest) = std::mem::take(&mut self.removed)
+      .into_iter()
+      .partition::<Vec<_>, _>(|asset| asset.is::<T>());
+    self.removed = rest;
+
+    removed
+      .into_iter()
+      .filter_map(|asset| asset.downcast().ok())
+      .map(|asset| *asset)
+      .collect()
+  }
+
+  /// Takes the previous version of reloaded assets of type `T`
+  pub fn drain_replaced<T: Asset>(&mut self) -> Vec<(AssetId, T)> {
+    let (replaced, rest) = std::mem::take(&mut self.replaced)
+      .into_iter()
+      .partition::<Vec<_>, _>(|(_, asset)| asset.is::<T>());
+    self.replaced = rest;
+
+    replaced
+      .into_iter()
+      .filter_map(|(id, asset)| asset.downcast().ok().map(|asset| (id, *asset)))
+      .collect()
+  }
+
+  pub(crate) fn asset_mut<T: Asset>(&mut self, id: AssetId) -> Option<&mut T> {
+    self.assets.get_mut(&id)?.asset.as_mut()?.downcast_mut()
+  }
+
+  /// Applies finished loads, starts reloads of changed files and frees assets without strong handles.
+  /// Freed or replaced assets not taken until the next update are dropped
+  pub fn update(&mut self) {
+    self.removed.clear();
+    self.replaced.clear();
+
+    let changed = self
+      .watcher
+      .as_mut()
+      .map(|watcher| watcher.poll())
+      .unwrap_or_default();
+    for key in changed {
+      if let Some(id) = self.paths.get(&key) {
+        if let Err(message) = self.spawn_load(*id, &key) {
+          error!("Failed to reload {}: {message}", key.1.display());
+        }
+      }
+    }
+
+    for (id, result) in self.loaded.1.try_iter() {
+      let Some(entry) = self.assets.get_mut(&id) else {
+        continue;
+      };
+
+      match result {
+        Ok(asset) => {
+          if let Some(previous) = entry.asset.replace(asset) {
+            self.replaced.push((id, previous));
+          }
+          entry.state = LoadState::Loaded;
+        }
+        Err(err) => {
+          if let Some((_, path)) = &entry.path {
+            error!("Failed to load {}: {err}", path.display());
+          }
+          // a failed reload keeps the previous version
+          if entry.asset.is_none() {
+            entry.state = LoadState::Failed(err.to_string());
+          }
+        }
+      }
+    }
+
+    for id in self.dropped.1.try_iter() {
+      let Some(entry) = self.assets.remove(&id) else {
+        continue;
+      };
+
+      if let Some(key) = entry.path {
+        if self.paths.get(&key) == Some(&id) {
+          if let Some(watcher) = &mut self.watcher {
+            watcher.unwatch(&key);
+          }
+          self.paths.remove(&key);
+        }
+      }
+      self.removed.extend(entry.asset);
+    }
+  }
+
+  fn create_entry<T>(
+    &mut self,
+    asset: Option<Box<dyn Any + Send>>,
+    state: LoadState,
+    path: Option<(TypeId, PathBuf)>,
+  ) -> Handle<T> {
+    let id = AssetId(self.last_id);
+    self.last_id += 1;
+
+    let inner = HandleRef::new(id, self.dropped.0.clone());
+    if let Some(key) = &path {
+      self.paths.insert(key.clone(), id);
+    }
+    self.assets.insert(
+      id,
+      AssetEntry {
+        state,
+        asset,
+        handle: Arc::downgrade(&inner),
+        path,
+      },
+    );
+
+    Handle::new(inner)
+  }
+}
+
+impl Default for AssetServer {
+  fn default() -> Self {
+    Self::new("")
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::{path::Path, time::Duration};
+
+  use anyhow::Error;
+
+  use super::{AssetServer, LoadState};
+  use crate::asset::{
+    handle::AssetId,
+    loader::{Asset, AssetLoader},
+    watcher::FileWatcher,
+  };
+
+  #[derive(Debug, PartialEq)]
+  struct Text(String);
+
+  impl Asset for Text {}
+
+  struct TextLoader;
+
+  impl AssetLoader for TextLoader {
+    type Asset = Text;
+
+    fn extensions(&self) -> &[&str] {
+      &["txt"]
+    }
+
+    fn load(&self, data: Vec<u8>, _: &Path) -> Result<Self::Asset, Error> {
+      Ok(Text(String::from_utf8(data)?))
+    }
+  }
+
+  fn server(name: &str) -> AssetServer {
+    let root = std::env::temp_dir().join(format!("gravitron_assets_{name}"));
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("hello.txt"), "hello").unwrap();
+    std::fs::write(root.join("invalid.txt"), [0xFF, 0xFE]).unwrap();
+
+    let mut server = AssetServer::new(root);
+    server.add_loader(TextLoader);
+    server
+  }
+
+  fn wait(server: &mut AssetServer, id: AssetId) -> LoadState {
+    for _ in 0..1000 {
+      server.update();
+      if server.load_state(id) != LoadState::Loading {
+        break;
+      }
+      std::thread::sleep(Duration::from_millis(1));
+    }
+    server.load_state(id)
+  }
+
+  #[test]
+  fn load() {
+    let mut server = server("load");
+    let handle = server.load::<Text>("hello.txt");
+    assert_eq!(wait(&mut server, handle.id()), LoadState::Loaded);
+    assert_eq!(server.get(&handle), Some(&Text("hello".into())));
+
+    let again = server.load::<Text>("hello.txt");
+    assert_eq!(again, handle);
+  }
+
+  #[test]
+  fn load_errors() {
+    let mut server = server("errors");
+
+    let unsupported = server.load::<Text>("hello.png");
+    assert!(matches!(
+      server.load_state(unsupported.id()),
+      LoadState::Failed(_)
+    ));
+
+    let missing = server.load::<Text>("missing.txt");
+    assert!(matches!(
+      wait(&mut server, missing.id()),
+      LoadState::Failed(_)
+    ));
+
+    let invalid = server.load::<Text>("invalid.txt");
+    assert!(matches!(
+      wait(&mut server, invalid.id()),
+      LoadState::Failed(_)
+    ));
+    assert!(server.get(&invalid).is_none());
+  }
+
+  #[test]
+  fn reference_counting() {
+    let mut server = AssetServer::default();
+    let handle = server.add(Text("asset".into()));
+    let id = handle.id();
+    let weak = handle.downgrade();
+    let clone = handle.clone();
+
+    drop(handle);
+    server.update();
+    assert!(server.is_loaded(id));
+    assert!(weak.upgrade().is_some());
+
+    drop(clone);
+    assert!(weak.upgrade().is_none());
+    server.update();
+    assert_eq!(server.load_state(id), LoadState::NotLoaded);
+    assert_eq!(server.drain_removed::<Text>(), vec![Text("asset".into())]);
+
+    server.update();
+    assert!(server.drain_removed::<Text>().is_empty());
+  }
+
+  #[test]
+  fn hot_reload() {
+    let mut server = server("hot_reload");
+    server.watcher = Some(FileWatcher::with_interval(Duration::ZERO));
+    let handle = server.load::<Text>("hello.txt");
+    wait(&mut server, handle.id());
+
+    let path = server.root.join("hello.txt");
+    std::fs::write(&path, "reloaded").unwrap();
+    std::fs::File::options()
+      .write(true)
+      .open(&path)
+      .unwrap()
+      .set_modified(std::time::SystemTime::now() + Duration::from_secs(10))
+      .unwrap();
+
+    let mut replaced = Vec::new();
+    for _ in 0..1000 {
+      server.update();
+      replaced.extend(server.drain_replaced::<Text>());
+      if !replaced.is_empty() {
+        break;
+      }
+      std::thread::sleep(Duration::from_millis(1));
+    }
+
+    assert_eq!(replaced, vec![(handle.id(), Text("hello".into()))]);
+    assert_eq!(server.get(&handle), Some(&Text("reloaded".into())));
+    assert!(server.is_loaded(handle.id()));
+  }
+
+  #[test]
+  fn reload_after_free() {
+    let mut server = server("reload");
+    let handle = server.load::<Text>("hello.txt");
+    let id = handle.id();
+    wait(&mut server, id);
+
+    drop(handle);
+    let handle = server.load::<Text>("hello.txt");
+    assert_ne!(handle.id(), id);
+    assert_eq!(wait(&mut server, handle.id()), LoadState::Loaded);
+    assert_eq!(server.load_state(id), LoadState::NotLoaded);
+  }
+}
@@ -0,0 +1,130 @@
+use std::{
+  collections::HashMap,
+  path::{Path, PathBuf},
+  time::{Duration, Instant, SystemTime},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Polls the modification time of files, used for hot reloading
+pub(crate) struct FileWatcher<K> {
+  files: HashMap<PathBuf, (Option<SystemTime>, Vec<K>)>,
+  interval: Duration,
+  last_poll: Instant,
+}
+
+impl<K: Clone + PartialEq> FileWatcher<K> {
+  #[inline]
+  pub(crate) fn new() -> Self {
+    Self::with_interval(POLL_INTERVAL)
+  }
+
+  pub(crate) fn with_interval(interval: Duration) -> Self {
+    Self {
+      files: HashMap::new(),
+      interval,
+      last_poll: Instant::now(),
+    }
+  }
+
+  /// `key` is returned by [`FileWatcher::poll`] whenever the file changes
+  pub(crate) fn watch(&mut self, path: impl AsRef<Path>, key: K) {
+    let path = path.as_ref();
+    let (_, keys) = self
+      .files
+      .entry(path.to_path_buf())
+      .or_insert_with(|| (modified(path), Vec::new()));
+
+    if !keys.contains(&key) {
+      keys.push(key);
+    }
+  }
+
+  pub(crate) fn unwatch(&mut self, key: &K) {
+    self.files.retain(|_, (_, keys)| {
+      keys.retain(|k| k != key);
+      !keys.is_empty()
+    });
+  }
+
+  /// Returns the keys of all files modified since the last poll, does nothing until the interval elapsed.
+  /// Files that are missing, e.g. while an editor replaces them, are reported once they are back
+  pub(crate) fn poll(&mut self) -> Vec<K> {
+    if self.last_poll.elapsed() < self.interval {
+      return Vec::new();
+    }
+    self.last_poll = Instant::now();
+
+    let mut changed = Vec::new();
+    for (path, (last_modified, keys)) in &mut self.files {
+      let modified = modified(path);
+      if modified.is_some() && modified != *last_modified {
+        for key in keys.iter() {
+          if !changed.contains(key) {
+            changed.push(key.clone());
+          }
+        }
+      }
+      *last_modified = modified.or(*last_modified);
+    }
+
+    changed
+  }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+  std::fs::metadata(path)
+    .and_then(|meta| meta.modified())
+    .ok()
+}
+
+#[cfg(test)]
+mod test {
+  use std::{
+    fs::File,
+    time::{Duration, SystemTime},
+  };
+
+  use super::FileWatcher;
+
+  #[test]
+  fn file_changes() {
+    let dir = std::env::temp_dir().join("gravitron_watcher");
+    std::fs::create_dir_all(&dir).unwrap();
+    let first = dir.join("first.txt");
+    let second = dir.join("second.txt");
+    std::fs::write(&first, "first").unwrap();
+    std::fs::write(&second, "second").unwrap();
+
+    let mut watcher = FileWatcher::with_interval(Duration::ZERO);
+    watcher.watch(&first, 1);
+    watcher.watch(&first, 2);
+    watcher.watch(&second, 2);
+    assert!(watcher.poll().is_empty());
+
+    let touch = |path: &std::path::Path, offset: u64| {
+      File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(SystemTime::now() + Duration::from_secs(offset))
+        .unwrap();
+    };
+
+    touch(&first, 10);
+    touch(&second, 10);
+    let mut changed = watcher.poll();
+    changed.sort();
+    assert_eq!(changed, vec![1, 2]);
+    assert!(watcher.poll().is_empty());
+
+    watcher.unwatch(&1);
+    touch(&first, 20);
+    assert_eq!(watcher.poll(), vec![2]);
+
+    let mut slow = FileWatcher::with_interval(Duration::from_secs(3600));
+    slow.watch(&first, 1);
+    touch(&first, 30);
+    assert!(slow.poll().is_empty());
+  }
+}
@@ -1,11 +1,23 @@
+use std::path::PathBuf;
+
 use ash::vk;
 
-use crate::renderer::TextureHandle;
+use crate::{
+  renderer::TextureHandle,
+  texture::{ColorSpace, SamplerDescriptor},
+};
 
 #[derive(Default, Clone)]
 pub struct RendererConfig {
   pub device: DeviceConfig<'static>,
   pub graphics: GraphicsConfig,
+  /// Root directory of the paths passed to `AssetServer::load`
+  pub asset_root: PathBuf,
+  /// Reloads texture files and assets once they change on disk, shader files need the `hot_reload` feature
+  pub hot_reload: bool,
+  /// Size of the offscreen image rendered to instead of a window, the `WindowPlugin` is not needed then
+  pub headless: Option<(u32, u32)>,
+  pub swapchain: SwapchainConfig,
 }
 
 impl RendererConfig {
@@ -14,6 +26,112 @@ impl RendererConfig {
     self.device = device;
     self
   }
+
+  #[inline]
+  pub fn set_swapchain_config(mut self, swapchain: SwapchainConfig) -> Self {
+    self.swapchain = swapchain;
+    self
+  }
+
+  #[inline]
+  pub fn enable_hot_reload(mut self) -> Self {
+    self.hot_reload = true;
+    self
+  }
+
+  /// `RenderTarget::Window` cameras render into an offscreen image that can be read with `Renderer::read_pixels`
+  #[inline]
+  pub fn enable_headless(mut self, width: u32, height: u32) -> Self {
+    self.headless = Some((width.max(1), height.max(1)));
+    self
+  }
+
+  #[inline]
+  pub fn set_asset_root(mut self, root: impl Into<PathBuf>) -> Self {
+    self.asset_root = root.into();
+    self
+  }
+}
+
+/// How frames are presented to the window, falls back to `Fifo` which is always supported
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PresentMode {
+  /// Vsync, waits for the next vertical blank
+  Fifo,
+  /// Vsync, but late frames are shown immediately and might tear. Falls back to `Fifo`
+  FifoRelaxed,
+  /// No tearing and no waiting, the newest frame replaces queued ones. Falls back to `Fifo`
+  #[default]
+  Mailbox,
+  /// No vsync, frames are shown immediately and might tear. Falls back to `Mailbox`, then `Fifo`
+  Immediate,
+}
+
+impl PresentMode {
+  /// The vulkan present modes tried in order
+  pub(crate) fn candidates(self) -> &'static [vk::PresentModeKHR] {
+    match self {
+      PresentMode::Fifo => &[vk::PresentModeKHR::FIFO],
+      PresentMode::FifoRelaxed => &[vk::PresentModeKHR::FIFO_RELAXED, vk::PresentModeKHR::FIFO],
+      PresentMode::Mailbox => &[vk::PresentModeKHR::MAILBOX, vk::PresentModeKHR::FIFO],
+      PresentMode::Immediate => &[
+        vk::PresentModeKHR::IMMEDIATE,
+        vk::PresentModeKHR::MAILBOX,
+        vk::PresentModeKHR::FIFO,
+      ],
+    }
+  }
+
+  /// Whether frames wait for the vertical blank
+  #[inline]
+  pub fn is_vsync(self) -> bool {
+    matches!(self, PresentMode::Fifo | PresentMode::FifoRelaxed)
+  }
+}
+
+const DEFAULT_FRAMES_IN_FLIGHT: usize = 2;
+
+/// Settings of the swapchain, only used when rendering to a window
+#[derive(Clone, Debug)]
+pub struct SwapchainConfig {
+  pub present_mode: PresentMode,
+  /// Used if the surface supports it, otherwise an 8 bit srgb format is preferred
+  pub surface_format: Option<vk::SurfaceFormatKHR>,
+  /// Frames the cpu can prepare while the gpu is still working on earlier ones, at least 1
+  pub frames_in_flight: usize,
+}
+
+impl SwapchainConfig {
+  #[inline]
+  pub fn set_present_mode(mut self, present_mode: PresentMode) -> Self {
+    self.present_mode = present_mode;
+    self
+  }
+
+  #[inline]
+  pub fn set_surface_format(mut self, format: vk::Format, color_space: vk::ColorSpaceKHR) -> Self {
+    self.surface_format = Some(vk::SurfaceFormatKHR {
+      format,
+      color_space,
+    });
+    self
+  }
+
+  #[inline]
+  pub fn set_frames_in_flight(mut self, frames_in_flight: usize) -> Self {
+    self.frames_in_flight = frames_in_flight.max(1);
+    self
+  }
+}
+
+impl Default for SwapchainConfig {
+  fn default() -> Self {
+    Self {
+      present_mode: PresentMode::default(),
+      surface_format: None,
+      frames_in_flight: DEFAULT_FRAMES_IN_FLIGHT,
+    }
+  }
 }
 
 #[derive(Default, Clone)]
@@ -32,16 +150,92 @@ impl<'a> DeviceConfig<'a> {
   }
 }
 
+#[derive(Clone)]
+pub(crate) enum TextureSource {
+  Image(Vec<u8>, ColorSpace, SamplerDescriptor),
+  File(PathBuf, ColorSpace, SamplerDescriptor),
+  RenderTarget(u32, u32, vk::Filter),
+}
+
+const DEFAULT_MAX_TEXTURES: u32 = 128;
+
 #[derive(Clone)]
 pub struct GraphicsConfig {
-  pub(crate) textures: Vec<(Vec<u8>, vk::Filter)>,
+  pub(crate) textures: Vec<TextureSource>,
+  /// Size of the texture array, clamped to the device limits and the number of startup textures
+  pub max_textures: u32,
+  pub shadows: ShadowConfig,
+  pub post_process: PostProcessConfig,
+  pub msaa: Msaa,
   max_texture_id: u32,
 }
 
 impl GraphicsConfig {
+  /// Supports png, jpeg, tga, hdr, exr, ktx2 and dds images
+  #[inline]
+  pub fn add_texture(
+    &mut self,
+    texture: Vec<u8>,
+    color_space: ColorSpace,
+    sampler: impl Into<SamplerDescriptor>,
+  ) -> TextureHandle {
+    self.add_texture_source(TextureSource::Image(texture, color_space, sampler.into()))
+  }
+
+  /// The file is read when the renderer starts and reloaded on changes if hot reloading is enabled
+  #[inline]
+  pub fn add_texture_file(
+    &mut self,
+    path: impl Into<PathBuf>,
+    color_space: ColorSpace,
+    sampler: impl Into<SamplerDescriptor>,
+  ) -> TextureHandle {
+    self.add_texture_source(TextureSource::File(
+      path.into(),
+      color_space,
+      sampler.into(),
+    ))
+  }
+
+  /// Creates a texture cameras can render to with `RenderTarget::Texture`
+  #[inline]
+  pub fn add_render_target(
+    &mut self,
+    width: u32,
+    height: u32,
+    interpolation: vk::Filter,
+  ) -> TextureHandle {
+    self.add_texture_source(TextureSource::RenderTarget(
+      width.max(1),
+      height.max(1),
+      interpolation,
+    ))
+  }
+
+  #[inline]
+  pub fn set_max_textures(&mut self, max_textures: u32) {
+    self.max_textures = max_textures;
+  }
+
+  #[inline]
+  pub fn set_shadow_config(&mut self, shadows: ShadowConfig) {
+    self.shadows = shadows;
+  }
+
+  /// Can be changed at runtime with `Renderer::set_post_process`
+  #[inline]
+  pub fn set_post_process_config(&mut self, post_process: PostProcessConfig) {
+    self.post_process = post_process;
+  }
+
+  /// Can be changed at runtime with `Renderer::set_msaa`
   #[inline]
-  pub fn add_texture(&mut self, texture: Vec<u8>, interpolation: vk::Filter) -> TextureHandle {
-    self.textures.push((texture, interpolation));
+  pub fn set_msaa(&mut self, msaa: Msaa) {
+    self.msaa = msaa;
+  }
+
+  fn add_texture_source(&mut self, source: TextureSource) -> TextureHandle {
+    self.textures.push(source);
     let id = TextureHandle(self.max_texture_id);
     self.max_texture_id += 1;
     id
@@ -51,11 +245,248 @@ impl GraphicsConfig {
 impl Default for GraphicsConfig {
   fn default() -> Self {
     GraphicsConfig {
-      textures: vec![(
+      textures: vec![TextureSource::Image(
         include_bytes!("../assets/default.png").to_vec(),
-        vk::Filter::NEAREST,
+        ColorSpace::Srgb,
+        SamplerDescriptor::nearest(),
       )],
+      max_textures: DEFAULT_MAX_TEXTURES,
+      shadows: ShadowConfig::default(),
+      post_process: PostProcessConfig::default(),
+      msaa: Msaa::default(),
       max_texture_id: 1,
     }
   }
 }
+
+/// The directional light can use up to this many cascades
+pub const MAX_SHADOW_CASCADES: u32 = 4;
+
+/// Shadows of the directional light and spot lights with `cast_shadows` enabled
+#[derive(Clone, Debug)]
+pub struct ShadowConfig {
+  /// Width and height of every shadow map
+  pub map_size: u32,
+  /// Shadow maps the view of the camera is split into for the directional light, at most `MAX_SHADOW_CASCADES`
+  pub cascades: u32,
+  /// Distance from the camera up to which the directional light casts shadows
+  pub distance: f32,
+  /// Spot lights beyond this number do not cast shadows
+  pub max_spot_shadows: u32,
+  /// Shadow map texels sampled in each direction when filtering, 0 only uses the hardware filter
+  pub pcf_radius: u32,
+}
+
+impl ShadowConfig {
+  #[inline]
+  pub fn set_map_size(mut self, map_size: u32) -> Self {
+    self.map_size = map_size.max(1);
+    self
+  }
+
+  #[inline]
+  pub fn set_cascades(mut self, cascades: u32) -> Self {
+    self.cascades = cascades.clamp(1, MAX_SHADOW_CASCADES);
+    self
+  }
+
+  #[inline]
+  pub fn set_distance(mut self, distance: f32) -> Self {
+    self.distance = distance;
+    self
+  }
+
+  #[inline]
+  pub fn set_max_spot_shadows(mut self, max_spot_shadows: u32) -> Self {
+    self.max_spot_shadows = max_spot_shadows;
+    self
+  }
+
+  #[inline]
+  pub fn set_pcf_radius(mut self, pcf_radius: u32) -> Self {
+    self.pcf_radius = pcf_radius;
+    self
+  }
+
+  /// Layers of the shadow map array, the cascades come first
+  #[inline]
+  pub(crate) fn layers(&self) -> u32 {
+    self.cascades() + self.max_spot_shadows
+  }
+
+  #[inline]
+  pub(crate) fn cascades(&self) -> u32 {
+    self.cascades.clamp(1, MAX_SHADOW_CASCADES)
+  }
+}
+
+impl Default for ShadowConfig {
+  fn default() -> Self {
+    Self {
+      map_size: 2048,
+      cascades: MAX_SHADOW_CASCADES,
+      distance: 100.0,
+      max_spot_shadows: 4,
+      pcf_radius: 1,
+    }
+  }
+}
+
+/// Samples per pixel the scene is rendered with, the lighting is computed for every sample
+#[derive(Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Msaa {
+  #[default]
+  Off,
+  X2,
+  X4,
+  X8,
+}
+
+impl Msaa {
+  #[inline]
+  pub(crate) fn sample_count(self) -> vk::SampleCountFlags {
+    match self {
+      Msaa::Off => vk::SampleCountFlags::TYPE_1,
+      Msaa::X2 => vk::SampleCountFlags::TYPE_2,
+      Msaa::X4 => vk::SampleCountFlags::TYPE_4,
+      Msaa::X8 => vk::SampleCountFlags::TYPE_8,
+    }
+  }
+
+  /// The highest sample count up to this one the device supports
+  pub(crate) fn clamp(self, supported: vk::SampleCountFlags) -> Self {
+    [Msaa::X8, Msaa::X4, Msaa::X2]
+      .into_iter()
+      .find(|msaa| *msaa <= self && supported.contains(msaa.sample_count()))
+      .unwrap_or(Msaa::Off)
+  }
+}
+
+/// Maps the hdr colors of the scene to the displayable range
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Tonemapping {
+  /// Colors above 1 are clipped
+  None,
+  #[default]
+  Reinhard,
+  /// Filmic curve of the Academy Color Encoding System
+  Aces,
+  /// Desaturates bright colors instead of shifting their hue
+  AgX,
+}
+
+/// Glow around colors brighter than `threshold`
+#[derive(Clone, Debug)]
+pub struct BloomConfig {
+  /// Luminance above which colors start to glow
+  pub threshold: f32,
+  /// Strength of the glow added to the scene
+  pub intensity: f32,
+  /// Distance in pixels between the samples of the blur, larger values spread the glow further
+  pub radius: f32,
+}
+
+impl BloomConfig {
+  #[inline]
+  pub fn set_threshold(mut self, threshold: f32) -> Self {
+    self.threshold = threshold.max(0.0);
+    self
+  }
+
+  #[inline]
+  pub fn set_intensity(mut self, intensity: f32) -> Self {
+    self.intensity = intensity.max(0.0);
+    self
+  }
+
+  #[inline]
+  pub fn set_radius(mut self, radius: f32) -> Self {
+    self.radius = radius.max(0.0);
+    self
+  }
+}
+
+impl Default for BloomConfig {
+  fn default() -> Self {
+    Self {
+      threshold: 1.0,
+      intensity: 0.5,
+      radius: 2.0,
+    }
+  }
+}
+
+/// The passes between the hdr scene and the target, pipelines with `RenderingStage::PostProcess` run after tonemapping
+#[derive(Clone, Debug)]
+pub struct PostProcessConfig {
+  /// Scene colors are multiplied by it before tonemapping
+  pub exposure: f32,
+  pub tonemapping: Tonemapping,
+  /// Disabled if `None`
+  pub bloom: Option<BloomConfig>,
+  /// Gamma of the display, 2.2 matches srgb
+  pub gamma: f32,
+  /// Smooths edges after the other passes, cheaper than msaa but slightly blurs details
+  pub fxaa: bool,
+}
+
+impl PostProcessConfig {
+  #[inline]
+  pub fn set_exposure(mut self, exposure: f32) -> Self {
+    self.exposure = exposure.max(0.0);
+    self
+  }
+
+  #[inline]
+  pub fn set_tonemapping(mut self, tonemapping: Tonemapping) -> Self {
+    self.tonemapping = tonemapping;
+    self
+  }
+
+  #[inline]
+  pub fn set_bloom(mut self, bloom: Option<BloomConfig>) -> Self {
+    self.bloom = bloom;
+    self
+  }
+
+  #[inline]
+  pub fn set_gamma(mut self, gamma: f32) -> Self {
+    self.gamma = gamma.max(0.01);
+    self
+  }
+
+  #[inline]
+  pub fn set_fxaa(mut self, fxaa: bool) -> Self {
+    self.fxaa = fxaa;
+    self
+  }
+}
+
+impl Default for PostProcessConfig {
+  fn default() -> Self {
+    Self {
+      exposure: 1.0,
+      tonemapping: Tonemapping::default(),
+      bloom: None,
+      gamma: 2.2,
+      fxaa: false,
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use ash::vk;
+
+  use super::Msaa;
+
+  #[test]
+  fn msaa_clamp() {
+    let supported =
+      vk::SampleCountFlags::TYPE_1 | vk::SampleCountFlags::TYPE_2 | vk::SampleCountFlags::TYPE_4;
+    assert_eq!(Msaa::X8.clamp(supported), Msaa::X4);
+    assert_eq!(Msaa::X2.clamp(supported), Msaa::X2);
+    assert_eq!(Msaa::Off.clamp(supported), Msaa::Off);
+    assert_eq!(Msaa::X4.clamp(vk::SampleCountFlags::TYPE_1), Msaa::Off);
+  }
+}
@@ -15,11 +15,17 @@ impl Device {
   pub fn init(
     instance: &ash::Instance,
     physical_device: vk::PhysicalDevice,
-    surface: &Surface,
+    surface: Option<&Surface>,
     config: &DeviceConfig,
   ) -> Result<Self, Error> {
     let queue_families = QueueFamilies::init(instance, physical_device, surface)?;
-    let (device, queues) = Queues::init(instance, physical_device, &queue_families, config)?;
+    let (device, queues) = Queues::init(
+      instance,
+      physical_device,
+      &queue_families,
+      config,
+      surface.is_some(),
+    )?;
 
     Ok(Self {
       device,
@@ -61,10 +67,11 @@ pub struct QueueFamilies {
 }
 
 impl QueueFamilies {
+  /// Without a surface the graphics queue does not need to support presenting
   pub fn init(
     instance: &ash::Instance,
     physical_device: vk::PhysicalDevice,
-    surface: &Surface,
+    surface: Option<&Surface>,
   ) -> Result<Self, Error> {
     let queue_family_properties =
       unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
@@ -75,7 +82,9 @@ impl QueueFamilies {
     for (i, properties) in queue_family_properties.iter().enumerate() {
       if properties.queue_count > 0
         && properties.queue_flags.contains(vk::QueueFlags::GRAPHICS)
-        && surface.get_support(physical_device, i as u32)?
+        && surface.map_or(Ok(true), |surface| {
+          surface.get_support(physical_device, i as u32)
+        })?
         && queue_family_index_graphics.is_none()
       {
         queue_family_index_graphics = Some(i as u32);
@@ -140,6 +149,7 @@ impl Queues {
     physical_device: vk::PhysicalDevice,
     queue_families: &QueueFamilies,
     config: &DeviceConfig,
+    swapchain: bool,
   ) -> Result<(ash::Device, Self), vk::Result> {
     let queue_priorities = [1.0];
     let mut queue_create_infos = vec![vk::DeviceQueueCreateInfo::default()
@@ -160,18 +170,39 @@ impl Queues {
       );
     }
     let mut device_extension_name_ptrs = vec![
-      khr::swapchain::NAME.as_ptr(),
       khr::draw_indirect_count::NAME.as_ptr(),
       ext::descriptor_indexing::NAME.as_ptr(),
     ];
+    if swapchain {
+      device_extension_name_ptrs.push(khr::swapchain::NAME.as_ptr());
+    }
     device_extension_name_ptrs.extend(config.device_extensions.iter().map(|ext| ext.as_ptr()));
 
     let mut indexing = vk::PhysicalDeviceDescriptorIndexingFeaturesEXT::default()
       .runtime_descriptor_array(true)
       .descriptor_binding_storage_buffer_update_after_bind(true)
-      .descriptor_binding_uniform_buffer_update_after_bind(true);
-
-    let features = config.device_features.fill_mode_non_solid(true);
+      .descriptor_binding_uniform_buffer_update_after_bind(true)
+      .descriptor_binding_sampled_image_update_after_bind(true)
+      .descriptor_binding_update_unused_while_pending(true);
+
+    // textures opt into anisotropic filtering through their sampler and may be bc compressed,
+    // msaa lights every sample which needs sample rate shading
+    let supported = unsafe { instance.get_physical_device_features(physical_device) };
+    let features = config
+      .device_features
+      .fill_mode_non_solid(true)
+      .sampler_anisotropy(
+        config.device_features.sampler_anisotropy == vk::TRUE
+          || supported.sampler_anisotropy == vk::TRUE,
+      )
+      .texture_compression_bc(
+        config.device_features.texture_compression_bc == vk::TRUE
+          || supported.texture_compression_bc == vk::TRUE,
+      )
+      .sample_rate_shading(
+        config.device_features.sample_rate_shading == vk::TRUE
+          || supported.sample_rate_shading == vk::TRUE,
+      );
     let mut features2 = vk::PhysicalDeviceFeatures2::default()
       .features(features)
       .push_next(&mut indexing);
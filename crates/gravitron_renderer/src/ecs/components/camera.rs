@@ -3,11 +3,76 @@ use core::f32;
 use gravitron_components::components::transform::GlobalTransform;
 use gravitron_ecs::Component;
 
+use crate::renderer::TextureHandle;
+
+const MIN_DEPTH_RANGE: f32 = 0.0001;
+const DEFAULT_VIEWPORT_SIZE: glam::Vec2 = glam::Vec2::new(800.0, 600.0);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PerspectiveProjection {
+  pub fov: f32,
+  /// Gets overwritten with the aspect ratio of the viewport when it changes
+  pub aspect_ratio: f32,
+  pub near: f32,
+  pub far: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScalingMode {
+  /// Shows the given amount of pixels per world unit, so the visible area grows with the window
+  WindowSize(f32),
+  /// Always shows exactly `width` x `height` world units, stretching the image if needed
+  Fixed { width: f32, height: f32 },
+  /// Shows the given amount of world units vertically, the width follows the aspect ratio
+  FixedVertical(f32),
+  /// Shows the given amount of world units horizontally, the height follows the aspect ratio
+  FixedHorizontal(f32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrthographicProjection {
+  pub near: f32,
+  pub far: f32,
+  pub scaling_mode: ScalingMode,
+  /// Multiplier for the visible area, larger values zoom out
+  pub scale: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+  Perspective(PerspectiveProjection),
+  Orthographic(OrthographicProjection),
+  Custom(glam::Mat4),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+  pub origin: glam::Vec3,
+  pub direction: glam::Vec3,
+}
+
+/// A rectangle relative to the size of the render target, with (0, 0) being the top left corner
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+  pub position: glam::Vec2,
+  pub size: glam::Vec2,
+}
+
+/// Texture targets are always rendered before the window, so their content can be shown by window cameras
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RenderTarget {
+  /// Has to be created with `GraphicsConfig::add_render_target`
+  Texture(TextureHandle),
+  #[default]
+  Window,
+}
+
 pub struct CameraBuilder {
-  fov: f32,
-  aspect_ratio: f32,
-  near: f32,
-  far: f32,
+  projection: Projection,
+  viewport: Viewport,
+  priority: i32,
+  active: bool,
+  target: RenderTarget,
 }
 
 impl CameraBuilder {
@@ -16,38 +81,92 @@ impl CameraBuilder {
     Self::default()
   }
 
+  /// Only applies to perspective projections
   #[inline]
   pub fn fov(mut self, fov: f32) -> Self {
-    self.fov = fov.clamp(0.01, std::f32::consts::PI - 0.01);
+    if let Projection::Perspective(perspective) = &mut self.projection {
+      perspective.fov = fov.clamp(0.01, std::f32::consts::PI - 0.01);
+    }
     self
   }
 
+  /// Only applies to perspective projections
   #[inline]
   pub fn aspect_ratio(mut self, aspect_ratio: f32) -> Self {
-    self.aspect_ratio = aspect_ratio;
+    if let Projection::Perspective(perspective) = &mut self.projection {
+      perspective.aspect_ratio = aspect_ratio;
+    }
     self
   }
 
   #[inline]
   pub fn near(mut self, near: f32) -> Self {
-    self.near = near.max(self.far);
+    match &mut self.projection {
+      Projection::Perspective(perspective) => perspective.near = near,
+      Projection::Orthographic(orthographic) => orthographic.near = near,
+      Projection::Custom(_) => (),
+    }
     self
   }
 
   #[inline]
   pub fn far(mut self, far: f32) -> Self {
-    self.far = far.min(self.near);
+    match &mut self.projection {
+      Projection::Perspective(perspective) => perspective.far = far,
+      Projection::Orthographic(orthographic) => orthographic.far = far,
+      Projection::Custom(_) => (),
+    }
+    self
+  }
+
+  #[inline]
+  pub fn projection(mut self, projection: Projection) -> Self {
+    self.projection = projection;
+    self
+  }
+
+  #[inline]
+  pub fn orthographic(self, scaling_mode: ScalingMode) -> Self {
+    self.projection(Projection::Orthographic(OrthographicProjection {
+      scaling_mode,
+      ..Default::default()
+    }))
+  }
+
+  #[inline]
+  pub fn viewport(mut self, viewport: Viewport) -> Self {
+    self.viewport = viewport;
+    self
+  }
+
+  #[inline]
+  pub fn priority(mut self, priority: i32) -> Self {
+    self.priority = priority;
+    self
+  }
+
+  #[inline]
+  pub fn active(mut self, active: bool) -> Self {
+    self.active = active;
+    self
+  }
+
+  #[inline]
+  pub fn target(mut self, target: RenderTarget) -> Self {
+    self.target = target;
     self
   }
 
   pub fn build(self) -> Camera {
     let mut cam = Camera {
       view_matrix: glam::Mat4::IDENTITY,
-      fov: self.fov,
-      aspect_ratio: self.aspect_ratio,
-      far: self.far,
-      near: self.near,
+      projection: self.projection,
       projection_matrix: glam::Mat4::IDENTITY,
+      viewport_size: DEFAULT_VIEWPORT_SIZE,
+      viewport: self.viewport,
+      priority: self.priority,
+      active: self.active,
+      target: self.target,
     };
 
     cam.update_projection_matrix();
@@ -58,10 +177,11 @@ impl CameraBuilder {
 impl Default for CameraBuilder {
   fn default() -> Self {
     CameraBuilder {
-      fov: f32::consts::FRAC_PI_3,
-      aspect_ratio: 800.0 / 600.0,
-      near: 0.1,
-      far: 100.0,
+      projection: Projection::Perspective(Default::default()),
+      viewport: Default::default(),
+      priority: 0,
+      active: true,
+      target: Default::default(),
     }
   }
 }
@@ -69,11 +189,13 @@ impl Default for CameraBuilder {
 #[derive(Component)]
 pub struct Camera {
   view_matrix: glam::Mat4,
-  fov: f32,
-  aspect_ratio: f32,
-  near: f32,
-  far: f32,
+  projection: Projection,
   projection_matrix: glam::Mat4,
+  viewport_size: glam::Vec2,
+  viewport: Viewport,
+  priority: i32,
+  active: bool,
+  target: RenderTarget,
 }
 
 impl Camera {
@@ -82,19 +204,25 @@ impl Camera {
     CameraBuilder::new()
   }
 
+  /// The view matrix uses -Y as up to account for the flipped Y axis in vulkans clip space
   #[inline]
-  pub(crate) fn update_view_matrix(&mut self, transform: &GlobalTransform) {
-    self.view_matrix = glam::Mat4::look_at_rh(
+  pub(crate) fn compute_view_matrix(transform: &GlobalTransform) -> glam::Mat4 {
+    glam::Mat4::look_at_rh(
       transform.position(),
       transform.position() + transform.rotation() * glam::Vec3::X,
       transform.rotation() * -glam::Vec3::Y,
-    );
+    )
+  }
+
+  #[inline]
+  pub(crate) fn update_view_matrix(&mut self, transform: &GlobalTransform) {
+    self.view_matrix = Self::compute_view_matrix(transform);
   }
 
   #[inline]
   fn update_projection_matrix(&mut self) {
-    self.projection_matrix =
-      glam::Mat4::perspective_rh(self.fov, self.aspect_ratio, self.near, self.far);
+    self.projection.sanitize();
+    self.projection_matrix = self.projection.matrix(self.viewport_size);
   }
 
   #[inline]
@@ -106,4 +234,438 @@ impl Camera {
   pub fn projection_matrix(&self) -> glam::Mat4 {
     self.projection_matrix
   }
+
+  #[inline]
+  pub fn projection(&self) -> &Projection {
+    &self.projection
+  }
+
+  pub fn set_projection(&mut self, projection: Projection) {
+    self.projection = projection;
+    self.projection.set_aspect_ratio(self.viewport_size);
+    self.update_projection_matrix();
+  }
+
+  /// The size in pixels of the area the camera renders to
+  #[inline]
+  pub fn viewport_size(&self) -> glam::Vec2 {
+    self.viewport_size
+  }
+
+  /// Is updated automatically when the window or the viewport is resized
+  pub fn set_viewport_size(&mut self, viewport_size: glam::Vec2) {
+    if viewport_size.x <= 0.0 || viewport_size.y <= 0.0 {
+      return;
+    }
+
+    self.viewport_size = viewport_size;
+    self.projection.set_aspect_ratio(viewport_size);
+    self.update_projection_matrix();
+  }
+
+  #[inline]
+  pub fn viewport(&self) -> Viewport {
+    self.viewport
+  }
+
+  #[inline]
+  pub fn set_viewport(&mut self, viewport: Viewport) {
+    self.viewport = viewport;
+  }
+
+  /// Cameras with a lower priority are rendered first, so higher priorities are drawn on top
+  #[inline]
+  pub fn priority(&self) -> i32 {
+    self.priority
+  }
+
+  #[inline]
+  pub fn set_priority(&mut self, priority: i32) {
+    self.priority = priority;
+  }
+
+  #[inline]
+  pub fn is_active(&self) -> bool {
+    self.active
+  }
+
+  #[inline]
+  pub fn set_active(&mut self, active: bool) {
+    self.active = active;
+  }
+
+  #[inline]
+  pub fn target(&self) -> RenderTarget {
+    self.target
+  }
+
+  #[inline]
+  pub fn set_target(&mut self, target: RenderTarget) {
+    self.target = target;
+  }
+
+  /// Returns the ray going from the camera through the given pixel, with (0, 0) being the top left corner
+  pub fn viewport_to_world_ray(
+    &self,
+    transform: &GlobalTransform,
+    viewport_position: glam::Vec2,
+  ) -> Option<Ray> {
+    let ndc = viewport_position / self.viewport_size * 2.0 - glam::Vec2::ONE;
+    let inverse = (self.projection_matrix * Self::compute_view_matrix(transform)).inverse();
+
+    let near = inverse.project_point3(ndc.extend(0.0));
+    let far = inverse.project_point3(ndc.extend(1.0));
+
+    let direction = (far - near).try_normalize()?;
+    if !near.is_finite() {
+      return None;
+    }
+
+    Some(Ray {
+      origin: near,
+      direction,
+    })
+  }
+
+  /// Returns the pixel the given world position is rendered at, with (0, 0) being the top left corner.
+  /// Returns `None` if the position is outside of the depth range of the camera
+  pub fn world_to_viewport(
+    &self,
+    transform: &GlobalTransform,
+    world_position: glam::Vec3,
+  ) -> Option<glam::Vec2> {
+    let clip =
+      self.projection_matrix * Self::compute_view_matrix(transform) * world_position.extend(1.0);
+    if clip.w <= 0.0 {
+      return None;
+    }
+
+    let ndc = clip.truncate() / clip.w;
+    if !(0.0..=1.0).contains(&ndc.z) {
+      return None;
+    }
+
+    Some((ndc.truncate() + glam::Vec2::ONE) / 2.0 * self.viewport_size)
+  }
+}
+
+impl Viewport {
+  #[inline]
+  pub fn new(position: glam::Vec2, size: glam::Vec2) -> Self {
+    Self { position, size }
+  }
+
+  /// Returns the offset and extent in pixels inside a target of the given size.
+  /// Returns `None` if no pixel of the target is covered
+  pub fn physical_rect(&self, target_size: glam::UVec2) -> Option<(glam::UVec2, glam::UVec2)> {
+    let target = target_size.as_vec2();
+    let start = (self.position.clamp(glam::Vec2::ZERO, glam::Vec2::ONE) * target).round();
+    let end =
+      ((self.position + self.size).clamp(glam::Vec2::ZERO, glam::Vec2::ONE) * target).round();
+
+    if end.x <= start.x || end.y <= start.y {
+      return None;
+    }
+
+    Some((start.as_uvec2(), (end - start).as_uvec2()))
+  }
+}
+
+impl Default for Viewport {
+  fn default() -> Self {
+    Self {
+      position: glam::Vec2::ZERO,
+      size: glam::Vec2::ONE,
+    }
+  }
+}
+
+impl Projection {
+  pub fn matrix(&self, viewport_size: glam::Vec2) -> glam::Mat4 {
+    match self {
+      Projection::Perspective(perspective) => glam::Mat4::perspective_rh(
+        perspective.fov,
+        perspective.aspect_ratio,
+        perspective.near,
+        perspective.far,
+      ),
+      Projection::Orthographic(orthographic) => {
+        let size = orthographic.area_size(viewport_size) * orthographic.scale / 2.0;
+        glam::Mat4::orthographic_rh(
+          -size.x,
+          size.x,
+          -size.y,
+          size.y,
+          orthographic.near,
+          orthographic.far,
+        )
+      }
+      Projection::Custom(matrix) => *matrix,
+    }
+  }
+
+  fn set_aspect_ratio(&mut self, viewport_size: glam::Vec2) {
+    if let Projection::Perspective(perspective) = self {
+      perspective.aspect_ratio = viewport_size.x / viewport_size.y;
+    }
+  }
+
+  fn sanitize(&mut self) {
+    match self {
+      Projection::Perspective(perspective) => {
+        perspective.near = perspective.near.max(MIN_DEPTH_RANGE);
+        perspective.far = perspective.far.max(perspective.near + MIN_DEPTH_RANGE);
+      }
+      Projection::Orthographic(orthographic) => {
+        orthographic.far = orthographic.far.max(orthographic.near + MIN_DEPTH_RANGE);
+      }
+      Projection::Custom(_) => (),
+    }
+  }
+}
+
+impl OrthographicProjection {
+  /// The visible area in world units before `scale` is applied
+  pub fn area_size(&self, viewport_size: glam::Vec2) -> glam::Vec2 {
+    let aspect_ratio = if viewport_size.y > 0.0 {
+      viewport_size.x / viewport_size.y
+    } else {
+      1.0
+    };
+
+    match self.scaling_mode {
+      ScalingMode::WindowSize(pixels_per_unit) => viewport_size / pixels_per_unit,
+      ScalingMode::Fixed { width, height } => glam::Vec2::new(width, height),
+      ScalingMode::FixedVertical(height) => glam::Vec2::new(height * aspect_ratio, height),
+      ScalingMode::FixedHorizontal(width) => glam::Vec2::new(width, width / aspect_ratio),
+    }
+  }
+}
+
+impl Ray {
+  #[inline]
+  pub fn get_point(&self, distance: f32) -> glam::Vec3 {
+    self.origin + self.direction * distance
+  }
+}
+
+impl Default for PerspectiveProjection {
+  fn default() -> Self {
+    Self {
+      fov: f32::consts::FRAC_PI_3,
+      aspect_ratio: DEFAULT_VIEWPORT_SIZE.x / DEFAULT_VIEWPORT_SIZE.y,
+      near: 0.1,
+      far: 100.0,
+    }
+  }
+}
+
+impl Default for OrthographicProjection {
+  fn default() -> Self {
+    Self {
+      near: 0.0,
+      far: 100.0,
+      scaling_mode: ScalingMode::FixedVertical(10.0),
+      scale: 1.0,
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use gravitron_components::components::transform::{GlobalTransform, Transform};
+  use gravitron_hierarchy::propagation::PropagationUpdate;
+
+  use super::{Camera, OrthographicProjection, Projection, RenderTarget, ScalingMode, Viewport};
+  use crate::renderer::TextureHandle;
+
+  const EPSILON: f32 = 1e-3;
+
+  fn global(transform: Transform) -> GlobalTransform {
+    let mut global = GlobalTransform::default();
+    global.update(&transform);
+    global
+  }
+
+  #[test]
+  fn near_far() {
+    let camera = Camera::builder().near(0.5).far(500.0).build();
+    let Projection::Perspective(perspective) = camera.projection() else {
+      panic!("default projection is not perspective");
+    };
+    assert_eq!(perspective.near, 0.5);
+    assert_eq!(perspective.far, 500.0);
+
+    let camera = Camera::builder().near(10.0).far(1.0).build();
+    let Projection::Perspective(perspective) = camera.projection() else {
+      panic!("default projection is not perspective");
+    };
+    assert!(perspective.near < perspective.far);
+  }
+
+  #[test]
+  fn aspect_ratio_follows_viewport() {
+    let mut camera = Camera::builder().build();
+    camera.set_viewport_size(glam::Vec2::new(1920.0, 1080.0));
+
+    let Projection::Perspective(perspective) = *camera.projection() else {
+      panic!("default projection is not perspective");
+    };
+    assert_eq!(perspective.aspect_ratio, 1920.0 / 1080.0);
+    assert!(camera.projection_matrix().abs_diff_eq(
+      glam::Mat4::perspective_rh(perspective.fov, 1920.0 / 1080.0, 0.1, 100.0),
+      EPSILON
+    ));
+
+    camera.set_viewport_size(glam::Vec2::new(0.0, 1080.0));
+    assert_eq!(camera.viewport_size(), glam::Vec2::new(1920.0, 1080.0));
+  }
+
+  #[test]
+  fn orthographic_scaling() {
+    let viewport = glam::Vec2::new(200.0, 100.0);
+    let area = |scaling_mode| {
+      OrthographicProjection {
+        scaling_mode,
+        ..Default::default()
+      }
+      .area_size(viewport)
+    };
+
+    assert_eq!(
+      area(ScalingMode::WindowSize(10.0)),
+      glam::Vec2::new(20.0, 10.0)
+    );
+    assert_eq!(
+      area(ScalingMode::Fixed {
+        width: 3.0,
+        height: 4.0
+      }),
+      glam::Vec2::new(3.0, 4.0)
+    );
+    assert_eq!(
+      area(ScalingMode::FixedVertical(5.0)),
+      glam::Vec2::new(10.0, 5.0)
+    );
+    assert_eq!(
+      area(ScalingMode::FixedHorizontal(5.0)),
+      glam::Vec2::new(5.0, 2.5)
+    );
+
+    let mut camera = Camera::builder()
+      .orthographic(ScalingMode::FixedVertical(5.0))
+      .build();
+    camera.set_viewport_size(viewport);
+    assert!(camera.projection_matrix().abs_diff_eq(
+      glam::Mat4::orthographic_rh(-5.0, 5.0, -2.5, 2.5, 0.0, 100.0),
+      EPSILON
+    ));
+  }
+
+  #[test]
+  fn custom_projection() {
+    let matrix = glam::Mat4::from_scale(glam::Vec3::splat(2.0));
+    let mut camera = Camera::builder()
+      .projection(Projection::Custom(matrix))
+      .build();
+    camera.set_viewport_size(glam::Vec2::new(10.0, 10.0));
+    assert_eq!(camera.projection_matrix(), matrix);
+  }
+
+  #[test]
+  fn viewport_round_trip() {
+    let transform = global(
+      Transform::default()
+        .with_position(glam::Vec3::new(10.0, 10.0, 10.0))
+        .looking_at(glam::Vec3::ZERO, glam::Vec3::Y),
+    );
+
+    for projection in [
+      Projection::Perspective(Default::default()),
+      Projection::Orthographic(Default::default()),
+    ] {
+      let mut camera = Camera::builder().projection(projection).build();
+      camera.set_viewport_size(glam::Vec2::new(800.0, 600.0));
+
+      let center = camera
+        .world_to_viewport(&transform, glam::Vec3::ZERO)
+        .unwrap();
+      assert!(center.abs_diff_eq(glam::Vec2::new(400.0, 300.0), EPSILON));
+
+      let above = camera
+        .world_to_viewport(&transform, glam::Vec3::new(0.0, 1.0, 0.0))
+        .unwrap();
+      assert!(above.y < center.y, "world up is not screen up");
+
+      let ray = camera
+        .viewport_to_world_ray(&transform, glam::Vec2::new(400.0, 300.0))
+        .unwrap();
+      let forward = glam::Vec3::splat(-1.0).normalize();
+      assert!(ray.direction.abs_diff_eq(forward, EPSILON));
+
+      let pixel = glam::Vec2::new(123.0, 456.0);
+      let ray = camera.viewport_to_world_ray(&transform, pixel).unwrap();
+      let point = camera
+        .world_to_viewport(&transform, ray.get_point(5.0))
+        .unwrap();
+      assert!(point.abs_diff_eq(pixel, 0.01));
+    }
+  }
+
+  #[test]
+  fn behind_camera() {
+    let transform = global(Transform::default());
+    let camera = Camera::builder().build();
+
+    assert!(camera
+      .world_to_viewport(&transform, glam::Vec3::new(-5.0, 0.0, 0.0))
+      .is_none());
+    assert!(camera
+      .world_to_viewport(&transform, glam::Vec3::new(5.0, 0.0, 0.0))
+      .is_some());
+  }
+
+  #[test]
+  fn viewport_physical_rect() {
+    let target = glam::UVec2::new(800, 600);
+    assert_eq!(
+      Viewport::default().physical_rect(target),
+      Some((glam::UVec2::ZERO, target))
+    );
+
+    let right_half = Viewport::new(glam::Vec2::new(0.5, 0.0), glam::Vec2::new(0.5, 1.0));
+    assert_eq!(
+      right_half.physical_rect(target),
+      Some((glam::UVec2::new(400, 0), glam::UVec2::new(400, 600)))
+    );
+
+    let overflowing = Viewport::new(glam::Vec2::new(0.75, 0.75), glam::Vec2::new(0.5, 0.5));
+    assert_eq!(
+      overflowing.physical_rect(target),
+      Some((glam::UVec2::new(600, 450), glam::UVec2::new(200, 150)))
+    );
+
+    let outside = Viewport::new(glam::Vec2::new(1.5, 0.0), glam::Vec2::ONE);
+    assert_eq!(outside.physical_rect(target), None);
+    let empty = Viewport::new(glam::Vec2::ZERO, glam::Vec2::new(0.0, 1.0));
+    assert_eq!(empty.physical_rect(target), None);
+  }
+
+  #[test]
+  fn texture_targets_first() {
+    let mut targets = vec![
+      RenderTarget::Window,
+      RenderTarget::Texture(TextureHandle(3)),
+      RenderTarget::Texture(TextureHandle(1)),
+    ];
+    targets.sort();
+    assert_eq!(
+      targets,
+      vec![
+        RenderTarget::Texture(TextureHandle(1)),
+        RenderTarget::Texture(TextureHandle(3)),
+        RenderTarget::Window
+      ]
+    );
+  }
 }
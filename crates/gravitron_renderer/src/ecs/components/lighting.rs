@@ -6,6 +6,8 @@ pub struct DirectionalLight {
   pub intensity: f32,
   pub ambient_color: glam::Vec3,
   pub ambient_intensity: f32,
+  /// Renders cascaded shadow maps around the first camera rendering to the window
+  pub cast_shadows: bool,
 }
 
 #[derive(Component)]
@@ -21,4 +23,6 @@ pub struct SpotLight {
   pub intensity: f32,
   pub range: f32,
   pub angle: f32,
+  /// Limited to `ShadowConfig::max_spot_shadows` spot lights
+  pub cast_shadows: bool,
 }
@@ -1,3 +1,5 @@
 pub mod camera;
 pub mod lighting;
 pub mod renderer;
+pub mod skybox;
+pub mod visibility;
@@ -1,9 +1,145 @@
 use gravitron_ecs::Component;
 
-use crate::{model::model::ModelHandle, renderer::resources::material::Material};
+use crate::{
+  asset::{AssetServer, Handle},
+  model::{mesh::Mesh, model::ModelHandle},
+  renderer::{resources::material::Material, TextureHandle},
+};
 
 #[derive(Component)]
 pub struct MeshRenderer {
-  pub model_id: ModelHandle,
-  pub material: Material,
+  pub model_id: MeshModel,
+  pub material: Handle<Material>,
+}
+
+/// The model drawn by a `MeshRenderer`
+#[derive(Clone, Debug, PartialEq)]
+pub enum MeshModel {
+  /// A model of the `ModelManager` like `CUBE_MODEL`, it is not freed by the renderer
+  Model(ModelHandle),
+  /// Keeps the mesh alive as long as the entity exists, nothing is drawn until it is uploaded
+  Mesh(Handle<Mesh>),
+}
+
+/// Replaces single values of the `MeshRenderer` material for this entity only.
+/// Every overridden instance uploads its own copy of the material each frame
+#[derive(Component, Clone, Default, Debug, PartialEq)]
+pub struct MaterialOverride {
+  pub color: Option<glam::Vec4>,
+  pub texture_id: Option<TextureHandle>,
+  pub metallic: Option<f32>,
+  pub roughness: Option<f32>,
+  pub emissive: Option<glam::Vec3>,
+}
+
+/// Entities with this component are always drawn, even when outside of every camera
+#[derive(Component)]
+pub struct NoFrustumCulling;
+
+/// The entity is not drawn into shadow maps
+#[derive(Component)]
+pub struct NotShadowCaster;
+
+/// Shadows are not cast onto the entity
+#[derive(Component)]
+pub struct NotShadowReceiver;
+
+impl MeshModel {
+  /// `None` while the mesh is loading or if it was not uploaded
+  pub(crate) fn resolve(&self, asset_server: &AssetServer) -> Option<ModelHandle> {
+    match self {
+      MeshModel::Model(model) => Some(*model),
+      MeshModel::Mesh(mesh) => asset_server.get(mesh).and_then(Mesh::model),
+    }
+  }
+}
+
+impl From<ModelHandle> for MeshModel {
+  #[inline]
+  fn from(model: ModelHandle) -> Self {
+    MeshModel::Model(model)
+  }
+}
+
+impl From<Handle<Mesh>> for MeshModel {
+  #[inline]
+  fn from(mesh: Handle<Mesh>) -> Self {
+    MeshModel::Mesh(mesh)
+  }
+}
+
+impl MaterialOverride {
+  #[inline]
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  #[inline]
+  pub fn with_color(mut self, color: glam::Vec4) -> Self {
+    self.color = Some(color);
+    self
+  }
+
+  #[inline]
+  pub fn with_texture(mut self, texture: TextureHandle) -> Self {
+    self.texture_id = Some(texture);
+    self
+  }
+
+  #[inline]
+  pub fn with_metallic(mut self, metallic: f32) -> Self {
+    self.metallic = Some(metallic);
+    self
+  }
+
+  #[inline]
+  pub fn with_roughness(mut self, roughness: f32) -> Self {
+    self.roughness = Some(roughness);
+    self
+  }
+
+  #[inline]
+  pub fn with_emissive(mut self, emissive: glam::Vec3) -> Self {
+    self.emissive = Some(emissive);
+    self
+  }
+
+  /// The material with the overridden values replaced
+  pub fn apply(&self, material: &Material) -> Material {
+    Material {
+      color: self.color.unwrap_or(material.color),
+      texture_id: self.texture_id.unwrap_or(material.texture_id),
+      metallic: self.metallic.unwrap_or(material.metallic),
+      roughness: self.roughness.unwrap_or(material.roughness),
+      emissive: self.emissive.unwrap_or(material.emissive),
+      ..material.clone()
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::MeshModel;
+  use crate::{
+    asset::AssetServer,
+    model::{mesh::Mesh, model::ModelHandle},
+  };
+
+  #[test]
+  fn mesh_kept_alive() {
+    let mut server = AssetServer::default();
+    let handle = server.add(Mesh::default());
+    let renderer = MeshModel::from(handle.clone());
+    assert_eq!(renderer.resolve(&server), None);
+
+    server.get_mut(&handle).unwrap().model = Some(ModelHandle(3));
+    drop(handle);
+    server.update();
+    assert!(server.drain_removed::<Mesh>().is_empty());
+    assert_eq!(renderer.resolve(&server), Some(ModelHandle(3)));
+
+    drop(renderer);
+    server.update();
+    assert_eq!(server.drain_removed::<Mesh>().len(), 1);
+  }
 }
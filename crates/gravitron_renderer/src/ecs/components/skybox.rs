@@ -0,0 +1,114 @@
+use std::{
+  path::Path,
+  sync::atomic::{AtomicU64, Ordering},
+};
+
+use anyhow::Error;
+use gravitron_ecs::Component;
+
+use crate::{
+  error::TextureError,
+  texture::{
+    cubemap::{Cubemap, FACES},
+    ColorSpace, TextureData,
+  },
+};
+
+/// Largest face size a skybox is rendered with
+pub const MAX_SKYBOX_SIZE: u32 = 1024;
+
+static NEXT_SKYBOX: AtomicU64 = AtomicU64::new(0);
+
+/// Background of every camera and source of the ambient light. The environment maps are
+/// generated when a skybox is spawned, only one skybox is used at a time
+#[derive(Component)]
+pub struct Skybox {
+  image: SkyboxImage,
+  /// Scales the background and the environment lighting
+  pub intensity: f32,
+  id: u64,
+}
+
+enum SkyboxImage {
+  Equirect(TextureData),
+  Faces(Box<[TextureData; FACES]>),
+}
+
+impl Skybox {
+  /// An equirectangular image, usually an HDR or EXR panorama
+  pub fn equirect(image: TextureData) -> Self {
+    Self::new(SkyboxImage::Equirect(image))
+  }
+
+  /// Square faces in the order +X, -X, +Y, -Y, +Z, -Z
+  pub fn cubemap(faces: [TextureData; FACES]) -> Self {
+    Self::new(SkyboxImage::Faces(Box::new(faces)))
+  }
+
+  /// Loads an equirectangular image, 8 bit images are treated as sRGB
+  pub fn load_equirect(path: impl AsRef<Path>) -> Result<Self, Error> {
+    Ok(Self::equirect(TextureData::load_file(
+      path,
+      ColorSpace::Srgb,
+    )?))
+  }
+
+  #[inline]
+  pub fn set_intensity(mut self, intensity: f32) -> Self {
+    self.intensity = intensity;
+    self
+  }
+
+  fn new(image: SkyboxImage) -> Self {
+    Self {
+      image,
+      intensity: 1.0,
+      id: NEXT_SKYBOX.fetch_add(1, Ordering::Relaxed),
+    }
+  }
+
+  /// Unique for every skybox, so a new one can be detected
+  #[inline]
+  pub(crate) fn id(&self) -> u64 {
+    self.id
+  }
+
+  /// The skybox as linear cubemap with at most `MAX_SKYBOX_SIZE` texels per side
+  pub(crate) fn to_cubemap(&self) -> Result<Cubemap, Error> {
+    match &self.image {
+      SkyboxImage::Equirect(image) => {
+        // a face covers a quarter of the width and half of the height
+        let size = (image.height() / 2)
+          .max(1)
+          .next_power_of_two()
+          .min(MAX_SKYBOX_SIZE);
+        Cubemap::from_equirect(image.width(), image.height(), &linear_texels(image)?, size)
+      }
+      SkyboxImage::Faces(faces) => {
+        let size = faces[0].width();
+        if faces
+          .iter()
+          .any(|face| face.width() != size || face.height() != size)
+        {
+          return Err(TextureError::InvalidSize(faces[0].width(), faces[0].height()).into());
+        }
+
+        let texels = faces
+          .iter()
+          .map(linear_texels)
+          .collect::<Result<Vec<_>, _>>()?;
+        let mut cubemap = Cubemap::new(size, texels.try_into().expect("a face per side"))?;
+        while cubemap.size() > MAX_SKYBOX_SIZE {
+          cubemap = cubemap.downsample();
+        }
+        Ok(cubemap)
+      }
+    }
+  }
+}
+
+fn linear_texels(image: &TextureData) -> Result<Vec<glam::Vec4>, Error> {
+  image
+    .linear_texels()
+    .ok_or_else(|| TextureError::Unsupported(format!("{:?} skybox images", image.format())).into())
+}
@@ -0,0 +1,155 @@
+use gravitron_ecs::Component;
+use gravitron_hierarchy::propagation::PropagationUpdate;
+
+/// Children need a `Visibility` as well to inherit the visibility of their parent
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Visibility {
+  Visible,
+  Hidden,
+  /// Uses the visibility of the parent, entities without a parent are visible
+  #[default]
+  Inherited,
+}
+
+/// Computed from the `Visibility` of the entity and its parents, entities without it are visible
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ViewVisibility(bool);
+
+/// Entities are only rendered by cameras sharing at least one layer with them.
+/// Entities and cameras without this component are on layer 0
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RenderLayers(u32);
+
+impl ViewVisibility {
+  #[inline]
+  pub fn is_visible(&self) -> bool {
+    self.0
+  }
+}
+
+impl Default for ViewVisibility {
+  fn default() -> Self {
+    Self(true)
+  }
+}
+
+impl PropagationUpdate for ViewVisibility {
+  type Data = Visibility;
+
+  fn copy(&self) -> Self {
+    *self
+  }
+
+  fn update(&mut self, data: &Self::Data) {
+    match data {
+      Visibility::Visible => self.0 = true,
+      Visibility::Hidden => self.0 = false,
+      Visibility::Inherited => (),
+    }
+  }
+}
+
+impl RenderLayers {
+  pub const MAX_LAYERS: u8 = 32;
+
+  /// Layers outside of `0..MAX_LAYERS` are ignored
+  #[inline]
+  pub fn layer(layer: u8) -> Self {
+    Self::none().with(layer)
+  }
+
+  #[inline]
+  pub fn none() -> Self {
+    Self(0)
+  }
+
+  #[inline]
+  pub fn all() -> Self {
+    Self(u32::MAX)
+  }
+
+  #[inline]
+  pub fn from_bits(bits: u32) -> Self {
+    Self(bits)
+  }
+
+  #[inline]
+  pub fn bits(&self) -> u32 {
+    self.0
+  }
+
+  #[inline]
+  pub fn with(mut self, layer: u8) -> Self {
+    if layer < Self::MAX_LAYERS {
+      self.0 |= 1 << layer;
+    }
+    self
+  }
+
+  #[inline]
+  pub fn without(mut self, layer: u8) -> Self {
+    if layer < Self::MAX_LAYERS {
+      self.0 &= !(1 << layer);
+    }
+    self
+  }
+
+  #[inline]
+  pub fn contains(&self, layer: u8) -> bool {
+    layer < Self::MAX_LAYERS && self.0 & (1 << layer) != 0
+  }
+
+  #[inline]
+  pub fn intersects(&self, other: &RenderLayers) -> bool {
+    self.0 & other.0 != 0
+  }
+}
+
+impl Default for RenderLayers {
+  fn default() -> Self {
+    Self::layer(0)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use gravitron_hierarchy::propagation::PropagationUpdate;
+
+  use super::{RenderLayers, ViewVisibility, Visibility};
+
+  fn propagate(chain: &[Visibility]) -> bool {
+    let mut state = ViewVisibility::default();
+    for visibility in chain {
+      state.update(visibility);
+    }
+    state.is_visible()
+  }
+
+  #[test]
+  fn visibility_propagation() {
+    assert!(propagate(&[Visibility::Inherited]));
+    assert!(!propagate(&[Visibility::Hidden]));
+    assert!(!propagate(&[Visibility::Hidden, Visibility::Inherited]));
+    assert!(propagate(&[Visibility::Hidden, Visibility::Visible]));
+    assert!(!propagate(&[
+      Visibility::Visible,
+      Visibility::Inherited,
+      Visibility::Hidden
+    ]));
+  }
+
+  #[test]
+  fn render_layers() {
+    let layers = RenderLayers::layer(1).with(3);
+    assert_eq!(layers.bits(), 0b1010);
+    assert!(layers.contains(3));
+    assert!(!layers.contains(0));
+    assert!(!layers.without(3).contains(3));
+
+    assert!(!RenderLayers::default().intersects(&layers));
+    assert!(RenderLayers::all().intersects(&layers));
+    assert!(!RenderLayers::none().intersects(&RenderLayers::all()));
+
+    assert_eq!(RenderLayers::layer(32), RenderLayers::none());
+  }
+}
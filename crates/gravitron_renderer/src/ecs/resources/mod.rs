@@ -1,4 +1,5 @@
 use anyhow::Error;
+use ash::vk;
 use gravitron_plugin::{
   app::{App, AppBuilder, Cleanup, Finalize},
   config::AppConfig,
@@ -14,12 +15,26 @@ use crate::debug::Debugger;
 use crate::{
   config::RendererConfig,
   device::Device,
+  error::RendererInitError,
   instance::{InstanceDevice, InstanceDeviceConfig},
+  material::MaterialManager,
   pipeline::{manager::PipelineManager, pools::Pools},
-  renderer::Renderer,
+  renderer::{output::OutputTarget, Renderer},
   surface::Surface,
+  texture::TextureManager,
 };
 
+pub mod screenshot;
+pub mod stats;
+
+pub mod asset {
+  pub use crate::asset::*;
+}
+
+pub mod material {
+  pub use crate::material::*;
+}
+
 pub mod memory {
   pub use crate::memory::*;
 }
@@ -36,18 +51,44 @@ pub mod renderer {
   pub use crate::renderer::*;
 }
 
-struct Vulkan {
+pub mod texture {
+  pub use crate::texture::*;
+}
+
+pub(crate) struct Vulkan {
   #[allow(dead_code)]
   entry: ash::Entry,
   #[cfg(feature = "debug")]
   debugger: Debugger,
   instance: InstanceDevice,
-  surface: Surface,
+  surface: Option<Surface>,
   device: Device,
   pools: Pools,
 }
 
 impl Vulkan {
+  /// Adapts the window output to the window size, does nothing when rendering headless
+  pub(crate) fn resize_output(
+    &mut self,
+    renderer: &mut Renderer,
+    memory_manager: &mut MemoryManager,
+    descriptor_manager: &mut DescriptorManager,
+    size: vk::Extent2D,
+  ) -> Result<(), Error> {
+    let Some(surface) = &self.surface else {
+      return Ok(());
+    };
+    renderer.resize_output(
+      &self.instance,
+      &self.device,
+      surface,
+      &mut self.pools,
+      memory_manager,
+      descriptor_manager,
+      size,
+    )
+  }
+
   fn wait_for_idle(&self) {
     unsafe {
       self
@@ -63,7 +104,9 @@ impl Vulkan {
       self.pools.cleanup();
     }
     self.device.cleanup();
-    self.surface.cleanup();
+    if let Some(surface) = &self.surface {
+      surface.cleanup();
+    }
     #[cfg(feature = "debug")]
     self.debugger.cleanup();
     self.instance.cleanup();
@@ -75,6 +118,8 @@ pub(crate) struct Resources {
   model_manager: ModelManager,
   descriptor_manager: DescriptorManager,
   pipeline_manager: PipelineManager,
+  texture_manager: TextureManager,
+  material_manager: MaterialManager,
   renderer: Renderer,
   vulkan: Vulkan,
 }
@@ -85,17 +130,21 @@ impl Resources {
     builder.add_resource(self.model_manager);
     builder.add_resource(self.descriptor_manager);
     builder.add_resource(self.pipeline_manager);
+    builder.add_resource(self.texture_manager);
+    builder.add_resource(self.material_manager);
     builder.add_resource(self.renderer);
     builder.add_resource(self.vulkan);
   }
 
+  /// Renders into the window if there is one and headless rendering is not enabled
   pub(crate) fn create(
     mut config: RendererConfig,
     app_config: &AppConfig,
-    window_config: &WindowConfig,
-    window: &WindowHandle,
+    window: Option<(&WindowConfig, &WindowHandle)>,
     #[cfg(target_os = "linux")] is_wayland: bool,
   ) -> Result<Self, Error> {
+    let window = window.filter(|_| config.headless.is_none());
+
     let entry = unsafe { ash::Entry::load() }?;
 
     #[cfg(feature = "debug")]
@@ -114,7 +163,7 @@ impl Resources {
       &mut instance_config,
       &entry,
       app_config,
-      window_config,
+      window.map(|(window_config, _)| window_config),
       #[cfg(target_os = "linux")]
       is_wayland,
     )?;
@@ -122,37 +171,48 @@ impl Resources {
     #[cfg(feature = "debug")]
     let debugger = Debugger::init(&entry, instance.get_instance(), debugger_info)?;
 
-    let surface = Surface::init(&entry, instance.get_instance(), window)?;
+    let surface = window
+      .map(|(_, window)| Surface::init(&entry, instance.get_instance(), window))
+      .transpose()?;
     let device = Device::init(
       instance.get_instance(),
       instance.get_physical_device(),
-      &surface,
+      surface.as_ref(),
       &config.device,
     )?;
 
+    let target = match (&surface, window) {
+      (Some(surface), Some((window_config, _))) => {
+        OutputTarget::Window(surface, window_config, &config.swapchain)
+      }
+      _ => {
+        let (width, height) = config.headless.ok_or(RendererInitError::NoTarget)?;
+        OutputTarget::Headless(vk::Extent2D { width, height })
+      }
+    };
+
     let mut pools = Pools::init(device.get_device(), device.get_queue_families())?;
 
     let mut memory_manager = MemoryManager::new(&instance, &device, &mut pools)?;
-    let model_manager = ModelManager::new(&mut memory_manager)?;
     let mut descriptor_manager = DescriptorManager::new(device.get_device())?;
 
-    let (mut renderer, pipeline_manager) = Renderer::init(
+    #[cfg_attr(not(feature = "hot_reload"), allow(unused_mut))]
+    let (mut renderer, mut pipeline_manager, texture_manager) = Renderer::init(
       &instance,
       &device,
       &mut memory_manager,
       &mut descriptor_manager,
-      &surface,
-      window_config,
+      target,
       &mut pools,
       &config.graphics,
     )?;
+    let model_manager = ModelManager::new(&mut memory_manager, renderer.frames_in_flight())?;
 
-    renderer.record_command_buffer(
-      &pipeline_manager,
-      &descriptor_manager,
-      &mut memory_manager,
-      &model_manager,
-    )?;
+    if config.hot_reload {
+      renderer.watch_texture_files();
+      #[cfg(feature = "hot_reload")]
+      pipeline_manager.enable_hot_reload();
+    }
 
     let vulkan = Vulkan {
       entry,
@@ -169,6 +229,8 @@ impl Resources {
       model_manager,
       descriptor_manager,
       pipeline_manager,
+      texture_manager,
+      material_manager: MaterialManager::new(),
       renderer,
       vulkan,
     })
@@ -0,0 +1,167 @@
+use std::{
+  path::{Path, PathBuf},
+  sync::mpsc::{self, Sender},
+  thread::{self, JoinHandle},
+};
+
+use image::RgbaImage;
+use log::{error, info};
+
+/// Captures what `RenderTarget::Window` cameras rendered, the window image or the offscreen image when rendering headless.
+/// Captured frames are saved as png on a background thread
+#[derive(Default)]
+pub struct Screenshots {
+  requests: Vec<PathBuf>,
+  recording: Option<Recording>,
+  encoder: Option<Encoder>,
+}
+
+struct Recording {
+  directory: PathBuf,
+  next: u32,
+  remaining: u32,
+}
+
+struct Encoder {
+  sender: Sender<(RgbaImage, Vec<PathBuf>)>,
+  thread: JoinHandle<()>,
+}
+
+impl Screenshots {
+  /// Saves the next frame to the path
+  #[inline]
+  pub fn take(&mut self, path: impl Into<PathBuf>) {
+    self.requests.push(path.into());
+  }
+
+  /// Saves the next `frames` frames as numbered images into the directory, replacing a running recording
+  pub fn record(&mut self, directory: impl Into<PathBuf>, frames: u32) {
+    self.recording = (frames > 0).then(|| Recording {
+      directory: directory.into(),
+      next: 0,
+      remaining: frames,
+    });
+  }
+
+  #[inline]
+  pub fn stop_recording(&mut self) {
+    self.recording = None;
+  }
+
+  #[inline]
+  pub fn is_recording(&self) -> bool {
+    self.recording.is_some()
+  }
+
+  /// Paths the current frame has to be saved to
+  pub(crate) fn next_capture(&mut self) -> Vec<PathBuf> {
+    let mut paths = std::mem::take(&mut self.requests);
+
+    if let Some(recording) = &mut self.recording {
+      if recording.next == 0 {
+        if let Err(err) = std::fs::create_dir_all(&recording.directory) {
+          error!("Failed to create {}: {err}", recording.directory.display());
+        }
+      }
+
+      paths.push(frame_path(&recording.directory, recording.next));
+      recording.next += 1;
+      recording.remaining -= 1;
+      if recording.remaining == 0 {
+        self.recording = None;
+      }
+    }
+
+    paths
+  }
+
+  pub(crate) fn save(&mut self, image: RgbaImage, paths: Vec<PathBuf>) {
+    let encoder = self.encoder.get_or_insert_with(|| {
+      let (sender, receiver) = mpsc::channel::<(RgbaImage, Vec<PathBuf>)>();
+      let thread = thread::spawn(move || {
+        for (image, paths) in receiver {
+          for path in paths {
+            match image.save_with_format(&path, image::ImageFormat::Png) {
+              Ok(()) => info!("Saved screenshot {}", path.display()),
+              Err(err) => error!("Failed to save screenshot {}: {err}", path.display()),
+            }
+          }
+        }
+      });
+
+      Encoder { sender, thread }
+    });
+
+    let _ = encoder.sender.send((image, paths));
+  }
+
+  /// Waits until every captured frame is written
+  pub(crate) fn finish(&mut self) {
+    if let Some(Encoder { sender, thread }) = self.encoder.take() {
+      drop(sender);
+      let _ = thread.join();
+    }
+  }
+}
+
+fn frame_path(directory: &Path, frame: u32) -> PathBuf {
+  directory.join(format!("{frame:05}.png"))
+}
+
+#[cfg(test)]
+mod test {
+  use std::path::PathBuf;
+
+  use super::Screenshots;
+
+  #[test]
+  fn requests() {
+    let mut screenshots = Screenshots::default();
+    assert!(screenshots.next_capture().is_empty());
+
+    screenshots.take("a.png");
+    screenshots.take("b.png");
+    assert_eq!(
+      screenshots.next_capture(),
+      vec![PathBuf::from("a.png"), PathBuf::from("b.png")]
+    );
+    assert!(screenshots.next_capture().is_empty());
+  }
+
+  #[test]
+  fn recording() {
+    let directory = std::env::temp_dir().join("gravitron_recording_test");
+    let mut screenshots = Screenshots::default();
+    screenshots.record(&directory, 2);
+    assert!(screenshots.is_recording());
+
+    // a screenshot during a recording is saved from the same frame
+    screenshots.take("shot.png");
+    assert_eq!(
+      screenshots.next_capture(),
+      vec![PathBuf::from("shot.png"), directory.join("00000.png")]
+    );
+    assert_eq!(
+      screenshots.next_capture(),
+      vec![directory.join("00001.png")]
+    );
+    assert!(!screenshots.is_recording());
+    assert!(screenshots.next_capture().is_empty());
+    assert!(directory.is_dir());
+  }
+
+  #[test]
+  fn encode() {
+    let path = std::env::temp_dir().join("gravitron_screenshot_test.png");
+    let mut screenshots = Screenshots::default();
+    screenshots.save(
+      image::RgbaImage::from_pixel(2, 1, image::Rgba([255, 0, 0, 255])),
+      vec![path.clone()],
+    );
+    screenshots.finish();
+
+    let image = image::open(&path).unwrap().into_rgba8();
+    assert_eq!(image.dimensions(), (2, 1));
+    assert_eq!(image.get_pixel(1, 0).0, [255, 0, 0, 255]);
+  }
+}
@@ -0,0 +1,14 @@
+/// Instance counts of the last frame, hidden entities are not counted
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CullingStats {
+  pub total: usize,
+  /// Instances outside of every camera frustum or not on any camera render layer
+  pub culled: usize,
+}
+
+impl CullingStats {
+  #[inline]
+  pub fn visible(&self) -> usize {
+    self.total - self.culled
+  }
+}
@@ -0,0 +1,121 @@
+use gravitron_ecs::systems::resources::{Res, ResMut};
+use log::error;
+
+use crate::{
+  asset::AssetServer,
+  memory::MemoryManager,
+  model::{mesh::Mesh, model::InstanceCount, ModelManager},
+  pipeline::{DescriptorManager, PipelineManager},
+  renderer::Renderer,
+  texture::{Texture, TextureManager},
+};
+
+pub fn update_assets(mut asset_server: ResMut<AssetServer>) {
+  asset_server.update();
+}
+
+/// Reloads texture files and shader files changed on disk, does nothing unless hot reloading is enabled
+pub fn reload_changed_files(
+  mut renderer: ResMut<Renderer>,
+  #[allow(unused_mut, unused_variables)] mut pipeline_manager: ResMut<PipelineManager>,
+  mut memory_manager: ResMut<MemoryManager>,
+  mut descriptor_manager: ResMut<DescriptorManager>,
+) {
+  renderer.reload_textures(&mut memory_manager, &mut descriptor_manager);
+  #[cfg(feature = "hot_reload")]
+  pipeline_manager.reload_changed(&descriptor_manager);
+}
+
+pub fn sync_mesh_assets(
+  renderer: Res<Renderer>,
+  mut asset_server: ResMut<AssetServer>,
+  mut model_manager: ResMut<ModelManager>,
+  mut memory_manager: ResMut<MemoryManager>,
+) {
+  model_manager.update(&mut memory_manager, renderer.is_drawing());
+
+  for mesh in asset_server.drain_removed::<Mesh>() {
+    if let Some(model) = mesh.model {
+      model_manager.remove_model(model);
+    }
+  }
+
+  for (id, previous) in asset_server.drain_replaced::<Mesh>() {
+    let (Some(model), Some(mesh)) = (previous.model, asset_server.asset_mut::<Mesh>(id)) else {
+      continue;
+    };
+
+    let vertices = std::mem::take(&mut mesh.vertices);
+    let indices = std::mem::take(&mut mesh.indices);
+    if model_manager.replace_model(&mut memory_manager, model, vertices, indices) {
+      mesh.model = Some(model);
+    } else {
+      error!("Failed to reload mesh {id:?}");
+      model_manager.remove_model(model);
+    }
+  }
+
+  for (id, mesh) in asset_server.iter_mut::<Mesh>() {
+    if mesh.model.is_some() || mesh.vertices.is_empty() {
+      continue;
+    }
+
+    let vertices = std::mem::take(&mut mesh.vertices);
+    let indices = std::mem::take(&mut mesh.indices);
+    mesh.model = model_manager.add_model(
+      &mut memory_manager,
+      vertices,
+      indices,
+      InstanceCount::Medium,
+    );
+
+    if mesh.model.is_none() {
+      error!("Failed to upload mesh {id:?}");
+    }
+  }
+}
+
+pub fn sync_texture_assets(
+  mut asset_server: ResMut<AssetServer>,
+  mut texture_manager: ResMut<TextureManager>,
+  mut memory_manager: ResMut<MemoryManager>,
+) {
+  for texture in asset_server.drain_removed::<Texture>() {
+    if let Some(handle) = texture.handle {
+      texture_manager.remove_texture(handle);
+    }
+  }
+
+  for (id, previous) in asset_server.drain_replaced::<Texture>() {
+    let (Some(handle), Some(texture)) = (previous.handle, asset_server.asset_mut::<Texture>(id))
+    else {
+      continue;
+    };
+    let Some(data) = texture.data.take() else {
+      continue;
+    };
+
+    match texture_manager.replace_texture_data(&mut memory_manager, handle, data, texture.sampler) {
+      Ok(true) => texture.handle = Some(handle),
+      Ok(false) => {}
+      Err(err) => {
+        error!("Failed to reload texture {id:?}: {err}");
+        texture_manager.remove_texture(handle);
+      }
+    }
+  }
+
+  for (id, texture) in asset_server.iter_mut::<Texture>() {
+    if texture.handle.is_some() {
+      continue;
+    }
+    let Some(data) = texture.data.take() else {
+      continue;
+    };
+
+    match texture_manager.add_texture_data(&mut memory_manager, data, texture.sampler) {
+      Ok(handle) => texture.handle = Some(handle),
+      Err(err) => error!("Failed to upload texture {id:?}: {err}"),
+    }
+  }
+}
@@ -0,0 +1,22 @@
+use gravitron_ecs::systems::{query::Query, resources::Res};
+
+use crate::{ecs::components::camera::Camera, renderer::Renderer};
+
+/// Window cameras follow the size of the swapchain, or the offscreen image when rendering headless
+pub fn update_camera_projection(renderer: Res<Renderer>, cameras: Query<&mut Camera>) {
+  for (_, mut camera) in cameras {
+    let Some(extent) = renderer.target_extent(camera.target()) else {
+      continue;
+    };
+    let target_size = glam::UVec2::new(extent.width, extent.height);
+
+    let Some((_, size)) = camera.viewport().physical_rect(target_size) else {
+      continue;
+    };
+
+    let size = size.as_vec2();
+    if camera.viewport_size() != size {
+      camera.set_viewport_size(size);
+    }
+  }
+}
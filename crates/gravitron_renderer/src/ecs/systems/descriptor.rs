@@ -1,7 +1,6 @@
 use std::ops::Deref;
 
 use gravitron_components::components::transform::GlobalTransform;
-use gravitron_ecs::systems::query::filter::{Added, Changed, Or};
 use gravitron_ecs::systems::{
   query::Query,
   resources::{Res, ResMut},
@@ -11,9 +10,17 @@ use crate::ecs::components::lighting::{
   DirectionalLight as DirectionalLightComp, PointLight as PointLightComp,
   SpotLight as SpotLightComp,
 };
-use crate::renderer::resources::lighting::{DirectionalLight, LightInfo, PointLight, SpotLight};
+use crate::ecs::components::visibility::RenderLayers;
+use crate::model::bounds::BoundingSphere;
+use crate::pipeline::descriptor::DescriptorHandle;
+use crate::renderer::resources::camera::CameraData;
+use crate::renderer::resources::cluster::{spot_bounds, LightClusters};
+use crate::renderer::resources::lighting::{
+  DirectionalLight, LightInfo, PointLight, SpotLight, NO_SHADOW,
+};
 use crate::renderer::{
-  CAMERA_DESCRIPTOR, LIGHT_INFO_DESCRIPTOR, POINT_LIGHT_DESCRIPTOR, SPOT_LIGHT_DESCRIPTOR,
+  CameraPass, Renderer, CAMERA_DESCRIPTOR, CLUSTER_CAMERA_DESCRIPTOR, CLUSTER_DESCRIPTOR,
+  LIGHT_INDEX_DESCRIPTOR, LIGHT_INFO_DESCRIPTOR, POINT_LIGHT_DESCRIPTOR, SPOT_LIGHT_DESCRIPTOR,
 };
 use crate::{ecs::components::camera::Camera, memory::MemoryManager, pipeline::DescriptorManager};
 
@@ -21,33 +28,64 @@ use crate::{ecs::components::camera::Camera, memory::MemoryManager, pipeline::De
 pub fn update_default_descriptors(
   mut descriptor_manager: ResMut<DescriptorManager>,
   mut memory_manager: ResMut<MemoryManager>,
-  camera: Query<
-    (&mut Camera, &GlobalTransform),
-    Or<Changed<GlobalTransform>, Added<GlobalTransform>>,
-  >,
+  mut renderer: ResMut<Renderer>,
+  cameras: Query<(&mut Camera, &GlobalTransform)>,
+  mut camera_layers: Query<&RenderLayers>,
   dl_query: Query<(&DirectionalLightComp, &GlobalTransform)>,
   pls_query: Query<(&PointLightComp, &GlobalTransform)>,
   sls_query: Query<(&SpotLightComp, &GlobalTransform)>,
 ) {
   #[cfg(feature = "debug")]
   log::trace!("Updating default Descriptors");
-  if let Some((_, mut camera, transform)) = camera.into_iter().next() {
-    camera.update_view_matrix(transform.deref());
+  let mut active_cameras = Vec::new();
+  for (entity, mut camera, transform) in cameras {
+    if !camera.is_active() || renderer.target_extent(camera.target()).is_none() {
+      continue;
+    }
 
-    let camera_desc = descriptor_manager
-      .descriptor(CAMERA_DESCRIPTOR)
-      .expect("Failed to get Camera Descriptor");
+    if camera.view_matrix() != Camera::compute_view_matrix(transform.deref()) {
+      camera.update_view_matrix(transform.deref());
+    }
 
-    let camera_mem = camera_desc.uniform().expect("Camera not uniform");
-    memory_manager
-      .write_to_buffer(
-        camera_mem,
-        &[camera.view_matrix(), camera.projection_matrix()],
-      )
-      .expect("Failed to update Camera");
+    active_cameras.push((
+      camera.target(),
+      camera.priority(),
+      entity,
+      camera.viewport(),
+      CameraData {
+        view_matrix: camera.view_matrix(),
+        projection_matrix: camera.projection_matrix(),
+        render_layers: camera_layers
+          .by_id(entity)
+          .map(|(_, layers)| *layers)
+          .unwrap_or_default()
+          .bits(),
+      },
+    ));
+  }
+  active_cameras.sort_by_key(|(target, priority, entity, _, _)| (*target, *priority, *entity));
+
+  let mut camera_passes = Vec::new();
+  let mut camera_data = Vec::new();
+  for (i, (target, _, _, viewport, data)) in active_cameras.into_iter().enumerate() {
+    camera_passes.push(CameraPass {
+      target,
+      viewport,
+      camera: i as u32,
+    });
+    camera_data.push(data);
   }
+  renderer.set_camera_passes(camera_passes);
+
+  write_storage(
+    &mut descriptor_manager,
+    &mut memory_manager,
+    CAMERA_DESCRIPTOR,
+    &camera_data,
+  );
 
   let mut pls = Vec::new();
+  let mut pl_bounds = Vec::new();
   for (_, pl, t) in pls_query {
     pls.push(PointLight {
       position: t.position().into(),
@@ -55,49 +93,77 @@ pub fn update_default_descriptors(
       intensity: pl.intensity,
       range: pl.range,
     });
+    pl_bounds.push(BoundingSphere {
+      center: t.position(),
+      radius: pl.range,
+    });
   }
 
-  let mut pls_desc = descriptor_manager
-    .descriptor_mut(POINT_LIGHT_DESCRIPTOR)
-    .expect("Failed to get PointLight Descriptor");
-
-  let pls_mem = pls_desc.storage_mut().expect("PointLights not storage");
-  let size = size_of_val(pls.as_slice());
-  if pls_mem.size() < size {
-    memory_manager
-      .resize_buffer_mem(pls_mem, size)
-      .expect("Failed to resize PointLights Mem");
-  }
-  memory_manager
-    .write_to_buffer(pls_mem, &pls)
-    .expect("Failed to write PointLights");
+  write_storage(
+    &mut descriptor_manager,
+    &mut memory_manager,
+    POINT_LIGHT_DESCRIPTOR,
+    &pls,
+  );
 
+  // matches the order the shadow matrices are computed in
+  let shadow_config = renderer.shadow_config();
+  let mut spot_shadows = 0;
   let mut sls = Vec::new();
+  let mut sl_bounds = Vec::new();
   for (_, sl, t) in sls_query {
+    let shadow = if sl.cast_shadows && spot_shadows < shadow_config.max_spot_shadows {
+      spot_shadows += 1;
+      shadow_config.cascades() + spot_shadows - 1
+    } else {
+      NO_SHADOW
+    };
+    let direction = t.rotation() * glam::Vec3::X;
     sls.push(SpotLight {
       position: t.position().into(),
-      direction: (t.rotation() * glam::Vec3::X).into(),
+      direction: direction.into(),
       color: sl.color,
       intensity: sl.intensity,
       range: sl.range,
       angle: sl.angle,
+      shadow,
     });
+    sl_bounds.push(spot_bounds(t.position(), direction, sl.angle, sl.range));
   }
 
-  let mut sls_desc = descriptor_manager
-    .descriptor_mut(SPOT_LIGHT_DESCRIPTOR)
-    .expect("Failed to get SpotLight Descriptor");
-
-  let sls_mem = sls_desc.storage_mut().expect("SpotLights not storage");
-  let size = size_of_val(sls.as_slice());
-  if sls_mem.size() < size {
-    memory_manager
-      .resize_buffer_mem(sls_mem, size)
-      .expect("Failed to resize SpotLights Mem");
-  }
-  memory_manager
-    .write_to_buffer(sls_mem, &sls)
-    .expect("Failed to write SpotLights");
+  write_storage(
+    &mut descriptor_manager,
+    &mut memory_manager,
+    SPOT_LIGHT_DESCRIPTOR,
+    &sls,
+  );
+
+  let clusters = LightClusters::new(
+    &camera_data
+      .iter()
+      .map(|camera| (camera.view_matrix, camera.projection_matrix))
+      .collect::<Vec<_>>(),
+    &pl_bounds,
+    &sl_bounds,
+  );
+  write_storage(
+    &mut descriptor_manager,
+    &mut memory_manager,
+    CLUSTER_CAMERA_DESCRIPTOR,
+    &clusters.cameras,
+  );
+  write_storage(
+    &mut descriptor_manager,
+    &mut memory_manager,
+    CLUSTER_DESCRIPTOR,
+    &clusters.clusters,
+  );
+  write_storage(
+    &mut descriptor_manager,
+    &mut memory_manager,
+    LIGHT_INDEX_DESCRIPTOR,
+    &clusters.indices,
+  );
 
   let dl = if let Some((_, dl, t)) = dl_query.into_iter().next() {
     DirectionalLight {
@@ -127,6 +193,29 @@ pub fn update_default_descriptors(
     .expect("Failed to update LightInfo");
 }
 
+/// Grows the storage to the next power of two, so a slowly growing number of elements does not reallocate every frame
+fn write_storage<T: Sized>(
+  descriptor_manager: &mut DescriptorManager,
+  memory_manager: &mut MemoryManager,
+  descriptor: DescriptorHandle,
+  data: &[T],
+) {
+  let mut desc = descriptor_manager
+    .descriptor_mut(descriptor)
+    .expect("Failed to get Descriptor");
+
+  let mem = desc.storage_mut().expect("Descriptor not storage");
+  let size = size_of_val(data);
+  if mem.size() < size {
+    memory_manager
+      .resize_buffer_mem(mem, size.next_power_of_two())
+      .expect("Failed to resize Storage Mem");
+  }
+  memory_manager
+    .write_to_buffer(mem, data)
+    .expect("Failed to write Storage");
+}
+
 pub fn update_descriptors(
   mut descriptor_manager: ResMut<DescriptorManager>,
   memory_manager: Res<MemoryManager>,
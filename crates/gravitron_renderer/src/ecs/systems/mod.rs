@@ -1,4 +1,9 @@
+pub mod asset;
+pub mod camera;
 pub mod descriptor;
 pub mod memory;
 pub mod pipeline;
 pub mod renderer;
+pub mod shadow;
+pub mod texture;
+pub mod visibility;
@@ -1,49 +1,189 @@
 use std::collections::HashMap;
 use std::ops::{Deref, DerefMut};
 
+use ash::vk;
 use gravitron_ecs::systems::resources::Res;
+use gravitron_window::ecs::resources::event_loop::EventLoop;
+use log::error;
 #[cfg(feature = "debug")]
 use log::trace;
 
-use crate::ecs::components::renderer::MeshRenderer;
+use crate::asset::AssetServer;
+use crate::ecs::components::camera::Camera;
+use crate::ecs::components::renderer::{
+  MaterialOverride, MeshRenderer, NoFrustumCulling, NotShadowCaster, NotShadowReceiver,
+};
+use crate::ecs::components::skybox::Skybox;
+use crate::ecs::components::visibility::{RenderLayers, ViewVisibility};
+use crate::ecs::resources::screenshot::Screenshots;
+use crate::ecs::resources::stats::CullingStats;
+use crate::ecs::resources::Vulkan;
+use crate::material::MaterialManager;
 use crate::memory::MemoryManager;
-use crate::model::model::{InstanceData, ModelHandle};
+use crate::model::bounds::Frustum;
+use crate::model::model::{
+  InstanceData, ModelHandle, INSTANCE_NOT_SHADOW_CASTER, INSTANCE_NOT_SHADOW_RECEIVER,
+};
 use crate::model::ModelManager;
 use crate::pipeline::manager::GraphicsPipelineHandle;
 use crate::pipeline::{DescriptorManager, PipelineManager};
+use crate::renderer::resources::material::AlphaMode;
+use crate::renderer::transparent::TransparentInstance;
 use crate::renderer::Renderer;
 use gravitron_components::components::transform::GlobalTransform;
+use gravitron_ecs::systems::query::filter::{With, Without};
 use gravitron_ecs::{systems::query::Query, systems::resources::ResMut};
 
-pub fn init_renderer(renderer: Res<Renderer>) {
+pub fn init_renderer(mut renderer: ResMut<Renderer>) {
   #[cfg(feature = "debug")]
   trace!("Initializing Renderer");
   renderer.wait_for_draw_start();
 }
 
+#[allow(clippy::complexity)]
 pub fn draw_data_update(
   mut renderer: ResMut<Renderer>,
   mut memory_manager: ResMut<MemoryManager>,
   mut model_manager: ResMut<ModelManager>,
-  to_render: Query<(&MeshRenderer, &GlobalTransform)>,
+  mut material_manager: ResMut<MaterialManager>,
+  mut descriptor_manager: ResMut<DescriptorManager>,
+  mut asset_server: ResMut<AssetServer>,
+  mut stats: ResMut<CullingStats>,
+  cameras: Query<(&Camera, &GlobalTransform)>,
+  to_render: Query<(&MeshRenderer, &GlobalTransform), Without<NoFrustumCulling>>,
+  always_render: Query<(&MeshRenderer, &GlobalTransform), With<NoFrustumCulling>>,
+  mut visibilities: Query<&ViewVisibility>,
+  mut layers: Query<&RenderLayers>,
+  mut overrides: Query<&MaterialOverride>,
+  mut not_casters: Query<&NotShadowCaster>,
+  mut not_receivers: Query<&NotShadowReceiver>,
 ) {
   #[cfg(feature = "debug")]
   trace!("Updating Renderer Buffers");
 
+  material_manager.sync(&mut asset_server);
+
+  // instances are shared by all passes, so they are kept if any camera sees them.
+  // Sorted like the camera descriptor, blended instances are drawn per camera
+  let mut views = Vec::new();
+  for (id, camera, transform) in cameras {
+    if !camera.is_active() || renderer.target_extent(camera.target()).is_none() {
+      continue;
+    }
+
+    let view = Camera::compute_view_matrix(transform.deref());
+    let frustum = Frustum::from_matrix(&(camera.projection_matrix() * view));
+    let camera_layers = layers
+      .by_id(id)
+      .map(|(_, layers)| *layers)
+      .unwrap_or_default();
+    views.push((
+      (camera.target(), camera.priority(), id),
+      view,
+      frustum,
+      camera_layers,
+    ));
+  }
+  views.sort_by_key(|(order, _, _, _)| *order);
+  let mut transparent = vec![Vec::new(); views.len()];
+
+  let mut total = 0;
+  let mut culled = 0;
   let mut models: HashMap<ModelHandle, HashMap<GraphicsPipelineHandle, Vec<InstanceData>>> =
     HashMap::new();
-  for (_, mesh_render, transform) in to_render {
-    let shader = models.entry(mesh_render.model_id).or_default();
-    let instances = shader.entry(mesh_render.material.shader).or_default();
-    let material = &mesh_render.material;
-    instances.push(InstanceData::new(
-      transform.matrix(),
-      transform.inv_matrix(),
-      material.color,
-      material.metallic,
-      material.roughness,
-      material.texture_id.0,
-    ));
+
+  let instances = to_render
+    .into_iter()
+    .map(|(id, mesh_render, transform)| (id, mesh_render, transform, true))
+    .chain(
+      always_render
+        .into_iter()
+        .map(|(id, mesh_render, transform)| (id, mesh_render, transform, false)),
+    );
+  for (id, mesh_render, transform, frustum_culling) in instances {
+    if visibilities
+      .by_id(id)
+      .is_some_and(|(_, visibility)| !visibility.is_visible())
+    {
+      continue;
+    }
+    let Some(model) = mesh_render.model_id.resolve(&asset_server) else {
+      continue;
+    };
+    total += 1;
+
+    let instance_layers = layers
+      .by_id(id)
+      .map(|(_, layers)| *layers)
+      .unwrap_or_default();
+    let matrix = transform.matrix();
+    let bounds = model_manager.model_bounds(model);
+
+    // models without bounds are always drawn
+    let in_view = |frustum: &Frustum| match bounds {
+      Some(bounds) if frustum_culling => frustum.intersects(bounds, &matrix),
+      _ => true,
+    };
+    let caster = not_casters.by_id(id).is_none();
+    // render layers do not apply to shadows
+    let camera_sees = |(_, _, frustum, camera_layers): &(_, _, Frustum, RenderLayers)| {
+      camera_layers.intersects(&instance_layers) && in_view(frustum)
+    };
+    let visible =
+      views.iter().any(camera_sees) || (caster && renderer.shadow_frusta().iter().any(in_view));
+    if !visible {
+      culled += 1;
+      continue;
+    }
+
+    let Some(material) = asset_server.get(&mesh_render.material) else {
+      continue;
+    };
+    let material_index = match overrides.by_id(id) {
+      Some((_, material_override)) => {
+        material_manager.add_override(&material_override.apply(material))
+      }
+      None => material_manager.index(&mesh_render.material),
+    };
+
+    let mut flags = 0;
+    if !caster {
+      flags |= INSTANCE_NOT_SHADOW_CASTER;
+    }
+    if not_receivers.by_id(id).is_some() {
+      flags |= INSTANCE_NOT_SHADOW_RECEIVER;
+    }
+    let instance = InstanceData::new(matrix, material_index, instance_layers.bits(), flags);
+
+    if material.alpha_mode == AlphaMode::Blend {
+      let center = bounds.map_or(matrix.w_axis.truncate(), |bounds| {
+        matrix.transform_point3(bounds.sphere.center)
+      });
+      for (view, instances) in views.iter().zip(&mut transparent) {
+        if camera_sees(view) {
+          instances.push(TransparentInstance {
+            model,
+            center,
+            data: instance.clone(),
+          });
+        }
+      }
+      continue;
+    }
+
+    let shader = models.entry(model).or_default();
+    let instances = shader.entry(material.shader).or_default();
+    instances.push(instance);
+  }
+
+  *stats = CullingStats { total, culled };
+
+  // keeps instances sharing a material next to each other
+  for instances in models.values_mut().flat_map(|shaders| shaders.values_mut()) {
+    instances.sort_by_key(|instance| instance.material);
+  }
+  if let Err(err) = material_manager.upload(&mut memory_manager, &mut descriptor_manager) {
+    error!("Failed to upload materials: {err}");
   }
 
   renderer.update_draw_buffer(
@@ -51,6 +191,15 @@ pub fn draw_data_update(
     models,
     model_manager.deref_mut(),
   );
+  renderer.update_transparent(
+    memory_manager.deref_mut(),
+    model_manager.deref(),
+    views
+      .into_iter()
+      .map(|(_, view, _, _)| view)
+      .zip(transparent)
+      .collect(),
+  );
 }
 
 pub fn renderer_recording(
@@ -72,8 +221,89 @@ pub fn renderer_recording(
     .expect("Failed to record CommandBuffer");
 }
 
-pub fn execute_renderer(mut renderer: ResMut<Renderer>) {
+/// Rebuilds the scene pass if another sample count was requested
+pub fn update_msaa(
+  mut renderer: ResMut<Renderer>,
+  mut pipeline_manager: ResMut<PipelineManager>,
+  mut descriptor_manager: ResMut<DescriptorManager>,
+  mut memory_manager: ResMut<MemoryManager>,
+) {
+  if let Err(err) = renderer.apply_msaa(
+    &mut pipeline_manager,
+    &mut descriptor_manager,
+    &mut memory_manager,
+  ) {
+    error!("Failed to change the msaa sample count: {err}");
+  }
+}
+
+pub fn update_skybox(
+  mut renderer: ResMut<Renderer>,
+  mut memory_manager: ResMut<MemoryManager>,
+  mut descriptor_manager: ResMut<DescriptorManager>,
+  skyboxes: Query<&Skybox>,
+) {
+  let skybox = skyboxes.into_iter().next();
+  if let Err(err) = renderer.update_environment(
+    skybox.as_ref().map(|(_, skybox)| skybox.deref()),
+    &mut memory_manager,
+    &mut descriptor_manager,
+  ) {
+    error!("Failed to update the skybox: {err}");
+  }
+}
+
+pub fn execute_renderer(
+  mut renderer: ResMut<Renderer>,
+  mut memory_manager: ResMut<MemoryManager>,
+  mut screenshots: ResMut<Screenshots>,
+) {
+  renderer.collect_captures(&memory_manager, &mut screenshots, false);
+  if !renderer.is_drawing() {
+    return;
+  }
+
+  let paths = screenshots.next_capture();
+  if !paths.is_empty() {
+    if let Err(err) = renderer.capture(&mut memory_manager, &mut screenshots, paths) {
+      error!("Failed to capture screenshot: {err}");
+    }
+  }
+
   #[cfg(feature = "debug")]
   trace!("Drawing Frame");
   renderer.draw_frame();
 }
+
+/// Recreates the swapchain when the window was resized, rendering is paused while the window is minimized
+pub fn resize_swapchain(
+  mut renderer: ResMut<Renderer>,
+  mut vulkan: ResMut<Vulkan>,
+  mut memory_manager: ResMut<MemoryManager>,
+  mut descriptor_manager: ResMut<DescriptorManager>,
+  event_loop: Res<EventLoop>,
+) {
+  let size = event_loop.size();
+  vulkan
+    .resize_output(
+      &mut renderer,
+      &mut memory_manager,
+      &mut descriptor_manager,
+      vk::Extent2D {
+        width: size.width,
+        height: size.height,
+      },
+    )
+    .expect("Failed to recreate Swapchain");
+}
+
+/// Saves the captures of the last frames before the renderer is cleaned up
+pub fn finish_captures(
+  mut renderer: ResMut<Renderer>,
+  memory_manager: Res<MemoryManager>,
+  mut screenshots: ResMut<Screenshots>,
+) {
+  renderer.wait_for_idle();
+  renderer.collect_captures(&memory_manager, &mut screenshots, false);
+  screenshots.finish();
+}
@@ -0,0 +1,76 @@
+use std::ops::Deref;
+
+use gravitron_components::components::transform::GlobalTransform;
+use gravitron_ecs::systems::{query::Query, resources::ResMut};
+
+use crate::ecs::components::camera::{Camera, RenderTarget};
+use crate::ecs::components::lighting::{DirectionalLight, SpotLight};
+use crate::model::bounds::Frustum;
+use crate::renderer::resources::shadow::{ShadowMatrices, SpotShadow};
+use crate::renderer::Renderer;
+use crate::{memory::MemoryManager, pipeline::DescriptorManager};
+
+/// Fits the cascades of the directional light to the camera rendered first into the window
+/// and places the shadow maps of the first spot lights casting shadows
+pub fn update_shadows(
+  descriptor_manager: ResMut<DescriptorManager>,
+  mut memory_manager: ResMut<MemoryManager>,
+  mut renderer: ResMut<Renderer>,
+  cameras: Query<(&Camera, &GlobalTransform)>,
+  dl_query: Query<(&DirectionalLight, &GlobalTransform)>,
+  sls_query: Query<(&SpotLight, &GlobalTransform)>,
+) {
+  #[cfg(feature = "debug")]
+  log::trace!("Updating Shadows");
+  let camera = cameras
+    .into_iter()
+    .filter(|(_, camera, _)| camera.is_active() && camera.target() == RenderTarget::Window)
+    .min_by_key(|(id, camera, _)| (camera.priority(), *id))
+    .map(|(_, camera, transform)| {
+      (
+        Camera::compute_view_matrix(transform.deref()),
+        camera.projection_matrix(),
+      )
+    });
+
+  let directional = dl_query
+    .into_iter()
+    .next()
+    .filter(|(_, dl, _)| dl.cast_shadows)
+    .map(|(_, _, t)| t.rotation() * glam::Vec3::X);
+  let spots = sls_query
+    .into_iter()
+    .filter(|(_, sl, _)| sl.cast_shadows)
+    .map(|(_, sl, t)| SpotShadow {
+      position: t.position(),
+      direction: t.rotation() * glam::Vec3::X,
+      angle: sl.angle,
+      range: sl.range,
+    });
+
+  let shadows = ShadowMatrices::new(renderer.shadow_config(), camera, directional, spots);
+
+  let descriptors = renderer.shadow_descriptors();
+  let info_desc = descriptor_manager
+    .descriptor(descriptors.info)
+    .expect("Failed to get ShadowInfo Descriptor");
+  let info_mem = info_desc.uniform().expect("ShadowInfo not uniform");
+  memory_manager
+    .write_to_buffer(info_mem, &[shadows.info])
+    .expect("Failed to write ShadowInfo");
+
+  let matrices_desc = descriptor_manager
+    .descriptor(descriptors.matrices)
+    .expect("Failed to get ShadowMatrices Descriptor");
+  let matrices_mem = matrices_desc.storage().expect("ShadowMatrices not storage");
+  memory_manager
+    .write_to_buffer(matrices_mem, &shadows.matrices)
+    .expect("Failed to write ShadowMatrices");
+
+  let frusta = shadows
+    .layers
+    .iter()
+    .map(|layer| Frustum::from_matrix(&shadows.matrices[*layer as usize]))
+    .collect();
+  renderer.set_shadows(shadows.layers, frusta);
+}
@@ -0,0 +1,21 @@
+use gravitron_ecs::systems::resources::{Res, ResMut};
+use log::error;
+
+use crate::{
+  memory::MemoryManager, pipeline::DescriptorManager, renderer::Renderer, texture::TextureManager,
+};
+
+pub fn update_textures(
+  renderer: Res<Renderer>,
+  mut texture_manager: ResMut<TextureManager>,
+  mut memory_manager: ResMut<MemoryManager>,
+  mut descriptor_manager: ResMut<DescriptorManager>,
+) {
+  if let Err(err) = texture_manager.update(
+    &mut memory_manager,
+    &mut descriptor_manager,
+    renderer.is_drawing(),
+  ) {
+    error!("Failed to update textures: {err}");
+  }
+}
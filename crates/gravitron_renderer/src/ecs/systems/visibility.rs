@@ -0,0 +1,11 @@
+use gravitron_ecs::commands::Commands;
+use gravitron_hierarchy::propagation::UpdatePropagationQuery;
+
+use crate::ecs::components::visibility::{ViewVisibility, Visibility};
+
+pub fn visibility_propagate(
+  query: UpdatePropagationQuery<Visibility, ViewVisibility>,
+  cmds: &mut Commands,
+) {
+  query.propagate(cmds);
+}
@@ -14,4 +14,109 @@ pub enum QueueFamilyMissingError {
 pub enum RendererInitError {
   #[error("No surface formats found")]
   FormatMissing,
+  #[error("No vulkan device found")]
+  NoDevice,
+  #[error("Either a window or headless rendering is required")]
+  NoTarget,
+  #[error("Failed to build the {0} pipeline")]
+  Pipeline(&'static str),
+}
+
+#[derive(Error, Debug)]
+pub enum ReadbackError {
+  #[error("Pixels can only be read back when rendering headless")]
+  NotHeadless,
+  #[error("Readback buffer does not match the image size")]
+  Size,
+  #[error("Images in the format {0:?} can not be captured")]
+  Format(ash::vk::Format),
+}
+
+#[cfg(any(feature = "shader_compiler", feature = "wgsl"))]
+#[derive(Error, Debug)]
+pub enum ShaderCompileError {
+  #[error("Failed to compile {0}: {1}")]
+  Compile(String, String),
+  #[cfg(feature = "wgsl")]
+  #[error("No {0:?} entry point found in {1}")]
+  MissingEntryPoint(crate::pipeline::shader::ShaderStage, String),
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ShaderReflectError {
+  #[error("Invalid SPIR-V header")]
+  InvalidHeader,
+  #[error("SPIR-V instruction at word {0} is truncated")]
+  Truncated(usize),
+  #[error("SPIR-V module has no entry point")]
+  MissingEntryPoint,
+  #[error("Unsupported execution model {0}")]
+  UnsupportedStage(u32),
+  #[error("Id {0} is not defined")]
+  UnknownId(u32),
+  #[error("Unsupported type of {0}")]
+  UnsupportedType(String),
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PipelineValidationError {
+  #[error("Expected a {expected:?} shader but got a {found:?} shader")]
+  WrongStage {
+    expected: ash::vk::ShaderStageFlags,
+    found: ash::vk::ShaderStageFlags,
+  },
+  #[error("Binding {binding} of set {set} is declared with different types")]
+  ConflictingBinding { set: u32, binding: u32 },
+  #[error("Shaders use set {set} but only {provided} descriptor sets are given")]
+  MissingSet { set: u32, provided: usize },
+  #[error("Binding {binding} of set {set} is missing")]
+  MissingBinding { set: u32, binding: u32 },
+  #[error("Binding {binding} of set {set} is {provided:?} but shaders expect {expected:?}")]
+  TypeMismatch {
+    set: u32,
+    binding: u32,
+    expected: ash::vk::DescriptorType,
+    provided: ash::vk::DescriptorType,
+  },
+  #[error(
+    "Binding {binding} of set {set} has {provided} descriptors but shaders expect {expected}"
+  )]
+  CountMismatch {
+    set: u32,
+    binding: u32,
+    expected: u32,
+    provided: u32,
+  },
+  #[error("Binding {binding} of set {set} is not visible to {stages:?}")]
+  StageMismatch {
+    set: u32,
+    binding: u32,
+    stages: ash::vk::ShaderStageFlags,
+  },
+  #[error("Vertex input at location {0} is not provided by the rendering stage")]
+  MissingVertexInput(u32),
+  #[error(
+    "Vertex input at location {location} is {provided:?} but the shader expects {expected:?}"
+  )]
+  VertexInputFormat {
+    location: u32,
+    expected: ash::vk::Format,
+    provided: ash::vk::Format,
+  },
+}
+
+#[derive(Error, Debug)]
+pub enum TextureError {
+  #[error("All {0} texture slots are in use")]
+  NoFreeSlot(u32),
+  #[error("Pixel data does not match a size of {0}x{1}")]
+  InvalidSize(u32, u32),
+  #[error("No space left in the texture atlas")]
+  AtlasFull,
+  #[error("Unknown image format")]
+  UnknownFormat,
+  #[error("Unsupported texture: {0}")]
+  Unsupported(String),
+  #[error("Invalid texture data: {0}")]
+  InvalidData(String),
 }
@@ -3,6 +3,8 @@ use ash::{khr, vk};
 use gravitron_plugin::config::AppConfig;
 use gravitron_window::config::WindowConfig;
 
+use crate::error::RendererInitError;
+
 const REQUIRED_EXTENSION_NAMES: [*const i8; 1] = [khr::surface::NAME.as_ptr()];
 
 #[cfg(target_os = "linux")]
@@ -20,11 +22,12 @@ pub struct InstanceDevice {
 }
 
 impl InstanceDevice {
+  /// Surface extensions are only enabled if a window is given
   pub fn init(
     config: &mut InstanceDeviceConfig,
     entry: &ash::Entry,
     app_config: &AppConfig,
-    window_config: &WindowConfig,
+    window_config: Option<&WindowConfig>,
     #[cfg(target_os = "linux")] is_wayland: bool,
   ) -> Result<Self, Error> {
     let instance = InstanceDevice::init_instance(
@@ -55,11 +58,15 @@ impl InstanceDevice {
     entry: &ash::Entry,
     config: &mut InstanceDeviceConfig,
     app_config: &AppConfig,
-    window_config: &WindowConfig,
+    window_config: Option<&WindowConfig>,
     #[cfg(target_os = "linux")] is_wayland: bool,
   ) -> Result<ash::Instance, Error> {
     let engine_name = std::ffi::CString::new("Vulkan Game Engine")?;
-    let app_name = std::ffi::CString::new(window_config.title.clone())?;
+    let app_name = std::ffi::CString::new(
+      window_config
+        .map(|window_config| window_config.title.as_str())
+        .unwrap_or("Gravitron"),
+    )?;
 
     let app_info = vk::ApplicationInfo::default()
       .application_name(&app_name)
@@ -79,14 +86,16 @@ impl InstanceDevice {
       .iter()
       .map(|extension_name| extension_name.as_ptr())
       .collect();
-    extension_name_ptrs.extend(REQUIRED_EXTENSION_NAMES.iter());
-    extension_name_ptrs.extend(REQUIRED_PLATFORM_EXTENSION_NAMES.iter());
-
-    #[cfg(target_os = "linux")]
-    if is_wayland {
-      extension_name_ptrs.push(khr::wayland_surface::NAME.as_ptr());
-    } else {
-      extension_name_ptrs.push(khr::xlib_surface::NAME.as_ptr());
+    if window_config.is_some() {
+      extension_name_ptrs.extend(REQUIRED_EXTENSION_NAMES.iter());
+      extension_name_ptrs.extend(REQUIRED_PLATFORM_EXTENSION_NAMES.iter());
+
+      #[cfg(target_os = "linux")]
+      if is_wayland {
+        extension_name_ptrs.push(khr::wayland_surface::NAME.as_ptr());
+      } else {
+        extension_name_ptrs.push(khr::xlib_surface::NAME.as_ptr());
+      }
     }
 
     let mut instance_create_info = vk::InstanceCreateInfo::default()
@@ -103,21 +112,19 @@ impl InstanceDevice {
 
   fn init_physical_device_and_properties(
     instance: &ash::Instance,
-  ) -> Result<(vk::PhysicalDevice, vk::PhysicalDeviceProperties), vk::Result> {
-    let phys_devices = unsafe { instance.enumerate_physical_devices() }?;
-
-    let mut physical_device = None;
-    for p in phys_devices {
-      let properties = unsafe { instance.get_physical_device_properties(p) };
-      //let features = unsafe { instance.get_physical_device_features(p) };
-      if properties.device_type == vk::PhysicalDeviceType::DISCRETE_GPU {
-        physical_device = Some((p, properties));
-        break;
-      } else if properties.device_type == vk::PhysicalDeviceType::INTEGRATED_GPU {
-        physical_device = Some((p, properties));
-      }
-    }
-    Ok(physical_device.unwrap())
+  ) -> Result<(vk::PhysicalDevice, vk::PhysicalDeviceProperties), Error> {
+    let phys_devices = unsafe { instance.enumerate_physical_devices() }?
+      .into_iter()
+      .map(|p| (p, unsafe { instance.get_physical_device_properties(p) }))
+      .collect::<Vec<_>>();
+
+    let preferred = preferred_device(
+      phys_devices
+        .iter()
+        .map(|(_, properties)| properties.device_type),
+    )
+    .ok_or(RendererInitError::NoDevice)?;
+    Ok(phys_devices[preferred])
   }
 
   pub fn cleanup(&self) {
@@ -127,6 +134,21 @@ impl InstanceDevice {
   }
 }
 
+/// Prefers discrete over integrated gpus, other devices like software drivers are only used if there is no gpu
+fn preferred_device(types: impl IntoIterator<Item = vk::PhysicalDeviceType>) -> Option<usize> {
+  let rank = |device_type| match device_type {
+    vk::PhysicalDeviceType::DISCRETE_GPU => 2,
+    vk::PhysicalDeviceType::INTEGRATED_GPU => 1,
+    _ => 0,
+  };
+
+  types
+    .into_iter()
+    .enumerate()
+    .min_by_key(|(_, device_type)| std::cmp::Reverse(rank(*device_type)))
+    .map(|(i, _)| i)
+}
+
 #[derive(Default)]
 pub struct InstanceDeviceConfig<'a> {
   layer_names: Vec<&'a std::ffi::CStr>,
@@ -162,3 +184,29 @@ impl<'a> InstanceDeviceConfig<'a> {
     self
   }
 }
+
+#[cfg(test)]
+mod test {
+  use ash::vk::PhysicalDeviceType;
+
+  use super::preferred_device;
+
+  #[test]
+  fn device_preference() {
+    assert_eq!(preferred_device([]), None);
+    // software drivers like lavapipe are used when there is no gpu
+    assert_eq!(preferred_device([PhysicalDeviceType::CPU]), Some(0));
+    assert_eq!(
+      preferred_device([PhysicalDeviceType::CPU, PhysicalDeviceType::INTEGRATED_GPU]),
+      Some(1)
+    );
+    assert_eq!(
+      preferred_device([
+        PhysicalDeviceType::INTEGRATED_GPU,
+        PhysicalDeviceType::DISCRETE_GPU,
+        PhysicalDeviceType::DISCRETE_GPU,
+      ]),
+      Some(1)
+    );
+  }
+}
@@ -1,11 +1,20 @@
+use asset::AssetServer;
 use config::RendererConfig;
 use ecs::{
-  resources::{cleanup_resource, Resources},
+  resources::{cleanup_resource, screenshot::Screenshots, stats::CullingStats, Resources},
   systems::{
+    asset::{reload_changed_files, sync_mesh_assets, sync_texture_assets, update_assets},
+    camera::update_camera_projection,
     descriptor::{reset_descriptors, update_default_descriptors, update_descriptors},
     memory::reset_buffer_reallocated,
     pipeline::pipeline_changed_reset,
-    renderer::{draw_data_update, execute_renderer, init_renderer, renderer_recording},
+    renderer::{
+      draw_data_update, execute_renderer, finish_captures, init_renderer, renderer_recording,
+      resize_swapchain, update_msaa, update_skybox,
+    },
+    shadow::update_shadows,
+    texture::update_textures,
+    visibility::visibility_propagate,
   },
 };
 use gravitron_components::ComponentPlugin;
@@ -15,11 +24,16 @@ use gravitron_plugin::{
   stages::MainSystemStage,
   Plugin,
 };
+use gravitron_window::config::WindowConfig;
 #[cfg(target_os = "linux")]
 use gravitron_window::ecs::resources::event_loop::EventLoop;
-use gravitron_window::{config::WindowConfig, WindowPlugin};
 use log::debug;
+use model::mesh::ObjLoader;
+use texture::TextureLoader;
 
+pub use image;
+
+mod asset;
 pub mod config;
 #[cfg(feature = "debug")]
 mod debug;
@@ -27,19 +41,32 @@ mod device;
 pub mod ecs;
 mod error;
 mod instance;
+mod material;
 mod memory;
 mod model;
 mod pipeline;
 mod renderer;
 mod surface;
+mod texture;
 
 pub struct RendererPlugin;
 
 impl Plugin for RendererPlugin {
   fn build(&self, builder: &mut AppBuilder<Build>) {
     builder.add_config(RendererConfig::default());
+    builder.add_resource(CullingStats::default());
+    builder.add_resource(Screenshots::default());
+    builder.add_main_system_at_stage(update_assets, MainSystemStage::PreRender);
+    builder.add_main_system_at_stage(reload_changed_files, MainSystemStage::PreRender);
+    builder.add_main_system_at_stage(update_msaa, MainSystemStage::PreRender);
     builder.add_main_system_at_stage(init_renderer, MainSystemStage::RenderInit);
+    builder.add_main_system_at_stage(sync_mesh_assets, MainSystemStage::RenderInit);
+    builder.add_main_system_at_stage(sync_texture_assets, MainSystemStage::RenderInit);
+    builder.add_main_system_at_stage(update_camera_projection, MainSystemStage::PreRender);
+    builder.add_main_system_at_stage(update_shadows, MainSystemStage::PreRender);
     builder.add_main_system_at_stage(update_default_descriptors, MainSystemStage::RenderInit);
+    builder.add_main_system_at_stage(update_textures, MainSystemStage::RenderInit);
+    builder.add_main_system_at_stage(update_skybox, MainSystemStage::RenderInit);
     builder.add_main_system_at_stage(draw_data_update, MainSystemStage::RenderInit);
     builder.add_main_system_at_stage(update_descriptors, MainSystemStage::RenderPrepare);
     builder.add_main_system_at_stage(renderer_recording, MainSystemStage::RenderRecording);
@@ -47,38 +74,59 @@ impl Plugin for RendererPlugin {
     builder.add_main_system_at_stage(reset_buffer_reallocated, MainSystemStage::PostRender);
     builder.add_main_system_at_stage(pipeline_changed_reset, MainSystemStage::PostRender);
     builder.add_main_system_at_stage(reset_descriptors, MainSystemStage::PostRender);
+    builder.add_main_system_at_stage(visibility_propagate, MainSystemStage::PostRender);
+    builder.add_cleanup_system(finish_captures);
   }
 
   fn finalize(&self, builder: &mut AppBuilder<Finalize>) {
     let app_config = builder
       .config::<AppConfig>()
       .expect("Error: Failed to get AppConfig");
-    let window_config = builder
-      .config::<WindowConfig>()
-      .expect("Error: Failed to get AppConfig");
     let config = builder
       .config::<RendererConfig>()
       .expect("Error: Failed to get Vulkan Config");
 
-    let window = builder
-      .get_resource()
-      .expect("Error: Window Plugin must be initialized before the Renderer Plugin");
+    // headless rendering works without the window plugin
+    let window = config.headless.is_none().then(|| {
+      let window_config = builder
+        .config::<WindowConfig>()
+        .expect("Error: Failed to get WindowConfig");
+      let window = builder.get_resource().expect(
+        "Error: Window Plugin must be initialized before the Renderer Plugin if rendering is not headless",
+      );
+      (window_config, window)
+    });
 
     #[cfg(target_os = "linux")]
-    let event_loop = builder
-      .get_resource::<EventLoop>()
-      .expect("Error: Window Plugin must be initialized before the Renderer Plugin");
+    let is_wayland = window.is_some()
+      && builder
+        .get_resource::<EventLoop>()
+        .expect("Error: Window Plugin must be initialized before the Renderer Plugin")
+        .wayland();
+
+    let windowed = window.is_some();
+    let mut asset_server = AssetServer::new(config.asset_root.clone());
+    asset_server.add_loader(ObjLoader::default());
+    asset_server.add_loader(TextureLoader::default());
+    if config.hot_reload {
+      asset_server.watch_for_changes();
+    }
 
     Resources::create(
       config.clone(),
       app_config,
-      window_config,
       window,
       #[cfg(target_os = "linux")]
-      event_loop.wayland(),
+      is_wayland,
     )
     .expect("Error: Failed to create Renderer resources")
     .add_resources(builder);
+    builder.add_resource(asset_server);
+
+    // the swapchain follows the window size
+    if windowed {
+      builder.add_main_system_at_stage(resize_swapchain, MainSystemStage::PostRender);
+    }
   }
 
   fn cleanup(&self, app: &mut App<Cleanup>) {
@@ -87,6 +135,6 @@ impl Plugin for RendererPlugin {
   }
 
   fn dependencies(&self) -> Vec<gravitron_plugin::PluginID> {
-    vec![WindowPlugin.id(), ComponentPlugin.id()]
+    vec![ComponentPlugin.id()]
   }
 }
@@ -0,0 +1,212 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Error;
+
+use crate::{
+  asset::{AssetId, AssetServer, Handle},
+  memory::MemoryManager,
+  pipeline::DescriptorManager,
+  renderer::{
+    resources::material::{GpuMaterial, Material},
+    MATERIAL_DESCRIPTOR,
+  },
+  texture::slot::SlotAllocator,
+};
+
+/// Index of the material used for handles whose material is not loaded
+pub const DEFAULT_MATERIAL: u32 = 0;
+
+/// Mirrors the `Material` assets into the material storage buffer, instances refer to a material by its index.
+/// Overridden instances get their own material appended after the asset materials every frame
+pub struct MaterialManager {
+  slots: SlotAllocator,
+  indices: HashMap<AssetId, u32>,
+  materials: Vec<GpuMaterial>,
+  overrides: Vec<GpuMaterial>,
+  override_count: usize,
+  changed: bool,
+}
+
+impl MaterialManager {
+  pub(crate) fn new() -> Self {
+    let mut slots = SlotAllocator::new(u32::MAX);
+    slots.alloc();
+
+    Self {
+      slots,
+      indices: HashMap::new(),
+      materials: vec![GpuMaterial::from(&Material::default())],
+      overrides: Vec::new(),
+      override_count: 0,
+      changed: true,
+    }
+  }
+
+  /// Index of the material in the material buffer, `DEFAULT_MATERIAL` if it is not loaded
+  #[inline]
+  pub fn index(&self, material: &Handle<Material>) -> u32 {
+    self
+      .indices
+      .get(&material.id())
+      .copied()
+      .unwrap_or(DEFAULT_MATERIAL)
+  }
+
+  /// Number of material assets in the buffer
+  #[inline]
+  pub fn len(&self) -> usize {
+    self.indices.len()
+  }
+
+  #[inline]
+  pub fn is_empty(&self) -> bool {
+    self.indices.is_empty()
+  }
+
+  /// Gives new materials a slot, frees the slots of dropped ones and resets the overrides of the last frame
+  pub(crate) fn sync(&mut self, asset_server: &mut AssetServer) {
+    let mut alive = HashSet::new();
+    for (id, material) in asset_server.iter_mut::<Material>() {
+      alive.insert(id);
+      let slot = *self
+        .indices
+        .entry(id)
+        .or_insert_with(|| self.slots.alloc().expect("Material slots exhausted"))
+        as usize;
+
+      let material = GpuMaterial::from(&*material);
+      if slot >= self.materials.len() {
+        self.materials.resize(slot + 1, material);
+        self.changed = true;
+      } else if self.materials[slot] != material {
+        self.materials[slot] = material;
+        self.changed = true;
+      }
+    }
+
+    self.indices.retain(|id, slot| {
+      let keep = alive.contains(id);
+      if !keep {
+        self.slots.free(*slot);
+      }
+      keep
+    });
+    self.override_count = 0;
+  }
+
+  /// Adds a material for this frame only and returns its index
+  pub(crate) fn add_override(&mut self, material: &Material) -> u32 {
+    let material = GpuMaterial::from(material);
+    match self.overrides.get_mut(self.override_count) {
+      Some(previous) if *previous == material => {}
+      Some(previous) => {
+        *previous = material;
+        self.changed = true;
+      }
+      None => {
+        self.overrides.push(material);
+        self.changed = true;
+      }
+    }
+
+    self.override_count += 1;
+    (self.materials.len() + self.override_count - 1) as u32
+  }
+
+  /// Writes the material buffer if a material changed since the last upload
+  pub(crate) fn upload(
+    &mut self,
+    memory_manager: &mut MemoryManager,
+    descriptor_manager: &mut DescriptorManager,
+  ) -> Result<(), Error> {
+    self.overrides.truncate(self.override_count);
+    if !self.changed {
+      return Ok(());
+    }
+
+    let data = [self.materials.as_slice(), self.overrides.as_slice()].concat();
+    let mut descriptor = descriptor_manager
+      .descriptor_mut(MATERIAL_DESCRIPTOR)
+      .expect("Failed to get Material Descriptor");
+    let mem = descriptor.storage_mut().expect("Materials not storage");
+
+    let size = size_of_val(data.as_slice());
+    if mem.size() < size {
+      memory_manager.resize_buffer_mem(mem, size)?;
+    }
+    memory_manager.write_to_buffer(mem, &data)?;
+
+    self.changed = false;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{MaterialManager, DEFAULT_MATERIAL};
+  use crate::{asset::AssetServer, renderer::resources::material::Material};
+
+  fn material(metallic: f32) -> Material {
+    Material {
+      metallic,
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn asset_slots() {
+    let mut server = AssetServer::default();
+    let mut manager = MaterialManager::new();
+
+    let first = server.add(material(0.1));
+    let second = server.add(material(0.2));
+    manager.sync(&mut server);
+    assert_eq!(manager.len(), 2);
+    assert_ne!(manager.index(&first), DEFAULT_MATERIAL);
+    assert_ne!(manager.index(&first), manager.index(&second));
+
+    manager.changed = false;
+    manager.sync(&mut server);
+    assert!(!manager.changed);
+
+    // every user sees the change
+    server.get_mut(&second).unwrap().metallic = 1.0;
+    manager.sync(&mut server);
+    assert!(manager.changed);
+    assert_eq!(
+      manager.materials[manager.index(&second) as usize].factors.x,
+      1.0
+    );
+
+    let freed = manager.index(&first);
+    drop(first);
+    server.update();
+    manager.sync(&mut server);
+    assert_eq!(manager.len(), 1);
+
+    let third = server.add(material(0.3));
+    manager.sync(&mut server);
+    assert_eq!(manager.index(&third), freed);
+  }
+
+  #[test]
+  fn overrides() {
+    let mut server = AssetServer::default();
+    let mut manager = MaterialManager::new();
+    let handle = server.add(material(0.1));
+    manager.sync(&mut server);
+
+    // overrides follow the asset materials
+    assert_eq!(manager.add_override(&material(0.5)), 2);
+    assert_eq!(manager.add_override(&material(0.6)), 3);
+    assert_eq!(manager.overrides.len(), 2);
+
+    manager.changed = false;
+    manager.sync(&mut server);
+    assert_eq!(manager.add_override(&material(0.5)), 2);
+    assert!(!manager.changed);
+    assert_eq!(manager.add_override(&material(0.7)), 3);
+    assert!(manager.changed);
+    assert_eq!(manager.index(&handle), 1);
+  }
+}
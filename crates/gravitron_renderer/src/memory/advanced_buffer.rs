@@ -174,6 +174,8 @@ impl AdvancedBuffer {
       self.reallocated = true;
 
       let old_gpu = std::mem::replace(&mut self.gpu, new_gpu);
+      // frames in flight may still read from the old buffer
+      unsafe { device.device_wait_idle().ok()? };
       unsafe { old_gpu.cleanup(device, allocator).ok()? };
 
       self.allocator.grow(additional_size);
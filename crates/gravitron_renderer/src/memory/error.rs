@@ -6,4 +6,6 @@ pub enum MemoryError {
   NotFound,
   #[error("Buffer reallocate error")]
   Reallocate,
+  #[error("Buffer is not host visible")]
+  NotHostVisible,
 }
@@ -8,6 +8,10 @@ use crate::{
   device::Device,
   instance::InstanceDevice,
   pipeline::pools::{CommandBufferType, Pools},
+  texture::{
+    loader::{ColorSpace, TextureData},
+    Cubemap, SamplerDescriptor,
+  },
 };
 
 use super::{
@@ -15,7 +19,7 @@ use super::{
   allocator::BufferMemory,
   error::MemoryError,
   image::Image,
-  sampler_image::SamplerImage,
+  sampler_image::{SamplerImage, TextureSupport},
   simple_buffer::SimpleBuffer,
   types::{
     BufferBlockSize, BufferId, BufferMemoryLocation, BufferType, ImageId, ImageType,
@@ -33,6 +37,7 @@ pub struct MemoryManager {
   device: ash::Device,
   transfers: Vec<Transfer>,
   graphics_transfer: Transfer,
+  texture_support: TextureSupport,
 }
 
 impl MemoryManager {
@@ -88,6 +93,10 @@ impl MemoryManager {
       device: logical_device.clone(),
       transfers,
       graphics_transfer,
+      texture_support: TextureSupport::query(
+        instance.get_instance(),
+        instance.get_physical_device(),
+      ),
     })
   }
 
@@ -151,19 +160,90 @@ impl MemoryManager {
     Ok(id)
   }
 
+  /// Recreates the image behind `id`, e.g. with a new extent. The image must not be in use by the gpu anymore,
+  /// descriptors using it have to be rewritten afterwards
+  pub(crate) fn replace_image(
+    &mut self,
+    id: ImageId,
+    image_info: &vk::ImageCreateInfo,
+    image_view_info: &vk::ImageViewCreateInfo,
+  ) -> Result<(), Error> {
+    let Some(old) = self.images.remove(&id) else {
+      return Err(MemoryError::NotFound.into());
+    };
+    old.cleanup(&self.device, &mut self.allocator)?;
+
+    let image = Image::new(
+      &self.device,
+      &mut self.allocator,
+      image_info,
+      image_view_info,
+    )?;
+    self.images.insert(id, ImageType::Simple(image));
+    Ok(())
+  }
+
+  /// Like `replace_image` for images created with `create_sampler_image`
+  pub(crate) fn replace_sampler_image(
+    &mut self,
+    id: ImageId,
+    image_info: &vk::ImageCreateInfo,
+    image_view_info: &vk::ImageViewCreateInfo,
+    sampler_info: &vk::SamplerCreateInfo,
+  ) -> Result<(), Error> {
+    let Some(old) = self.images.remove(&id) else {
+      return Err(MemoryError::NotFound.into());
+    };
+    old.cleanup(&self.device, &mut self.allocator)?;
+
+    let sampler_image = SamplerImage::new(
+      &self.device,
+      &mut self.allocator,
+      image_info,
+      image_view_info,
+      sampler_info,
+    )?;
+    self.images.insert(id, ImageType::Sampler(sampler_image));
+    Ok(())
+  }
+
+  /// Decodes an encoded image, e.g. a png, mip levels are generated unless disabled in `sampler`
   pub fn create_texture_image(
     &mut self,
-    interpolation: vk::Filter,
+    sampler: impl Into<SamplerDescriptor>,
+    color_space: ColorSpace,
     data: &[u8],
+  ) -> Result<ImageId, Error> {
+    self.create_texture(sampler, TextureData::decode(data, color_space)?)
+  }
+
+  /// `pixels` holds `width * height` RGBA pixels
+  pub fn create_texture_image_rgba(
+    &mut self,
+    sampler: impl Into<SamplerDescriptor>,
+    color_space: ColorSpace,
+    width: u32,
+    height: u32,
+    pixels: &[u8],
+  ) -> Result<ImageId, Error> {
+    let texture = TextureData::from_rgba8(width, height, pixels.to_vec(), color_space)?;
+    self.create_texture(sampler, texture)
+  }
+
+  pub fn create_texture(
+    &mut self,
+    sampler: impl Into<SamplerDescriptor>,
+    texture: TextureData,
   ) -> Result<ImageId, Error> {
     let id = ImageId::Sampler(self.last_image_id);
 
     let sampler_image = SamplerImage::new_texture(
-      interpolation,
-      data,
+      &sampler.into(),
+      texture,
       &self.device,
       &mut self.allocator,
       &self.graphics_transfer,
+      &self.texture_support,
     )?;
 
     self.images.insert(id, ImageType::Sampler(sampler_image));
@@ -172,6 +252,81 @@ impl MemoryManager {
     Ok(id)
   }
 
+  /// The image must not be in use by the gpu anymore
+  pub(crate) fn remove_image(&mut self, id: ImageId) -> Result<bool, Error> {
+    let Some(image) = self.images.remove(&id) else {
+      return Ok(false);
+    };
+    image.cleanup(&self.device, &mut self.allocator)?;
+    Ok(true)
+  }
+
+  /// Replaces the texture behind `id`, descriptors using it have to be rewritten afterwards
+  pub(crate) fn replace_texture_image(
+    &mut self,
+    id: ImageId,
+    sampler: &SamplerDescriptor,
+    texture: TextureData,
+  ) -> Result<(), Error> {
+    if !self.images.contains_key(&id) {
+      return Err(MemoryError::NotFound.into());
+    }
+
+    let sampler_image = SamplerImage::new_texture(
+      sampler,
+      texture,
+      &self.device,
+      &mut self.allocator,
+      &self.graphics_transfer,
+      &self.texture_support,
+    )?;
+
+    // the old image might still be used by a frame in flight
+    unsafe { self.device.device_wait_idle() }?;
+    if let Some(old) = self.images.insert(id, ImageType::Sampler(sampler_image)) {
+      old.cleanup(&self.device, &mut self.allocator)?;
+    }
+    Ok(())
+  }
+
+  /// `levels` is the mip chain of the cube image, see `SamplerImage::new_cubemap`
+  pub(crate) fn create_cubemap(&mut self, levels: &[Cubemap]) -> Result<ImageId, Error> {
+    let id = ImageId::Sampler(self.last_image_id);
+
+    let sampler_image = SamplerImage::new_cubemap(
+      levels,
+      &self.device,
+      &mut self.allocator,
+      &self.graphics_transfer,
+    )?;
+
+    self.images.insert(id, ImageType::Sampler(sampler_image));
+
+    self.last_image_id += 1;
+    Ok(id)
+  }
+
+  /// Replaces the cube image behind `id`, descriptors using it have to be rewritten afterwards
+  pub(crate) fn replace_cubemap(&mut self, id: ImageId, levels: &[Cubemap]) -> Result<(), Error> {
+    if !self.images.contains_key(&id) {
+      return Err(MemoryError::NotFound.into());
+    }
+
+    let sampler_image = SamplerImage::new_cubemap(
+      levels,
+      &self.device,
+      &mut self.allocator,
+      &self.graphics_transfer,
+    )?;
+
+    // the old image might still be used by a frame in flight
+    unsafe { self.device.device_wait_idle() }?;
+    if let Some(old) = self.images.insert(id, ImageType::Sampler(sampler_image)) {
+      old.cleanup(&self.device, &mut self.allocator)?;
+    }
+    Ok(())
+  }
+
   pub fn create_sampler_image(
     &mut self,
     image_info: &vk::ImageCreateInfo,
@@ -327,6 +482,25 @@ impl MemoryManager {
     }
   }
 
+  pub(crate) fn get_vk_image(&self, image_id: ImageId) -> Option<vk::Image> {
+    match self.images.get(&image_id)? {
+      ImageType::Simple(image) => Some(image.image()),
+      ImageType::Sampler(image) => Some(image.image()),
+    }
+  }
+
+  /// Copies the memory out of a host visible buffer, the gpu has to be done writing it
+  pub(crate) fn read_buffer(&self, mem: &BufferMemory) -> Result<Vec<u8>, Error> {
+    match self
+      .buffers
+      .get(&mem.buffer())
+      .ok_or(MemoryError::NotFound)?
+    {
+      BufferType::Simple(buffer) => Ok(buffer.read(mem)),
+      BufferType::Advanced(_) => Err(MemoryError::NotHostVisible.into()),
+    }
+  }
+
   pub(crate) fn get_vk_image_view(&self, image_id: ImageId) -> Option<vk::ImageView> {
     match self.images.get(&image_id)? {
       ImageType::Simple(image) => Some(image.image_view()),
@@ -1,5 +1,5 @@
 mod advanced_buffer;
-mod allocator;
+pub(crate) mod allocator;
 mod buffer;
 pub mod error;
 mod image;
@@ -1,9 +1,28 @@
+use std::collections::HashMap;
+
 use anyhow::Error;
 use ash::vk;
 use gpu_allocator::vulkan;
 
+use crate::{
+  error::TextureError,
+  texture::{
+    cubemap::{Cubemap, FACES},
+    loader::{ColorSpace, TexelFormat, TextureData},
+    mipmap, SamplerDescriptor,
+  },
+};
+
 use super::{buffer::Buffer, image::Image, manager::Transfer};
 
+/// What the device supports for sampled textures
+#[derive(Clone, Debug)]
+pub(crate) struct TextureSupport {
+  /// 0 if anisotropic filtering is not supported
+  pub max_anisotropy: f32,
+  formats: HashMap<vk::Format, vk::FormatFeatureFlags>,
+}
+
 pub struct SamplerImage {
   image: Image,
   sampler: vk::Sampler,
@@ -23,44 +42,77 @@ impl SamplerImage {
     Ok(Self { image, sampler })
   }
 
-  pub fn new_texture(
-    interpolation: vk::Filter,
-    data: &[u8],
+  /// Uploads pre-baked mips, otherwise mips are generated with blits if the format supports it
+  /// and on the cpu if not
+  pub(crate) fn new_texture(
+    sampler: &SamplerDescriptor,
+    mut texture: TextureData,
     device: &ash::Device,
     allocator: &mut vulkan::Allocator,
     transfer: &Transfer,
+    support: &TextureSupport,
   ) -> Result<Self, Error> {
-    let image_file = image::load_from_memory(data)?.to_rgba8();
-    let (width, height) = image_file.dimensions();
+    let format = texture.vk_format();
+    let features = support.features(format);
+    if !features.contains(vk::FormatFeatureFlags::SAMPLED_IMAGE) {
+      return Err(TextureError::Unsupported(format!("{format:?} on this device")).into());
+    }
+
+    let (width, height) = (texture.width(), texture.height());
+    if !sampler.mipmaps {
+      texture.truncate_levels(1);
+    }
+
+    let full_chain = mipmap::mip_levels(width, height);
+    let blit = sampler.mipmaps
+      && texture.levels().len() == 1
+      && full_chain > 1
+      && !texture.format().is_compressed()
+      && features.contains(
+        vk::FormatFeatureFlags::BLIT_SRC
+          | vk::FormatFeatureFlags::BLIT_DST
+          | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR,
+      );
+    if sampler.mipmaps && texture.levels().len() == 1 && !blit {
+      texture.generate_mips();
+    }
+    let mip_levels = if blit {
+      full_chain
+    } else {
+      texture.levels().len() as u32
+    };
+
+    let mut usage = vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST;
+    if blit {
+      usage |= vk::ImageUsageFlags::TRANSFER_SRC;
+    }
 
     let image_info = vk::ImageCreateInfo::default()
       .image_type(vk::ImageType::TYPE_2D)
-      .format(vk::Format::R8G8B8A8_SRGB)
+      .format(format)
       .extent(vk::Extent3D {
         width,
         height,
         depth: 1,
       })
-      .mip_levels(1)
+      .mip_levels(mip_levels)
       .array_layers(1)
       .samples(vk::SampleCountFlags::TYPE_1)
-      .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST);
+      .usage(usage);
 
     let subresource_range = vk::ImageSubresourceRange {
       aspect_mask: vk::ImageAspectFlags::COLOR,
       layer_count: 1,
-      level_count: 1,
+      level_count: mip_levels,
       ..Default::default()
     };
 
     let image_view_info = vk::ImageViewCreateInfo::default()
       .view_type(vk::ImageViewType::TYPE_2D)
-      .format(vk::Format::R8G8B8A8_SRGB)
+      .format(format)
       .subresource_range(subresource_range);
 
-    let sampler_info = vk::SamplerCreateInfo::default()
-      .mag_filter(interpolation)
-      .min_filter(interpolation);
+    let sampler_info = sampler.create_info(mip_levels, support.max_anisotropy);
 
     let sampler = Self::new(
       device,
@@ -70,31 +122,199 @@ impl SamplerImage {
       &sampler_info,
     )?;
 
-    let data = image_file.into_raw();
+    let levels = texture.levels();
     let mut transfer_buffer = Buffer::new(
       allocator,
       device,
-      data.len(),
+      levels.iter().map(Vec::len).sum(),
       vk::BufferUsageFlags::TRANSFER_SRC,
       gpu_allocator::MemoryLocation::CpuToGpu,
     )?;
-    transfer_buffer.fill(&data)?;
+    transfer_buffer.fill(&levels.concat())?;
+
+    let mut offset = 0;
+    let regions = levels
+      .iter()
+      .enumerate()
+      .map(|(level, pixels)| {
+        let (width, height) = mipmap::mip_extent(width, height, level as u32);
+        let region = vk::BufferImageCopy::default()
+          .buffer_offset(offset)
+          .image_subresource(color_layers(level as u32))
+          .image_extent(vk::Extent3D {
+            width,
+            height,
+            depth: 1,
+          });
+        offset += pixels.len() as u64;
+        region
+      })
+      .collect::<Vec<_>>();
 
     let begin_info =
       vk::CommandBufferBeginInfo::default().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
 
-    let image_subresource = vk::ImageSubresourceLayers::default()
-      .aspect_mask(vk::ImageAspectFlags::COLOR)
-      .layer_count(1)
-      .base_array_layer(0)
-      .mip_level(0);
-    let region = vk::BufferImageCopy::default()
-      .image_subresource(image_subresource)
-      .image_extent(vk::Extent3D {
-        width,
-        height,
+    let transfer_barrier = vk::ImageMemoryBarrier::default()
+      .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+      .old_layout(vk::ImageLayout::UNDEFINED)
+      .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+      .image(sampler.image())
+      .subresource_range(subresource_range);
+
+    let command_buffer = transfer.buffer();
+    let command_buffers = [command_buffer];
+
+    let submit_info = [vk::SubmitInfo::default().command_buffers(&command_buffers)];
+
+    unsafe {
+      device.begin_command_buffer(command_buffer, &begin_info)?;
+      device.cmd_pipeline_barrier(
+        command_buffer,
+        vk::PipelineStageFlags::TOP_OF_PIPE,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::DependencyFlags::empty(),
+        &[],
+        &[],
+        &[transfer_barrier],
+      );
+      device.cmd_copy_buffer_to_image(
+        command_buffer,
+        transfer_buffer.buffer(),
+        sampler.image(),
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        &regions,
+      );
+
+      if blit {
+        record_mip_blits(
+          device,
+          command_buffer,
+          sampler.image(),
+          width,
+          height,
+          mip_levels,
+        );
+      } else {
+        let layout_barrier = vk::ImageMemoryBarrier::default()
+          .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+          .dst_access_mask(vk::AccessFlags::SHADER_READ)
+          .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+          .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+          .image(sampler.image())
+          .subresource_range(subresource_range);
+
+        device.cmd_pipeline_barrier(
+          command_buffer,
+          vk::PipelineStageFlags::TRANSFER,
+          vk::PipelineStageFlags::FRAGMENT_SHADER,
+          vk::DependencyFlags::empty(),
+          &[],
+          &[],
+          &[layout_barrier],
+        );
+      }
+      device.end_command_buffer(command_buffer)?;
+
+      device.reset_fences(&[transfer.fence()])?;
+      device.queue_submit(transfer.queue(), &submit_info, transfer.fence())?;
+      device.wait_for_fences(&[transfer.fence()], true, u64::MAX)?;
+
+      transfer_buffer.cleanup(device, allocator)?;
+    }
+
+    Ok(sampler)
+  }
+
+  /// Uploads a cube image with one mip level per cubemap, each level has to be half the size
+  /// of the previous one. Stored as half floats which every device can filter
+  pub(crate) fn new_cubemap(
+    levels: &[Cubemap],
+    device: &ash::Device,
+    allocator: &mut vulkan::Allocator,
+    transfer: &Transfer,
+  ) -> Result<Self, Error> {
+    let size = levels.first().map_or(0, Cubemap::size);
+    if levels.is_empty()
+      || levels
+        .iter()
+        .enumerate()
+        .any(|(level, cubemap)| cubemap.size() != (size >> level).max(1))
+    {
+      return Err(TextureError::InvalidSize(size, size).into());
+    }
+    let format = vk::Format::R16G16B16A16_SFLOAT;
+    let mip_levels = levels.len() as u32;
+
+    let image_info = vk::ImageCreateInfo::default()
+      .flags(vk::ImageCreateFlags::CUBE_COMPATIBLE)
+      .image_type(vk::ImageType::TYPE_2D)
+      .format(format)
+      .extent(vk::Extent3D {
+        width: size,
+        height: size,
         depth: 1,
-      });
+      })
+      .mip_levels(mip_levels)
+      .array_layers(FACES as u32)
+      .samples(vk::SampleCountFlags::TYPE_1)
+      .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST);
+
+    let subresource_range = vk::ImageSubresourceRange {
+      aspect_mask: vk::ImageAspectFlags::COLOR,
+      layer_count: FACES as u32,
+      level_count: mip_levels,
+      ..Default::default()
+    };
+
+    let image_view_info = vk::ImageViewCreateInfo::default()
+      .view_type(vk::ImageViewType::CUBE)
+      .format(format)
+      .subresource_range(subresource_range);
+
+    let sampler_info = SamplerDescriptor::linear()
+      .with_address_mode(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+      .create_info(mip_levels, 0.0);
+
+    let sampler = Self::new(
+      device,
+      allocator,
+      &image_info,
+      &image_view_info,
+      &sampler_info,
+    )?;
+
+    let data = levels.iter().map(Cubemap::to_rgba16f).collect::<Vec<_>>();
+    let mut transfer_buffer = Buffer::new(
+      allocator,
+      device,
+      data.iter().map(Vec::len).sum(),
+      vk::BufferUsageFlags::TRANSFER_SRC,
+      gpu_allocator::MemoryLocation::CpuToGpu,
+    )?;
+    transfer_buffer.fill(&data.concat())?;
+
+    // the faces of a level follow each other like array layers
+    let mut offset = 0;
+    let regions = data
+      .iter()
+      .enumerate()
+      .map(|(level, pixels)| {
+        let size = (size >> level).max(1);
+        let region = vk::BufferImageCopy::default()
+          .buffer_offset(offset)
+          .image_subresource(color_layers(level as u32).layer_count(FACES as u32))
+          .image_extent(vk::Extent3D {
+            width: size,
+            height: size,
+            depth: 1,
+          });
+        offset += pixels.len() as u64;
+        region
+      })
+      .collect::<Vec<_>>();
+
+    let begin_info =
+      vk::CommandBufferBeginInfo::default().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
 
     let transfer_barrier = vk::ImageMemoryBarrier::default()
       .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
@@ -102,7 +322,6 @@ impl SamplerImage {
       .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
       .image(sampler.image())
       .subresource_range(subresource_range);
-
     let layout_barrier = vk::ImageMemoryBarrier::default()
       .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
       .dst_access_mask(vk::AccessFlags::SHADER_READ)
@@ -132,7 +351,7 @@ impl SamplerImage {
         transfer_buffer.buffer(),
         sampler.image(),
         vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-        &[region],
+        &regions,
       );
       device.cmd_pipeline_barrier(
         command_buffer,
@@ -178,3 +397,158 @@ impl SamplerImage {
     self.sampler
   }
 }
+
+impl TextureSupport {
+  pub(crate) fn query(instance: &ash::Instance, physical_device: vk::PhysicalDevice) -> Self {
+    let (features, properties) = unsafe {
+      (
+        instance.get_physical_device_features(physical_device),
+        instance.get_physical_device_properties(physical_device),
+      )
+    };
+
+    let max_anisotropy = if features.sampler_anisotropy == vk::TRUE {
+      properties.limits.max_sampler_anisotropy
+    } else {
+      0.0
+    };
+
+    let formats = TexelFormat::ALL
+      .iter()
+      .flat_map(|format| {
+        [
+          format.vk_format(ColorSpace::Srgb),
+          format.vk_format(ColorSpace::Linear),
+        ]
+      })
+      .map(|format| {
+        let properties =
+          unsafe { instance.get_physical_device_format_properties(physical_device, format) };
+        (format, properties.optimal_tiling_features)
+      })
+      .collect();
+
+    Self {
+      max_anisotropy,
+      formats,
+    }
+  }
+
+  #[inline]
+  pub(crate) fn features(&self, format: vk::Format) -> vk::FormatFeatureFlags {
+    self.formats.get(&format).copied().unwrap_or_default()
+  }
+}
+
+fn color_layers(mip_level: u32) -> vk::ImageSubresourceLayers {
+  vk::ImageSubresourceLayers::default()
+    .aspect_mask(vk::ImageAspectFlags::COLOR)
+    .layer_count(1)
+    .base_array_layer(0)
+    .mip_level(mip_level)
+}
+
+/// Expects all levels in `TRANSFER_DST_OPTIMAL` with level 0 filled, leaves all levels in
+/// `SHADER_READ_ONLY_OPTIMAL`
+unsafe fn record_mip_blits(
+  device: &ash::Device,
+  command_buffer: vk::CommandBuffer,
+  image: vk::Image,
+  width: u32,
+  height: u32,
+  mip_levels: u32,
+) {
+  let barrier = |level: u32,
+                 src_access: vk::AccessFlags,
+                 dst_access: vk::AccessFlags,
+                 old_layout: vk::ImageLayout,
+                 new_layout: vk::ImageLayout| {
+    vk::ImageMemoryBarrier::default()
+      .src_access_mask(src_access)
+      .dst_access_mask(dst_access)
+      .old_layout(old_layout)
+      .new_layout(new_layout)
+      .image(image)
+      .subresource_range(vk::ImageSubresourceRange {
+        aspect_mask: vk::ImageAspectFlags::COLOR,
+        base_mip_level: level,
+        level_count: 1,
+        base_array_layer: 0,
+        layer_count: 1,
+      })
+  };
+
+  let offset = |(width, height): (u32, u32)| vk::Offset3D {
+    x: width as i32,
+    y: height as i32,
+    z: 1,
+  };
+
+  for level in 1..mip_levels {
+    let src_barrier = barrier(
+      level - 1,
+      vk::AccessFlags::TRANSFER_WRITE,
+      vk::AccessFlags::TRANSFER_READ,
+      vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+      vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+    );
+    device.cmd_pipeline_barrier(
+      command_buffer,
+      vk::PipelineStageFlags::TRANSFER,
+      vk::PipelineStageFlags::TRANSFER,
+      vk::DependencyFlags::empty(),
+      &[],
+      &[],
+      &[src_barrier],
+    );
+
+    let blit = vk::ImageBlit::default()
+      .src_subresource(color_layers(level - 1))
+      .src_offsets([
+        vk::Offset3D::default(),
+        offset(mipmap::mip_extent(width, height, level - 1)),
+      ])
+      .dst_subresource(color_layers(level))
+      .dst_offsets([
+        vk::Offset3D::default(),
+        offset(mipmap::mip_extent(width, height, level)),
+      ]);
+    device.cmd_blit_image(
+      command_buffer,
+      image,
+      vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+      image,
+      vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+      &[blit],
+      vk::Filter::LINEAR,
+    );
+  }
+
+  let mut read_barriers = (0..mip_levels - 1)
+    .map(|level| {
+      barrier(
+        level,
+        vk::AccessFlags::TRANSFER_READ,
+        vk::AccessFlags::SHADER_READ,
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+      )
+    })
+    .collect::<Vec<_>>();
+  read_barriers.push(barrier(
+    mip_levels - 1,
+    vk::AccessFlags::TRANSFER_WRITE,
+    vk::AccessFlags::SHADER_READ,
+    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+  ));
+  device.cmd_pipeline_barrier(
+    command_buffer,
+    vk::PipelineStageFlags::TRANSFER,
+    vk::PipelineStageFlags::FRAGMENT_SHADER,
+    vk::DependencyFlags::empty(),
+    &[],
+    &[],
+    &read_barriers,
+  );
+}
@@ -114,6 +114,8 @@ impl SimpleBuffer {
       new_buffer.write(old_ptr, self.buffer.size(), 0).ok()?;
 
       let old_buffer = std::mem::replace(&mut self.buffer, new_buffer);
+      // frames in flight may still read from the old buffer
+      unsafe { device.device_wait_idle().ok()? };
       unsafe { old_buffer.cleanup(device, allocator).ok()? };
 
       self.allocator.grow(additional_size);
@@ -145,6 +147,15 @@ impl SimpleBuffer {
     Ok(())
   }
 
+  pub fn read(&self, mem: &BufferMemory) -> Vec<u8> {
+    let mut data = vec![0; mem.size()];
+    unsafe {
+      let ptr = self.buffer.ptr().unwrap().byte_add(mem.offset());
+      ptr.copy_to_nonoverlapping(data.as_mut_ptr(), mem.size());
+    }
+    data
+  }
+
   #[inline]
   pub fn free_buffer_mem(&mut self, mem: BufferMemory) {
     self.allocator.free(mem.offset(), mem.size());
@@ -0,0 +1,277 @@
+use super::model::VertexData;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+  pub min: glam::Vec3,
+  pub max: glam::Vec3,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingSphere {
+  pub center: glam::Vec3,
+  pub radius: f32,
+}
+
+/// Bounding volumes of a model in model space
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ModelBounds {
+  pub aabb: Aabb,
+  pub sphere: BoundingSphere,
+}
+
+/// Planes are stored as (normal, distance) with the normals pointing inwards
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frustum {
+  planes: [glam::Vec4; 6],
+}
+
+impl Aabb {
+  pub fn from_points(points: impl IntoIterator<Item = glam::Vec3>) -> Option<Self> {
+    let mut points = points.into_iter();
+    let first = points.next()?;
+
+    let (min, max) = points.fold((first, first), |(min, max), point| {
+      (min.min(point), max.max(point))
+    });
+    Some(Self { min, max })
+  }
+
+  #[inline]
+  pub fn center(&self) -> glam::Vec3 {
+    (self.min + self.max) / 2.0
+  }
+
+  #[inline]
+  pub fn half_extents(&self) -> glam::Vec3 {
+    (self.max - self.min) / 2.0
+  }
+
+  /// Returns the axis aligned box enclosing this box after it is transformed by `matrix`
+  pub fn transformed(&self, matrix: &glam::Mat4) -> Self {
+    let center = matrix.transform_point3(self.center());
+    let half_extents = self.half_extents();
+    let extents = matrix.x_axis.truncate().abs() * half_extents.x
+      + matrix.y_axis.truncate().abs() * half_extents.y
+      + matrix.z_axis.truncate().abs() * half_extents.z;
+
+    Self {
+      min: center - extents,
+      max: center + extents,
+    }
+  }
+}
+
+impl BoundingSphere {
+  /// Returns the sphere around `center` enclosing all points
+  pub fn from_points(center: glam::Vec3, points: impl IntoIterator<Item = glam::Vec3>) -> Self {
+    let radius = points
+      .into_iter()
+      .map(|point| point.distance(center))
+      .fold(0.0, f32::max);
+
+    Self { center, radius }
+  }
+
+  /// Non uniform scaling grows the sphere by the largest scale factor
+  pub fn transformed(&self, matrix: &glam::Mat4) -> Self {
+    let scale = matrix
+      .x_axis
+      .truncate()
+      .length()
+      .max(matrix.y_axis.truncate().length())
+      .max(matrix.z_axis.truncate().length());
+
+    Self {
+      center: matrix.transform_point3(self.center),
+      radius: self.radius * scale,
+    }
+  }
+}
+
+impl ModelBounds {
+  pub fn from_vertices(vertices: &[VertexData]) -> Self {
+    let aabb = Aabb::from_points(vertices.iter().map(|vertex| vertex.position)).unwrap_or(Aabb {
+      min: glam::Vec3::ZERO,
+      max: glam::Vec3::ZERO,
+    });
+    let sphere =
+      BoundingSphere::from_points(aabb.center(), vertices.iter().map(|vertex| vertex.position));
+
+    Self { aabb, sphere }
+  }
+}
+
+impl Frustum {
+  /// Expects vulkans depth range of 0 to 1
+  pub fn from_matrix(view_projection: &glam::Mat4) -> Self {
+    let x = view_projection.row(0);
+    let y = view_projection.row(1);
+    let z = view_projection.row(2);
+    let w = view_projection.row(3);
+
+    let planes = [w + x, w - x, w + y, w - y, z, w - z].map(|plane| {
+      let length = plane.truncate().length();
+      if length > f32::EPSILON {
+        plane / length
+      } else {
+        // e.g. the far plane of an infinite projection, never culls anything
+        glam::Vec4::W
+      }
+    });
+
+    Self { planes }
+  }
+
+  pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+    self
+      .planes
+      .iter()
+      .all(|plane| plane.truncate().dot(sphere.center) + plane.w >= -sphere.radius)
+  }
+
+  pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+    self.planes.iter().all(|plane| {
+      let normal = plane.truncate();
+      let furthest = glam::Vec3::select(normal.cmpge(glam::Vec3::ZERO), aabb.max, aabb.min);
+      normal.dot(furthest) + plane.w >= 0.0
+    })
+  }
+
+  /// Tests the bounds placed in the world with `model_matrix`, the cheaper sphere test runs first
+  pub fn intersects(&self, bounds: &ModelBounds, model_matrix: &glam::Mat4) -> bool {
+    self.intersects_sphere(&bounds.sphere.transformed(model_matrix))
+      && self.intersects_aabb(&bounds.aabb.transformed(model_matrix))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{Aabb, BoundingSphere, Frustum, ModelBounds};
+  use crate::model::model::VertexData;
+
+  fn vertex(x: f32, y: f32, z: f32) -> VertexData {
+    VertexData {
+      position: glam::Vec3::new(x, y, z),
+      normal: glam::Vec3::Y,
+      uv: glam::Vec2::ZERO,
+      tangent: glam::Vec4::X,
+    }
+  }
+
+  fn unit_cube() -> ModelBounds {
+    ModelBounds::from_vertices(&[vertex(-1.0, -1.0, -1.0), vertex(1.0, 1.0, 1.0)])
+  }
+
+  /// Camera at the origin looking along -Z
+  fn frustum() -> Frustum {
+    let projection = glam::Mat4::perspective_rh(std::f32::consts::FRAC_PI_2, 1.0, 0.1, 100.0);
+    Frustum::from_matrix(&projection)
+  }
+
+  #[test]
+  fn bounds_from_vertices() {
+    let bounds = ModelBounds::from_vertices(&[
+      vertex(-1.0, 0.0, 2.0),
+      vertex(3.0, -2.0, 0.0),
+      vertex(1.0, 2.0, 1.0),
+    ]);
+
+    assert_eq!(
+      bounds.aabb,
+      Aabb {
+        min: glam::Vec3::new(-1.0, -2.0, 0.0),
+        max: glam::Vec3::new(3.0, 2.0, 2.0),
+      }
+    );
+    assert_eq!(bounds.sphere.center, glam::Vec3::new(1.0, 0.0, 1.0));
+    assert!((bounds.sphere.radius - 3.0).abs() < 1e-5);
+
+    let empty = ModelBounds::from_vertices(&[]);
+    assert_eq!(empty.sphere.radius, 0.0);
+  }
+
+  #[test]
+  fn transformed_bounds() {
+    let aabb = unit_cube().aabb;
+    let matrix = glam::Mat4::from_scale_rotation_translation(
+      glam::Vec3::new(2.0, 1.0, 1.0),
+      glam::Quat::from_rotation_z(std::f32::consts::FRAC_PI_2),
+      glam::Vec3::new(10.0, 0.0, 0.0),
+    );
+
+    let transformed = aabb.transformed(&matrix);
+    assert!(transformed
+      .min
+      .abs_diff_eq(glam::Vec3::new(9.0, -2.0, -1.0), 1e-5));
+    assert!(transformed
+      .max
+      .abs_diff_eq(glam::Vec3::new(11.0, 2.0, 1.0), 1e-5));
+
+    let sphere = BoundingSphere {
+      center: glam::Vec3::ZERO,
+      radius: 1.0,
+    }
+    .transformed(&matrix);
+    assert!(sphere
+      .center
+      .abs_diff_eq(glam::Vec3::new(10.0, 0.0, 0.0), 1e-5));
+    assert!((sphere.radius - 2.0).abs() < 1e-5);
+  }
+
+  #[test]
+  fn frustum_culling() {
+    let frustum = frustum();
+    let bounds = unit_cube();
+
+    let visible = [
+      glam::Vec3::new(0.0, 0.0, -10.0),
+      // partially inside the left plane
+      glam::Vec3::new(-10.5, 0.0, -10.0),
+      // intersecting the near plane
+      glam::Vec3::new(0.0, 0.0, 0.5),
+    ];
+    for position in visible {
+      let matrix = glam::Mat4::from_translation(position);
+      assert!(frustum.intersects(&bounds, &matrix), "{position} culled");
+    }
+
+    let culled = [
+      glam::Vec3::new(0.0, 0.0, 10.0),
+      glam::Vec3::new(-20.0, 0.0, -10.0),
+      glam::Vec3::new(0.0, 20.0, -10.0),
+      glam::Vec3::new(0.0, 0.0, -200.0),
+    ];
+    for position in culled {
+      let matrix = glam::Mat4::from_translation(position);
+      assert!(!frustum.intersects(&bounds, &matrix), "{position} visible");
+    }
+  }
+
+  #[test]
+  fn aabb_corner_case() {
+    let frustum = frustum();
+
+    // the sphere of the box touches the frustum, but the box itself does not
+    let aabb = Aabb {
+      min: glam::Vec3::new(-1.0, -1.0, -1.0),
+      max: glam::Vec3::new(1.0, 1.0, 1.0),
+    };
+    let sphere = BoundingSphere {
+      center: glam::Vec3::ZERO,
+      radius: 3.0_f32.sqrt(),
+    };
+    let bounds = ModelBounds { aabb, sphere };
+
+    let matrix = glam::Mat4::from_translation(glam::Vec3::new(-12.2, 0.0, -10.0));
+    assert!(frustum.intersects_sphere(&sphere.transformed(&matrix)));
+    assert!(!frustum.intersects(&bounds, &matrix));
+  }
+
+  #[test]
+  fn infinite_projection() {
+    let projection = glam::Mat4::perspective_infinite_rh(std::f32::consts::FRAC_PI_2, 1.0, 0.1);
+    let frustum = Frustum::from_matrix(&projection);
+    let matrix = glam::Mat4::from_translation(glam::Vec3::new(0.0, 0.0, -10000.0));
+    assert!(frustum.intersects(&unit_cube(), &matrix));
+  }
+}
@@ -1,29 +1,37 @@
-use crate::model::model::VertexData;
+use crate::model::{model::VertexData, tangent::generate_tangents};
 
 pub fn plane() -> (Vec<VertexData>, Vec<u32>) {
   let tl = VertexData {
     position: glam::Vec3::new(-1.0, 1.0, 0.0),
     normal: glam::Vec3::new(0.0, 1.0, 0.0),
     uv: glam::Vec2::new(0.0, 1.0),
+    tangent: glam::Vec4::ZERO,
   };
 
   let tr = VertexData {
     position: glam::Vec3::new(1.0, 1.0, 0.0),
     normal: glam::Vec3::new(0.0, 1.0, 0.0),
     uv: glam::Vec2::new(1.0, 1.0),
+    tangent: glam::Vec4::ZERO,
   };
 
   let br = VertexData {
     position: glam::Vec3::new(1.0, -1.0, 0.0),
     normal: glam::Vec3::new(0.0, 1.0, 0.0),
     uv: glam::Vec2::new(1.0, 0.0),
+    tangent: glam::Vec4::ZERO,
   };
 
   let bl = VertexData {
     position: glam::Vec3::new(-1.0, -1.0, 0.0),
     normal: glam::Vec3::new(0.0, 1.0, 0.0),
     uv: glam::Vec2::new(0.0, 0.0),
+    tangent: glam::Vec4::ZERO,
   };
 
-  (vec![tl, tr, br, bl], vec![0, 1, 2, 0, 2, 3])
+  let mut vertices = vec![tl, tr, br, bl];
+  let indices = vec![0, 1, 2, 0, 2, 3];
+  generate_tangents(&mut vertices, &indices);
+
+  (vertices, indices)
 }
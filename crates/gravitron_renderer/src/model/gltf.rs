@@ -0,0 +1,1463 @@
+use std::{
+  borrow::Cow,
+  collections::{hash_map::Entry, HashMap},
+  path::Path,
+};
+
+use anyhow::Error;
+use ash::vk;
+use gravitron_components::components::transform::Transform;
+use gravitron_ecs::{commands::Commands, EntityId};
+use gravitron_hierarchy::command_ext::HierarchyCommandExt;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{
+  asset::{AssetServer, Handle},
+  ecs::components::renderer::MeshRenderer,
+  memory::MemoryManager,
+  renderer::{
+    resources::material::{AlphaMode, Material},
+    TextureHandle, DEFAULT_TEXTURE,
+  },
+  texture::{ColorSpace, SamplerDescriptor, TextureData, TextureManager},
+};
+
+use super::{
+  manager::ModelManager,
+  model::{InstanceCount, ModelHandle, VertexData},
+  tangent::generate_tangents,
+};
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_JSON_CHUNK: u32 = 0x4E4F534A;
+const GLB_BIN_CHUNK: u32 = 0x004E4942;
+const MODE_TRIANGLES: u32 = 4;
+const FILTER_NEAREST: u32 = 9728;
+const FILTER_LINEAR: u32 = 9729;
+const FILTER_NEAREST_MIPMAP_NEAREST: u32 = 9984;
+const FILTER_LINEAR_MIPMAP_NEAREST: u32 = 9985;
+const FILTER_NEAREST_MIPMAP_LINEAR: u32 = 9986;
+const WRAP_CLAMP_TO_EDGE: u32 = 33071;
+const WRAP_MIRRORED_REPEAT: u32 = 33648;
+const WRAP_REPEAT: u32 = 10497;
+
+#[derive(Error, Debug)]
+pub enum GltfError {
+  #[error("Invalid glb file: {0}")]
+  InvalidGlb(&'static str),
+  #[error("Invalid json: {0}")]
+  Json(#[from] serde_json::Error),
+  #[error("Only glTF 2.x is supported, found version {0}")]
+  UnsupportedVersion(String),
+  #[error("Required extension {0} is not supported")]
+  UnsupportedExtension(String),
+  #[error("{kind} {index} does not exist")]
+  MissingIndex { kind: &'static str, index: usize },
+  #[error("Buffer {index} could not be loaded: {reason}")]
+  Buffer { index: usize, reason: String },
+  #[error("Accessor {index} is invalid: {reason}")]
+  InvalidAccessor { index: usize, reason: &'static str },
+  #[error("Primitive mode {0} is not supported, only triangle lists are")]
+  UnsupportedMode(u32),
+  #[error("Mesh primitive has no POSITION attribute")]
+  MissingPositions,
+  #[error("Node {0} is part of a cycle or has multiple parents")]
+  InvalidHierarchy(usize),
+  #[error("Model could not be uploaded")]
+  Upload,
+  #[error("Texture {index} could not be loaded: {reason}")]
+  Texture { index: usize, reason: String },
+  #[error(transparent)]
+  Io(#[from] std::io::Error),
+}
+
+/// Receives the meshes, textures and materials of an imported scene, implemented by [`ModelUploader`] to upload them to the gpu
+pub trait ModelSink {
+  fn add_model(&mut self, vertices: Vec<VertexData>, indices: Vec<u32>) -> Option<ModelHandle>;
+
+  fn add_texture(
+    &mut self,
+    texture: TextureData,
+    sampler: SamplerDescriptor,
+  ) -> Result<TextureHandle, Error>;
+
+  fn add_material(&mut self, material: Material) -> Handle<Material>;
+}
+
+pub struct ModelUploader<'a> {
+  model_manager: &'a mut ModelManager,
+  memory_manager: &'a mut MemoryManager,
+  texture_manager: &'a mut TextureManager,
+  asset_server: &'a mut AssetServer,
+  instance_count: InstanceCount,
+}
+
+/// A mesh primitive, nodes with multiple primitives get one child entity per primitive
+#[derive(Clone)]
+pub struct GltfPrimitive {
+  pub model: ModelHandle,
+  pub material: Handle<Material>,
+}
+
+#[derive(Clone)]
+pub struct GltfNode {
+  pub name: Option<String>,
+  pub transform: Transform,
+  pub primitives: Vec<GltfPrimitive>,
+  pub children: Vec<usize>,
+}
+
+/// The node tree of the default scene with the meshes already added to a [`ModelSink`]
+pub struct GltfScene {
+  pub nodes: Vec<GltfNode>,
+  pub roots: Vec<usize>,
+}
+
+impl<'a> ModelUploader<'a> {
+  pub fn new(
+    model_manager: &'a mut ModelManager,
+    memory_manager: &'a mut MemoryManager,
+    texture_manager: &'a mut TextureManager,
+    asset_server: &'a mut AssetServer,
+    instance_count: InstanceCount,
+  ) -> Self {
+    Self {
+      model_manager,
+      memory_manager,
+      texture_manager,
+      asset_server,
+      instance_count,
+    }
+  }
+}
+
+impl ModelSink for ModelUploader<'_> {
+  fn add_model(&mut self, vertices: Vec<VertexData>, indices: Vec<u32>) -> Option<ModelHandle> {
+    self
+      .model_manager
+      .add_model(self.memory_manager, vertices, indices, self.instance_count)
+  }
+
+  #[inline]
+  fn add_texture(
+    &mut self,
+    texture: TextureData,
+    sampler: SamplerDescriptor,
+  ) -> Result<TextureHandle, Error> {
+    self
+      .texture_manager
+      .add_texture_data(self.memory_manager, texture, sampler)
+  }
+
+  #[inline]
+  fn add_material(&mut self, material: Material) -> Handle<Material> {
+    self.asset_server.add(material)
+  }
+}
+
+impl GltfScene {
+  /// Loads a `.gltf` or `.glb` file, external buffers and images are resolved relative to the file
+  pub fn load(path: impl AsRef<Path>, sink: &mut impl ModelSink) -> Result<Self, GltfError> {
+    let path = path.as_ref();
+    let data = std::fs::read(path)?;
+    Self::from_slice(&data, path.parent(), sink)
+  }
+
+  /// Parses glTF json or binary glb data, `base` is used to resolve external buffers and images.
+  /// Textures always use the first uv set
+  pub fn from_slice(
+    data: &[u8],
+    base: Option<&Path>,
+    sink: &mut impl ModelSink,
+  ) -> Result<Self, GltfError> {
+    let (json, bin) = if data.starts_with(GLB_MAGIC) {
+      parse_glb(data)?
+    } else {
+      (data, None)
+    };
+
+    let document: Document = serde_json::from_slice(json)?;
+    if !document.asset.version.starts_with("2.") {
+      return Err(GltfError::UnsupportedVersion(document.asset.version));
+    }
+    if let Some(extension) = document.extensions_required.first() {
+      return Err(GltfError::UnsupportedExtension(extension.clone()));
+    }
+
+    let buffers = document
+      .buffers
+      .iter()
+      .enumerate()
+      .map(|(index, buffer)| load_buffer(index, buffer, bin, base))
+      .collect::<Result<Vec<_>, _>>()?;
+    let importer = Importer {
+      document,
+      buffers,
+      base,
+    };
+
+    // color and emissive textures are sRGB, so a texture is uploaded once per color space it is used with
+    let mut textures = HashMap::new();
+    for (info, color_space) in importer
+      .document
+      .materials
+      .iter()
+      .flat_map(GltfMaterial::textures)
+    {
+      if let Some(info) = info {
+        if let Entry::Vacant(entry) = textures.entry((info.index, color_space)) {
+          entry.insert(importer.upload_texture(info.index, color_space, sink)?);
+        }
+      }
+    }
+
+    let materials = importer
+      .document
+      .materials
+      .iter()
+      .map(|material| sink.add_material(material.to_material(&textures)))
+      .collect::<Vec<_>>();
+    let mut default_material = None;
+
+    let meshes = importer
+      .document
+      .meshes
+      .iter()
+      .map(|mesh| {
+        mesh
+          .primitives
+          .iter()
+          .map(|primitive| {
+            let (vertices, indices) = importer.read_primitive(primitive)?;
+            let model = sink.add_model(vertices, indices).ok_or(GltfError::Upload)?;
+            let material = match primitive.material {
+              Some(index) => materials
+                .get(index)
+                .cloned()
+                .ok_or(GltfError::MissingIndex {
+                  kind: "Material",
+                  index,
+                })?,
+              None => default_material
+                .get_or_insert_with(|| {
+                  sink.add_material(GltfMaterial::default().to_material(&HashMap::new()))
+                })
+                .clone(),
+            };
+
+            Ok(GltfPrimitive { model, material })
+          })
+          .collect::<Result<Vec<_>, GltfError>>()
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+
+    let nodes = importer
+      .document
+      .nodes
+      .iter()
+      .map(|node| {
+        let primitives = match node.mesh {
+          Some(index) => meshes.get(index).cloned().ok_or(GltfError::MissingIndex {
+            kind: "Mesh",
+            index,
+          })?,
+          None => Vec::new(),
+        };
+
+        Ok(GltfNode {
+          name: node.name.clone(),
+          transform: node.transform(),
+          primitives,
+          children: node.children.clone(),
+        })
+      })
+      .collect::<Result<Vec<_>, GltfError>>()?;
+
+    let roots = importer.roots()?;
+    validate_hierarchy(&nodes, &roots)?;
+
+    Ok(Self { nodes, roots })
+  }
+
+  /// Spawns the node tree below a new root entity and returns the root
+  pub fn spawn(&self, cmds: &mut Commands) -> EntityId {
+    let root = cmds.create_entity(Transform::default());
+    for node in &self.roots {
+      self.spawn_node(cmds, root, *node);
+    }
+    root
+  }
+
+  fn spawn_node(&self, cmds: &mut Commands, parent: EntityId, index: usize) {
+    let node = &self.nodes[index];
+
+    let entity = match node.primitives.as_slice() {
+      [primitive] => cmds.create_child(
+        parent,
+        (node.transform.clone(), primitive.to_mesh_renderer()),
+      ),
+      primitives => {
+        let entity = cmds.create_child(parent, node.transform.clone());
+        for primitive in primitives {
+          cmds.create_child(entity, (Transform::default(), primitive.to_mesh_renderer()));
+        }
+        entity
+      }
+    };
+
+    for child in &node.children {
+      self.spawn_node(cmds, entity, *child);
+    }
+  }
+}
+
+impl GltfPrimitive {
+  fn to_mesh_renderer(&self) -> MeshRenderer {
+    MeshRenderer {
+      model_id: self.model.into(),
+      material: self.material.clone(),
+    }
+  }
+}
+
+/// Every node has to be reachable exactly once, otherwise spawning would not terminate
+fn validate_hierarchy(nodes: &[GltfNode], roots: &[usize]) -> Result<(), GltfError> {
+  let mut visited = vec![false; nodes.len()];
+  let mut stack = roots.to_vec();
+
+  while let Some(index) = stack.pop() {
+    let node = nodes.get(index).ok_or(GltfError::MissingIndex {
+      kind: "Node",
+      index,
+    })?;
+    if std::mem::replace(&mut visited[index], true) {
+      return Err(GltfError::InvalidHierarchy(index));
+    }
+    stack.extend(&node.children);
+  }
+
+  Ok(())
+}
+
+fn parse_glb(data: &[u8]) -> Result<(&[u8], Option<&[u8]>), GltfError> {
+  let read_u32 = |offset: usize| {
+    data
+      .get(offset..offset + 4)
+      .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+      .ok_or(GltfError::InvalidGlb("unexpected end of file"))
+  };
+
+  if read_u32(4)? != 2 {
+    return Err(GltfError::InvalidGlb("only version 2 is supported"));
+  }
+  let length = read_u32(8)? as usize;
+  let data = data.get(..length).ok_or(GltfError::InvalidGlb(
+    "file is shorter than its header states",
+  ))?;
+
+  let mut chunks = Vec::new();
+  let mut offset = 12;
+  while offset < data.len() {
+    let chunk_length = read_u32(offset)? as usize;
+    let chunk_type = read_u32(offset + 4)?;
+    let chunk = data
+      .get(offset + 8..offset + 8 + chunk_length)
+      .ok_or(GltfError::InvalidGlb("chunk exceeds the file"))?;
+    chunks.push((chunk_type, chunk));
+    offset += 8 + chunk_length;
+  }
+
+  match chunks.as_slice() {
+    [(GLB_JSON_CHUNK, json), rest @ ..] => Ok((
+      json,
+      rest
+        .iter()
+        .find(|(chunk_type, _)| *chunk_type == GLB_BIN_CHUNK)
+        .map(|(_, bin)| *bin),
+    )),
+    _ => Err(GltfError::InvalidGlb("the first chunk has to be json")),
+  }
+}
+
+fn load_buffer(
+  index: usize,
+  buffer: &Buffer,
+  bin: Option<&[u8]>,
+  base: Option<&Path>,
+) -> Result<Vec<u8>, GltfError> {
+  let error = |reason: &str| GltfError::Buffer {
+    index,
+    reason: reason.to_string(),
+  };
+
+  let data = match &buffer.uri {
+    None if index == 0 => bin
+      .ok_or_else(|| error("glb binary chunk is missing"))?
+      .to_vec(),
+    None => return Err(error("only the first buffer can use the glb binary chunk")),
+    Some(uri) if uri.starts_with("data:") => {
+      let (_, data) = uri
+        .split_once(";base64,")
+        .ok_or_else(|| error("only base64 data uris are supported"))?;
+      decode_base64(data).ok_or_else(|| error("invalid base64"))?
+    }
+    Some(uri) => {
+      let base = base.ok_or_else(|| error("external buffers need a base path"))?;
+      std::fs::read(base.join(uri)).map_err(|err| error(&err.to_string()))?
+    }
+  };
+
+  if data.len() < buffer.byte_length {
+    return Err(error("buffer is shorter than its byteLength"));
+  }
+  Ok(data)
+}
+
+fn decode_base64(data: &str) -> Option<Vec<u8>> {
+  let mut decoded = Vec::with_capacity(data.len() * 3 / 4);
+  let mut bits = 0u32;
+  let mut bit_count = 0;
+
+  for char in data.bytes().take_while(|char| *char != b'=') {
+    let value = match char {
+      b'A'..=b'Z' => char - b'A',
+      b'a'..=b'z' => char - b'a' + 26,
+      b'0'..=b'9' => char - b'0' + 52,
+      b'+' | b'-' => 62,
+      b'/' | b'_' => 63,
+      _ => return None,
+    };
+
+    bits = (bits << 6) | value as u32;
+    bit_count += 6;
+    if bit_count >= 8 {
+      bit_count -= 8;
+      decoded.push((bits >> bit_count) as u8);
+      bits &= (1 << bit_count) - 1;
+    }
+  }
+
+  Some(decoded)
+}
+
+struct Importer<'a> {
+  document: Document,
+  buffers: Vec<Vec<u8>>,
+  base: Option<&'a Path>,
+}
+
+impl Importer<'_> {
+  /// Uses the default scene, or all nodes without a parent if the file has no scenes
+  fn roots(&self) -> Result<Vec<usize>, GltfError> {
+    let document = &self.document;
+    if !document.scenes.is_empty() {
+      let index = document.scene.unwrap_or(0);
+      let scene = document.scenes.get(index).ok_or(GltfError::MissingIndex {
+        kind: "Scene",
+        index,
+      })?;
+      return Ok(scene.nodes.clone());
+    }
+
+    let mut has_parent = vec![false; document.nodes.len()];
+    for child in document.nodes.iter().flat_map(|node| &node.children) {
+      if let Some(has_parent) = has_parent.get_mut(*child) {
+        *has_parent = true;
+      }
+    }
+    Ok(
+      (0..document.nodes.len())
+        .filter(|node| !has_parent[*node])
+        .collect(),
+    )
+  }
+
+  fn read_primitive(
+    &self,
+    primitive: &Primitive,
+  ) -> Result<(Vec<VertexData>, Vec<u32>), GltfError> {
+    if primitive.mode != MODE_TRIANGLES {
+      return Err(GltfError::UnsupportedMode(primitive.mode));
+    }
+
+    let positions = self.read_floats(
+      *primitive
+        .attributes
+        .get("POSITION")
+        .ok_or(GltfError::MissingPositions)?,
+      3,
+    )?;
+    let vertex_count = positions.len() / 3;
+
+    let attribute = |name: &str, components: usize| {
+      primitive
+        .attributes
+        .get(name)
+        .map(|index| {
+          let values = self.read_floats(*index, components)?;
+          if values.len() != vertex_count * components {
+            return Err(GltfError::InvalidAccessor {
+              index: *index,
+              reason: "count differs from the position count",
+            });
+          }
+          Ok(values)
+        })
+        .transpose()
+    };
+    let normals = attribute("NORMAL", 3)?;
+    let uvs = attribute("TEXCOORD_0", 2)?;
+    // tangents are only kept next to the normals they were authored for
+    let tangents = normals
+      .is_some()
+      .then(|| attribute("TANGENT", 4))
+      .transpose()?
+      .flatten();
+
+    let mut vertices = (0..vertex_count)
+      .map(|i| VertexData {
+        position: glam::Vec3::from_slice(&positions[i * 3..]),
+        normal: normals
+          .as_ref()
+          .map(|normals| glam::Vec3::from_slice(&normals[i * 3..]))
+          .unwrap_or_default(),
+        uv: uvs
+          .as_ref()
+          .map(|uvs| glam::Vec2::from_slice(&uvs[i * 2..]))
+          .unwrap_or_default(),
+        tangent: tangents
+          .as_ref()
+          .map(|tangents| glam::Vec4::from_slice(&tangents[i * 4..]))
+          .unwrap_or_default(),
+      })
+      .collect::<Vec<_>>();
+
+    let mut indices = match primitive.indices {
+      Some(index) => {
+        let indices = self.read_accessor(index, 1, read_uint)?;
+        if indices.iter().any(|i| *i as usize >= vertex_count) {
+          return Err(GltfError::InvalidAccessor {
+            index,
+            reason: "index out of range",
+          });
+        }
+        indices
+      }
+      None => (0..vertex_count as u32).collect(),
+    };
+    indices.truncate(indices.len() / 3 * 3);
+
+    // the spec requires flat normals when they are missing, so every triangle gets its own vertices
+    if normals.is_none() {
+      vertices = indices
+        .chunks(3)
+        .flat_map(|triangle| {
+          let [a, b, c] = [0, 1, 2].map(|i| vertices[triangle[i] as usize]);
+          let normal = (b.position - a.position)
+            .cross(c.position - a.position)
+            .normalize_or_zero();
+          [a, b, c].map(|vertex| VertexData { normal, ..vertex })
+        })
+        .collect();
+      indices = (0..vertices.len() as u32).collect();
+    }
+
+    if tangents.is_none() {
+      generate_tangents(&mut vertices, &indices);
+    }
+
+    Ok((vertices, indices))
+  }
+
+  fn read_floats(&self, index: usize, components: usize) -> Result<Vec<f32>, GltfError> {
+    self.read_accessor(index, components, read_float)
+  }
+
+  /// Returns the components of all elements in order
+  fn read_accessor<T: Default + Clone>(
+    &self,
+    index: usize,
+    components: usize,
+    read: fn(&[u8], u32, bool) -> Option<T>,
+  ) -> Result<Vec<T>, GltfError> {
+    let invalid = |reason| GltfError::InvalidAccessor { index, reason };
+    let accessor = self
+      .document
+      .accessors
+      .get(index)
+      .ok_or(GltfError::MissingIndex {
+        kind: "Accessor",
+        index,
+      })?;
+
+    if accessor.sparse.is_some() {
+      return Err(invalid("sparse accessors are not supported"));
+    }
+    let accessor_components = match accessor.kind.as_str() {
+      "SCALAR" => 1,
+      "VEC2" => 2,
+      "VEC3" => 3,
+      "VEC4" => 4,
+      _ => return Err(invalid("unsupported type")),
+    };
+    if accessor_components != components {
+      return Err(invalid("unexpected type"));
+    }
+    let component_size = match accessor.component_type {
+      5120 | 5121 => 1,
+      5122 | 5123 => 2,
+      5125 | 5126 => 4,
+      _ => return Err(invalid("unknown component type")),
+    };
+
+    // the count is checked before anything is allocated for it
+    let element_size = component_size * components;
+    let size = accessor
+      .count
+      .checked_mul(element_size)
+      .ok_or(invalid("count is too large"))?;
+
+    // accessors without a buffer view are initialized with zeros, no mesh has more of them
+    // than its buffers have bytes
+    let Some(view_index) = accessor.buffer_view else {
+      if size > self.buffers.iter().map(Vec::len).sum() {
+        return Err(invalid("count is too large"));
+      }
+      return Ok(vec![T::default(); accessor.count * components]);
+    };
+    let (view, view_data) = self.buffer_view(view_index)?;
+
+    let stride = view.byte_stride.unwrap_or(element_size);
+    // the last element only takes its own size instead of a full stride
+    let end = match accessor.count.checked_sub(1) {
+      Some(last) => last
+        .checked_mul(stride)
+        .and_then(|start| start.checked_add(accessor.byte_offset))
+        .and_then(|start| start.checked_add(element_size)),
+      None => Some(0),
+    };
+    match end {
+      Some(end) if end <= view_data.len() => (),
+      _ => return Err(invalid("data exceeds its buffer view")),
+    }
+
+    let mut values = Vec::with_capacity(accessor.count * components);
+    for element in 0..accessor.count {
+      let start = accessor.byte_offset + element * stride;
+      let data = view_data
+        .get(start..start + element_size)
+        .ok_or(invalid("data exceeds its buffer view"))?;
+
+      for component in data.chunks(component_size) {
+        values.push(
+          read(component, accessor.component_type, accessor.normalized)
+            .ok_or(invalid("unexpected component type"))?,
+        );
+      }
+    }
+
+    Ok(values)
+  }
+
+  fn buffer_view(&self, index: usize) -> Result<(&BufferView, &[u8]), GltfError> {
+    let view = self
+      .document
+      .buffer_views
+      .get(index)
+      .ok_or(GltfError::MissingIndex {
+        kind: "BufferView",
+        index,
+      })?;
+    let buffer = self
+      .buffers
+      .get(view.buffer)
+      .ok_or(GltfError::MissingIndex {
+        kind: "Buffer",
+        index: view.buffer,
+      })?;
+    let data = view
+      .byte_offset
+      .checked_add(view.byte_length)
+      .and_then(|end| buffer.get(view.byte_offset..end))
+      .ok_or_else(|| GltfError::Buffer {
+        index: view.buffer,
+        reason: format!("buffer view {index} exceeds it"),
+      })?;
+    Ok((view, data))
+  }
+
+  /// Decodes the image of a texture and uploads it with the sampler of the texture
+  fn upload_texture(
+    &self,
+    index: usize,
+    color_space: ColorSpace,
+    sink: &mut impl ModelSink,
+  ) -> Result<TextureHandle, GltfError> {
+    let error = |reason: String| GltfError::Texture { index, reason };
+    let texture = self
+      .document
+      .textures
+      .get(index)
+      .ok_or(GltfError::MissingIndex {
+        kind: "Texture",
+        index,
+      })?;
+
+    let source = texture
+      .source
+      .ok_or_else(|| error("only textures with a source image are supported".into()))?;
+    let image = self
+      .document
+      .images
+      .get(source)
+      .ok_or(GltfError::MissingIndex {
+        kind: "Image",
+        index: source,
+      })?;
+    let data = self.image_data(image).map_err(error)?;
+    let data = TextureData::decode(&data, color_space).map_err(|err| error(err.to_string()))?;
+
+    let sampler = match texture.sampler {
+      Some(sampler) => self
+        .document
+        .samplers
+        .get(sampler)
+        .ok_or(GltfError::MissingIndex {
+          kind: "Sampler",
+          index: sampler,
+        })?
+        .descriptor(),
+      None => SamplerDescriptor::linear(),
+    };
+    sink
+      .add_texture(data, sampler)
+      .map_err(|err| error(err.to_string()))
+  }
+
+  fn image_data(&self, image: &Image) -> Result<Cow<'_, [u8]>, String> {
+    match (&image.uri, image.buffer_view) {
+      (Some(uri), _) if uri.starts_with("data:") => {
+        let (_, data) = uri
+          .split_once(";base64,")
+          .ok_or("only base64 data uris are supported")?;
+        decode_base64(data)
+          .map(Cow::Owned)
+          .ok_or_else(|| "invalid base64".into())
+      }
+      (Some(uri), _) => {
+        let base = self.base.ok_or("external images need a base path")?;
+        std::fs::read(base.join(uri))
+          .map(Cow::Owned)
+          .map_err(|err| err.to_string())
+      }
+      (None, Some(view)) => self
+        .buffer_view(view)
+        .map(|(_, data)| Cow::Borrowed(data))
+        .map_err(|err| err.to_string()),
+      (None, None) => Err("the image has neither an uri nor a buffer view".into()),
+    }
+  }
+}
+
+fn read_float(bytes: &[u8], component_type: u32, normalized: bool) -> Option<f32> {
+  let (value, max) = match component_type {
+    5120 => (bytes[0] as i8 as f32, i8::MAX as f32),
+    5121 => (bytes[0] as f32, u8::MAX as f32),
+    5122 => (
+      i16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+      i16::MAX as f32,
+    ),
+    5123 => (
+      u16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+      u16::MAX as f32,
+    ),
+    5125 => (
+      u32::from_le_bytes(bytes.try_into().ok()?) as f32,
+      u32::MAX as f32,
+    ),
+    5126 => return Some(f32::from_le_bytes(bytes.try_into().ok()?)),
+    _ => return None,
+  };
+
+  Some(if normalized {
+    (value / max).max(-1.0)
+  } else {
+    value
+  })
+}
+
+fn read_uint(bytes: &[u8], component_type: u32, _: bool) -> Option<u32> {
+  match component_type {
+    5121 => Some(bytes[0] as u32),
+    5123 => Some(u16::from_le_bytes([bytes[0], bytes[1]]) as u32),
+    5125 => Some(u32::from_le_bytes(bytes.try_into().ok()?)),
+    _ => None,
+  }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Document {
+  asset: Asset,
+  #[serde(default)]
+  extensions_required: Vec<String>,
+  scene: Option<usize>,
+  #[serde(default)]
+  scenes: Vec<Scene>,
+  #[serde(default)]
+  nodes: Vec<Node>,
+  #[serde(default)]
+  meshes: Vec<Mesh>,
+  #[serde(default)]
+  materials: Vec<GltfMaterial>,
+  #[serde(default)]
+  textures: Vec<Texture>,
+  #[serde(default)]
+  images: Vec<Image>,
+  #[serde(default)]
+  samplers: Vec<Sampler>,
+  #[serde(default)]
+  accessors: Vec<Accessor>,
+  #[serde(default)]
+  buffer_views: Vec<BufferView>,
+  #[serde(default)]
+  buffers: Vec<Buffer>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+  version: String,
+}
+
+#[derive(Deserialize)]
+struct Scene {
+  #[serde(default)]
+  nodes: Vec<usize>,
+}
+
+#[derive(Deserialize)]
+struct Node {
+  name: Option<String>,
+  #[serde(default)]
+  children: Vec<usize>,
+  mesh: Option<usize>,
+  matrix: Option<[f32; 16]>,
+  translation: Option<[f32; 3]>,
+  rotation: Option<[f32; 4]>,
+  scale: Option<[f32; 3]>,
+}
+
+#[derive(Deserialize)]
+struct Mesh {
+  primitives: Vec<Primitive>,
+}
+
+#[derive(Deserialize)]
+struct Primitive {
+  attributes: HashMap<String, usize>,
+  indices: Option<usize>,
+  material: Option<usize>,
+  #[serde(default = "default_mode")]
+  mode: u32,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct GltfMaterial {
+  #[serde(default)]
+  pbr_metallic_roughness: PbrMetallicRoughness,
+  normal_texture: Option<TextureInfo>,
+  occlusion_texture: Option<TextureInfo>,
+  emissive_texture: Option<TextureInfo>,
+  #[serde(default)]
+  emissive_factor: [f32; 3],
+  #[serde(default = "default_alpha_mode")]
+  alpha_mode: String,
+  #[serde(default = "default_alpha_cutoff")]
+  alpha_cutoff: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PbrMetallicRoughness {
+  #[serde(default = "default_base_color")]
+  base_color_factor: [f32; 4],
+  #[serde(default = "default_factor")]
+  metallic_factor: f32,
+  #[serde(default = "default_factor")]
+  roughness_factor: f32,
+  base_color_texture: Option<TextureInfo>,
+  metallic_roughness_texture: Option<TextureInfo>,
+}
+
+/// `scale` is only used by normal and `strength` only by occlusion textures
+#[derive(Deserialize)]
+struct TextureInfo {
+  index: usize,
+  #[serde(default = "default_factor")]
+  scale: f32,
+  #[serde(default = "default_factor")]
+  strength: f32,
+}
+
+#[derive(Deserialize)]
+struct Texture {
+  sampler: Option<usize>,
+  source: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Image {
+  uri: Option<String>,
+  buffer_view: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Sampler {
+  mag_filter: Option<u32>,
+  min_filter: Option<u32>,
+  #[serde(default = "default_wrap")]
+  wrap_s: u32,
+  #[serde(default = "default_wrap")]
+  wrap_t: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Accessor {
+  buffer_view: Option<usize>,
+  #[serde(default)]
+  byte_offset: usize,
+  component_type: u32,
+  #[serde(default)]
+  normalized: bool,
+  count: usize,
+  #[serde(rename = "type")]
+  kind: String,
+  sparse: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BufferView {
+  buffer: usize,
+  #[serde(default)]
+  byte_offset: usize,
+  byte_length: usize,
+  byte_stride: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Buffer {
+  uri: Option<String>,
+  byte_length: usize,
+}
+
+fn default_mode() -> u32 {
+  MODE_TRIANGLES
+}
+
+fn default_base_color() -> [f32; 4] {
+  [1.0; 4]
+}
+
+fn default_factor() -> f32 {
+  1.0
+}
+
+fn default_alpha_mode() -> String {
+  "OPAQUE".into()
+}
+
+fn default_alpha_cutoff() -> f32 {
+  0.5
+}
+
+fn default_wrap() -> u32 {
+  WRAP_REPEAT
+}
+
+impl Default for PbrMetallicRoughness {
+  fn default() -> Self {
+    Self {
+      base_color_factor: default_base_color(),
+      metallic_factor: default_factor(),
+      roughness_factor: default_factor(),
+      base_color_texture: None,
+      metallic_roughness_texture: None,
+    }
+  }
+}
+
+impl GltfMaterial {
+  /// The base color, metallic-roughness, normal, occlusion and emissive texture
+  fn textures(&self) -> [(Option<&TextureInfo>, ColorSpace); 5] {
+    let pbr = &self.pbr_metallic_roughness;
+    [
+      (pbr.base_color_texture.as_ref(), ColorSpace::Srgb),
+      (pbr.metallic_roughness_texture.as_ref(), ColorSpace::Linear),
+      (self.normal_texture.as_ref(), ColorSpace::Linear),
+      (self.occlusion_texture.as_ref(), ColorSpace::Linear),
+      (self.emissive_texture.as_ref(), ColorSpace::Srgb),
+    ]
+  }
+
+  /// `textures` holds every texture of the material by index and color space
+  fn to_material(&self, textures: &HashMap<(usize, ColorSpace), TextureHandle>) -> Material {
+    let [base_color, metallic_roughness, normal, occlusion, emissive] = self
+      .textures()
+      .map(|(info, color_space)| info.map(|info| textures[&(info.index, color_space)]));
+
+    let pbr = &self.pbr_metallic_roughness;
+    Material {
+      color: glam::Vec4::from_array(pbr.base_color_factor),
+      texture_id: base_color.unwrap_or(DEFAULT_TEXTURE),
+      metallic: pbr.metallic_factor,
+      roughness: pbr.roughness_factor,
+      normal_texture: normal,
+      normal_scale: self.normal_texture.as_ref().map_or(1.0, |info| info.scale),
+      metallic_roughness_texture: metallic_roughness,
+      occlusion_texture: occlusion,
+      occlusion_strength: self
+        .occlusion_texture
+        .as_ref()
+        .map_or(1.0, |info| info.strength),
+      emissive: glam::Vec3::from_array(self.emissive_factor),
+      emissive_texture: emissive,
+      alpha_mode: match self.alpha_mode.as_str() {
+        "MASK" => AlphaMode::Mask(self.alpha_cutoff),
+        "BLEND" => AlphaMode::Blend,
+        _ => AlphaMode::Opaque,
+      },
+      ..Default::default()
+    }
+  }
+}
+
+impl Sampler {
+  fn descriptor(&self) -> SamplerDescriptor {
+    let filter = |filter| match filter {
+      FILTER_NEAREST | FILTER_NEAREST_MIPMAP_NEAREST | FILTER_NEAREST_MIPMAP_LINEAR => {
+        vk::Filter::NEAREST
+      }
+      _ => vk::Filter::LINEAR,
+    };
+    let mipmap_mode = match self.min_filter {
+      Some(FILTER_NEAREST_MIPMAP_NEAREST | FILTER_LINEAR_MIPMAP_NEAREST) => {
+        vk::SamplerMipmapMode::NEAREST
+      }
+      _ => vk::SamplerMipmapMode::LINEAR,
+    };
+    let wrap = |wrap| match wrap {
+      WRAP_CLAMP_TO_EDGE => vk::SamplerAddressMode::CLAMP_TO_EDGE,
+      WRAP_MIRRORED_REPEAT => vk::SamplerAddressMode::MIRRORED_REPEAT,
+      _ => vk::SamplerAddressMode::REPEAT,
+    };
+
+    let sampler = SamplerDescriptor::linear()
+      .with_filters(
+        self.mag_filter.map_or(vk::Filter::LINEAR, filter),
+        self.min_filter.map_or(vk::Filter::LINEAR, filter),
+        mipmap_mode,
+      )
+      .with_address_modes(
+        wrap(self.wrap_s),
+        wrap(self.wrap_t),
+        vk::SamplerAddressMode::REPEAT,
+      );
+    // minification filters without mipmaps only sample the base level
+    match self.min_filter {
+      Some(FILTER_NEAREST | FILTER_LINEAR) => sampler.without_mipmaps(),
+      _ => sampler,
+    }
+  }
+}
+
+impl Node {
+  fn transform(&self) -> Transform {
+    if let Some(matrix) = self.matrix {
+      return Transform::from_matrix(glam::Mat4::from_cols_array(&matrix));
+    }
+
+    Transform::new(
+      self
+        .translation
+        .map(glam::Vec3::from_array)
+        .unwrap_or_default(),
+      self
+        .rotation
+        .map(glam::Quat::from_array)
+        .unwrap_or_default(),
+      self
+        .scale
+        .map(glam::Vec3::from_array)
+        .unwrap_or(glam::Vec3::ONE),
+    )
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::{
+    io::Cursor,
+    sync::{Arc, Mutex},
+  };
+
+  use anyhow::Error;
+  use ash::vk;
+  use gravitron_components::components::transform::Transform;
+  use gravitron_ecs::{
+    commands::Commands, scheduler::SchedulerBuilder, systems::query::Query, world::World,
+  };
+  use gravitron_hierarchy::components::Parent;
+
+  use super::{decode_base64, GltfError, GltfScene, ModelSink};
+  use crate::{
+    asset::{AssetServer, Handle},
+    ecs::components::renderer::MeshRenderer,
+    model::model::{ModelHandle, VertexData},
+    renderer::{
+      resources::material::{AlphaMode, Material},
+      TextureHandle,
+    },
+    texture::{ColorSpace, SamplerDescriptor, TextureData},
+  };
+
+  #[derive(Default)]
+  struct MockSink {
+    models: Vec<(Vec<VertexData>, Vec<u32>)>,
+    textures: Vec<(TextureData, SamplerDescriptor)>,
+    assets: AssetServer,
+  }
+
+  impl ModelSink for MockSink {
+    fn add_model(&mut self, vertices: Vec<VertexData>, indices: Vec<u32>) -> Option<ModelHandle> {
+      self.models.push((vertices, indices));
+      Some(ModelHandle(self.models.len() as u64 - 1))
+    }
+
+    fn add_texture(
+      &mut self,
+      texture: TextureData,
+      sampler: SamplerDescriptor,
+    ) -> Result<TextureHandle, Error> {
+      self.textures.push((texture, sampler));
+      Ok(TextureHandle(self.textures.len() as u32))
+    }
+
+    fn add_material(&mut self, material: Material) -> Handle<Material> {
+      self.assets.add(material)
+    }
+  }
+
+  /// A triangle in the xy plane with u16 indices
+  const TRIANGLE_BASE64: &str = "AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAABAAIAAAA=";
+
+  fn triangle_bytes() -> Vec<u8> {
+    decode_base64(TRIANGLE_BASE64).unwrap()
+  }
+
+  fn document(buffer: &str, extra: &str) -> String {
+    format!(
+      r#"{{
+        "asset": {{ "version": "2.0" }},
+        "scene": 0,
+        "scenes": [{{ "nodes": [0] }}],
+        "nodes": [
+          {{ "name": "root", "translation": [1, 2, 3], "children": [1, 2] }},
+          {{ "mesh": 0, "scale": [2, 2, 2] }},
+          {{ "mesh": 1, "matrix": [1,0,0,0, 0,1,0,0, 0,0,1,0, 5,0,0,1] }}
+        ],
+        "meshes": [
+          {{ "primitives": [{{ "attributes": {{ "POSITION": 0 }}, "indices": 1, "material": 0 }}] }},
+          {{ "primitives": [
+            {{ "attributes": {{ "POSITION": 0 }} }},
+            {{ "attributes": {{ "POSITION": 0 }}, "indices": 1 }}
+          ] }}
+        ],
+        "materials": [{{ "pbrMetallicRoughness": {{ "baseColorFactor": [1, 0, 0, 1], "metallicFactor": 0.5 }}, "emissiveFactor": [0, 1, 0], "alphaMode": "MASK", "alphaCutoff": 0.25 }}],
+        "accessors": [
+          {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" }},
+          {{ "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }}
+        ],
+        "bufferViews": [
+          {{ "buffer": 0, "byteLength": 36 }},
+          {{ "buffer": 0, "byteOffset": 36, "byteLength": 6 }}
+        ],
+        "buffers": [{{ {buffer} "byteLength": 44 }}]
+        {extra}
+      }}"#
+    )
+  }
+
+  fn build_glb(json: &str, bin: &[u8]) -> Vec<u8> {
+    let mut json = json.as_bytes().to_vec();
+    json.resize(json.len().div_ceil(4) * 4, b' ');
+
+    let mut data = b"glTF".to_vec();
+    data.extend(2u32.to_le_bytes());
+    data.extend(((12 + 8 + json.len() + 8 + bin.len()) as u32).to_le_bytes());
+    data.extend((json.len() as u32).to_le_bytes());
+    data.extend(0x4E4F534Au32.to_le_bytes());
+    data.extend(json);
+    data.extend((bin.len() as u32).to_le_bytes());
+    data.extend(0x004E4942u32.to_le_bytes());
+    data.extend(bin);
+    data
+  }
+
+  fn check_scene(scene: &GltfScene, sink: &MockSink) {
+    assert_eq!(scene.roots, vec![0]);
+    assert_eq!(scene.nodes.len(), 3);
+    assert_eq!(scene.nodes[0].name.as_deref(), Some("root"));
+    assert_eq!(scene.nodes[0].children, vec![1, 2]);
+    assert_eq!(
+      scene.nodes[0].transform.position(),
+      glam::Vec3::new(1.0, 2.0, 3.0)
+    );
+    assert_eq!(scene.nodes[1].transform.scale(), glam::Vec3::splat(2.0));
+    assert_eq!(
+      scene.nodes[2].transform.position(),
+      glam::Vec3::new(5.0, 0.0, 0.0)
+    );
+
+    assert_eq!(scene.nodes[1].primitives.len(), 1);
+    assert_eq!(scene.nodes[2].primitives.len(), 2);
+    let material = sink
+      .assets
+      .get(&scene.nodes[1].primitives[0].material)
+      .unwrap();
+    assert_eq!(material.color, glam::Vec4::new(1.0, 0.0, 0.0, 1.0));
+    assert_eq!(material.metallic, 0.5);
+    assert_eq!(material.roughness, 1.0);
+    assert_eq!(material.emissive, glam::Vec3::Y);
+    assert_eq!(material.alpha_mode, AlphaMode::Mask(0.25));
+
+    // primitives without a material share the default one
+    let primitives = &scene.nodes[2].primitives;
+    assert_eq!(primitives[0].material, primitives[1].material);
+    let default = sink.assets.get(&primitives[0].material).unwrap();
+    assert_eq!(default.color, glam::Vec4::ONE);
+    assert_eq!(default.alpha_mode, AlphaMode::Opaque);
+
+    assert_eq!(sink.models.len(), 3);
+    let (vertices, indices) = &sink.models[0];
+    assert_eq!(indices, &vec![0, 1, 2]);
+    assert_eq!(vertices[1].position, glam::Vec3::X);
+    assert!(vertices.iter().all(|vertex| vertex.normal == glam::Vec3::Z));
+    assert!(vertices
+      .iter()
+      .all(|vertex| vertex.tangent.truncate().dot(vertex.normal) == 0.0));
+  }
+
+  #[test]
+  fn gltf_data_uri() {
+    let json = document(
+      &format!(r#""uri": "data:application/octet-stream;base64,{TRIANGLE_BASE64}","#),
+      "",
+    );
+    let mut sink = MockSink::default();
+    let scene = GltfScene::from_slice(json.as_bytes(), None, &mut sink).unwrap();
+    check_scene(&scene, &sink);
+  }
+
+  #[test]
+  fn glb() {
+    let data = build_glb(&document("", ""), &triangle_bytes());
+    let mut sink = MockSink::default();
+    let scene = GltfScene::from_slice(&data, None, &mut sink).unwrap();
+    check_scene(&scene, &sink);
+  }
+
+  /// The document with a png in the binary chunk used by textured materials
+  fn textured_glb(material: &str, textures: &str) -> Vec<u8> {
+    let mut png = Cursor::new(Vec::new());
+    image::DynamicImage::from(image::RgbaImage::new(2, 2))
+      .write_to(&mut png, image::ImageFormat::Png)
+      .unwrap();
+    let png = png.into_inner();
+    let mut bin = triangle_bytes();
+    bin.extend(&png);
+
+    let json = document(
+      "",
+      &format!(
+        r#", "textures": [{textures}], "images": [{{ "bufferView": 2, "mimeType": "image/png" }}],
+        "samplers": [{{ "magFilter": 9728, "minFilter": 9728, "wrapS": 33071 }}]"#
+      ),
+    )
+    .replace(
+      r#"{ "buffer": 0, "byteOffset": 36, "byteLength": 6 }"#,
+      &format!(
+        r#"{{ "buffer": 0, "byteOffset": 36, "byteLength": 6 }}, {{ "buffer": 0, "byteOffset": 44, "byteLength": {} }}"#,
+        png.len()
+      ),
+    )
+    .replace(
+      r#""byteLength": 44 }]"#,
+      &format!(r#""byteLength": {} }}]"#, bin.len()),
+    )
+    .replace(r#""metallicFactor": 0.5 }"#, material);
+    build_glb(&json, &bin)
+  }
+
+  #[test]
+  fn textured_material() {
+    let data = textured_glb(
+      r#""metallicFactor": 0.5, "baseColorTexture": { "index": 0 }, "metallicRoughnessTexture": { "index": 1 } },
+      "normalTexture": { "index": 1, "scale": 0.5 }, "emissiveTexture": { "index": 0 }"#,
+      r#"{ "source": 0 }, { "source": 0, "sampler": 0 }"#,
+    );
+    let mut sink = MockSink::default();
+    let scene = GltfScene::from_slice(&data, None, &mut sink).unwrap();
+
+    // textures are shared by slots with the same color space
+    assert_eq!(sink.textures.len(), 2);
+    let (color, color_sampler) = &sink.textures[0];
+    assert_eq!((color.width(), color.height()), (2, 2));
+    assert_eq!(color.color_space(), ColorSpace::Srgb);
+    assert_eq!(*color_sampler, SamplerDescriptor::linear());
+    let (data, sampler) = &sink.textures[1];
+    assert_eq!(data.color_space(), ColorSpace::Linear);
+    assert_eq!(sampler.mag_filter, vk::Filter::NEAREST);
+    assert_eq!(
+      sampler.address_mode_u,
+      vk::SamplerAddressMode::CLAMP_TO_EDGE
+    );
+    assert_eq!(sampler.address_mode_v, vk::SamplerAddressMode::REPEAT);
+    assert!(!sampler.mipmaps);
+
+    let material = sink
+      .assets
+      .get(&scene.nodes[1].primitives[0].material)
+      .unwrap();
+    assert_eq!(material.texture_id, TextureHandle(1));
+    assert_eq!(material.emissive_texture, Some(TextureHandle(1)));
+    assert_eq!(material.metallic_roughness_texture, Some(TextureHandle(2)));
+    assert_eq!(material.normal_texture, Some(TextureHandle(2)));
+    assert_eq!(material.normal_scale, 0.5);
+    assert_eq!(material.occlusion_texture, None);
+    assert_eq!(material.metallic, 0.5);
+  }
+
+  #[test]
+  fn texture_errors() {
+    let mut sink = MockSink::default();
+    let missing = textured_glb(
+      r#""baseColorTexture": { "index": 5 } }"#,
+      r#"{ "source": 0 }"#,
+    );
+    assert!(matches!(
+      GltfScene::from_slice(&missing, None, &mut sink),
+      Err(GltfError::MissingIndex {
+        kind: "Texture",
+        index: 5
+      })
+    ));
+
+    let without_source = textured_glb(r#""baseColorTexture": { "index": 0 } }"#, "{}");
+    assert!(matches!(
+      GltfScene::from_slice(&without_source, None, &mut sink),
+      Err(GltfError::Texture { index: 0, .. })
+    ));
+  }
+
+  #[test]
+  fn base64() {
+    assert_eq!(decode_base64("aGVsbG8=").unwrap(), b"hello");
+    assert_eq!(decode_base64("aGVsbG8h").unwrap(), b"hello!");
+    assert!(decode_base64("a*").is_none());
+  }
+
+  #[test]
+  fn errors() {
+    let mut sink = MockSink::default();
+    let bin = triangle_bytes();
+
+    let version = document("", "").replace("2.0", "1.0");
+    assert!(matches!(
+      GltfScene::from_slice(&build_glb(&version, &bin), None, &mut sink),
+      Err(GltfError::UnsupportedVersion(_))
+    ));
+
+    let extension = document(
+      "",
+      r#", "extensionsRequired": ["KHR_draco_mesh_compression"]"#,
+    );
+    assert!(matches!(
+      GltfScene::from_slice(&build_glb(&extension, &bin), None, &mut sink),
+      Err(GltfError::UnsupportedExtension(_))
+    ));
+
+    let cycle = document("", "").replace(r#""mesh": 0,"#, r#""mesh": 0, "children": [0],"#);
+    assert!(matches!(
+      GltfScene::from_slice(&build_glb(&cycle, &bin), None, &mut sink),
+      Err(GltfError::InvalidHierarchy(0))
+    ));
+
+    let lines = document("", "").replace(r#""indices": 1 }"#, r#""indices": 1, "mode": 1 }"#);
+    assert!(matches!(
+      GltfScene::from_slice(&build_glb(&lines, &bin), None, &mut sink),
+      Err(GltfError::UnsupportedMode(1))
+    ));
+
+    assert!(matches!(
+      GltfScene::from_slice(&build_glb(&document("", ""), &bin[..20]), None, &mut sink),
+      Err(GltfError::Buffer { index: 0, .. })
+    ));
+
+    for count in ["4", "18446744073709551615"] {
+      let accessor = document("", "").replacen(r#""count": 3"#, &format!(r#""count": {count}"#), 1);
+      assert!(matches!(
+        GltfScene::from_slice(&build_glb(&accessor, &bin), None, &mut sink),
+        Err(GltfError::InvalidAccessor { index: 0, .. })
+      ));
+    }
+
+    assert!(matches!(
+      GltfScene::from_slice(b"glTF\x01\0\0\0", None, &mut sink),
+      Err(GltfError::InvalidGlb(_))
+    ));
+  }
+
+  #[test]
+  fn spawn_hierarchy() {
+    let data = build_glb(&document("", ""), &triangle_bytes());
+    let scene = GltfScene::from_slice(&data, None, &mut MockSink::default()).unwrap();
+
+    let mut world = World::new();
+    let mut scheduler: SchedulerBuilder<usize> = SchedulerBuilder::default();
+    let root = Arc::new(Mutex::new(None));
+    let root_clone = root.clone();
+    scheduler.add_system(move |cmds: &mut Commands| {
+      let mut root = root_clone.lock().unwrap();
+      if root.is_none() {
+        *root = Some(scene.spawn(cmds));
+      }
+    });
+    scheduler.build(true).run(&mut world);
+
+    let counts = Arc::new(Mutex::new((0, 0, 0)));
+    let counts_clone = counts.clone();
+    let mut scheduler: SchedulerBuilder<usize> = SchedulerBuilder::default();
+    scheduler.add_system(
+      move |transforms: Query<&Transform>,
+            parents: Query<&Parent>,
+            meshes: Query<&MeshRenderer>| {
+        let mut counts = counts_clone.lock().unwrap();
+        counts.0 = transforms.into_iter().count();
+        counts.1 = parents.into_iter().count();
+        counts.2 = meshes.into_iter().count();
+      },
+    );
+    scheduler.build(true).run(&mut world);
+
+    assert!(root.lock().unwrap().is_some());
+    // scene root, node 0, node 1, node 2 and its two primitive children
+    assert_eq!(*counts.lock().unwrap(), (6, 5, 3));
+  }
+}
@@ -1,31 +1,43 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use anyhow::Error;
 use ash::vk;
 
 use crate::{
   memory::{
-    types::{BufferBlockSize, BufferId},
+    error::MemoryError,
+    types::{BufferBlockSize, BufferId, BufferMemory},
     MemoryManager,
   },
   pipeline::manager::GraphicsPipelineHandle,
 };
 
 use super::{
+  bounds::ModelBounds,
   default::{cube::cube, plane::plane},
   model::{InstanceCount, InstanceData, Model, ModelHandle, VertexData},
+  obj::{parse_obj, NormalGeneration},
 };
 
 pub struct ModelManager {
   models: HashMap<ModelHandle, Model>,
+  /// Models whose draw commands have to be recreated
+  invalidated: HashSet<ModelHandle>,
   last_id: u64,
   vertex_buffer: BufferId,
   index_buffer: BufferId,
   instance_buffer: BufferId,
+  /// Memory of removed or replaced models with the frame it was released in, kept until no frame in flight uses it
+  released: VecDeque<(u64, Vec<BufferMemory>)>,
+  frame: u64,
+  frames_in_flight: u64,
 }
 
 impl ModelManager {
-  pub(crate) fn new(memory_manager: &mut MemoryManager) -> Result<Self, Error> {
+  pub(crate) fn new(
+    memory_manager: &mut MemoryManager,
+    frames_in_flight: usize,
+  ) -> Result<Self, Error> {
     let vertex_buffer = memory_manager
       .create_advanced_buffer(vk::BufferUsageFlags::VERTEX_BUFFER, BufferBlockSize::Large)?;
     let index_buffer = memory_manager
@@ -35,10 +47,14 @@ impl ModelManager {
 
     let mut manager = ModelManager {
       models: HashMap::new(),
+      invalidated: HashSet::new(),
       last_id: 0,
       vertex_buffer,
       index_buffer,
       instance_buffer,
+      released: VecDeque::new(),
+      frame: 0,
+      frames_in_flight: frames_in_flight as u64,
     };
 
     let (vertex_data, index_data) = cube();
@@ -71,6 +87,7 @@ impl ModelManager {
     index_data: Vec<u32>,
     instance_count: InstanceCount,
   ) -> Option<ModelHandle> {
+    let bounds = ModelBounds::from_vertices(&vertex_data);
     let vertices_slice = vertex_data.as_slice();
     let vertices = memory_manager
       .add_to_buffer(self.vertex_buffer, vertices_slice)
@@ -83,13 +100,144 @@ impl ModelManager {
     let id = ModelHandle(self.last_id);
     self.models.insert(
       id,
-      Model::new(vertices, indices, index_data.len() as u32, instance_count),
+      Model::new(
+        vertices,
+        indices,
+        index_data.len() as u32,
+        bounds,
+        instance_count,
+      ),
     );
     self.last_id += 1;
 
     Some(id)
   }
 
+  /// Adds one model per object or group of the OBJ file, in the order they appear in the file
+  pub fn add_obj_models(
+    &mut self,
+    memory_manager: &mut MemoryManager,
+    source: &str,
+    normals: NormalGeneration,
+    instance_count: InstanceCount,
+  ) -> Result<Vec<ModelHandle>, Error> {
+    parse_obj(source, normals)?
+      .into_iter()
+      .map(|mesh| {
+        self
+          .add_model(memory_manager, mesh.vertices, mesh.indices, instance_count)
+          .ok_or(MemoryError::Reallocate.into())
+      })
+      .collect()
+  }
+
+  /// Entities still using the model are no longer drawn. Its gpu memory is freed once no frame
+  /// in flight can draw it anymore
+  pub fn remove_model(&mut self, model: ModelHandle) -> bool {
+    let Some(model) = self.models.remove(&model) else {
+      return false;
+    };
+
+    let mut memory = vec![model.vertices, model.indices];
+    memory.extend(model.instances.into_values().map(|(mem, _)| mem));
+    self.released.push_back((self.frame, memory));
+    true
+  }
+
+  /// Replaces the geometry of the model while keeping its handle, the old memory is freed once
+  /// no frame in flight can draw it anymore
+  pub fn replace_model(
+    &mut self,
+    memory_manager: &mut MemoryManager,
+    model: ModelHandle,
+    vertex_data: Vec<VertexData>,
+    index_data: Vec<u32>,
+  ) -> bool {
+    let Some(model_data) = self.models.get_mut(&model) else {
+      return false;
+    };
+    let Some(vertices) = memory_manager
+      .add_to_buffer(self.vertex_buffer, &vertex_data)
+      .ok()
+    else {
+      return false;
+    };
+    let Some(indices) = memory_manager
+      .add_to_buffer(self.index_buffer, &index_data)
+      .ok()
+    else {
+      memory_manager.free_buffer_mem(vertices);
+      return false;
+    };
+
+    let mut memory = vec![
+      std::mem::replace(&mut model_data.vertices, vertices),
+      std::mem::replace(&mut model_data.indices, indices),
+    ];
+    memory.extend(
+      std::mem::take(&mut model_data.instances)
+        .into_values()
+        .map(|(mem, _)| mem),
+    );
+    self.released.push_back((self.frame, memory));
+    model_data.index_len = index_data.len() as u32;
+    model_data.bounds = ModelBounds::from_vertices(&vertex_data);
+
+    self.invalidated.insert(model);
+    true
+  }
+
+  #[inline]
+  pub fn contains(&self, model: ModelHandle) -> bool {
+    self.models.contains_key(&model)
+  }
+
+  /// Frees the memory of removed models which is not used by a frame in flight anymore.
+  /// `drawing` is whether a frame is recorded
+  pub(crate) fn update(&mut self, memory_manager: &mut MemoryManager, drawing: bool) {
+    for mem in self.retire(drawing) {
+      memory_manager.free_buffer_mem(mem);
+    }
+  }
+
+  /// Counts frames like `TextureManager::retire`
+  fn retire(&mut self, drawing: bool) -> Vec<BufferMemory> {
+    if drawing {
+      self.frame += 1;
+    }
+
+    let mut retired = Vec::new();
+    while let Some((frame, _)) = self.released.front() {
+      if self.frame - frame <= self.frames_in_flight {
+        break;
+      }
+      retired.extend(self.released.pop_front().unwrap().1);
+    }
+    retired
+  }
+
+  #[inline]
+  pub fn model_bounds(&self, model: ModelHandle) -> Option<&ModelBounds> {
+    self.models.get(&model).map(|model| &model.bounds)
+  }
+
+  /// Draw of `instance_count` instances of the model starting at `first_instance` of the bound instance buffer
+  pub(crate) fn draw_command(
+    &self,
+    model: ModelHandle,
+    first_instance: u32,
+    instance_count: u32,
+  ) -> Option<vk::DrawIndexedIndirectCommand> {
+    let model = self.models.get(&model)?;
+    Some(vk::DrawIndexedIndirectCommand {
+      index_count: model.index_len,
+      instance_count,
+      first_index: (model.indices.offset() / size_of::<u32>()) as u32,
+      vertex_offset: (model.vertices.offset() / size_of::<VertexData>()) as i32,
+      first_instance,
+    })
+  }
+
   pub(crate) fn record_command_buffer(
     &self,
     memory_manager: &MemoryManager,
@@ -128,10 +276,20 @@ impl ModelManager {
     let mut cmd_new = HashMap::new();
 
     for (model_id, shaders) in commands.iter_mut() {
-      if !instances.contains_key(model_id) {
-        for (cmd, offset) in shaders.values_mut() {
+      if !instances.contains_key(model_id)
+        || !self.models.contains_key(model_id)
+        || self.invalidated.contains(model_id)
+      {
+        for (shader, (cmd, offset)) in shaders.iter_mut() {
           if cmd.instance_count > 0 {
             cmd.instance_count = 0;
+            if let Some((_, model_instances)) = self
+              .models
+              .get_mut(model_id)
+              .and_then(|model| model.instances.get_mut(shader))
+            {
+              model_instances.clear();
+            }
             cmd_copies_info.push(vk::BufferCopy {
               size: cmd_size,
               src_offset: cmd_copies.len() as u64 * cmd_size,
@@ -143,6 +301,9 @@ impl ModelManager {
       }
     }
 
+    // zeroed above, the commands are rewritten in their slots below as the instances were reset
+    self.invalidated.clear();
+
     for (model_id, shaders) in instances {
       let Some(model) = self.models.get_mut(&model_id) else {
         continue;
@@ -152,6 +313,9 @@ impl ModelManager {
       for (shader, (cmd, offset)) in model_commands.iter_mut() {
         if !shaders.contains_key(shader) && cmd.instance_count > 0 {
           cmd.instance_count = 0;
+          if let Some((_, model_instances)) = model.instances.get_mut(shader) {
+            model_instances.clear();
+          }
           cmd_copies_info.push(vk::BufferCopy {
             size: cmd_size,
             src_offset: cmd_copies.len() as u64 * cmd_size,
@@ -224,6 +388,8 @@ impl ModelManager {
 
             copy_offset += copy_size;
           }
+
+          model_instances.truncate(instances.len());
         } else {
           let instances_size = instance_size * instances.len();
           let required_size = (instances_size as f32 / model.instance_alloc_size as f32).ceil()
@@ -245,9 +411,28 @@ impl ModelManager {
             first_instance: (mem.offset() / instance_size) as u32,
           };
 
-          let shader_cmd: &mut Vec<(ModelHandle, vk::DrawIndexedIndirectCommand)> =
-            cmd_new.entry(shader).or_default();
-          shader_cmd.push((model_id, cmd));
+          if let Some((command, offset)) = model_commands.get_mut(&shader) {
+            // a reloaded model keeps its slot, the zeroing copy above is replaced as regions must not overlap
+            *command = cmd;
+            match cmd_copies_info
+              .iter()
+              .position(|copy| copy.dst_offset == *offset)
+            {
+              Some(i) => cmd_copies[i] = cmd,
+              None => {
+                cmd_copies_info.push(vk::BufferCopy {
+                  size: cmd_size,
+                  src_offset: cmd_copies.len() as u64 * cmd_size,
+                  dst_offset: *offset,
+                });
+                cmd_copies.push(cmd);
+              }
+            }
+          } else {
+            let shader_cmd: &mut Vec<(ModelHandle, vk::DrawIndexedIndirectCommand)> =
+              cmd_new.entry(shader).or_default();
+            shader_cmd.push((model_id, cmd));
+          }
 
           model.instances.insert(shader, (mem, instances));
         }
@@ -288,3 +473,56 @@ impl ModelManager {
     self.instance_buffer
   }
 }
+
+#[cfg(test)]
+mod test {
+  use std::collections::{HashMap, HashSet, VecDeque};
+
+  use super::ModelManager;
+  use crate::{
+    memory::{allocator::Allocator, types::BufferId},
+    model::{
+      bounds::ModelBounds,
+      default::cube::cube,
+      model::{InstanceCount, Model, ModelHandle},
+    },
+  };
+
+  #[test]
+  fn removed_after_frames_in_flight() {
+    let buffer = BufferId::Advanced(0);
+    let mut allocator = Allocator::new(64);
+    let mut manager = ModelManager {
+      models: HashMap::new(),
+      invalidated: HashSet::new(),
+      last_id: 1,
+      vertex_buffer: buffer,
+      index_buffer: buffer,
+      instance_buffer: buffer,
+      released: VecDeque::new(),
+      frame: 0,
+      frames_in_flight: 2,
+    };
+    let model = Model::new(
+      allocator.alloc(16, buffer).unwrap(),
+      allocator.alloc(16, buffer).unwrap(),
+      6,
+      ModelBounds::from_vertices(&cube().0),
+      InstanceCount::Low,
+    );
+    manager.models.insert(ModelHandle(0), model);
+
+    assert!(manager.remove_model(ModelHandle(0)));
+    assert!(!manager.contains(ModelHandle(0)));
+    assert!(!manager.remove_model(ModelHandle(0)));
+
+    assert!(manager.retire(true).is_empty());
+    assert!(manager.retire(true).is_empty());
+    // frames which are not drawn do not retire anything
+    assert!(manager.retire(false).is_empty());
+    let retired = manager.retire(true);
+    assert_eq!(retired.len(), 2);
+    assert!(retired.iter().all(|mem| mem.size() == 16));
+    assert!(manager.released.is_empty());
+  }
+}
@@ -0,0 +1,95 @@
+use std::path::Path;
+
+use anyhow::Error;
+
+use crate::asset::{Asset, AssetLoader};
+
+use super::{
+  model::{ModelHandle, VertexData},
+  obj::{parse_obj, NormalGeneration},
+};
+
+/// Mesh asset, uploaded to the `ModelManager` once loaded and removed from it when freed.
+/// A `MeshRenderer` holding its handle keeps it alive. The vertex data is moved to the gpu on upload
+#[derive(Debug, Default)]
+pub struct Mesh {
+  pub(crate) vertices: Vec<VertexData>,
+  pub(crate) indices: Vec<u32>,
+  pub(crate) model: Option<ModelHandle>,
+}
+
+/// Loads `.obj` files, all objects and groups are merged into one mesh
+#[derive(Default)]
+pub struct ObjLoader {
+  pub normals: NormalGeneration,
+}
+
+impl Mesh {
+  #[inline]
+  pub fn new(vertices: Vec<VertexData>, indices: Vec<u32>) -> Self {
+    Self {
+      vertices,
+      indices,
+      model: None,
+    }
+  }
+
+  /// `None` until the mesh is uploaded
+  #[inline]
+  pub fn model(&self) -> Option<ModelHandle> {
+    self.model
+  }
+}
+
+impl Asset for Mesh {}
+
+impl AssetLoader for ObjLoader {
+  type Asset = Mesh;
+
+  fn extensions(&self) -> &[&str] {
+    &["obj"]
+  }
+
+  fn load(&self, data: Vec<u8>, _: &Path) -> Result<Self::Asset, Error> {
+    let source = String::from_utf8(data)?;
+
+    let mut mesh = Mesh::default();
+    for object in parse_obj(&source, self.normals)? {
+      let offset = mesh.vertices.len() as u32;
+      mesh.vertices.extend(object.vertices);
+      mesh
+        .indices
+        .extend(object.indices.into_iter().map(|index| index + offset));
+    }
+
+    Ok(mesh)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::path::Path;
+
+  use super::ObjLoader;
+  use crate::asset::AssetLoader;
+
+  #[test]
+  fn obj_objects_merged() {
+    let source = "
+      v 0 0 0
+      v 1 0 0
+      v 0 1 0
+      o first
+      f 1 2 3
+      o second
+      f 3 2 1
+    ";
+    let mesh = ObjLoader::default()
+      .load(source.as_bytes().to_vec(), Path::new("test.obj"))
+      .unwrap();
+
+    assert_eq!(mesh.vertices.len(), 6);
+    assert_eq!(mesh.indices, vec![0, 1, 2, 3, 4, 5]);
+    assert!(mesh.model().is_none());
+  }
+}
@@ -1,6 +1,11 @@
+pub mod bounds;
 mod default;
+pub mod gltf;
 mod manager;
+pub mod mesh;
 #[allow(clippy::module_inception)]
 pub mod model;
+pub mod obj;
+pub mod tangent;
 
 pub use manager::ModelManager;
@@ -2,6 +2,8 @@ use std::collections::HashMap;
 
 use crate::{memory::types::BufferMemory, pipeline::manager::GraphicsPipelineHandle};
 
+use super::bounds::ModelBounds;
+
 pub const CUBE_MODEL: ModelHandle = ModelHandle(0);
 pub const PLANE_MODEL: ModelHandle = ModelHandle(1);
 
@@ -12,29 +14,36 @@ pub(crate) struct Model {
   pub(crate) vertices: BufferMemory,
   pub(crate) indices: BufferMemory,
   pub(crate) index_len: u32,
+  pub(crate) bounds: ModelBounds,
   pub(crate) instance_alloc_size: usize,
   pub(crate) instances: HashMap<GraphicsPipelineHandle, (BufferMemory, Vec<InstanceData>)>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 #[repr(C)]
 pub struct VertexData {
   pub position: glam::Vec3,
   pub normal: glam::Vec3,
   pub uv: glam::Vec2,
+  /// xyz is the tangent, w the sign of the bitangent `cross(normal, tangent) * w`
+  pub tangent: glam::Vec4,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 #[repr(C, packed)]
 pub struct InstanceData {
   pub model_matrix: glam::Mat4,
-  pub inv_model_matrix: glam::Mat4,
-  pub color: glam::Vec4,
-  pub metallic: f32,
-  pub roughness: f32,
-  pub texture_id: u32,
+  /// Index into the material buffer
+  pub material: u32,
+  pub render_layers: u32,
+  /// `INSTANCE_NOT_SHADOW_CASTER` and `INSTANCE_NOT_SHADOW_RECEIVER`
+  pub flags: u32,
 }
 
+pub const INSTANCE_NOT_SHADOW_CASTER: u32 = 1;
+pub const INSTANCE_NOT_SHADOW_RECEIVER: u32 = 1 << 1;
+
+#[derive(Clone, Copy, Debug)]
 pub enum InstanceCount {
   High,
   Medium,
@@ -47,12 +56,14 @@ impl Model {
     vertices: BufferMemory,
     indices: BufferMemory,
     index_len: u32,
+    bounds: ModelBounds,
     instance_count: InstanceCount,
   ) -> Self {
     Self {
       vertices,
       index_len,
       indices,
+      bounds,
       instance_alloc_size: usize::from(instance_count) * std::mem::size_of::<InstanceData>(),
       instances: HashMap::new(),
     }
@@ -60,21 +71,12 @@ impl Model {
 }
 
 impl InstanceData {
-  pub fn new(
-    model_matrix: glam::Mat4,
-    inv_model_matrix: glam::Mat4,
-    color: glam::Vec4,
-    metallic: f32,
-    roughness: f32,
-    texture_id: u32,
-  ) -> Self {
+  pub fn new(model_matrix: glam::Mat4, material: u32, render_layers: u32, flags: u32) -> Self {
     Self {
       model_matrix,
-      inv_model_matrix,
-      color,
-      metallic,
-      roughness,
-      texture_id,
+      material,
+      render_layers,
+      flags,
     }
   }
 }
@@ -0,0 +1,459 @@
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use super::{model::VertexData, tangent::generate_tangents};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ObjError {
+  #[error("Line {line}: invalid number `{value}`")]
+  InvalidNumber { line: usize, value: String },
+  #[error("Line {line}: `{keyword}` needs at least {expected} values")]
+  MissingValues {
+    line: usize,
+    keyword: &'static str,
+    expected: usize,
+  },
+  #[error("Line {line}: invalid face vertex `{value}`")]
+  InvalidFaceVertex { line: usize, value: String },
+  #[error("Line {line}: a face needs at least 3 vertices")]
+  DegenerateFace { line: usize },
+  #[error("Line {line}: {kind} index {index} is out of range")]
+  IndexOutOfRange {
+    line: usize,
+    kind: &'static str,
+    index: i64,
+  },
+}
+
+/// How normals are generated for face vertices without a normal
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NormalGeneration {
+  /// Every face uses its own normal, giving hard edges
+  Flat,
+  /// Normals of all faces sharing a position are averaged
+  #[default]
+  Smooth,
+}
+
+/// One object or group of an OBJ file
+#[derive(Debug)]
+pub struct ObjMesh {
+  pub name: Option<String>,
+  pub vertices: Vec<VertexData>,
+  pub indices: Vec<u32>,
+}
+
+/// Indices into the position, uv and normal lists
+type FaceVertex = (usize, Option<usize>, Option<usize>);
+
+#[derive(Default)]
+struct Group {
+  name: Option<String>,
+  faces: Vec<Vec<FaceVertex>>,
+}
+
+#[derive(PartialEq, Eq, Hash)]
+enum NormalSource {
+  Given(usize),
+  Smooth,
+  Flat(usize),
+}
+
+/// Parses the OBJ source into one mesh per object or group, faces outside of any group are put in an unnamed mesh.
+/// UVs are flipped vertically, as OBJ uses the bottom left as origin.
+/// Materials, smoothing groups, lines and points are ignored
+pub fn parse_obj(source: &str, normals: NormalGeneration) -> Result<Vec<ObjMesh>, ObjError> {
+  let mut positions = Vec::new();
+  let mut uvs = Vec::new();
+  let mut vertex_normals = Vec::new();
+  let mut groups = vec![Group::default()];
+
+  for (i, line) in source.lines().enumerate() {
+    let line_nr = i + 1;
+    let line = line.split('#').next().unwrap_or_default().trim();
+    let mut parts = line.split_whitespace();
+    let Some(keyword) = parts.next() else {
+      continue;
+    };
+
+    match keyword {
+      "v" => positions.push(parse_vec3(parts, line_nr, "v")?),
+      "vn" => vertex_normals.push(parse_vec3(parts, line_nr, "vn")?),
+      "vt" => {
+        let values = parse_floats(parts, line_nr)?;
+        if values.is_empty() {
+          return Err(ObjError::MissingValues {
+            line: line_nr,
+            keyword: "vt",
+            expected: 1,
+          });
+        }
+        let v = values.get(1).copied().unwrap_or(0.0);
+        uvs.push(glam::Vec2::new(values[0], 1.0 - v));
+      }
+      "f" => {
+        let face = parts
+          .map(|vertex| {
+            parse_face_vertex(
+              vertex,
+              line_nr,
+              positions.len(),
+              uvs.len(),
+              vertex_normals.len(),
+            )
+          })
+          .collect::<Result<Vec<_>, _>>()?;
+        if face.len() < 3 {
+          return Err(ObjError::DegenerateFace { line: line_nr });
+        }
+
+        groups.last_mut().unwrap().faces.push(face);
+      }
+      "o" | "g" => {
+        let name = parts.collect::<Vec<_>>().join(" ");
+        groups.push(Group {
+          name: (!name.is_empty()).then_some(name),
+          faces: Vec::new(),
+        });
+      }
+      _ => (),
+    }
+  }
+
+  Ok(
+    groups
+      .into_iter()
+      .filter(|group| !group.faces.is_empty())
+      .map(|group| build_mesh(group, &positions, &uvs, &vertex_normals, normals))
+      .collect(),
+  )
+}
+
+fn build_mesh(
+  group: Group,
+  positions: &[glam::Vec3],
+  uvs: &[glam::Vec2],
+  vertex_normals: &[glam::Vec3],
+  generation: NormalGeneration,
+) -> ObjMesh {
+  let face_normals = group
+    .faces
+    .iter()
+    .map(|face| face_normal(face.iter().map(|(position, _, _)| positions[*position])))
+    .collect::<Vec<_>>();
+
+  let mut smooth_normals: HashMap<usize, glam::Vec3> = HashMap::new();
+  if generation == NormalGeneration::Smooth {
+    for (face, normal) in group.faces.iter().zip(&face_normals) {
+      for (position, _, vertex_normal) in face {
+        if vertex_normal.is_none() {
+          *smooth_normals.entry(*position).or_default() += *normal;
+        }
+      }
+    }
+  }
+
+  let mut vertices = Vec::new();
+  let mut indices = Vec::new();
+  let mut deduplicated = HashMap::new();
+
+  for (face_index, face) in group.faces.iter().enumerate() {
+    let face_indices = face
+      .iter()
+      .map(|&(position, uv, vertex_normal)| {
+        let source = match (vertex_normal, generation) {
+          (Some(normal), _) => NormalSource::Given(normal),
+          (None, NormalGeneration::Smooth) => NormalSource::Smooth,
+          (None, NormalGeneration::Flat) => NormalSource::Flat(face_index),
+        };
+
+        *deduplicated
+          .entry((position, uv, source))
+          .or_insert_with_key(|(_, _, source)| {
+            let normal = match source {
+              NormalSource::Given(normal) => vertex_normals[*normal],
+              NormalSource::Smooth => smooth_normals[&position].normalize_or_zero(),
+              NormalSource::Flat(face) => face_normals[*face].normalize_or_zero(),
+            };
+
+            vertices.push(VertexData {
+              position: positions[position],
+              normal,
+              uv: uv.map(|uv| uvs[uv]).unwrap_or_default(),
+              tangent: glam::Vec4::ZERO,
+            });
+            vertices.len() as u32 - 1
+          })
+      })
+      .collect::<Vec<_>>();
+
+    for i in 1..face_indices.len() - 1 {
+      indices.extend([face_indices[0], face_indices[i], face_indices[i + 1]]);
+    }
+  }
+
+  generate_tangents(&mut vertices, &indices);
+
+  ObjMesh {
+    name: group.name,
+    vertices,
+    indices,
+  }
+}
+
+/// Newell's method, works for non planar polygons and is weighted by the area of the face
+fn face_normal(positions: impl Iterator<Item = glam::Vec3> + Clone) -> glam::Vec3 {
+  positions.clone().zip(positions.cycle().skip(1)).fold(
+    glam::Vec3::ZERO,
+    |normal, (current, next)| {
+      normal
+        + glam::Vec3::new(
+          (current.y - next.y) * (current.z + next.z),
+          (current.z - next.z) * (current.x + next.x),
+          (current.x - next.x) * (current.y + next.y),
+        )
+    },
+  )
+}
+
+fn parse_floats<'a>(
+  parts: impl Iterator<Item = &'a str>,
+  line: usize,
+) -> Result<Vec<f32>, ObjError> {
+  parts
+    .map(|value| {
+      value.parse().map_err(|_| ObjError::InvalidNumber {
+        line,
+        value: value.to_string(),
+      })
+    })
+    .collect()
+}
+
+fn parse_vec3<'a>(
+  parts: impl Iterator<Item = &'a str>,
+  line: usize,
+  keyword: &'static str,
+) -> Result<glam::Vec3, ObjError> {
+  let values = parse_floats(parts, line)?;
+  if values.len() < 3 {
+    return Err(ObjError::MissingValues {
+      line,
+      keyword,
+      expected: 3,
+    });
+  }
+
+  Ok(glam::Vec3::new(values[0], values[1], values[2]))
+}
+
+/// Parses `v`, `v/vt`, `v//vn` or `v/vt/vn`, negative indices are relative to the end of the lists
+fn parse_face_vertex(
+  value: &str,
+  line: usize,
+  positions: usize,
+  uvs: usize,
+  normals: usize,
+) -> Result<FaceVertex, ObjError> {
+  let invalid = || ObjError::InvalidFaceVertex {
+    line,
+    value: value.to_string(),
+  };
+
+  let mut parts = value.split('/');
+  let position = parts.next().filter(|p| !p.is_empty()).ok_or_else(invalid)?;
+  let uv = parts.next().filter(|p| !p.is_empty());
+  let normal = parts.next().filter(|p| !p.is_empty());
+  if parts.next().is_some() {
+    return Err(invalid());
+  }
+
+  let resolve = |index: &str, len: usize, kind: &'static str| {
+    let index = index.parse::<i64>().map_err(|_| invalid())?;
+    let resolved = if index < 0 {
+      len as i64 + index
+    } else {
+      index - 1
+    };
+
+    if index == 0 || resolved < 0 || resolved >= len as i64 {
+      return Err(ObjError::IndexOutOfRange { line, kind, index });
+    }
+    Ok(resolved as usize)
+  };
+
+  Ok((
+    resolve(position, positions, "position")?,
+    uv.map(|uv| resolve(uv, uvs, "uv")).transpose()?,
+    normal
+      .map(|normal| resolve(normal, normals, "normal"))
+      .transpose()?,
+  ))
+}
+
+#[cfg(test)]
+mod test {
+  use super::{parse_obj, NormalGeneration, ObjError};
+
+  const QUAD: &str = "
+    # a quad in the xy plane
+    v 0 0 0
+    v 1 0 0
+    v 1 1 0
+    v 0 1 0
+    vt 0 0
+    vt 1 0
+    vt 1 1
+    vt 0 1
+    f 1/1 2/2 3/3 4/4
+  ";
+
+  #[test]
+  fn quad() {
+    let meshes = parse_obj(QUAD, NormalGeneration::Smooth).unwrap();
+    assert_eq!(meshes.len(), 1);
+
+    let mesh = &meshes[0];
+    assert_eq!(mesh.name, None);
+    assert_eq!(mesh.vertices.len(), 4);
+    assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3]);
+
+    for vertex in &mesh.vertices {
+      assert_eq!(vertex.normal, glam::Vec3::Z);
+      // v is flipped on import, so the bitangent points along -y
+      assert!(vertex
+        .tangent
+        .abs_diff_eq(glam::Vec4::new(1.0, 0.0, 0.0, -1.0), 1e-5));
+    }
+    assert_eq!(mesh.vertices[0].uv, glam::Vec2::new(0.0, 1.0));
+    assert_eq!(mesh.vertices[2].uv, glam::Vec2::new(1.0, 0.0));
+  }
+
+  #[test]
+  fn ngon_and_negative_indices() {
+    let source = "
+      v 0 0 0
+      v 1 0 0
+      v 2 1 0
+      v 1 2 0
+      v 0 1 0
+      f -5 -4 -3 -2 -1
+    ";
+    let mesh = &parse_obj(source, NormalGeneration::Flat).unwrap()[0];
+    assert_eq!(mesh.vertices.len(), 5);
+    assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3, 0, 3, 4]);
+  }
+
+  #[test]
+  fn deduplication() {
+    let source = "
+      v 0 0 0
+      v 1 0 0
+      v 1 1 0
+      v 0 1 0
+      vn 0 0 1
+      f 1//1 2//1 3//1
+      f 1//1 3//1 4//1
+    ";
+    let mesh = &parse_obj(source, NormalGeneration::Smooth).unwrap()[0];
+    assert_eq!(mesh.vertices.len(), 4);
+    assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3]);
+  }
+
+  #[test]
+  fn groups() {
+    let source = "
+      v 0 0 0
+      v 1 0 0
+      v 0 1 0
+      f 1 2 3
+      o first
+      f 1 2 3
+      g second group
+      f 3 2 1
+      o empty
+    ";
+    let meshes = parse_obj(source, NormalGeneration::Smooth).unwrap();
+    let names = meshes
+      .iter()
+      .map(|mesh| mesh.name.as_deref())
+      .collect::<Vec<_>>();
+    assert_eq!(names, vec![None, Some("first"), Some("second group")]);
+    assert_eq!(meshes[2].vertices[0].normal, -glam::Vec3::Z);
+  }
+
+  #[test]
+  fn normal_generation() {
+    // two faces of a cube sharing an edge
+    let source = "
+      v 0 0 0
+      v 1 0 0
+      v 1 1 0
+      v 0 1 0
+      v 0 0 -1
+      v 0 1 -1
+      f 1 2 3 4
+      f 5 1 4 6
+    ";
+
+    let flat = &parse_obj(source, NormalGeneration::Flat).unwrap()[0];
+    assert_eq!(flat.vertices.len(), 8);
+    assert!(flat
+      .vertices
+      .iter()
+      .all(|vertex| vertex.normal == glam::Vec3::Z || vertex.normal == -glam::Vec3::X));
+
+    let smooth = &parse_obj(source, NormalGeneration::Smooth).unwrap()[0];
+    assert_eq!(smooth.vertices.len(), 6);
+    let shared = glam::Vec3::new(-1.0, 0.0, 1.0).normalize();
+    assert!(smooth.vertices[0].normal.abs_diff_eq(shared, 1e-5));
+    assert!(smooth.vertices[1].normal.abs_diff_eq(glam::Vec3::Z, 1e-5));
+  }
+
+  #[test]
+  fn errors() {
+    assert_eq!(
+      parse_obj("v 1 a 0", NormalGeneration::Flat).unwrap_err(),
+      ObjError::InvalidNumber {
+        line: 1,
+        value: "a".into()
+      }
+    );
+    assert_eq!(
+      parse_obj("\nvn 1 0", NormalGeneration::Flat).unwrap_err(),
+      ObjError::MissingValues {
+        line: 2,
+        keyword: "vn",
+        expected: 3
+      }
+    );
+    assert_eq!(
+      parse_obj("v 0 0 0\nv 1 0 0\nf 1 2", NormalGeneration::Flat).unwrap_err(),
+      ObjError::DegenerateFace { line: 3 }
+    );
+    assert_eq!(
+      parse_obj("v 0 0 0\nf 1 2 1", NormalGeneration::Flat).unwrap_err(),
+      ObjError::IndexOutOfRange {
+        line: 2,
+        kind: "position",
+        index: 2
+      }
+    );
+    assert_eq!(
+      parse_obj("v 0 0 0\nf 1/1 1 1", NormalGeneration::Flat).unwrap_err(),
+      ObjError::IndexOutOfRange {
+        line: 2,
+        kind: "uv",
+        index: 1
+      }
+    );
+    assert_eq!(
+      parse_obj("v 0 0 0\nf 1/x 1 1", NormalGeneration::Flat).unwrap_err(),
+      ObjError::InvalidFaceVertex {
+        line: 2,
+        value: "1/x".into()
+      }
+    );
+  }
+}
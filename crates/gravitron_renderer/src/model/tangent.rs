@@ -0,0 +1,136 @@
+use bevy_mikktspace::Geometry;
+
+use super::model::VertexData;
+
+struct TangentGeometry<'a> {
+  vertices: &'a mut [VertexData],
+  indices: &'a [u32],
+}
+
+impl TangentGeometry<'_> {
+  fn index(&self, face: usize, vert: usize) -> usize {
+    self.indices[face * 3 + vert] as usize
+  }
+
+  fn vertex(&self, face: usize, vert: usize) -> &VertexData {
+    &self.vertices[self.index(face, vert)]
+  }
+}
+
+impl Geometry for TangentGeometry<'_> {
+  fn num_faces(&self) -> usize {
+    self.indices.len() / 3
+  }
+
+  fn num_vertices_of_face(&self, _: usize) -> usize {
+    3
+  }
+
+  fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+    self.vertex(face, vert).position.to_array()
+  }
+
+  fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+    self.vertex(face, vert).normal.to_array()
+  }
+
+  fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+    self.vertex(face, vert).uv.to_array()
+  }
+
+  fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+    let index = self.index(face, vert);
+    self.vertices[index].tangent = glam::Vec4::from_array(tangent);
+  }
+}
+
+/// Generates MikkTSpace tangents for an indexed triangle list, `w` holds the handedness of the bitangent.
+/// Returns false if the generation failed, in which case every vertex gets an arbitrary tangent orthogonal to its normal.
+/// Vertices without a usable tangent, e.g. because of missing uvs, get one as well
+pub fn generate_tangents(vertices: &mut [VertexData], indices: &[u32]) -> bool {
+  let generated = indices.len() >= 3
+    && bevy_mikktspace::generate_tangents(&mut TangentGeometry { vertices, indices });
+
+  for vertex in vertices {
+    let tangent = vertex.tangent.truncate();
+    if !generated || !tangent.is_finite() || tangent.length_squared() < 1e-12 {
+      vertex.tangent = fallback_tangent(vertex.normal);
+    }
+  }
+  generated
+}
+
+fn fallback_tangent(normal: glam::Vec3) -> glam::Vec4 {
+  normal
+    .try_normalize()
+    .map(|normal| normal.any_orthonormal_vector())
+    .unwrap_or(glam::Vec3::X)
+    .extend(1.0)
+}
+
+#[cfg(test)]
+mod test {
+  use crate::model::{default::cube::cube, model::VertexData};
+
+  use super::generate_tangents;
+
+  fn quad(mirrored: bool) -> Vec<VertexData> {
+    [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+      .map(|(x, y)| VertexData {
+        position: glam::Vec3::new(x, y, 0.0),
+        normal: glam::Vec3::Z,
+        uv: glam::Vec2::new(if mirrored { -x } else { x }, y) * 0.5 + 0.5,
+        tangent: glam::Vec4::ZERO,
+      })
+      .to_vec()
+  }
+
+  #[test]
+  fn plane() {
+    let indices = [0, 1, 2, 0, 2, 3];
+
+    let mut vertices = quad(false);
+    assert!(generate_tangents(&mut vertices, &indices));
+    for vertex in &vertices {
+      assert!(vertex
+        .tangent
+        .abs_diff_eq(glam::Vec4::new(1.0, 0.0, 0.0, 1.0), 1e-5));
+    }
+
+    let mut vertices = quad(true);
+    assert!(generate_tangents(&mut vertices, &indices));
+    for vertex in &vertices {
+      assert!(vertex
+        .tangent
+        .abs_diff_eq(glam::Vec4::new(-1.0, 0.0, 0.0, -1.0), 1e-5));
+      // the bitangent still follows +v
+      let bitangent = vertex.normal.cross(vertex.tangent.truncate()) * vertex.tangent.w;
+      assert!(bitangent.abs_diff_eq(glam::Vec3::Y, 1e-5));
+    }
+  }
+
+  #[test]
+  fn cube_tangents() {
+    let (vertices, _) = cube();
+    for vertex in vertices {
+      let tangent = vertex.tangent.truncate();
+      assert!((tangent.length() - 1.0).abs() < 1e-4);
+      assert!(tangent.dot(vertex.normal).abs() < 1e-4);
+      assert_eq!(vertex.tangent.w.abs(), 1.0);
+    }
+  }
+
+  #[test]
+  fn fallback() {
+    let mut vertices = quad(false);
+    assert!(!generate_tangents(&mut vertices, &[]));
+    for vertex in &vertices {
+      assert_eq!(vertex.tangent.truncate().dot(vertex.normal), 0.0);
+      assert_eq!(vertex.tangent.w, 1.0);
+    }
+
+    vertices[0].normal = glam::Vec3::ZERO;
+    generate_tangents(&mut vertices, &[0, 1]);
+    assert_eq!(vertices[0].tangent, glam::Vec4::new(1.0, 0.0, 0.0, 1.0));
+  }
+}
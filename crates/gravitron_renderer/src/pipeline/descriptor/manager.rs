@@ -3,7 +3,10 @@ use std::collections::HashMap;
 use anyhow::Error;
 use ash::vk;
 
-use crate::memory::MemoryManager;
+use crate::{
+  memory::{types::ImageId, MemoryManager},
+  pipeline::shader::reflect::DescriptorBinding,
+};
 
 use super::{
   pool::{DescriptorPool, DescriptorPoolHandle},
@@ -46,6 +49,7 @@ impl DescriptorManager {
   ) -> Option<(DescriptorSetHandle, Vec<DescriptorHandle>)> {
     let mut descriptors = HashMap::new();
     let mut bind_desc = Vec::new();
+    let mut bindings = Vec::new();
     let mut size_needed = HashMap::new();
     let mut flags = Vec::new();
 
@@ -57,6 +61,12 @@ impl DescriptorManager {
           .descriptor_type(info.r#type.vk_type())
           .descriptor_count(info.r#type.count()),
       );
+      bindings.push(DescriptorBinding {
+        binding: i as u32,
+        descriptor_type: info.r#type.vk_type(),
+        count: info.r#type.count(),
+        stages: info.stage,
+      });
 
       *size_needed.entry(info.r#type.vk_type()).or_default() += info.r#type.count();
 
@@ -64,6 +74,11 @@ impl DescriptorManager {
         DescriptorType::StorageBuffer(_) | DescriptorType::UniformBuffer(_) => {
           flags.push(vk::DescriptorBindingFlags::UPDATE_AFTER_BIND)
         }
+        // single textures of the texture array are replaced while frames are in flight
+        DescriptorType::Sampler(_) => flags.push(
+          vk::DescriptorBindingFlags::UPDATE_AFTER_BIND
+            | vk::DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING,
+        ),
         _ => flags.push(vk::DescriptorBindingFlags::empty()),
       }
     }
@@ -138,6 +153,7 @@ impl DescriptorManager {
       pool: pool_id,
       set,
       layout: layout[0],
+      bindings,
       descriptors,
     };
     self.max_descriptor_set_id += 1;
@@ -163,6 +179,20 @@ impl DescriptorManager {
       .collect()
   }
 
+  /// Bindings of each set, unknown sets have none
+  pub(crate) fn set_bindings(&self, ids: &[DescriptorSetHandle]) -> Vec<&[DescriptorBinding]> {
+    ids
+      .iter()
+      .map(|id| {
+        self
+          .descriptor_sets
+          .get(id)
+          .map(|set| set.bindings())
+          .unwrap_or_default()
+      })
+      .collect()
+  }
+
   pub(crate) fn vk_sets(&self, ids: &[DescriptorSetHandle]) -> Vec<vk::DescriptorSet> {
     ids
       .iter()
@@ -191,23 +221,93 @@ impl DescriptorManager {
   pub(crate) fn update_changed(&mut self, memory_manager: &MemoryManager) {
     for set in self.descriptor_sets.values() {
       for descriptor in set.descriptors.values() {
-        if let Some(prev) = &descriptor.previous {
-          if *prev != descriptor.r#type {
-            self.changed = true;
-
-            write_descriptor(
-              &self.logical_device,
-              &descriptor.r#type,
-              descriptor.binding,
-              set.set,
-              memory_manager,
-            );
+        let modified = descriptor
+          .previous
+          .as_ref()
+          .is_some_and(|prev| *prev != descriptor.r#type);
+        // the memory moved into a new vk buffer when the buffer had to grow
+        let reallocated = match &descriptor.r#type {
+          DescriptorType::StorageBuffer(mem) | DescriptorType::UniformBuffer(mem) => {
+            memory_manager.buffer_reallocated(&[mem.buffer()])
           }
+          _ => false,
+        };
+
+        if modified || reallocated {
+          self.changed = true;
+
+          write_descriptor(
+            &self.logical_device,
+            &descriptor.r#type,
+            descriptor.binding,
+            set.set,
+            memory_manager,
+          );
         }
       }
     }
   }
 
+  /// Writes every descriptor using `image` again, e.g. after the image was replaced
+  pub(crate) fn rewrite_image(&mut self, image: ImageId, memory_manager: &MemoryManager) {
+    for set in self.descriptor_sets.values() {
+      for descriptor in set.descriptors.values() {
+        let images = match &descriptor.r#type {
+          DescriptorType::Image(images) | DescriptorType::Sampler(images) => images.as_slice(),
+          DescriptorType::InputAttachment(attachment) => std::slice::from_ref(attachment),
+          _ => continue,
+        };
+
+        if images.contains(&image) {
+          self.changed = true;
+          write_descriptor(
+            &self.logical_device,
+            &descriptor.r#type,
+            descriptor.binding,
+            set.set,
+            memory_manager,
+          );
+        }
+      }
+    }
+  }
+
+  /// Replaces one image of a sampler array and writes only that element.
+  /// Samplers are updated after bind, so recorded command buffers stay valid
+  pub(crate) fn replace_sampler(
+    &mut self,
+    id: DescriptorHandle,
+    index: usize,
+    image: ImageId,
+    memory_manager: &MemoryManager,
+  ) -> bool {
+    for set in self.descriptor_sets.values_mut() {
+      let Some(descriptor) = set.descriptors.get_mut(&id) else {
+        continue;
+      };
+      let DescriptorType::Sampler(images) = &mut descriptor.r#type else {
+        return false;
+      };
+      let Some(current) = images.get_mut(index) else {
+        return false;
+      };
+      *current = image;
+
+      let image_info = [image_info(&descriptor.r#type, image, memory_manager)];
+      let write = vk::WriteDescriptorSet::default()
+        .dst_set(set.set)
+        .dst_binding(descriptor.binding)
+        .dst_array_element(index as u32)
+        .descriptor_type(descriptor.r#type.vk_type())
+        .image_info(&image_info);
+      unsafe {
+        self.logical_device.update_descriptor_sets(&[write], &[]);
+      }
+      return true;
+    }
+    false
+  }
+
   #[inline]
   pub(crate) fn descriptor_changed(&self) -> bool {
     self.changed
@@ -253,26 +353,10 @@ fn write_descriptor(
       }
     }
     DescriptorType::Image(images) | DescriptorType::Sampler(images) => {
-      let mut image_infos = Vec::new();
-      for image in images {
-        let view = memory_manager
-          .get_vk_image_view(*image)
-          .expect("Failed to get Image View");
-
-        let mut image_info = vk::DescriptorImageInfo::default()
-          .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-          .image_view(view);
-
-        if let DescriptorType::Sampler(_) = r#type {
-          image_info = image_info.sampler(
-            memory_manager
-              .get_vk_sampler(*image)
-              .expect("Sampler Descriptor requires a Sampler Image"),
-          );
-        }
-
-        image_infos.push(image_info);
-      }
+      let image_infos = images
+        .iter()
+        .map(|image| image_info(r#type, *image, memory_manager))
+        .collect::<Vec<_>>();
 
       let write = vk::WriteDescriptorSet::default()
         .dst_set(set)
@@ -305,3 +389,27 @@ fn write_descriptor(
     }
   }
 }
+
+/// Info of one image of an image or sampler descriptor
+fn image_info(
+  r#type: &DescriptorType,
+  image: ImageId,
+  memory_manager: &MemoryManager,
+) -> vk::DescriptorImageInfo {
+  let view = memory_manager
+    .get_vk_image_view(image)
+    .expect("Failed to get Image View");
+
+  let image_info = vk::DescriptorImageInfo::default()
+    .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+    .image_view(view);
+
+  match r#type {
+    DescriptorType::Sampler(_) => image_info.sampler(
+      memory_manager
+        .get_vk_sampler(image)
+        .expect("Sampler Descriptor requires a Sampler Image"),
+    ),
+    _ => image_info,
+  }
+}
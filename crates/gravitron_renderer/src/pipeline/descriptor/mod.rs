@@ -8,7 +8,10 @@ use ash::vk;
 use pool::DescriptorPoolHandle;
 pub use vk::ShaderStageFlags;
 
-use crate::memory::types::{BufferMemory, ImageId};
+use crate::{
+  memory::types::{BufferMemory, ImageId},
+  pipeline::shader::reflect::DescriptorBinding,
+};
 
 pub(crate) mod manager;
 mod pool;
@@ -25,6 +28,7 @@ pub(crate) struct DescriptorSet {
   pool: DescriptorPoolHandle,
   set: vk::DescriptorSet,
   layout: vk::DescriptorSetLayout,
+  bindings: Vec<DescriptorBinding>,
   descriptors: HashMap<DescriptorHandle, Descriptor>,
 }
 
@@ -39,6 +43,11 @@ impl DescriptorSet {
     self.layout
   }
 
+  #[inline]
+  pub fn bindings(&self) -> &[DescriptorBinding] {
+    &self.bindings
+  }
+
   pub fn cleanup(&self, logical_device: &ash::Device) {
     unsafe { logical_device.destroy_descriptor_set_layout(self.layout, None) };
   }
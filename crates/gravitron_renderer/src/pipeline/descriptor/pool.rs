@@ -4,11 +4,12 @@ use anyhow::Error;
 use ash::vk;
 
 const DEFAULT_COUNT: u32 = 20;
-const TYPES: [vk::DescriptorType; 4] = [
+const TYPES: [vk::DescriptorType; 5] = [
   vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
   vk::DescriptorType::STORAGE_IMAGE,
   vk::DescriptorType::STORAGE_BUFFER,
   vk::DescriptorType::UNIFORM_BUFFER,
+  vk::DescriptorType::INPUT_ATTACHMENT,
 ];
 
 #[derive(Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
@@ -1,23 +1,37 @@
+use std::ffi::CString;
+
 use anyhow::Error;
 use ash::vk;
 use stage::RenderingStage;
 
-use crate::pipeline::manager::MAIN_FN;
+use crate::error::PipelineValidationError;
 
+#[cfg(feature = "hot_reload")]
+pub(crate) mod reload;
 pub mod stage;
 
+/// Pipelines declaring push constants receive the index of the camera they render for at offset 0
+const PUSH_CONSTANTS_SIZE: u32 = size_of::<u32>() as u32;
+
 use super::{
   descriptor::{manager::DescriptorManager, DescriptorSetHandle},
   manager::{cleanup_pipeline_cache, create_pipeline_cache, GraphicsPipelineHandle},
+  shader::reflect::{PipelineReflection, ShaderReflection},
 };
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct GraphicsPipelineBuilder<'s> {
   pub vertex_shader: Option<&'s [u32]>,
   pub geometry_shader: Option<&'s [u32]>,
   pub fragment_shader: Option<&'s [u32]>,
   pub descriptor_sets: Vec<DescriptorSetHandle>,
   pub rendering_stage: RenderingStage,
+  #[cfg(feature = "hot_reload")]
+  pub vertex_shader_file: Option<std::path::PathBuf>,
+  #[cfg(feature = "hot_reload")]
+  pub geometry_shader_file: Option<std::path::PathBuf>,
+  #[cfg(feature = "hot_reload")]
+  pub fragment_shader_file: Option<std::path::PathBuf>,
 }
 
 impl<'s> GraphicsPipelineBuilder<'s> {
@@ -44,6 +58,29 @@ impl<'s> GraphicsPipelineBuilder<'s> {
     self
   }
 
+  /// GLSL or WGSL file compiled at runtime and rebuilt on changes if hot reloading is enabled.
+  /// Takes precedence over `vertex_shader`, changes of included files are not detected
+  #[cfg(feature = "hot_reload")]
+  #[inline]
+  pub fn vertex_shader_file(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+    self.vertex_shader_file = Some(path.into());
+    self
+  }
+
+  #[cfg(feature = "hot_reload")]
+  #[inline]
+  pub fn geometry_shader_file(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+    self.geometry_shader_file = Some(path.into());
+    self
+  }
+
+  #[cfg(feature = "hot_reload")]
+  #[inline]
+  pub fn fragment_shader_file(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+    self.fragment_shader_file = Some(path.into());
+    self
+  }
+
   #[inline]
   pub fn add_descriptor_sets(mut self, sets: Vec<DescriptorSetHandle>) -> Self {
     self.descriptor_sets.extend(sets);
@@ -62,88 +99,123 @@ impl<'s> GraphicsPipelineBuilder<'s> {
     self
   }
 
+  /// Owned copy of everything but the shader code, used to build the pipeline again.
+  /// No `..Default::default()`, so new settings can't be forgotten here
+  pub(crate) fn without_shaders(&self) -> GraphicsPipelineBuilder<'static> {
+    GraphicsPipelineBuilder {
+      vertex_shader: None,
+      geometry_shader: None,
+      fragment_shader: None,
+      descriptor_sets: self.descriptor_sets.clone(),
+      rendering_stage: self.rendering_stage,
+      #[cfg(feature = "hot_reload")]
+      vertex_shader_file: self.vertex_shader_file.clone(),
+      #[cfg(feature = "hot_reload")]
+      geometry_shader_file: self.geometry_shader_file.clone(),
+      #[cfg(feature = "hot_reload")]
+      fragment_shader_file: self.fragment_shader_file.clone(),
+    }
+  }
+
   pub(crate) fn build(
     self,
     logical_device: &ash::Device,
     descriptor_manager: &DescriptorManager,
     render_pass: vk::RenderPass,
-    swapchain_extent: vk::Extent2D,
     id: GraphicsPipelineHandle,
     subpass: u32,
+    samples: vk::SampleCountFlags,
   ) -> Result<GraphicsPipeline, Error> {
     //Shader code
-    let mut modules = Vec::new();
-
-    let shader_create_info = vk::ShaderModuleCreateInfo::default().code(
+    let mut shaders = vec![(
       self
         .vertex_shader
         .unwrap_or(self.rendering_stage.vertex_shader()),
-    );
-    let shader_module = unsafe { logical_device.create_shader_module(&shader_create_info, None) }?;
-    modules.push((shader_module, vk::ShaderStageFlags::VERTEX));
-
+      vk::ShaderStageFlags::VERTEX,
+    )];
     if let Some(code) = self.geometry_shader {
+      shaders.push((code, vk::ShaderStageFlags::GEOMETRY));
+    }
+    shaders.push((
+      self
+        .fragment_shader
+        .unwrap_or(self.rendering_stage.fragment_shader(samples)),
+      vk::ShaderStageFlags::FRAGMENT,
+    ));
+
+    //Reflection, mismatches are reported before anything is created
+    let mut reflections = Vec::new();
+    for (code, stage) in &shaders {
+      let reflection = ShaderReflection::new(code)?;
+      if reflection.stage() != *stage {
+        return Err(
+          PipelineValidationError::WrongStage {
+            expected: *stage,
+            found: reflection.stage(),
+          }
+          .into(),
+        );
+      }
+      reflections.push(reflection);
+    }
+    let reflection = PipelineReflection::new(&reflections)?;
+    reflection.validate_descriptor_sets(&descriptor_manager.set_bindings(&self.descriptor_sets))?;
+
+    let (vertex_binding, available_attrib) = self.rendering_stage.vertex_layout();
+    let vertex_attrib = reflection.vertex_attributes(&available_attrib)?;
+
+    let entry_points = reflections
+      .iter()
+      .map(|reflection| CString::new(reflection.entry_point()))
+      .collect::<Result<Vec<_>, _>>()?;
+
+    let mut modules = Vec::new();
+    for (code, stage) in &shaders {
       let shader_create_info = vk::ShaderModuleCreateInfo::default().code(code);
       let shader_module =
         unsafe { logical_device.create_shader_module(&shader_create_info, None) }?;
-      modules.push((shader_module, vk::ShaderStageFlags::GEOMETRY));
+      modules.push((shader_module, *stage));
     }
 
-    let shader_create_info = vk::ShaderModuleCreateInfo::default().code(
-      self
-        .fragment_shader
-        .unwrap_or(self.rendering_stage.fragment_shader()),
-    );
-    let shader_module = unsafe { logical_device.create_shader_module(&shader_create_info, None) }?;
-    modules.push((shader_module, vk::ShaderStageFlags::FRAGMENT));
-
     let mut stages = Vec::new();
-    for (module, stage) in &modules {
+    for ((module, stage), entry_point) in modules.iter().zip(&entry_points) {
       let stage = vk::PipelineShaderStageCreateInfo::default()
         .stage(*stage)
         .module(*module)
-        .name(MAIN_FN);
+        .name(entry_point);
       stages.push(stage);
     }
 
     //Inputs
-    let (vertex_binding, vertex_attrib) = self.rendering_stage.inputs();
-
     let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::default()
       .vertex_binding_descriptions(&vertex_binding)
       .vertex_attribute_descriptions(&vertex_attrib);
     let input_assembly_info = vk::PipelineInputAssemblyStateCreateInfo::default()
       .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
 
-    //Viewport
-    let viewport_size = (swapchain_extent.width, swapchain_extent.height);
-
-    let viewport = [vk::Viewport::default()
-      .x(0.0)
-      .y(0.0)
-      .width(viewport_size.0 as f32)
-      .height(viewport_size.1 as f32)
-      .min_depth(0.0)
-      .max_depth(1.0)];
-    let scissor = [vk::Rect2D::default()
-      .offset(vk::Offset2D::default())
-      .extent(vk::Extent2D {
-        width: viewport_size.0,
-        height: viewport_size.1,
-      })];
-
+    //Viewport, set per camera while recording
     let viewport_info = vk::PipelineViewportStateCreateInfo::default()
-      .viewports(&viewport)
-      .scissors(&scissor);
-
-    let rasterizer_info = vk::PipelineRasterizationStateCreateInfo::default()
-      .line_width(1.0)
-      .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
-      .cull_mode(self.rendering_stage.cull_mode())
-      .polygon_mode(vk::PolygonMode::FILL);
+      .viewport_count(1)
+      .scissor_count(1);
+    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+    let dynamic_info =
+      vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
+
+    let rasterizer_info = self.rendering_stage.depth_bias(
+      vk::PipelineRasterizationStateCreateInfo::default()
+        .line_width(1.0)
+        .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+        .cull_mode(self.rendering_stage.cull_mode())
+        .polygon_mode(vk::PolygonMode::FILL),
+    );
 
+    // the light pass reads the G-Buffer per sample, so every sample is shaded
     let multisample_info = vk::PipelineMultisampleStateCreateInfo::default()
-      .rasterization_samples(vk::SampleCountFlags::TYPE_1);
+      .rasterization_samples(samples)
+      .sample_shading_enable(
+        self.rendering_stage == RenderingStage::Light && samples != vk::SampleCountFlags::TYPE_1,
+      )
+      .min_sample_shading(1.0);
 
     //Output
     let color_blend_attachment = vk::PipelineColorBlendAttachmentState::default()
@@ -167,12 +239,24 @@ impl<'s> GraphicsPipelineBuilder<'s> {
 
     let depth_stencil_info = vk::PipelineDepthStencilStateCreateInfo::default()
       .depth_test_enable(true)
-      .depth_write_enable(true)
+      .depth_write_enable(self.rendering_stage.depth_write())
       .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL);
 
     //Layout
     let layouts = descriptor_manager.vk_layouts(&self.descriptor_sets);
-    let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo::default().set_layouts(&layouts);
+    let push_constant_stages = reflection.push_constant_stages();
+    let push_constants = (!push_constant_stages.is_empty())
+      .then(|| {
+        vk::PushConstantRange::default()
+          .stage_flags(push_constant_stages)
+          .offset(0)
+          .size(PUSH_CONSTANTS_SIZE)
+      })
+      .into_iter()
+      .collect::<Vec<_>>();
+    let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo::default()
+      .set_layouts(&layouts)
+      .push_constant_ranges(&push_constants);
     let layout =
       unsafe { logical_device.create_pipeline_layout(&pipeline_layout_create_info, None) }?;
 
@@ -183,6 +267,7 @@ impl<'s> GraphicsPipelineBuilder<'s> {
         .vertex_input_state(&vertex_input_info)
         .input_assembly_state(&input_assembly_info)
         .viewport_state(&viewport_info)
+        .dynamic_state(&dynamic_info)
         .rasterization_state(&rasterizer_info)
         .multisample_state(&multisample_info)
         .color_blend_state(&color_blend_info)
@@ -213,16 +298,50 @@ impl<'s> GraphicsPipelineBuilder<'s> {
       pipeline,
       layout,
       descriptor_sets: self.descriptor_sets,
+      push_constant_stages,
       cache,
     })
   }
 }
 
+/// Owned copy of a builder, pipelines of the scene pass are built again from it when the sample count changes
+pub(crate) struct PipelineRecipe {
+  config: GraphicsPipelineBuilder<'static>,
+  vertex_shader: Option<Vec<u32>>,
+  geometry_shader: Option<Vec<u32>>,
+  fragment_shader: Option<Vec<u32>>,
+}
+
+impl PipelineRecipe {
+  pub(crate) fn new(builder: &GraphicsPipelineBuilder) -> Self {
+    Self {
+      config: builder.without_shaders(),
+      vertex_shader: builder.vertex_shader.map(<[u32]>::to_vec),
+      geometry_shader: builder.geometry_shader.map(<[u32]>::to_vec),
+      fragment_shader: builder.fragment_shader.map(<[u32]>::to_vec),
+    }
+  }
+
+  pub(crate) fn builder(&self) -> GraphicsPipelineBuilder<'_> {
+    let mut builder = self.config.clone();
+    builder.vertex_shader = self.vertex_shader.as_deref();
+    builder.geometry_shader = self.geometry_shader.as_deref();
+    builder.fragment_shader = self.fragment_shader.as_deref();
+    builder
+  }
+
+  #[inline]
+  pub(crate) fn rendering_stage(&self) -> RenderingStage {
+    self.config.rendering_stage
+  }
+}
+
 pub(crate) struct GraphicsPipeline {
   id: GraphicsPipelineHandle,
   pipeline: vk::Pipeline,
   layout: vk::PipelineLayout,
   descriptor_sets: Vec<DescriptorSetHandle>,
+  push_constant_stages: vk::ShaderStageFlags,
   cache: vk::PipelineCache,
 }
 
@@ -259,6 +378,51 @@ impl GraphicsPipeline {
     );
   }
 
+  /// Binds `set` in place of `replaced`, which has to be one of the pipelines descriptor sets
+  #[inline]
+  pub unsafe fn rebind_descriptor_set(
+    &self,
+    command_buffer: vk::CommandBuffer,
+    logical_device: &ash::Device,
+    descriptor_manager: &DescriptorManager,
+    replaced: DescriptorSetHandle,
+    set: DescriptorSetHandle,
+  ) {
+    let Some(index) = self.descriptor_sets.iter().position(|s| *s == replaced) else {
+      return;
+    };
+
+    let sets = descriptor_manager.vk_sets(&[set]);
+    logical_device.cmd_bind_descriptor_sets(
+      command_buffer,
+      vk::PipelineBindPoint::GRAPHICS,
+      self.layout,
+      index as u32,
+      &sets,
+      &[],
+    );
+  }
+
+  #[inline]
+  pub unsafe fn push_camera(
+    &self,
+    command_buffer: vk::CommandBuffer,
+    logical_device: &ash::Device,
+    camera: u32,
+  ) {
+    if self.push_constant_stages.is_empty() {
+      return;
+    }
+
+    logical_device.cmd_push_constants(
+      command_buffer,
+      self.layout,
+      self.push_constant_stages,
+      0,
+      &camera.to_ne_bytes(),
+    );
+  }
+
   #[inline]
   pub fn id(&self) -> GraphicsPipelineHandle {
     self.id
@@ -0,0 +1,121 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Error;
+
+use crate::pipeline::shader::{self, ShaderStage};
+
+use super::{stage::RenderingStage, GraphicsPipelineBuilder};
+
+enum ShaderSource {
+  Code(Vec<u32>),
+  File(PathBuf),
+}
+
+/// Everything needed to build a pipeline with shader files again
+pub(crate) struct ReloadablePipeline {
+  /// The original builder, only the shaders are replaced on a rebuild
+  config: GraphicsPipelineBuilder<'static>,
+  vertex_shader: Option<ShaderSource>,
+  geometry_shader: Option<ShaderSource>,
+  fragment_shader: Option<ShaderSource>,
+}
+
+#[derive(Default)]
+pub(crate) struct CompiledShaders {
+  vertex_shader: Option<Vec<u32>>,
+  geometry_shader: Option<Vec<u32>>,
+  fragment_shader: Option<Vec<u32>>,
+}
+
+impl ShaderSource {
+  fn new(code: Option<&[u32]>, file: Option<PathBuf>) -> Option<Self> {
+    file
+      .map(ShaderSource::File)
+      .or(code.map(|code| ShaderSource::Code(code.to_vec())))
+  }
+
+  fn compile(&self, stage: ShaderStage) -> Result<Vec<u32>, Error> {
+    match self {
+      ShaderSource::Code(code) => Ok(code.clone()),
+      ShaderSource::File(path) => compile_file(path, stage),
+    }
+  }
+}
+
+impl ReloadablePipeline {
+  /// Returns `None` if the builder uses no shader files
+  pub(crate) fn new(builder: &GraphicsPipelineBuilder) -> Option<Self> {
+    if builder.vertex_shader_file.is_none()
+      && builder.geometry_shader_file.is_none()
+      && builder.fragment_shader_file.is_none()
+    {
+      return None;
+    }
+
+    Some(Self {
+      config: builder.without_shaders(),
+      vertex_shader: ShaderSource::new(builder.vertex_shader, builder.vertex_shader_file.clone()),
+      geometry_shader: ShaderSource::new(
+        builder.geometry_shader,
+        builder.geometry_shader_file.clone(),
+      ),
+      fragment_shader: ShaderSource::new(
+        builder.fragment_shader,
+        builder.fragment_shader_file.clone(),
+      ),
+    })
+  }
+
+  pub(crate) fn files(&self) -> impl Iterator<Item = &Path> {
+    [
+      &self.vertex_shader,
+      &self.geometry_shader,
+      &self.fragment_shader,
+    ]
+    .into_iter()
+    .filter_map(|source| match source {
+      Some(ShaderSource::File(path)) => Some(path.as_path()),
+      _ => None,
+    })
+  }
+
+  pub(crate) fn compile(&self) -> Result<CompiledShaders, Error> {
+    let compile = |source: &Option<ShaderSource>, kind| {
+      source
+        .as_ref()
+        .map(|source| source.compile(kind))
+        .transpose()
+    };
+
+    Ok(CompiledShaders {
+      vertex_shader: compile(&self.vertex_shader, ShaderStage::Vertex)?,
+      geometry_shader: compile(&self.geometry_shader, ShaderStage::Geometry)?,
+      fragment_shader: compile(&self.fragment_shader, ShaderStage::Fragment)?,
+    })
+  }
+
+  pub(crate) fn builder<'s>(&self, shaders: &'s CompiledShaders) -> GraphicsPipelineBuilder<'s> {
+    let mut builder = self.config.clone();
+    builder.vertex_shader = shaders.vertex_shader.as_deref();
+    builder.geometry_shader = shaders.geometry_shader.as_deref();
+    builder.fragment_shader = shaders.fragment_shader.as_deref();
+    builder
+  }
+
+  #[inline]
+  pub(crate) fn rendering_stage(&self) -> RenderingStage {
+    self.config.rendering_stage
+  }
+}
+
+/// `.wgsl` files are compiled as WGSL if the `wgsl` feature is enabled, everything else as GLSL
+fn compile_file(path: &Path, stage: ShaderStage) -> Result<Vec<u32>, Error> {
+  #[cfg(feature = "wgsl")]
+  if path
+    .extension()
+    .is_some_and(|extension| extension == "wgsl")
+  {
+    return shader::compile_wgsl(&std::fs::read_to_string(path)?, stage);
+  }
+  shader::compile_glsl_file(path, stage)
+}
@@ -1,11 +1,25 @@
+use std::mem::offset_of;
+
 use ash::vk;
 use RenderingStage::*;
 
-#[derive(Default, PartialEq, PartialOrd)]
+use crate::model::model::{InstanceData, VertexData};
+
+#[derive(Default, PartialEq, PartialOrd, Clone, Copy)]
 pub enum RenderingStage {
   Light,
   #[default]
   World,
+  /// Depth of shadow casters from a lights view, replaces the shadow pipeline when built
+  Shadow,
+  /// Blended meshes shaded after the light pass, replaces the transparent pipeline when built.
+  /// Uses the vertex layout and vertex shader of `World`
+  Transparent,
+  /// Fullscreen pass over the hdr scene, run in build order after tonemapping.
+  /// Samples the previous pass with binding 1 of the post-processing descriptor set
+  PostProcess,
+  /// Writes the post-processed scene into the target, replaces the output pipeline when built
+  Output,
 }
 
 impl RenderingStage {
@@ -13,86 +27,111 @@ impl RenderingStage {
   pub(crate) fn vertex_shader(&self) -> &'static [u32] {
     match self {
       Light => vk_shader_macros::include_glsl!("./assets/light.vert"),
-      World => vk_shader_macros::include_glsl!("./assets/shader.vert"),
+      World | Transparent => vk_shader_macros::include_glsl!("./assets/shader.vert"),
+      Shadow => vk_shader_macros::include_glsl!("./assets/shadow.vert"),
+      PostProcess | Output => vk_shader_macros::include_glsl!("./assets/post.vert"),
     }
   }
 
+  /// Pipelines of the scene pass are built again when the sample count changes
+  #[inline]
+  pub(crate) fn in_scene_pass(&self) -> bool {
+    matches!(self, Light | World | Transparent)
+  }
+
+  /// With multiple samples the light pass reads the G-Buffer per sample
   #[inline]
-  pub(crate) fn fragment_shader(&self) -> &'static [u32] {
+  pub(crate) fn fragment_shader(&self, samples: vk::SampleCountFlags) -> &'static [u32] {
     match self {
+      Light if samples != vk::SampleCountFlags::TYPE_1 => {
+        vk_shader_macros::include_glsl!("./assets/light.frag", define: MSAA)
+      }
       Light => vk_shader_macros::include_glsl!("./assets/light.frag"),
       World => vk_shader_macros::include_glsl!("./assets/shader.frag"),
+      Shadow => vk_shader_macros::include_glsl!("./assets/shadow.frag"),
+      Transparent => vk_shader_macros::include_glsl!("./assets/transparent.frag"),
+      PostProcess => vk_shader_macros::include_glsl!("./assets/tonemap.frag"),
+      Output => vk_shader_macros::include_glsl!("./assets/output.frag"),
     }
   }
 
-  #[inline]
-  pub(crate) fn inputs(
+  /// All attributes the vertex and instance buffers of the stage provide,
+  /// pipelines only use the ones their vertex shader reads
+  pub(crate) fn vertex_layout(
     &self,
   ) -> (
     Vec<vk::VertexInputBindingDescription>,
     Vec<vk::VertexInputAttributeDescription>,
   ) {
     match self {
-      Light => (vec![], vec![]),
-      World => {
+      Light | PostProcess | Output => (vec![], vec![]),
+      World | Shadow | Transparent => {
         let vertex_binding = vec![
           vk::VertexInputBindingDescription::default()
             .binding(0)
-            .stride(32)
+            .stride(size_of::<VertexData>() as u32)
             .input_rate(vk::VertexInputRate::VERTEX),
           vk::VertexInputBindingDescription::default()
             .binding(1)
-            .stride(156)
+            .stride(size_of::<InstanceData>() as u32)
             .input_rate(vk::VertexInputRate::INSTANCE),
         ];
 
-        let mut vertex_attrib = vec![];
-
-        for i in 0..2 {
-          vertex_attrib.push(
-            vk::VertexInputAttributeDescription::default()
-              .binding(0)
-              .location(i)
-              .offset(i * 12)
-              .format(vk::Format::R32G32B32_SFLOAT),
-          );
-        }
-
-        vertex_attrib.push(
-          vk::VertexInputAttributeDescription::default()
-            .binding(0)
-            .location(2)
-            .offset(24)
-            .format(vk::Format::R32G32_SFLOAT),
-        );
-
-        for i in 0..9 {
-          vertex_attrib.push(
-            vk::VertexInputAttributeDescription::default()
-              .binding(1)
-              .location(i + 3)
-              .offset(i * 16)
-              .format(vk::Format::R32G32B32A32_SFLOAT),
-          );
-        }
-
-        for i in 0..2 {
-          vertex_attrib.push(
-            vk::VertexInputAttributeDescription::default()
-              .binding(1)
-              .location(12 + i)
-              .offset(144 + i * 4)
-              .format(vk::Format::R32_SFLOAT),
-          );
-        }
-
-        vertex_attrib.push(
+        let attribute = |binding, location, offset, format| {
           vk::VertexInputAttributeDescription::default()
-            .binding(1)
-            .location(14)
-            .offset(152)
-            .format(vk::Format::R32_UINT),
-        );
+            .binding(binding)
+            .location(location)
+            .offset(offset as u32)
+            .format(format)
+        };
+        let mat4 = |location: u32, offset: usize| {
+          (0..4).map(move |i| {
+            attribute(
+              1,
+              location + i,
+              offset + i as usize * size_of::<glam::Vec4>(),
+              vk::Format::R32G32B32A32_SFLOAT,
+            )
+          })
+        };
+
+        let mut vertex_attrib = vec![
+          attribute(
+            0,
+            0,
+            offset_of!(VertexData, position),
+            vk::Format::R32G32B32_SFLOAT,
+          ),
+          attribute(
+            0,
+            1,
+            offset_of!(VertexData, normal),
+            vk::Format::R32G32B32_SFLOAT,
+          ),
+          attribute(0, 2, offset_of!(VertexData, uv), vk::Format::R32G32_SFLOAT),
+          attribute(
+            0,
+            3,
+            offset_of!(VertexData, tangent),
+            vk::Format::R32G32B32A32_SFLOAT,
+          ),
+        ];
+        vertex_attrib.extend(mat4(4, offset_of!(InstanceData, model_matrix)));
+        vertex_attrib.extend([
+          attribute(
+            1,
+            8,
+            offset_of!(InstanceData, material),
+            vk::Format::R32_UINT,
+          ),
+          attribute(
+            1,
+            9,
+            offset_of!(InstanceData, render_layers),
+            vk::Format::R32_UINT,
+          ),
+          attribute(1, 10, offset_of!(InstanceData, flags), vk::Format::R32_UINT),
+        ]);
 
         (vertex_binding, vertex_attrib)
       }
@@ -105,8 +144,10 @@ impl RenderingStage {
     color: vk::PipelineColorBlendAttachmentState,
   ) -> Vec<vk::PipelineColorBlendAttachmentState> {
     match self {
-      Light => vec![color],
-      World => vec![color, color, color],
+      Light | PostProcess | Output => vec![color],
+      World => vec![color; 4],
+      Shadow => vec![],
+      Transparent => vec![color.blend_enable(true)],
     }
   }
 
@@ -117,24 +158,69 @@ impl RenderingStage {
     depth: &'d vk::PipelineDepthStencilStateCreateInfo,
   ) -> vk::GraphicsPipelineCreateInfo<'d> {
     match self {
-      World => info.depth_stencil_state(depth),
-      Light => info,
+      World | Shadow | Transparent => info.depth_stencil_state(depth),
+      Light | PostProcess | Output => info,
     }
   }
 
+  /// Blended meshes are tested against the depth of the opaque ones without hiding each other
+  #[inline]
+  pub(crate) fn depth_write(&self) -> bool {
+    !matches!(self, Transparent)
+  }
+
   #[inline]
   pub(crate) fn subpass(&self) -> u32 {
     match self {
       Light => 1,
-      World => 0,
+      World | Shadow | PostProcess | Output => 0,
+      Transparent => 2,
     }
   }
 
   #[inline]
   pub(crate) fn cull_mode(&self) -> vk::CullModeFlags {
     match self {
-      Light => vk::CullModeFlags::NONE,
-      World => vk::CullModeFlags::BACK,
+      Light | Shadow | PostProcess | Output => vk::CullModeFlags::NONE,
+      World | Transparent => vk::CullModeFlags::BACK,
+    }
+  }
+
+  /// Slope scaled bias against shadow acne
+  #[inline]
+  pub(crate) fn depth_bias<'r>(
+    &self,
+    info: vk::PipelineRasterizationStateCreateInfo<'r>,
+  ) -> vk::PipelineRasterizationStateCreateInfo<'r> {
+    match self {
+      Shadow => info
+        .depth_bias_enable(true)
+        .depth_bias_constant_factor(1.25)
+        .depth_bias_slope_factor(1.75),
+      Light | World | Transparent | PostProcess | Output => info,
     }
   }
 }
+
+#[cfg(test)]
+mod test {
+  use crate::model::model::{InstanceData, VertexData};
+
+  use super::RenderingStage;
+
+  #[test]
+  fn world_vertex_layout() {
+    let (bindings, attributes) = RenderingStage::World.vertex_layout();
+    assert_eq!(bindings[0].stride, 48);
+    assert_eq!(bindings[1].stride, 76);
+    assert_eq!(bindings[0].stride as usize, size_of::<VertexData>());
+    assert_eq!(bindings[1].stride as usize, size_of::<InstanceData>());
+
+    let locations = attributes.iter().map(|a| a.location).collect::<Vec<_>>();
+    assert_eq!(locations, (0..11).collect::<Vec<_>>());
+    assert_eq!(attributes[3].offset, 32);
+    assert_eq!(attributes[7].offset, 48);
+    assert_eq!(attributes[9].offset, 68);
+    assert_eq!(attributes[10].offset, 72);
+  }
+}
@@ -1,18 +1,22 @@
-use std::{collections::HashMap, ffi::CStr};
+use std::collections::HashMap;
 
 use anyhow::Error;
 use ash::vk;
+use log::error;
+#[cfg(feature = "hot_reload")]
+use log::info;
 
-use crate::renderer::swapchain::SwapChain;
+#[cfg(feature = "hot_reload")]
+use super::graphics::reload::ReloadablePipeline;
+#[cfg(feature = "hot_reload")]
+use crate::asset::watcher::FileWatcher;
 
 use super::{
-  graphics::{stage::RenderingStage, GraphicsPipeline, GraphicsPipelineBuilder},
+  graphics::{stage::RenderingStage, GraphicsPipeline, GraphicsPipelineBuilder, PipelineRecipe},
   DescriptorManager,
 };
 
-pub(crate) const MAIN_FN: &CStr = c"main";
-
-#[derive(Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default)]
+#[derive(Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default, Debug)]
 pub struct GraphicsPipelineHandle(u64);
 
 #[inline]
@@ -43,10 +47,22 @@ pub struct PipelineManager {
   max_graphics_id: u64,
   graphics_pipelines: HashMap<GraphicsPipelineHandle, GraphicsPipeline>,
   light_pipeline: Option<GraphicsPipeline>,
+  shadow_pipeline: Option<GraphicsPipeline>,
+  transparent_pipeline: Option<GraphicsPipeline>,
+  post_pipelines: HashMap<GraphicsPipelineHandle, GraphicsPipeline>,
+  output_pipeline: Option<GraphicsPipeline>,
   logical_device: ash::Device,
   render_pass: vk::RenderPass,
-  swapchain_extent: vk::Extent2D,
+  shadow_render_pass: vk::RenderPass,
+  post_render_pass: vk::RenderPass,
+  output_render_pass: vk::RenderPass,
+  samples: vk::SampleCountFlags,
+  recipes: HashMap<GraphicsPipelineHandle, PipelineRecipe>,
   graphics_changed: bool,
+  #[cfg(feature = "hot_reload")]
+  reloadable: HashMap<GraphicsPipelineHandle, ReloadablePipeline>,
+  #[cfg(feature = "hot_reload")]
+  watcher: Option<FileWatcher<GraphicsPipelineHandle>>,
 }
 
 impl PipelineManager {
@@ -54,16 +70,31 @@ impl PipelineManager {
   pub(crate) fn init(
     logical_device: &ash::Device,
     render_pass: vk::RenderPass,
-    swapchain: &SwapChain,
+    shadow_render_pass: vk::RenderPass,
+    post_render_pass: vk::RenderPass,
+    output_render_pass: vk::RenderPass,
+    samples: vk::SampleCountFlags,
   ) -> Self {
     Self {
       max_graphics_id: 0,
       graphics_pipelines: HashMap::new(),
       light_pipeline: None,
+      shadow_pipeline: None,
+      transparent_pipeline: None,
+      post_pipelines: HashMap::new(),
+      output_pipeline: None,
       logical_device: logical_device.clone(),
       render_pass,
-      swapchain_extent: swapchain.get_extent(),
+      shadow_render_pass,
+      post_render_pass,
+      output_render_pass,
+      samples,
+      recipes: HashMap::new(),
       graphics_changed: false,
+      #[cfg(feature = "hot_reload")]
+      reloadable: HashMap::new(),
+      #[cfg(feature = "hot_reload")]
+      watcher: None,
     }
   }
 
@@ -75,33 +106,237 @@ impl PipelineManager {
     let id = GraphicsPipelineHandle(self.max_graphics_id);
     self.max_graphics_id += 1;
 
-    let subpass = builder.rendering_stage.subpass();
-    let is_light = builder.rendering_stage == RenderingStage::Light;
+    let stage = builder.rendering_stage;
+    let render_pass = self.render_pass(stage);
+
+    #[cfg(feature = "hot_reload")]
+    if let Some(reloadable) = ReloadablePipeline::new(&builder) {
+      let shaders = reloadable
+        .compile()
+        .inspect_err(|err| error!("{err}"))
+        .ok()?;
+      let pipeline = reloadable
+        .builder(&shaders)
+        .build(
+          &self.logical_device,
+          descriptor_manager,
+          render_pass,
+          id,
+          stage.subpass(),
+          self.samples(stage),
+        )
+        .inspect_err(|err| error!("Failed to build pipeline: {err}"))
+        .ok()?;
+
+      if let Some(watcher) = &mut self.watcher {
+        for file in reloadable.files() {
+          watcher.watch(file, id);
+        }
+      }
+      self.reloadable.insert(id, reloadable);
+      self.replace_pipeline(id, pipeline, stage);
+
+      return Some(id);
+    }
 
+    let recipe = stage.in_scene_pass().then(|| PipelineRecipe::new(&builder));
     let pipeline = builder
       .build(
         &self.logical_device,
         descriptor_manager,
-        self.render_pass,
-        self.swapchain_extent,
+        render_pass,
         id,
-        subpass,
+        stage.subpass(),
+        self.samples(stage),
       )
+      .inspect_err(|err| error!("Failed to build pipeline: {err}"))
       .ok()?;
+    self.replace_pipeline(id, pipeline, stage);
+    if let Some(recipe) = recipe {
+      self.recipes.insert(id, recipe);
+    }
+
+    Some(id)
+  }
+
+  /// Builds all pipelines of the scene pass again for a new render pass and sample count.
+  /// The replaced pipelines must not be in use by the gpu anymore
+  pub(crate) fn set_scene_render_pass(
+    &mut self,
+    render_pass: vk::RenderPass,
+    samples: vk::SampleCountFlags,
+    descriptor_manager: &DescriptorManager,
+  ) -> Result<(), Error> {
+    self.render_pass = render_pass;
+    self.samples = samples;
+
+    let mut pipelines = Vec::new();
+    for (id, recipe) in &self.recipes {
+      let stage = recipe.rendering_stage();
+      let pipeline = recipe.builder().build(
+        &self.logical_device,
+        descriptor_manager,
+        render_pass,
+        *id,
+        stage.subpass(),
+        samples,
+      )?;
+      pipelines.push((*id, pipeline, stage));
+    }
+    #[cfg(feature = "hot_reload")]
+    for (id, reloadable) in &self.reloadable {
+      let stage = reloadable.rendering_stage();
+      if !stage.in_scene_pass() {
+        continue;
+      }
+      let shaders = reloadable.compile()?;
+      let pipeline = reloadable.builder(&shaders).build(
+        &self.logical_device,
+        descriptor_manager,
+        render_pass,
+        *id,
+        stage.subpass(),
+        samples,
+      )?;
+      pipelines.push((*id, pipeline, stage));
+    }
 
-    if is_light {
-      self.light_pipeline = Some(pipeline);
+    std::fs::create_dir_all("cache")?;
+    for (id, pipeline, stage) in pipelines {
+      if let Some(old) = self.insert_pipeline(id, pipeline, stage) {
+        old.cleanup(&self.logical_device);
+      }
+    }
+    Ok(())
+  }
+
+  /// Only the scene pass is multisampled
+  #[inline]
+  fn samples(&self, stage: RenderingStage) -> vk::SampleCountFlags {
+    if stage.in_scene_pass() {
+      self.samples
     } else {
-      self.graphics_pipelines.insert(id, pipeline);
+      vk::SampleCountFlags::TYPE_1
     }
+  }
+
+  #[inline]
+  fn render_pass(&self, stage: RenderingStage) -> vk::RenderPass {
+    match stage {
+      RenderingStage::Shadow => self.shadow_render_pass,
+      RenderingStage::PostProcess => self.post_render_pass,
+      RenderingStage::Output => self.output_render_pass,
+      RenderingStage::Light | RenderingStage::World | RenderingStage::Transparent => {
+        self.render_pass
+      }
+    }
+  }
+
+  /// Returns the pipeline replaced by it
+  /// Inserts a pipeline outside of a frame. A replaced pipeline may still be used by a frame in
+  /// flight, so the device is waited for before it is destroyed
+  fn replace_pipeline(
+    &mut self,
+    id: GraphicsPipelineHandle,
+    pipeline: GraphicsPipeline,
+    stage: RenderingStage,
+  ) {
+    if let Some(old) = self.insert_pipeline(id, pipeline, stage) {
+      unsafe {
+        self
+          .logical_device
+          .device_wait_idle()
+          .expect("Unable to wait for device idle");
+      }
+      std::fs::create_dir_all("cache").unwrap();
+      old.cleanup(&self.logical_device);
+    }
+  }
+
+  fn insert_pipeline(
+    &mut self,
+    id: GraphicsPipelineHandle,
+    pipeline: GraphicsPipeline,
+    stage: RenderingStage,
+  ) -> Option<GraphicsPipeline> {
     self.graphics_changed = true;
 
-    Some(id)
+    // the previous pipeline of these stages is replaced, so it is not built again
+    if !matches!(stage, RenderingStage::World | RenderingStage::PostProcess) {
+      self
+        .recipes
+        .retain(|other, recipe| *other == id || recipe.rendering_stage() != stage);
+      #[cfg(feature = "hot_reload")]
+      self
+        .reloadable
+        .retain(|other, reloadable| *other == id || reloadable.rendering_stage() != stage);
+    }
+    match stage {
+      RenderingStage::Light => self.light_pipeline.replace(pipeline),
+      RenderingStage::Shadow => self.shadow_pipeline.replace(pipeline),
+      RenderingStage::Transparent => self.transparent_pipeline.replace(pipeline),
+      RenderingStage::World => self.graphics_pipelines.insert(id, pipeline),
+      RenderingStage::PostProcess => self.post_pipelines.insert(id, pipeline),
+      RenderingStage::Output => self.output_pipeline.replace(pipeline),
+    }
+  }
+
+  /// Starts watching the shader files of all pipelines built from files
+  #[cfg(feature = "hot_reload")]
+  pub(crate) fn enable_hot_reload(&mut self) {
+    let mut watcher = FileWatcher::new();
+    for (id, reloadable) in &self.reloadable {
+      for file in reloadable.files() {
+        watcher.watch(file, *id);
+      }
+    }
+    self.watcher = Some(watcher);
+  }
+
+  /// Rebuilds pipelines whose shader files changed, on errors the old pipeline is kept
+  #[cfg(feature = "hot_reload")]
+  pub(crate) fn reload_changed(&mut self, descriptor_manager: &DescriptorManager) {
+    let Some(watcher) = &mut self.watcher else {
+      return;
+    };
+
+    for id in watcher.poll() {
+      let Some(reloadable) = self.reloadable.get(&id) else {
+        continue;
+      };
+
+      let stage = reloadable.rendering_stage();
+      let pipeline = reloadable.compile().and_then(|shaders| {
+        reloadable.builder(&shaders).build(
+          &self.logical_device,
+          descriptor_manager,
+          self.render_pass(stage),
+          id,
+          stage.subpass(),
+          self.samples(stage),
+        )
+      });
+
+      let pipeline = match pipeline {
+        Ok(pipeline) => pipeline,
+        Err(err) => {
+          error!("Failed to reload pipeline {id:?}: {err}");
+          continue;
+        }
+      };
+
+      self.replace_pipeline(id, pipeline, stage);
+      info!("Reloaded pipeline {id:?}");
+    }
   }
 
   pub(crate) fn cleanup(&self) {
     std::fs::create_dir_all("cache").unwrap();
-    for pipeline in self.graphics_pipelines.values() {
+    for pipeline in self
+      .graphics_pipelines
+      .values()
+      .chain(self.post_pipelines.values())
+    {
       pipeline.cleanup(&self.logical_device);
     }
     self
@@ -109,6 +344,21 @@ impl PipelineManager {
       .as_ref()
       .unwrap()
       .cleanup(&self.logical_device);
+    self
+      .shadow_pipeline
+      .as_ref()
+      .unwrap()
+      .cleanup(&self.logical_device);
+    self
+      .transparent_pipeline
+      .as_ref()
+      .unwrap()
+      .cleanup(&self.logical_device);
+    self
+      .output_pipeline
+      .as_ref()
+      .unwrap()
+      .cleanup(&self.logical_device);
   }
 
   #[inline]
@@ -116,9 +366,39 @@ impl PipelineManager {
     self.light_pipeline.as_ref().unwrap()
   }
 
+  #[inline]
+  pub(crate) fn shadow_pipeline(&self) -> &GraphicsPipeline {
+    self.shadow_pipeline.as_ref().unwrap()
+  }
+
+  #[inline]
+  pub(crate) fn transparent_pipeline(&self) -> &GraphicsPipeline {
+    self.transparent_pipeline.as_ref().unwrap()
+  }
+
+  #[inline]
+  pub(crate) fn output_pipeline(&self) -> &GraphicsPipeline {
+    self.output_pipeline.as_ref().unwrap()
+  }
+
+  #[inline]
+  pub(crate) fn post_pipeline(&self, id: GraphicsPipelineHandle) -> &GraphicsPipeline {
+    &self.post_pipelines[&id]
+  }
+
+  /// Post-processing pipelines in build order
+  #[inline]
+  pub(crate) fn post_pipelines(&self) -> Vec<&GraphicsPipeline> {
+    let mut pipelines = self.post_pipelines.values().collect::<Vec<_>>();
+    pipelines.sort_by_key(|pipeline| pipeline.id());
+    pipelines
+  }
+
   #[inline]
   pub(crate) fn graphics_pipelines(&self) -> Vec<&GraphicsPipeline> {
-    self.graphics_pipelines.values().collect()
+    let mut pipelines = self.graphics_pipelines.values().collect::<Vec<_>>();
+    pipelines.sort_by_key(|pipeline| pipeline.id());
+    pipelines
   }
 
   #[inline]
@@ -2,6 +2,7 @@ pub mod descriptor;
 pub mod graphics;
 pub mod manager;
 pub(crate) mod pools;
+pub mod shader;
 
 pub use descriptor::manager::DescriptorManager;
 pub use manager::PipelineManager;
@@ -0,0 +1,174 @@
+#[cfg(any(feature = "shader_compiler", feature = "wgsl"))]
+use anyhow::Error;
+#[cfg(feature = "shader_compiler")]
+use std::path::Path;
+
+#[cfg(any(feature = "shader_compiler", feature = "wgsl"))]
+use crate::error::ShaderCompileError;
+
+pub mod reflect;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShaderStage {
+  Vertex,
+  Geometry,
+  Fragment,
+}
+
+/// Compiles GLSL to SPIR-V, includes are resolved relative to the working directory
+#[cfg(feature = "shader_compiler")]
+pub fn compile_glsl(source: &str, stage: ShaderStage) -> Result<Vec<u32>, Error> {
+  compile_glsl_source(source, "shader.glsl", stage)
+}
+
+/// Compiles a GLSL file to SPIR-V, includes are resolved relative to the including file
+#[cfg(feature = "shader_compiler")]
+pub fn compile_glsl_file(path: &Path, stage: ShaderStage) -> Result<Vec<u32>, Error> {
+  let source = std::fs::read_to_string(path)?;
+  compile_glsl_source(&source, &path.to_string_lossy(), stage)
+}
+
+#[cfg(feature = "shader_compiler")]
+fn compile_glsl_source(source: &str, name: &str, stage: ShaderStage) -> Result<Vec<u32>, Error> {
+  let kind = match stage {
+    ShaderStage::Vertex => shaderc::ShaderKind::Vertex,
+    ShaderStage::Geometry => shaderc::ShaderKind::Geometry,
+    ShaderStage::Fragment => shaderc::ShaderKind::Fragment,
+  };
+
+  let compiler = shaderc::Compiler::new()?;
+  let mut options = shaderc::CompileOptions::new()?;
+  options.set_include_callback(|requested, _, requesting, _| {
+    let path = Path::new(requesting)
+      .parent()
+      .unwrap_or(Path::new(""))
+      .join(requested);
+    std::fs::read_to_string(&path)
+      .map(|content| shaderc::ResolvedInclude {
+        resolved_name: path.to_string_lossy().into_owned(),
+        content,
+      })
+      .map_err(|err| err.to_string())
+  });
+
+  let artifact = compiler
+    .compile_into_spirv(source, kind, name, "main", Some(&options))
+    .map_err(|err| ShaderCompileError::Compile(name.into(), err.to_string()))?;
+
+  if artifact.get_num_warnings() > 0 {
+    log::warn!("{}", artifact.get_warning_messages());
+  }
+  Ok(artifact.as_binary().to_vec())
+}
+
+/// Compiles WGSL to SPIR-V, the first entry point of `stage` is used
+#[cfg(feature = "wgsl")]
+pub fn compile_wgsl(source: &str, stage: ShaderStage) -> Result<Vec<u32>, Error> {
+  const NAME: &str = "shader.wgsl";
+
+  let naga_stage = match stage {
+    ShaderStage::Vertex => naga::ShaderStage::Vertex,
+    ShaderStage::Fragment => naga::ShaderStage::Fragment,
+    ShaderStage::Geometry => {
+      return Err(
+        ShaderCompileError::Compile(NAME.into(), "WGSL has no geometry shaders".into()).into(),
+      )
+    }
+  };
+
+  let module = naga::front::wgsl::parse_str(source)
+    .map_err(|err| ShaderCompileError::Compile(NAME.into(), err.emit_to_string(source)))?;
+  let info = naga::valid::Validator::new(
+    naga::valid::ValidationFlags::all(),
+    naga::valid::Capabilities::all(),
+  )
+  .validate(&module)
+  .map_err(|err| ShaderCompileError::Compile(NAME.into(), err.emit_to_string(source)))?;
+
+  let entry_point = module
+    .entry_points
+    .iter()
+    .find(|entry_point| entry_point.stage == naga_stage)
+    .ok_or(ShaderCompileError::MissingEntryPoint(stage, NAME.into()))?;
+  let pipeline_options = naga::back::spv::PipelineOptions {
+    shader_stage: naga_stage,
+    entry_point: entry_point.name.clone(),
+  };
+
+  Ok(naga::back::spv::write_vec(
+    &module,
+    &info,
+    &naga::back::spv::Options::default(),
+    Some(&pipeline_options),
+  )?)
+}
+
+#[cfg(all(test, feature = "wgsl"))]
+mod test {
+  use ash::vk;
+
+  use super::{compile_wgsl, reflect::ShaderReflection, ShaderStage};
+
+  const SOURCE: &str = r#"
+struct Camera {
+  view_projection: mat4x4<f32>,
+}
+
+@group(0) @binding(0) var<storage, read> cameras: array<Camera>;
+@group(1) @binding(0) var texture: texture_2d<f32>;
+@group(1) @binding(1) var texture_sampler: sampler;
+
+struct Output {
+  @builtin(position) position: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>, @location(2) uv: vec2<f32>) -> Output {
+  return Output(cameras[0].view_projection * vec4<f32>(position, 1.0), uv);
+}
+
+@fragment
+fn fs_main(@location(0) uv: vec2<f32>) -> @location(0) vec4<f32> {
+  return textureSample(texture, texture_sampler, uv);
+}
+"#;
+
+  #[test]
+  fn compile_and_reflect_wgsl() {
+    let vertex =
+      ShaderReflection::new(&compile_wgsl(SOURCE, ShaderStage::Vertex).unwrap()).unwrap();
+    assert_eq!(vertex.stage(), vk::ShaderStageFlags::VERTEX);
+    assert_eq!(vertex.entry_point(), "vs_main");
+    let locations = vertex
+      .inputs()
+      .iter()
+      .map(|input| input.location)
+      .collect::<Vec<_>>();
+    assert_eq!(locations, [0, 2]);
+    assert_eq!(vertex.bindings().len(), 1);
+    assert_eq!(
+      vertex.bindings()[0].1.descriptor_type,
+      vk::DescriptorType::STORAGE_BUFFER
+    );
+
+    let fragment =
+      ShaderReflection::new(&compile_wgsl(SOURCE, ShaderStage::Fragment).unwrap()).unwrap();
+    assert_eq!(fragment.entry_point(), "fs_main");
+    let types = fragment
+      .bindings()
+      .iter()
+      .map(|(set, binding)| (*set, binding.descriptor_type))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      types,
+      [
+        (1, vk::DescriptorType::SAMPLED_IMAGE),
+        (1, vk::DescriptorType::SAMPLER)
+      ]
+    );
+
+    assert!(compile_wgsl(SOURCE, ShaderStage::Geometry).is_err());
+    assert!(compile_wgsl("fn broken(", ShaderStage::Vertex).is_err());
+  }
+}
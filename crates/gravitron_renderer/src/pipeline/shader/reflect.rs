@@ -0,0 +1,931 @@
+use std::collections::HashMap;
+
+use anyhow::Error;
+use ash::vk;
+use spirv::{Decoration, Dim, ExecutionModel, Op, StorageClass};
+
+use crate::error::{PipelineValidationError, ShaderReflectError};
+
+const HEADER_LEN: usize = 5;
+
+/// A binding of a descriptor set layout, `count` is 0 for runtime sized arrays
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DescriptorBinding {
+  pub binding: u32,
+  pub descriptor_type: vk::DescriptorType,
+  pub count: u32,
+  pub stages: vk::ShaderStageFlags,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VertexInput {
+  pub location: u32,
+  pub format: vk::Format,
+}
+
+/// The interface of a single SPIR-V module, only what is needed to validate a pipeline
+#[derive(Debug, Clone)]
+pub struct ShaderReflection {
+  stage: vk::ShaderStageFlags,
+  entry_point: String,
+  bindings: Vec<(u32, DescriptorBinding)>,
+  push_constants: bool,
+  inputs: Vec<VertexInput>,
+}
+
+/// The combined interface of all shaders of a pipeline
+#[derive(Debug, Clone, Default)]
+pub struct PipelineReflection {
+  sets: Vec<Vec<DescriptorBinding>>,
+  push_constant_stages: vk::ShaderStageFlags,
+  vertex_inputs: Vec<VertexInput>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ScalarKind {
+  Int,
+  Uint,
+  Float,
+}
+
+/// The types descriptors and vertex inputs can have, block structs are not needed
+enum Type {
+  Scalar(ScalarKind, u32),
+  Vector(u32, u32),
+  Matrix(u32, u32),
+  Image { dim: u32, sampled: u32 },
+  Sampler,
+  SampledImage,
+  Array(u32, u32),
+  RuntimeArray(u32),
+  Pointer(u32),
+}
+
+#[derive(Default)]
+struct Decorations {
+  set: Option<u32>,
+  binding: Option<u32>,
+  location: Option<u32>,
+  buffer_block: bool,
+}
+
+#[derive(Default)]
+struct Module {
+  entry_point: Option<(u32, String)>,
+  types: HashMap<u32, Type>,
+  constants: HashMap<u32, u32>,
+  decorations: HashMap<u32, Decorations>,
+  variables: Vec<(u32, u32, u32)>,
+}
+
+impl ShaderReflection {
+  /// Only the first entry point of the module is reflected
+  pub fn new(spirv: &[u32]) -> Result<Self, Error> {
+    let module = Module::parse(spirv)?;
+    let (model, entry_point) = module
+      .entry_point
+      .clone()
+      .ok_or(ShaderReflectError::MissingEntryPoint)?;
+    let stage = match ExecutionModel::from_u32(model) {
+      Some(ExecutionModel::Vertex) => vk::ShaderStageFlags::VERTEX,
+      Some(ExecutionModel::TessellationControl) => vk::ShaderStageFlags::TESSELLATION_CONTROL,
+      Some(ExecutionModel::TessellationEvaluation) => vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+      Some(ExecutionModel::Geometry) => vk::ShaderStageFlags::GEOMETRY,
+      Some(ExecutionModel::Fragment) => vk::ShaderStageFlags::FRAGMENT,
+      Some(ExecutionModel::GLCompute) => vk::ShaderStageFlags::COMPUTE,
+      _ => return Err(ShaderReflectError::UnsupportedStage(model).into()),
+    };
+
+    let mut bindings = Vec::new();
+    let mut push_constants = false;
+    let mut inputs = Vec::new();
+
+    for &(id, pointer, storage_class) in &module.variables {
+      let Some(Type::Pointer(ty)) = module.types.get(&pointer) else {
+        return Err(ShaderReflectError::UnknownId(pointer).into());
+      };
+      let decorations = module.decorations.get(&id);
+
+      match StorageClass::from_u32(storage_class) {
+        Some(
+          storage_class @ (StorageClass::UniformConstant
+          | StorageClass::Uniform
+          | StorageClass::StorageBuffer),
+        ) => {
+          let Some((set, binding)) = decorations.and_then(|d| d.set.zip(d.binding)) else {
+            continue;
+          };
+          let (descriptor_type, count) =
+            module.descriptor_type(*ty, storage_class)?.ok_or_else(|| {
+              ShaderReflectError::UnsupportedType(format!("binding {binding} of set {set}"))
+            })?;
+          bindings.push((
+            set,
+            DescriptorBinding {
+              binding,
+              descriptor_type,
+              count,
+              stages: stage,
+            },
+          ));
+        }
+        Some(StorageClass::PushConstant) => push_constants = true,
+        Some(StorageClass::Input) if stage == vk::ShaderStageFlags::VERTEX => {
+          // built-ins like gl_VertexIndex have no location
+          let Some(location) = decorations.and_then(|d| d.location) else {
+            continue;
+          };
+          let formats = module.input_formats(*ty)?.ok_or_else(|| {
+            ShaderReflectError::UnsupportedType(format!("vertex input {location}"))
+          })?;
+          inputs.extend(
+            formats
+              .into_iter()
+              .enumerate()
+              .map(|(i, format)| VertexInput {
+                location: location + i as u32,
+                format,
+              }),
+          );
+        }
+        _ => (),
+      }
+    }
+
+    bindings.sort_by_key(|(set, binding)| (*set, binding.binding));
+    inputs.sort_by_key(|input| input.location);
+
+    Ok(Self {
+      stage,
+      entry_point,
+      bindings,
+      push_constants,
+      inputs,
+    })
+  }
+
+  #[inline]
+  pub fn stage(&self) -> vk::ShaderStageFlags {
+    self.stage
+  }
+
+  #[inline]
+  pub fn entry_point(&self) -> &str {
+    &self.entry_point
+  }
+
+  /// Pairs of the set index and the binding
+  #[inline]
+  pub fn bindings(&self) -> &[(u32, DescriptorBinding)] {
+    &self.bindings
+  }
+
+  /// The size is not reflected, the renderer always pushes the same constants
+  #[inline]
+  pub fn uses_push_constants(&self) -> bool {
+    self.push_constants
+  }
+
+  /// Always empty for stages other than the vertex stage
+  #[inline]
+  pub fn inputs(&self) -> &[VertexInput] {
+    &self.inputs
+  }
+}
+
+impl PipelineReflection {
+  pub fn new(shaders: &[ShaderReflection]) -> Result<Self, Error> {
+    let mut reflection = Self::default();
+
+    for shader in shaders {
+      for &(set, binding) in &shader.bindings {
+        let index = set as usize;
+        if reflection.sets.len() <= index {
+          reflection.sets.resize(index + 1, Vec::new());
+        }
+        let layout = &mut reflection.sets[index];
+
+        match layout.iter_mut().find(|b| b.binding == binding.binding) {
+          Some(existing) if existing.descriptor_type != binding.descriptor_type => {
+            return Err(
+              PipelineValidationError::ConflictingBinding {
+                set,
+                binding: binding.binding,
+              }
+              .into(),
+            );
+          }
+          Some(existing) => {
+            existing.stages |= binding.stages;
+            existing.count = if existing.count == 0 || binding.count == 0 {
+              0
+            } else {
+              existing.count.max(binding.count)
+            };
+          }
+          None => layout.push(binding),
+        }
+      }
+
+      if shader.push_constants {
+        reflection.push_constant_stages |= shader.stage;
+      }
+
+      if shader.stage == vk::ShaderStageFlags::VERTEX {
+        reflection.vertex_inputs = shader.inputs.clone();
+      }
+    }
+
+    for layout in &mut reflection.sets {
+      layout.sort_by_key(|binding| binding.binding);
+    }
+
+    Ok(reflection)
+  }
+
+  /// The descriptor set layouts required by the shaders, indexed by set
+  #[inline]
+  pub fn descriptor_set_layouts(&self) -> &[Vec<DescriptorBinding>] {
+    &self.sets
+  }
+
+  /// Empty if no shader uses push constants
+  #[inline]
+  pub fn push_constant_stages(&self) -> vk::ShaderStageFlags {
+    self.push_constant_stages
+  }
+
+  #[inline]
+  pub fn vertex_inputs(&self) -> &[VertexInput] {
+    &self.vertex_inputs
+  }
+
+  /// Checks that `provided` contains every binding the shaders use with a compatible type,
+  /// count and stage visibility, `provided[i]` is bound as set `i`
+  pub fn validate_descriptor_sets(
+    &self,
+    provided: &[&[DescriptorBinding]],
+  ) -> Result<(), PipelineValidationError> {
+    for (set, bindings) in self.sets.iter().enumerate() {
+      let set = set as u32;
+      for expected in bindings {
+        let layout = provided
+          .get(set as usize)
+          .ok_or(PipelineValidationError::MissingSet {
+            set,
+            provided: provided.len(),
+          })?;
+        let found = layout
+          .iter()
+          .find(|b| b.binding == expected.binding)
+          .ok_or(PipelineValidationError::MissingBinding {
+            set,
+            binding: expected.binding,
+          })?;
+
+        if found.descriptor_type != expected.descriptor_type {
+          return Err(PipelineValidationError::TypeMismatch {
+            set,
+            binding: expected.binding,
+            expected: expected.descriptor_type,
+            provided: found.descriptor_type,
+          });
+        }
+        if expected.count > found.count {
+          return Err(PipelineValidationError::CountMismatch {
+            set,
+            binding: expected.binding,
+            expected: expected.count,
+            provided: found.count,
+          });
+        }
+        if !found.stages.contains(expected.stages) {
+          return Err(PipelineValidationError::StageMismatch {
+            set,
+            binding: expected.binding,
+            stages: expected.stages & !found.stages,
+          });
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Selects the attributes of `available` the vertex shader reads, matched by location
+  pub fn vertex_attributes(
+    &self,
+    available: &[vk::VertexInputAttributeDescription],
+  ) -> Result<Vec<vk::VertexInputAttributeDescription>, PipelineValidationError> {
+    self
+      .vertex_inputs
+      .iter()
+      .map(|input| {
+        let attribute = available
+          .iter()
+          .find(|attribute| attribute.location == input.location)
+          .ok_or(PipelineValidationError::MissingVertexInput(input.location))?;
+
+        if attribute.format != input.format {
+          return Err(PipelineValidationError::VertexInputFormat {
+            location: input.location,
+            expected: input.format,
+            provided: attribute.format,
+          });
+        }
+        Ok(*attribute)
+      })
+      .collect()
+  }
+}
+
+impl Module {
+  /// Only the instructions declaring the interface are read, everything else is skipped
+  fn parse(words: &[u32]) -> Result<Self, ShaderReflectError> {
+    if words.len() < HEADER_LEN || words[0] != spirv::MAGIC_NUMBER {
+      return Err(ShaderReflectError::InvalidHeader);
+    }
+
+    let mut module = Module::default();
+    let mut i = HEADER_LEN;
+    while i < words.len() {
+      let word_count = (words[i] >> 16) as usize;
+      if word_count == 0 || i + word_count > words.len() {
+        return Err(ShaderReflectError::Truncated(i));
+      }
+
+      let operands = &words[i + 1..i + word_count];
+      let operand = |index: usize| {
+        operands
+          .get(index)
+          .copied()
+          .ok_or(ShaderReflectError::Truncated(i))
+      };
+
+      let mut define = |ty: Type| -> Result<(), ShaderReflectError> {
+        module.types.insert(operand(0)?, ty);
+        Ok(())
+      };
+      match Op::from_u32(words[i] & 0xffff) {
+        Some(Op::EntryPoint) if module.entry_point.is_none() => {
+          operand(2)?;
+          module.entry_point = Some((operand(0)?, parse_string(&operands[2..])));
+        }
+        Some(Op::TypeInt) => {
+          let kind = if operand(2)? == 0 {
+            ScalarKind::Uint
+          } else {
+            ScalarKind::Int
+          };
+          define(Type::Scalar(kind, operand(1)?))?;
+        }
+        Some(Op::TypeFloat) => define(Type::Scalar(ScalarKind::Float, operand(1)?))?,
+        Some(Op::TypeVector) => define(Type::Vector(operand(1)?, operand(2)?))?,
+        Some(Op::TypeMatrix) => define(Type::Matrix(operand(1)?, operand(2)?))?,
+        Some(Op::TypeImage) => define(Type::Image {
+          dim: operand(2)?,
+          sampled: operand(6)?,
+        })?,
+        Some(Op::TypeSampler) => define(Type::Sampler)?,
+        Some(Op::TypeSampledImage) => define(Type::SampledImage)?,
+        Some(Op::TypeArray) => define(Type::Array(operand(1)?, operand(2)?))?,
+        Some(Op::TypeRuntimeArray) => define(Type::RuntimeArray(operand(1)?))?,
+        Some(Op::TypePointer) => define(Type::Pointer(operand(2)?))?,
+        Some(Op::Constant) => {
+          module.constants.insert(operand(1)?, operand(2)?);
+        }
+        Some(Op::Variable) => {
+          module
+            .variables
+            .push((operand(1)?, operand(0)?, operand(2)?));
+        }
+        Some(Op::Decorate) => {
+          let decorations = module.decorations.entry(operand(0)?).or_default();
+          match Decoration::from_u32(operand(1)?) {
+            Some(Decoration::BufferBlock) => decorations.buffer_block = true,
+            Some(Decoration::Location) => decorations.location = Some(operand(2)?),
+            Some(Decoration::Binding) => decorations.binding = Some(operand(2)?),
+            Some(Decoration::DescriptorSet) => decorations.set = Some(operand(2)?),
+            _ => (),
+          }
+        }
+        _ => (),
+      }
+
+      i += word_count;
+    }
+
+    Ok(module)
+  }
+
+  fn get_type(&self, id: u32) -> Result<&Type, ShaderReflectError> {
+    self.types.get(&id).ok_or(ShaderReflectError::UnknownId(id))
+  }
+
+  fn constant(&self, id: u32) -> Result<u32, ShaderReflectError> {
+    self
+      .constants
+      .get(&id)
+      .copied()
+      .ok_or(ShaderReflectError::UnknownId(id))
+  }
+
+  /// The type and count of a descriptor, `None` if the type cannot be bound
+  fn descriptor_type(
+    &self,
+    ty: u32,
+    storage_class: StorageClass,
+  ) -> Result<Option<(vk::DescriptorType, u32)>, ShaderReflectError> {
+    // blocks are structs, which are not parsed
+    let (ty, count) = match self.types.get(&ty) {
+      Some(Type::Array(element, length)) => (*element, self.constant(*length)?),
+      Some(Type::RuntimeArray(element)) => (*element, 0),
+      _ => (ty, 1),
+    };
+
+    let descriptor_type = match storage_class {
+      StorageClass::StorageBuffer => vk::DescriptorType::STORAGE_BUFFER,
+      // storage buffers before SPIR-V 1.3
+      StorageClass::Uniform if self.decorations.get(&ty).is_some_and(|d| d.buffer_block) => {
+        vk::DescriptorType::STORAGE_BUFFER
+      }
+      StorageClass::Uniform => vk::DescriptorType::UNIFORM_BUFFER,
+      _ => match self.get_type(ty)? {
+        Type::SampledImage => vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+        Type::Sampler => vk::DescriptorType::SAMPLER,
+        Type::Image { dim, sampled } => match (Dim::from_u32(*dim), *sampled) {
+          (Some(Dim::DimSubpassData), _) => vk::DescriptorType::INPUT_ATTACHMENT,
+          (Some(Dim::DimBuffer), 2) => vk::DescriptorType::STORAGE_TEXEL_BUFFER,
+          (Some(Dim::DimBuffer), _) => vk::DescriptorType::UNIFORM_TEXEL_BUFFER,
+          (_, 2) => vk::DescriptorType::STORAGE_IMAGE,
+          _ => vk::DescriptorType::SAMPLED_IMAGE,
+        },
+        _ => return Ok(None),
+      },
+    };
+
+    Ok(Some((descriptor_type, count)))
+  }
+
+  /// Formats of the consecutive locations a vertex input occupies, `None` if there is no
+  /// matching vertex format
+  fn input_formats(&self, ty: u32) -> Result<Option<Vec<vk::Format>>, ShaderReflectError> {
+    let format = |ty: u32| {
+      let (scalar, components) = match self.get_type(ty)? {
+        Type::Vector(component, count) => (*component, *count),
+        _ => (ty, 1),
+      };
+      Ok(match self.get_type(scalar)? {
+        Type::Scalar(kind, 32) => vertex_format(*kind, components),
+        _ => None,
+      })
+    };
+
+    Ok(match self.get_type(ty)? {
+      Type::Scalar(..) | Type::Vector(..) => format(ty)?.map(|format| vec![format]),
+      Type::Matrix(column, count) => format(*column)?.map(|format| vec![format; *count as usize]),
+      Type::Array(element, length) => {
+        let length = self.constant(*length)? as usize;
+        self
+          .input_formats(*element)?
+          .map(|element| element.repeat(length))
+      }
+      _ => None,
+    })
+  }
+}
+
+fn vertex_format(kind: ScalarKind, components: u32) -> Option<vk::Format> {
+  use vk::Format as F;
+
+  let formats = match kind {
+    ScalarKind::Float => [
+      F::R32_SFLOAT,
+      F::R32G32_SFLOAT,
+      F::R32G32B32_SFLOAT,
+      F::R32G32B32A32_SFLOAT,
+    ],
+    ScalarKind::Int => [
+      F::R32_SINT,
+      F::R32G32_SINT,
+      F::R32G32B32_SINT,
+      F::R32G32B32A32_SINT,
+    ],
+    ScalarKind::Uint => [
+      F::R32_UINT,
+      F::R32G32_UINT,
+      F::R32G32B32_UINT,
+      F::R32G32B32A32_UINT,
+    ],
+  };
+  formats.get(components.checked_sub(1)? as usize).copied()
+}
+
+/// Reads a nul terminated literal string
+fn parse_string(words: &[u32]) -> String {
+  let bytes = words
+    .iter()
+    .flat_map(|word| word.to_le_bytes())
+    .take_while(|byte| *byte != 0)
+    .collect::<Vec<_>>();
+  String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod test {
+  use ash::vk;
+  use spirv::{Decoration as D, Op, StorageClass as SC};
+
+  use super::{DescriptorBinding, PipelineReflection, ShaderReflection, VertexInput};
+  use crate::error::{PipelineValidationError, ShaderReflectError};
+
+  /// Minimal SPIR-V assembler, ids are handed out in order
+  struct Assembler {
+    words: Vec<u32>,
+    bound: u32,
+  }
+
+  impl Assembler {
+    fn new() -> Self {
+      Self {
+        words: vec![spirv::MAGIC_NUMBER, 0x0001_0000, 0, 0, 0],
+        bound: 1,
+      }
+    }
+
+    fn id(&mut self) -> u32 {
+      self.bound += 1;
+      self.bound - 1
+    }
+
+    fn op(&mut self, opcode: Op, operands: &[u32]) {
+      self
+        .words
+        .push(((operands.len() as u32 + 1) << 16) | opcode as u32);
+      self.words.extend_from_slice(operands);
+    }
+
+    /// Declares a type or constant and returns its id
+    fn def(&mut self, opcode: Op, operands: &[u32]) -> u32 {
+      let id = self.id();
+      self.op(opcode, &[&[id][..], operands].concat());
+      id
+    }
+
+    fn constant(&mut self, ty: u32, value: u32) -> u32 {
+      let id = self.id();
+      self.op(Op::Constant, &[ty, id, value]);
+      id
+    }
+
+    fn entry_point(&mut self, model: u32, name: &str) {
+      let mut bytes = name.as_bytes().to_vec();
+      bytes.resize((bytes.len() / 4 + 1) * 4, 0);
+      let mut operands = vec![model, 1];
+      operands.extend(
+        bytes
+          .chunks(4)
+          .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap())),
+      );
+      self.op(Op::EntryPoint, &operands);
+    }
+
+    fn decorate(&mut self, target: u32, decoration: D, literals: &[u32]) {
+      self.op(
+        Op::Decorate,
+        &[&[target, decoration as u32][..], literals].concat(),
+      );
+    }
+
+    fn variable(&mut self, ty: u32, storage_class: SC) -> u32 {
+      let pointer = self.def(Op::TypePointer, &[storage_class as u32, ty]);
+      let id = self.id();
+      self.op(Op::Variable, &[pointer, id, storage_class as u32]);
+      id
+    }
+
+    fn finish(mut self) -> Vec<u32> {
+      self.words[3] = self.bound;
+      self.words
+    }
+  }
+
+  fn binding(set: u32, binding: u32, asm: &mut Assembler, variable: u32) {
+    asm.decorate(variable, D::DescriptorSet, &[set]);
+    asm.decorate(variable, D::Binding, &[binding]);
+  }
+
+  /// Roughly the default world vertex shader
+  fn vertex_shader() -> Vec<u32> {
+    let mut asm = Assembler::new();
+    asm.entry_point(0, "main");
+
+    let float = asm.def(Op::TypeFloat, &[32]);
+    let uint = asm.def(Op::TypeInt, &[32, 0]);
+    let vec3 = asm.def(Op::TypeVector, &[float, 3]);
+    let vec4 = asm.def(Op::TypeVector, &[float, 4]);
+    let mat4 = asm.def(Op::TypeMatrix, &[vec4, 4]);
+
+    let position = asm.variable(vec3, SC::Input);
+    asm.decorate(position, D::Location, &[0]);
+    let model_matrix = asm.variable(mat4, SC::Input);
+    asm.decorate(model_matrix, D::Location, &[3]);
+    let texture_id = asm.variable(uint, SC::Input);
+    asm.decorate(texture_id, D::Location, &[14]);
+    let vertex_index = asm.variable(uint, SC::Input);
+    asm.decorate(vertex_index, D::BuiltIn, &[42]);
+
+    // buffer Cameras { mat4 cameras[]; } with the spir-v 1.0 storage buffer encoding
+    let cameras = asm.def(Op::TypeRuntimeArray, &[mat4]);
+    asm.decorate(cameras, D::ArrayStride, &[64]);
+    let block = asm.def(Op::TypeStruct, &[cameras]);
+    asm.decorate(block, D::BufferBlock, &[]);
+    let variable = asm.variable(block, SC::Uniform);
+    binding(0, 0, &mut asm, variable);
+
+    // push_constant { uint camera; }
+    let push = asm.def(Op::TypeStruct, &[uint]);
+    asm.decorate(push, D::Block, &[]);
+    asm.variable(push, SC::PushConstant);
+
+    asm.finish()
+  }
+
+  /// Roughly the default fragment shaders
+  fn fragment_shader() -> Vec<u32> {
+    let mut asm = Assembler::new();
+    asm.entry_point(4, "fs_main");
+
+    let float = asm.def(Op::TypeFloat, &[32]);
+    let uint = asm.def(Op::TypeInt, &[32, 0]);
+    let vec4 = asm.def(Op::TypeVector, &[float, 4]);
+
+    let image = asm.def(Op::TypeImage, &[float, 1, 0, 0, 0, 1, 0]);
+    let sampled = asm.def(Op::TypeSampledImage, &[image]);
+    let textures = asm.def(Op::TypeRuntimeArray, &[sampled]);
+    let variable = asm.variable(textures, SC::UniformConstant);
+    binding(1, 0, &mut asm, variable);
+
+    let subpass = asm.def(Op::TypeImage, &[float, 6, 0, 0, 0, 2, 0]);
+    let variable = asm.variable(subpass, SC::UniformConstant);
+    binding(1, 1, &mut asm, variable);
+
+    let three = asm.constant(uint, 3);
+    let block = asm.def(Op::TypeStruct, &[vec4]);
+    asm.decorate(block, D::Block, &[]);
+    let lights = asm.def(Op::TypeArray, &[block, three]);
+    let variable = asm.variable(lights, SC::Uniform);
+    binding(0, 2, &mut asm, variable);
+
+    // fragment inputs are not vertex attributes
+    let color = asm.variable(vec4, SC::Input);
+    asm.decorate(color, D::Location, &[0]);
+
+    let push = asm.def(Op::TypeStruct, &[uint]);
+    asm.decorate(push, D::Block, &[]);
+    asm.variable(push, SC::PushConstant);
+
+    asm.finish()
+  }
+
+  fn descriptor(
+    binding: u32,
+    descriptor_type: vk::DescriptorType,
+    count: u32,
+    stages: vk::ShaderStageFlags,
+  ) -> DescriptorBinding {
+    DescriptorBinding {
+      binding,
+      descriptor_type,
+      count,
+      stages,
+    }
+  }
+
+  fn provided_sets() -> [Vec<DescriptorBinding>; 2] {
+    let all = vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT;
+    [
+      vec![
+        descriptor(0, vk::DescriptorType::STORAGE_BUFFER, 1, all),
+        descriptor(1, vk::DescriptorType::UNIFORM_BUFFER, 1, all),
+        descriptor(2, vk::DescriptorType::UNIFORM_BUFFER, 3, all),
+      ],
+      vec![
+        descriptor(0, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 12, all),
+        descriptor(1, vk::DescriptorType::INPUT_ATTACHMENT, 1, all),
+      ],
+    ]
+  }
+
+  fn pipeline() -> PipelineReflection {
+    let shaders = [
+      ShaderReflection::new(&vertex_shader()).unwrap(),
+      ShaderReflection::new(&fragment_shader()).unwrap(),
+    ];
+    PipelineReflection::new(&shaders).unwrap()
+  }
+
+  #[test]
+  fn reflect_vertex_shader() {
+    let shader = ShaderReflection::new(&vertex_shader()).unwrap();
+
+    assert_eq!(shader.stage(), vk::ShaderStageFlags::VERTEX);
+    assert_eq!(shader.entry_point(), "main");
+    assert_eq!(
+      shader.bindings(),
+      &[(
+        0,
+        descriptor(
+          0,
+          vk::DescriptorType::STORAGE_BUFFER,
+          1,
+          vk::ShaderStageFlags::VERTEX
+        )
+      )]
+    );
+    assert!(shader.uses_push_constants());
+
+    let input = |location, format| VertexInput { location, format };
+    let vec4 = vk::Format::R32G32B32A32_SFLOAT;
+    assert_eq!(
+      shader.inputs(),
+      &[
+        input(0, vk::Format::R32G32B32_SFLOAT),
+        input(3, vec4),
+        input(4, vec4),
+        input(5, vec4),
+        input(6, vec4),
+        input(14, vk::Format::R32_UINT),
+      ]
+    );
+  }
+
+  #[test]
+  fn reflect_fragment_shader() {
+    let shader = ShaderReflection::new(&fragment_shader()).unwrap();
+    let fragment = vk::ShaderStageFlags::FRAGMENT;
+
+    assert_eq!(shader.stage(), fragment);
+    assert_eq!(shader.entry_point(), "fs_main");
+    assert_eq!(
+      shader.bindings(),
+      &[
+        (
+          0,
+          descriptor(2, vk::DescriptorType::UNIFORM_BUFFER, 3, fragment)
+        ),
+        (
+          1,
+          descriptor(0, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 0, fragment)
+        ),
+        (
+          1,
+          descriptor(1, vk::DescriptorType::INPUT_ATTACHMENT, 1, fragment)
+        ),
+      ]
+    );
+    assert!(shader.uses_push_constants());
+    assert!(shader.inputs().is_empty());
+  }
+
+  #[test]
+  fn invalid_modules() {
+    let error = |words: &[u32]| {
+      ShaderReflection::new(words)
+        .unwrap_err()
+        .downcast::<ShaderReflectError>()
+        .unwrap()
+    };
+
+    assert_eq!(error(&[0; 5]), ShaderReflectError::InvalidHeader);
+
+    let mut words = vertex_shader();
+    words.pop();
+    assert!(matches!(error(&words), ShaderReflectError::Truncated(_)));
+
+    assert_eq!(
+      error(&Assembler::new().finish()),
+      ShaderReflectError::MissingEntryPoint
+    );
+  }
+
+  #[test]
+  fn merge_stages() {
+    let pipeline = pipeline();
+    let both = vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT;
+
+    assert_eq!(pipeline.descriptor_set_layouts().len(), 2);
+    assert_eq!(pipeline.descriptor_set_layouts()[0].len(), 2);
+    assert_eq!(pipeline.push_constant_stages(), both);
+    assert_eq!(pipeline.vertex_inputs().len(), 6);
+
+    let mut asm = Assembler::new();
+    asm.entry_point(4, "main");
+    let float = asm.def(Op::TypeFloat, &[32]);
+    let block = asm.def(Op::TypeStruct, &[float]);
+    let variable = asm.variable(block, SC::Uniform);
+    binding(0, 0, &mut asm, variable);
+    let conflicting = ShaderReflection::new(&asm.finish()).unwrap();
+
+    let shaders = [
+      ShaderReflection::new(&vertex_shader()).unwrap(),
+      conflicting,
+    ];
+    let error = PipelineReflection::new(&shaders).unwrap_err();
+    assert_eq!(
+      error.downcast::<PipelineValidationError>().unwrap(),
+      PipelineValidationError::ConflictingBinding { set: 0, binding: 0 }
+    );
+  }
+
+  #[test]
+  fn validate_descriptor_sets() {
+    let pipeline = pipeline();
+    let [default, textures] = provided_sets();
+    assert_eq!(
+      pipeline.validate_descriptor_sets(&[&default, &textures]),
+      Ok(())
+    );
+
+    assert_eq!(
+      pipeline.validate_descriptor_sets(&[&default]),
+      Err(PipelineValidationError::MissingSet {
+        set: 1,
+        provided: 1
+      })
+    );
+    assert_eq!(
+      pipeline.validate_descriptor_sets(&[&default[..1], &textures]),
+      Err(PipelineValidationError::MissingBinding { set: 0, binding: 2 })
+    );
+    assert_eq!(
+      pipeline.validate_descriptor_sets(&[&textures, &default]),
+      Err(PipelineValidationError::TypeMismatch {
+        set: 0,
+        binding: 0,
+        expected: vk::DescriptorType::STORAGE_BUFFER,
+        provided: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+      })
+    );
+
+    let mut small = default.clone();
+    small[2].count = 2;
+    assert_eq!(
+      pipeline.validate_descriptor_sets(&[&small, &textures]),
+      Err(PipelineValidationError::CountMismatch {
+        set: 0,
+        binding: 2,
+        expected: 3,
+        provided: 2
+      })
+    );
+
+    let mut hidden = default.clone();
+    hidden[0].stages = vk::ShaderStageFlags::FRAGMENT;
+    assert_eq!(
+      pipeline.validate_descriptor_sets(&[&hidden, &textures]),
+      Err(PipelineValidationError::StageMismatch {
+        set: 0,
+        binding: 0,
+        stages: vk::ShaderStageFlags::VERTEX
+      })
+    );
+  }
+
+  #[test]
+  fn vertex_attributes() {
+    let pipeline = pipeline();
+    let attribute = |location, format| {
+      vk::VertexInputAttributeDescription::default()
+        .location(location)
+        .format(format)
+    };
+
+    let mut available = vec![
+      attribute(0, vk::Format::R32G32B32_SFLOAT),
+      attribute(1, vk::Format::R32G32B32_SFLOAT),
+      attribute(14, vk::Format::R32_UINT),
+    ];
+    available.extend((3..7).map(|location| attribute(location, vk::Format::R32G32B32A32_SFLOAT)));
+
+    let attributes = pipeline.vertex_attributes(&available).unwrap();
+    let locations = attributes.iter().map(|a| a.location).collect::<Vec<_>>();
+    assert_eq!(locations, [0, 3, 4, 5, 6, 14]);
+
+    available[2].format = vk::Format::R32_SFLOAT;
+    assert_eq!(
+      pipeline.vertex_attributes(&available).unwrap_err(),
+      PipelineValidationError::VertexInputFormat {
+        location: 14,
+        expected: vk::Format::R32_UINT,
+        provided: vk::Format::R32_SFLOAT
+      }
+    );
+
+    available.retain(|attribute| attribute.location != 0);
+    assert_eq!(
+      pipeline.vertex_attributes(&available).unwrap_err(),
+      PipelineValidationError::MissingVertexInput(0)
+    );
+  }
+}
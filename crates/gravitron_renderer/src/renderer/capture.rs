@@ -0,0 +1,291 @@
+use std::path::PathBuf;
+
+use anyhow::Error;
+use ash::vk;
+use image::RgbaImage;
+use log::error;
+
+use crate::{
+  ecs::resources::screenshot::Screenshots,
+  error::ReadbackError,
+  memory::{
+    types::{BufferBlockSize, BufferId, BufferMemory, BufferMemoryLocation},
+    MemoryManager,
+  },
+  pipeline::pools::{CommandBufferType, Pools},
+};
+
+/// Number of captures that can wait for the gpu at the same time
+const CAPTURE_SLOTS: usize = 3;
+
+struct CaptureSlot {
+  command_buffer: vk::CommandBuffer,
+  fence: vk::Fence,
+  buffer: BufferId,
+  mem: Option<BufferMemory>,
+  pending: Option<(Vec<PathBuf>, vk::Extent2D, vk::Format)>,
+}
+
+/// Copies of the output image into host visible buffers, submitted right after the frame they capture
+pub(crate) struct Captures {
+  slots: Vec<CaptureSlot>,
+  submit: Option<usize>,
+}
+
+/// The output image the copy is recorded for
+pub(crate) struct CaptureSource {
+  pub image: vk::Image,
+  pub layout: vk::ImageLayout,
+  pub format: vk::Format,
+  pub extent: vk::Extent2D,
+}
+
+impl Captures {
+  pub fn new(
+    logical_device: &ash::Device,
+    memory_manager: &mut MemoryManager,
+    pools: &mut Pools,
+  ) -> Result<Self, Error> {
+    let command_buffers =
+      pools.create_command_buffers(logical_device, CAPTURE_SLOTS, CommandBufferType::Graphics)?;
+    let fence_create_info = vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED);
+
+    let mut slots = Vec::new();
+    for command_buffer in command_buffers {
+      slots.push(CaptureSlot {
+        command_buffer,
+        fence: unsafe { logical_device.create_fence(&fence_create_info, None) }?,
+        buffer: memory_manager.create_simple_buffer(
+          vk::BufferUsageFlags::TRANSFER_DST,
+          BufferBlockSize::Medium,
+          BufferMemoryLocation::GpuToCpu,
+        )?,
+        mem: None,
+        pending: None,
+      });
+    }
+
+    Ok(Self {
+      slots,
+      submit: None,
+    })
+  }
+
+  pub fn cleanup(&self, logical_device: &ash::Device) {
+    for slot in &self.slots {
+      unsafe {
+        logical_device.destroy_fence(slot.fence, None);
+      }
+    }
+  }
+
+  /// Records the copy of the source, it is submitted with the next frame
+  pub fn record(
+    &mut self,
+    logical_device: &ash::Device,
+    memory_manager: &mut MemoryManager,
+    screenshots: &mut Screenshots,
+    source: CaptureSource,
+    paths: Vec<PathBuf>,
+  ) -> Result<(), Error> {
+    if to_rgba8(source.format, Vec::new()).is_none() {
+      return Err(ReadbackError::Format(source.format).into());
+    }
+
+    if self.slots.iter().all(|slot| slot.pending.is_some()) {
+      self.collect(logical_device, memory_manager, screenshots, true);
+    }
+    let i = self
+      .slots
+      .iter()
+      .position(|slot| slot.pending.is_none())
+      .expect("No free capture slot");
+    let slot = &mut self.slots[i];
+
+    let size = source.extent.width as usize * source.extent.height as usize * 4;
+    match &mut slot.mem {
+      Some(mem) if mem.size() >= size => {}
+      Some(mem) => memory_manager.resize_buffer_mem(mem, size)?,
+      None => {
+        slot.mem = Some(
+          memory_manager
+            .reserve_buffer_mem(slot.buffer, size)
+            .expect("Failed to reserve capture mem"),
+        )
+      }
+    }
+    let mem = slot.mem.as_ref().unwrap();
+    let buffer = memory_manager
+      .get_vk_buffer(slot.buffer)
+      .expect("Failed to get capture buffer");
+
+    let subresource_range = vk::ImageSubresourceRange::default()
+      .aspect_mask(vk::ImageAspectFlags::COLOR)
+      .level_count(1)
+      .layer_count(1);
+    let to_transfer = vk::ImageMemoryBarrier::default()
+      .image(source.image)
+      .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+      .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+      .old_layout(source.layout)
+      .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+      .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+      .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+      .subresource_range(subresource_range);
+    let to_source = to_transfer
+      .src_access_mask(vk::AccessFlags::TRANSFER_READ)
+      .dst_access_mask(vk::AccessFlags::MEMORY_READ)
+      .old_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+      .new_layout(source.layout);
+    let copied = vk::BufferMemoryBarrier::default()
+      .buffer(buffer)
+      .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+      .dst_access_mask(vk::AccessFlags::HOST_READ)
+      .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+      .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+      .offset(mem.offset() as u64)
+      .size(size as u64);
+    let region = vk::BufferImageCopy::default()
+      .buffer_offset(mem.offset() as u64)
+      .image_subresource(
+        vk::ImageSubresourceLayers::default()
+          .aspect_mask(vk::ImageAspectFlags::COLOR)
+          .layer_count(1),
+      )
+      .image_extent(vk::Extent3D {
+        width: source.extent.width,
+        height: source.extent.height,
+        depth: 1,
+      });
+
+    let begin_info =
+      vk::CommandBufferBeginInfo::default().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+    unsafe {
+      logical_device.reset_fences(&[slot.fence])?;
+      logical_device.begin_command_buffer(slot.command_buffer, &begin_info)?;
+      logical_device.cmd_pipeline_barrier(
+        slot.command_buffer,
+        vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::TRANSFER,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::DependencyFlags::empty(),
+        &[],
+        &[],
+        &[to_transfer],
+      );
+      logical_device.cmd_copy_image_to_buffer(
+        slot.command_buffer,
+        source.image,
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        buffer,
+        &[region],
+      );
+      logical_device.cmd_pipeline_barrier(
+        slot.command_buffer,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::PipelineStageFlags::BOTTOM_OF_PIPE | vk::PipelineStageFlags::HOST,
+        vk::DependencyFlags::empty(),
+        &[],
+        &[copied],
+        &[to_source],
+      );
+      logical_device.end_command_buffer(slot.command_buffer)?;
+    }
+
+    slot.pending = Some((paths, source.extent, source.format));
+    self.submit = Some(i);
+    Ok(())
+  }
+
+  /// The capture recorded for the current frame
+  #[inline]
+  pub fn take_submit(&mut self) -> Option<(vk::CommandBuffer, vk::Fence)> {
+    self
+      .submit
+      .take()
+      .map(|i| (self.slots[i].command_buffer, self.slots[i].fence))
+  }
+
+  /// Hands the finished captures to the encoder, `wait` blocks until at least one capture is finished
+  pub fn collect(
+    &mut self,
+    logical_device: &ash::Device,
+    memory_manager: &MemoryManager,
+    screenshots: &mut Screenshots,
+    wait: bool,
+  ) {
+    let pending = self
+      .slots
+      .iter()
+      .filter(|slot| slot.pending.is_some())
+      .map(|slot| slot.fence)
+      .collect::<Vec<_>>();
+    if pending.is_empty() {
+      return;
+    }
+    if wait {
+      unsafe { logical_device.wait_for_fences(&pending, false, u64::MAX) }
+        .expect("Unable to wait for fences");
+    }
+
+    for slot in &mut self.slots {
+      if slot.pending.is_none()
+        || !unsafe { logical_device.get_fence_status(slot.fence) }.unwrap_or(false)
+      {
+        continue;
+      }
+
+      let (paths, extent, format) = slot.pending.take().unwrap();
+      let result = memory_manager
+        .read_buffer(slot.mem.as_ref().unwrap())
+        .and_then(|mut data| {
+          data.truncate(extent.width as usize * extent.height as usize * 4);
+          let data = to_rgba8(format, data).ok_or(ReadbackError::Format(format))?;
+          Ok(RgbaImage::from_raw(extent.width, extent.height, data).ok_or(ReadbackError::Size)?)
+        });
+
+      match result {
+        Ok(image) => screenshots.save(image, paths),
+        Err(err) => error!("Failed to read back screenshot: {err}"),
+      }
+    }
+  }
+}
+
+/// Converts 8 bit output images to opaque rgba, `None` for other formats
+fn to_rgba8(format: vk::Format, mut data: Vec<u8>) -> Option<Vec<u8>> {
+  let bgra = match format {
+    vk::Format::R8G8B8A8_SRGB | vk::Format::R8G8B8A8_UNORM => false,
+    vk::Format::B8G8R8A8_SRGB | vk::Format::B8G8R8A8_UNORM => true,
+    _ => return None,
+  };
+
+  for pixel in data.chunks_exact_mut(4) {
+    if bgra {
+      pixel.swap(0, 2);
+    }
+    // the window is always composited opaque
+    pixel[3] = 255;
+  }
+  Some(data)
+}
+
+#[cfg(test)]
+mod test {
+  use ash::vk;
+
+  use super::to_rgba8;
+
+  #[test]
+  fn rgba_conversion() {
+    let data = vec![1, 2, 3, 0, 4, 5, 6, 128];
+    assert_eq!(
+      to_rgba8(vk::Format::R8G8B8A8_SRGB, data.clone()),
+      Some(vec![1, 2, 3, 255, 4, 5, 6, 255])
+    );
+    assert_eq!(
+      to_rgba8(vk::Format::B8G8R8A8_UNORM, data.clone()),
+      Some(vec![3, 2, 1, 255, 6, 5, 4, 255])
+    );
+    assert_eq!(to_rgba8(vk::Format::R16G16B16A16_SFLOAT, data), None);
+  }
+}
@@ -0,0 +1,165 @@
+use std::borrow::Cow;
+
+use anyhow::Error;
+use ash::vk;
+use glam::Vec4;
+
+use crate::{
+  ecs::components::skybox::Skybox,
+  memory::{
+    types::{BufferMemory, ImageId},
+    MemoryManager,
+  },
+  pipeline::{
+    descriptor::{DescriptorInfo, DescriptorSetHandle, DescriptorType},
+    DescriptorManager,
+  },
+  texture::{cubemap, Cubemap, SamplerDescriptor, TextureData},
+};
+
+use super::resources::environment::EnvironmentInfo;
+
+const IRRADIANCE_SIZE: u32 = 32;
+const PREFILTERED_SIZE: u32 = 128;
+/// Roughness goes from 0 to 1 over the levels
+const PREFILTERED_LEVELS: u32 = 5;
+const PREFILTER_SAMPLES: u32 = 64;
+const BRDF_LUT_SIZE: u32 = 64;
+const BRDF_LUT_SAMPLES: u32 = 128;
+
+/// The skybox and the maps of the image based lighting generated from it
+pub(crate) struct Environment {
+  /// Settings (binding 0), the skybox (binding 1), the irradiance (binding 2) and prefiltered (binding 3)
+  /// cubemaps and the BRDF lookup table (binding 4)
+  set: DescriptorSetHandle,
+  info: BufferMemory,
+  skybox: ImageId,
+  irradiance: ImageId,
+  prefiltered: ImageId,
+  /// The skybox the maps were generated from
+  current: Option<u64>,
+  enabled: bool,
+  intensity: f32,
+  changed: bool,
+}
+
+impl Environment {
+  /// Creates the environment descriptor set, black until a skybox is added
+  pub fn init(
+    info: BufferMemory,
+    memory_manager: &mut MemoryManager,
+    descriptor_manager: &mut DescriptorManager,
+  ) -> Result<Self, Error> {
+    let black = [Cubemap::from_fn(1, |_| Vec4::W)];
+    let skybox = memory_manager.create_cubemap(&black)?;
+    let irradiance = memory_manager.create_cubemap(&black)?;
+    let prefiltered = memory_manager.create_cubemap(&black)?;
+
+    let lut = cubemap::brdf_lut(BRDF_LUT_SIZE, BRDF_LUT_SAMPLES)
+      .into_iter()
+      .flat_map(|value| [value.x, value.y, 0.0, 1.0])
+      .flat_map(|value| half::f16::from_f32(value).to_ne_bytes())
+      .collect();
+    let brdf_lut = memory_manager.create_texture(
+      SamplerDescriptor::linear()
+        .with_address_mode(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+        .without_mipmaps(),
+      TextureData::from_rgba16f(BRDF_LUT_SIZE, BRDF_LUT_SIZE, lut)?,
+    )?;
+
+    let sampler = |image| DescriptorInfo {
+      stage: vk::ShaderStageFlags::FRAGMENT,
+      r#type: DescriptorType::Sampler(vec![image]),
+    };
+    let descriptor = vec![
+      DescriptorInfo {
+        stage: vk::ShaderStageFlags::FRAGMENT,
+        r#type: DescriptorType::UniformBuffer(info),
+      },
+      sampler(skybox),
+      sampler(irradiance),
+      sampler(prefiltered),
+      sampler(brdf_lut),
+    ];
+    let (set, _) = descriptor_manager
+      .create_descriptor_set(descriptor, memory_manager)
+      .expect("Failed to create environment descriptor set");
+
+    Ok(Self {
+      set,
+      info,
+      skybox,
+      irradiance,
+      prefiltered,
+      current: None,
+      enabled: false,
+      intensity: 0.0,
+      changed: true,
+    })
+  }
+
+  #[inline]
+  pub fn set(&self) -> DescriptorSetHandle {
+    self.set
+  }
+
+  /// Generates the maps if the skybox changed, `None` falls back to the flat ambient light
+  pub fn update(
+    &mut self,
+    skybox: Option<&Skybox>,
+    memory_manager: &mut MemoryManager,
+    descriptor_manager: &mut DescriptorManager,
+  ) -> Result<(), Error> {
+    let id = skybox.map(Skybox::id);
+    if id != self.current {
+      // a failed skybox is not generated again every frame
+      self.current = id;
+      self.enabled = false;
+      self.changed = true;
+
+      if let Some(skybox) = skybox {
+        self.generate(skybox, memory_manager, descriptor_manager)?;
+        self.enabled = true;
+      }
+    }
+
+    let intensity = skybox.map_or(0.0, |skybox| skybox.intensity);
+    if self.changed || intensity != self.intensity {
+      self.intensity = intensity;
+      let info = EnvironmentInfo {
+        intensity,
+        max_lod: (PREFILTERED_LEVELS - 1) as f32,
+        enabled: self.enabled as u32,
+      };
+      memory_manager.write_to_buffer(&self.info, &[info])?;
+      self.changed = false;
+    }
+    Ok(())
+  }
+
+  fn generate(
+    &self,
+    skybox: &Skybox,
+    memory_manager: &mut MemoryManager,
+    descriptor_manager: &mut DescriptorManager,
+  ) -> Result<(), Error> {
+    let cubemap = skybox.to_cubemap()?;
+
+    let mut source = Cow::Borrowed(&cubemap);
+    while source.size() > PREFILTERED_SIZE {
+      source = Cow::Owned(source.downsample());
+    }
+    let irradiance = source.irradiance(IRRADIANCE_SIZE);
+    let prefiltered = source.prefilter(PREFILTERED_SIZE, PREFILTERED_LEVELS, PREFILTER_SAMPLES);
+
+    for (image, levels) in [
+      (self.skybox, vec![cubemap]),
+      (self.irradiance, vec![irradiance]),
+      (self.prefiltered, prefiltered),
+    ] {
+      memory_manager.replace_cubemap(image, &levels)?;
+      descriptor_manager.rewrite_image(image, memory_manager);
+    }
+    Ok(())
+  }
+}
@@ -3,27 +3,28 @@ use ash::vk;
 
 use crate::memory::{types::ImageId, MemoryManager};
 
-pub const IMAGES_PER_FRAME_BUFFER: u32 = 3;
+use super::render_pass::RenderPasses;
 
+pub const IMAGES_PER_FRAME_BUFFER: u32 = 4;
+/// The hdr image of the scene followed by the two images the post-processing passes alternate between
+pub const HDR_IMAGES: usize = 3;
+/// Format the scene is lit and post-processed in
+pub const HDR_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+/// Output framebuffer of a swapchain image, the last post-processing pass writes into it
 pub struct Framebuffer {
   buffer: vk::Framebuffer,
-  light_view: vk::ImageView,
-  available: vk::Semaphore,
+  view: vk::ImageView,
   finished: vk::Semaphore,
-  begin_drawing: vk::Fence,
   command_buffer: vk::CommandBuffer,
 }
 
 impl Framebuffer {
-  #[allow(clippy::complexity)]
   pub fn create(
-    swapchain_image: vk::Image,
+    image: vk::Image,
     logical_device: &ash::Device,
     format: vk::Format,
-    images: [ImageId; IMAGES_PER_FRAME_BUFFER as usize],
-    depth_image: ImageId,
     render_pass: vk::RenderPass,
-    memory_manager: &MemoryManager,
     extent: vk::Extent2D,
     command_buffer: vk::CommandBuffer,
   ) -> Result<Self, Error> {
@@ -34,28 +35,13 @@ impl Framebuffer {
       .base_array_layer(0)
       .layer_count(1);
     let image_view_create_info = vk::ImageViewCreateInfo::default()
-      .image(swapchain_image)
+      .image(image)
       .view_type(vk::ImageViewType::TYPE_2D)
       .format(format)
       .subresource_range(subresource_range);
-    let light_view = unsafe { logical_device.create_image_view(&image_view_create_info, None) }?;
-
-    let views = [
-      memory_manager
-        .get_vk_image_view(images[0])
-        .expect("Failed to get framebuffer image_view"),
-      memory_manager
-        .get_vk_image_view(images[1])
-        .expect("Failed to get framebuffer image_view"),
-      memory_manager
-        .get_vk_image_view(images[2])
-        .expect("Failed to get framebuffer image_view"),
-      memory_manager
-        .get_vk_image_view(depth_image)
-        .expect("Failed to get framebuffer image_view"),
-      light_view,
-    ];
+    let view = unsafe { logical_device.create_image_view(&image_view_create_info, None) }?;
 
+    let views = [view];
     let frame_buffer_create_info = vk::FramebufferCreateInfo::default()
       .render_pass(render_pass)
       .attachments(&views)
@@ -66,108 +52,555 @@ impl Framebuffer {
     let buffer = unsafe { logical_device.create_framebuffer(&frame_buffer_create_info, None) }?;
 
     let semaphore_create_info = vk::SemaphoreCreateInfo::default();
-    let fence_create_info = vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED);
-
-    let available = unsafe { logical_device.create_semaphore(&semaphore_create_info, None) }?;
     let finished = unsafe { logical_device.create_semaphore(&semaphore_create_info, None) }?;
-    let begin_drawing = unsafe { logical_device.create_fence(&fence_create_info, None) }?;
 
     Ok(Self {
       buffer,
-      light_view,
-      available,
+      view,
       finished,
-      begin_drawing,
       command_buffer,
     })
   }
 
   pub fn cleanup(&self, logical_device: &ash::Device) {
     unsafe {
-      logical_device.destroy_fence(self.begin_drawing, None);
-      logical_device.destroy_semaphore(self.available, None);
       logical_device.destroy_semaphore(self.finished, None);
       logical_device.destroy_framebuffer(self.buffer, None);
-      logical_device.destroy_image_view(self.light_view, None);
+      logical_device.destroy_image_view(self.view, None);
     }
   }
 
   #[inline]
-  pub fn begin_drawing(&self) -> vk::Fence {
-    self.begin_drawing
+  pub fn finished(&self) -> vk::Semaphore {
+    self.finished
   }
 
   #[inline]
-  pub fn available(&self) -> vk::Semaphore {
-    self.available
+  pub fn command_buffer(&self) -> vk::CommandBuffer {
+    self.command_buffer
   }
 
   #[inline]
-  pub fn finished(&self) -> vk::Semaphore {
-    self.finished
+  pub fn buffer(&self) -> vk::Framebuffer {
+    self.buffer
+  }
+
+  pub fn start_record(&self, device: &ash::Device) -> Result<vk::CommandBuffer, vk::Result> {
+    let buffer_begin_info = vk::CommandBufferBeginInfo::default();
+    unsafe {
+      device.begin_command_buffer(self.command_buffer, &buffer_begin_info)?;
+    }
+
+    Ok(self.command_buffer)
+  }
+}
+
+/// The G-Buffer, depth and hdr images a target is rendered into, before post-processing writes into its output
+pub struct SceneFramebuffer {
+  scene: vk::Framebuffer,
+  post: [vk::Framebuffer; HDR_IMAGES - 1],
+  attachments: [ImageId; IMAGES_PER_FRAME_BUFFER as usize],
+  depth_image: ImageId,
+  hdr: [ImageId; HDR_IMAGES],
+  /// The multisampled image lighting writes into with msaa, resolved into the first hdr image
+  lit: Option<ImageId>,
+  extent: vk::Extent2D,
+}
+
+impl SceneFramebuffer {
+  pub fn create(
+    logical_device: &ash::Device,
+    render_passes: &RenderPasses,
+    memory_manager: &mut MemoryManager,
+    extent: vk::Extent2D,
+  ) -> Result<Self, Error> {
+    let samples = render_passes.samples();
+    let (attachments, depth_image) = create_attachments(memory_manager, extent, samples)?;
+
+    let (image_info, image_view_info, sampler_info) = hdr_infos(extent);
+    let mut hdr = Vec::new();
+    for _ in 0..HDR_IMAGES {
+      hdr.push(memory_manager.create_sampler_image(
+        &image_info,
+        &image_view_info,
+        &sampler_info,
+      )?);
+    }
+    let hdr = [hdr[0], hdr[1], hdr[2]];
+
+    let lit = if render_passes.is_multisampled() {
+      let (image_info, image_view_info) = lit_infos(extent, samples);
+      Some(memory_manager.create_image(&image_info, &image_view_info)?)
+    } else {
+      None
+    };
+
+    let (scene, post) = create_scene_framebuffers(
+      logical_device,
+      render_passes,
+      memory_manager,
+      &attachments,
+      depth_image,
+      &hdr,
+      lit,
+      extent,
+    )?;
+
+    Ok(Self {
+      scene,
+      post,
+      attachments,
+      depth_image,
+      hdr,
+      lit,
+      extent,
+    })
+  }
+
+  /// Recreates the images with a new extent and the sample count of the render passes keeping their ids,
+  /// descriptors using them have to be rewritten afterwards
+  pub fn resize(
+    &mut self,
+    logical_device: &ash::Device,
+    render_passes: &RenderPasses,
+    memory_manager: &mut MemoryManager,
+    extent: vk::Extent2D,
+  ) -> Result<(), Error> {
+    self.cleanup(logical_device);
+
+    let samples = render_passes.samples();
+    resize_attachments(
+      memory_manager,
+      extent,
+      samples,
+      &self.attachments,
+      self.depth_image,
+    )?;
+    let (image_info, image_view_info, sampler_info) = hdr_infos(extent);
+    for image in self.hdr {
+      memory_manager.replace_sampler_image(image, &image_info, &image_view_info, &sampler_info)?;
+    }
+
+    let (image_info, image_view_info) = lit_infos(extent, samples);
+    match (self.lit, render_passes.is_multisampled()) {
+      (Some(image), true) => memory_manager.replace_image(image, &image_info, &image_view_info)?,
+      (None, true) => self.lit = Some(memory_manager.create_image(&image_info, &image_view_info)?),
+      (Some(image), false) => {
+        memory_manager.remove_image(image)?;
+        self.lit = None;
+      }
+      (None, false) => {}
+    }
+
+    (self.scene, self.post) = create_scene_framebuffers(
+      logical_device,
+      render_passes,
+      memory_manager,
+      &self.attachments,
+      self.depth_image,
+      &self.hdr,
+      self.lit,
+      extent,
+    )?;
+    self.extent = extent;
+    Ok(())
   }
 
+  pub fn cleanup(&self, logical_device: &ash::Device) {
+    unsafe {
+      logical_device.destroy_framebuffer(self.scene, None);
+      for buffer in self.post {
+        logical_device.destroy_framebuffer(buffer, None);
+      }
+    }
+  }
+
+  /// The framebuffer of the camera passes, lighting writes into the first hdr image
   #[inline]
-  pub fn command_buffer(&self) -> vk::CommandBuffer {
-    self.command_buffer
+  pub fn buffer(&self) -> vk::Framebuffer {
+    self.scene
   }
 
-  pub fn start_record(
-    &self,
-    device: &ash::Device,
-    render_pass: vk::RenderPass,
+  /// The framebuffer of a post-processing pass writing into `hdr_images()[image]`, the first image can not be written
+  #[inline]
+  pub fn post_buffer(&self, image: usize) -> vk::Framebuffer {
+    self.post[image - 1]
+  }
+
+  #[inline]
+  pub fn attachments(&self) -> &[ImageId] {
+    &self.attachments
+  }
+
+  #[inline]
+  pub fn hdr_images(&self) -> &[ImageId; HDR_IMAGES] {
+    &self.hdr
+  }
+
+  #[inline]
+  pub fn extent(&self) -> vk::Extent2D {
+    self.extent
+  }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_scene_framebuffers(
+  logical_device: &ash::Device,
+  render_passes: &RenderPasses,
+  memory_manager: &MemoryManager,
+  attachments: &[ImageId; IMAGES_PER_FRAME_BUFFER as usize],
+  depth_image: ImageId,
+  hdr: &[ImageId; HDR_IMAGES],
+  lit: Option<ImageId>,
+  extent: vk::Extent2D,
+) -> Result<(vk::Framebuffer, [vk::Framebuffer; HDR_IMAGES - 1]), Error> {
+  let view = |image: ImageId| {
+    memory_manager
+      .get_vk_image_view(image)
+      .expect("Failed to get framebuffer image_view")
+  };
+  let create = |render_pass: vk::RenderPass, views: &[vk::ImageView]| {
+    let frame_buffer_create_info = vk::FramebufferCreateInfo::default()
+      .render_pass(render_pass)
+      .attachments(views)
+      .width(extent.width)
+      .height(extent.height)
+      .layers(1);
+    unsafe { logical_device.create_framebuffer(&frame_buffer_create_info, None) }
+  };
+
+  let mut views = vec![
+    view(attachments[0]),
+    view(attachments[1]),
+    view(attachments[2]),
+    view(attachments[3]),
+    view(depth_image),
+    view(hdr[0]),
+  ];
+  views.extend(lit.map(view));
+  let scene = create(render_passes.main(), &views)?;
+  let post = [
+    create(render_passes.post(), &[view(hdr[1])])?,
+    create(render_passes.post(), &[view(hdr[2])])?,
+  ];
+  Ok((scene, post))
+}
+
+/// Offscreen framebuffer with its own attachments, rendering into a sampled texture
+pub struct TextureFramebuffer {
+  scene: SceneFramebuffer,
+  buffer: vk::Framebuffer,
+  image: ImageId,
+}
+
+impl TextureFramebuffer {
+  pub fn create(
+    logical_device: &ash::Device,
+    format: vk::Format,
+    interpolation: vk::Filter,
+    render_passes: &RenderPasses,
+    memory_manager: &mut MemoryManager,
     extent: vk::Extent2D,
-  ) -> Result<vk::CommandBuffer, vk::Result> {
-    let buffer_begin_info = vk::CommandBufferBeginInfo::default();
+  ) -> Result<Self, Error> {
+    let scene = SceneFramebuffer::create(logical_device, render_passes, memory_manager, extent)?;
+
+    let image_info = vk::ImageCreateInfo::default()
+      .image_type(vk::ImageType::TYPE_2D)
+      .format(format)
+      .extent(vk::Extent3D {
+        width: extent.width,
+        height: extent.height,
+        depth: 1,
+      })
+      .mip_levels(1)
+      .array_layers(1)
+      .samples(vk::SampleCountFlags::TYPE_1)
+      .tiling(vk::ImageTiling::OPTIMAL)
+      .usage(
+        vk::ImageUsageFlags::COLOR_ATTACHMENT
+          | vk::ImageUsageFlags::SAMPLED
+          | vk::ImageUsageFlags::TRANSFER_SRC,
+      )
+      .sharing_mode(vk::SharingMode::EXCLUSIVE);
+
+    let subresource_range = vk::ImageSubresourceRange::default()
+      .aspect_mask(vk::ImageAspectFlags::COLOR)
+      .base_mip_level(0)
+      .level_count(1)
+      .base_array_layer(0)
+      .layer_count(1);
+    let image_view_info = vk::ImageViewCreateInfo::default()
+      .view_type(vk::ImageViewType::TYPE_2D)
+      .format(format)
+      .subresource_range(subresource_range);
+
+    let sampler_info = vk::SamplerCreateInfo::default()
+      .mag_filter(interpolation)
+      .min_filter(interpolation)
+      .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+      .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+      .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE);
+
+    let image =
+      memory_manager.create_sampler_image(&image_info, &image_view_info, &sampler_info)?;
+
+    let views = [memory_manager
+      .get_vk_image_view(image)
+      .expect("Failed to get framebuffer image_view")];
+
+    let frame_buffer_create_info = vk::FramebufferCreateInfo::default()
+      .render_pass(render_passes.texture_output())
+      .attachments(&views)
+      .width(extent.width)
+      .height(extent.height)
+      .layers(1);
+
+    let buffer = unsafe { logical_device.create_framebuffer(&frame_buffer_create_info, None) }?;
+
+    Ok(Self {
+      scene,
+      buffer,
+      image,
+    })
+  }
+
+  pub fn cleanup(&self, logical_device: &ash::Device) {
+    self.scene.cleanup(logical_device);
     unsafe {
-      device.begin_command_buffer(self.command_buffer, &buffer_begin_info)?;
+      logical_device.destroy_framebuffer(self.buffer, None);
     }
+  }
+
+  #[inline]
+  pub fn scene(&self) -> &SceneFramebuffer {
+    &self.scene
+  }
+
+  #[inline]
+  pub fn scene_mut(&mut self) -> &mut SceneFramebuffer {
+    &mut self.scene
+  }
 
-    let clear_values = [
-      vk::ClearValue {
-        color: vk::ClearColorValue {
-          float32: [0.0, 0.0, 0.0, 1.0],
-        },
+  /// The framebuffer of the output pass
+  #[inline]
+  pub fn buffer(&self) -> vk::Framebuffer {
+    self.buffer
+  }
+
+  #[inline]
+  pub fn image(&self) -> ImageId {
+    self.image
+  }
+
+  #[inline]
+  pub fn extent(&self) -> vk::Extent2D {
+    self.scene.extent()
+  }
+}
+
+/// Creates the G-Buffer images (color, normal + metallic, position + roughness, emissive + occlusion) and the depth image
+fn create_attachments(
+  memory_manager: &mut MemoryManager,
+  extent: vk::Extent2D,
+  samples: vk::SampleCountFlags,
+) -> Result<([ImageId; IMAGES_PER_FRAME_BUFFER as usize], ImageId), Error> {
+  let ((depth_info, depth_view_info), (image_info, image_view_info)) =
+    attachment_infos(extent, samples);
+
+  let depth_image = memory_manager.create_image(&depth_info, &depth_view_info)?;
+
+  let mut images = Vec::new();
+  for _ in 0..IMAGES_PER_FRAME_BUFFER {
+    images.push(memory_manager.create_image(&image_info, &image_view_info)?);
+  }
+
+  Ok(([images[0], images[1], images[2], images[3]], depth_image))
+}
+
+/// Recreates the G-Buffer images with a new extent and sample count, keeping their ids
+fn resize_attachments(
+  memory_manager: &mut MemoryManager,
+  extent: vk::Extent2D,
+  samples: vk::SampleCountFlags,
+  images: &[ImageId; IMAGES_PER_FRAME_BUFFER as usize],
+  depth_image: ImageId,
+) -> Result<(), Error> {
+  let ((depth_info, depth_view_info), (image_info, image_view_info)) =
+    attachment_infos(extent, samples);
+
+  memory_manager.replace_image(depth_image, &depth_info, &depth_view_info)?;
+  for image in images {
+    memory_manager.replace_image(*image, &image_info, &image_view_info)?;
+  }
+  Ok(())
+}
+
+type ImageInfos = (
+  vk::ImageCreateInfo<'static>,
+  vk::ImageViewCreateInfo<'static>,
+);
+
+/// Infos of the depth image and the color attachments
+fn attachment_infos(
+  extent: vk::Extent2D,
+  samples: vk::SampleCountFlags,
+) -> (ImageInfos, ImageInfos) {
+  let extend_3d = vk::Extent3D {
+    width: extent.width,
+    height: extent.height,
+    depth: 1,
+  };
+  let depth_image_create_info = vk::ImageCreateInfo::default()
+    .image_type(vk::ImageType::TYPE_2D)
+    .format(vk::Format::D32_SFLOAT)
+    .extent(extend_3d)
+    .mip_levels(1)
+    .array_layers(1)
+    .samples(samples)
+    .tiling(vk::ImageTiling::OPTIMAL)
+    .usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT)
+    .sharing_mode(vk::SharingMode::EXCLUSIVE);
+
+  let subresource_range = vk::ImageSubresourceRange::default()
+    .aspect_mask(vk::ImageAspectFlags::DEPTH)
+    .base_mip_level(0)
+    .level_count(1)
+    .base_array_layer(0)
+    .layer_count(1);
+  let depth_image_view_create_info = vk::ImageViewCreateInfo::default()
+    .view_type(vk::ImageViewType::TYPE_2D)
+    .format(vk::Format::D32_SFLOAT)
+    .subresource_range(subresource_range);
+
+  let image_info = depth_image_create_info
+    .usage(vk::ImageUsageFlags::INPUT_ATTACHMENT | vk::ImageUsageFlags::COLOR_ATTACHMENT)
+    .format(vk::Format::R32G32B32A32_SFLOAT);
+
+  let subresource_range = subresource_range.aspect_mask(vk::ImageAspectFlags::COLOR);
+  let image_view_info = depth_image_view_create_info
+    .format(vk::Format::R32G32B32A32_SFLOAT)
+    .subresource_range(subresource_range);
+
+  (
+    (depth_image_create_info, depth_image_view_create_info),
+    (image_info, image_view_info),
+  )
+}
+
+/// Infos of the multisampled image the scene is lit into with msaa
+fn lit_infos(extent: vk::Extent2D, samples: vk::SampleCountFlags) -> ImageInfos {
+  let (_, (image_info, image_view_info)) = attachment_infos(extent, samples);
+  (
+    image_info
+      .usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
+      .format(HDR_FORMAT),
+    image_view_info.format(HDR_FORMAT),
+  )
+}
+
+/// Infos of the hdr images, they are sampled by the post-processing passes
+fn hdr_infos(
+  extent: vk::Extent2D,
+) -> (
+  vk::ImageCreateInfo<'static>,
+  vk::ImageViewCreateInfo<'static>,
+  vk::SamplerCreateInfo<'static>,
+) {
+  let image_info = vk::ImageCreateInfo::default()
+    .image_type(vk::ImageType::TYPE_2D)
+    .format(HDR_FORMAT)
+    .extent(vk::Extent3D {
+      width: extent.width,
+      height: extent.height,
+      depth: 1,
+    })
+    .mip_levels(1)
+    .array_layers(1)
+    .samples(vk::SampleCountFlags::TYPE_1)
+    .tiling(vk::ImageTiling::OPTIMAL)
+    .usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED)
+    .sharing_mode(vk::SharingMode::EXCLUSIVE);
+
+  let subresource_range = vk::ImageSubresourceRange::default()
+    .aspect_mask(vk::ImageAspectFlags::COLOR)
+    .base_mip_level(0)
+    .level_count(1)
+    .base_array_layer(0)
+    .layer_count(1);
+  let image_view_info = vk::ImageViewCreateInfo::default()
+    .view_type(vk::ImageViewType::TYPE_2D)
+    .format(HDR_FORMAT)
+    .subresource_range(subresource_range);
+
+  let sampler_info = vk::SamplerCreateInfo::default()
+    .mag_filter(vk::Filter::LINEAR)
+    .min_filter(vk::Filter::LINEAR)
+    .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+    .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+    .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE);
+
+  (image_info, image_view_info, sampler_info)
+}
+
+/// Begins a render pass limited to the given area, only the area gets cleared
+pub fn begin_render_pass(
+  device: &ash::Device,
+  command_buffer: vk::CommandBuffer,
+  render_pass: vk::RenderPass,
+  framebuffer: vk::Framebuffer,
+  render_area: vk::Rect2D,
+) {
+  let clear_values = [
+    vk::ClearValue {
+      color: vk::ClearColorValue {
+        float32: [0.0, 0.0, 0.0, 1.0],
       },
-      vk::ClearValue {
-        color: vk::ClearColorValue {
-          float32: [0.0, 0.0, 0.0, 0.0],
-        },
+    },
+    vk::ClearValue {
+      color: vk::ClearColorValue {
+        float32: [0.0, 0.0, 0.0, 0.0],
       },
-      vk::ClearValue {
-        color: vk::ClearColorValue {
-          float32: [0.0, 0.0, 0.0, 0.0],
-        },
+    },
+    vk::ClearValue {
+      color: vk::ClearColorValue {
+        float32: [0.0, 0.0, 0.0, 0.0],
       },
-      vk::ClearValue {
-        depth_stencil: vk::ClearDepthStencilValue {
-          depth: 1.0,
-          stencil: 0,
-        },
+    },
+    // emissive color and full occlusion
+    vk::ClearValue {
+      color: vk::ClearColorValue {
+        float32: [0.0, 0.0, 0.0, 1.0],
       },
-      vk::ClearValue {
-        color: vk::ClearColorValue {
-          float32: [0.0, 0.0, 0.0, 1.0],
-        },
+    },
+    vk::ClearValue {
+      depth_stencil: vk::ClearDepthStencilValue {
+        depth: 1.0,
+        stencil: 0,
       },
-    ];
-    let render_pass_begin_info = vk::RenderPassBeginInfo::default()
-      .render_pass(render_pass)
-      .framebuffer(self.buffer)
-      .render_area(vk::Rect2D {
-        offset: vk::Offset2D { x: 0, y: 0 },
-        extent,
-      })
-      .clear_values(&clear_values);
-
-    unsafe {
-      device.cmd_begin_render_pass(
-        self.command_buffer,
-        &render_pass_begin_info,
-        vk::SubpassContents::INLINE,
-      );
-    }
+    },
+    vk::ClearValue {
+      color: vk::ClearColorValue {
+        float32: [0.0, 0.0, 0.0, 1.0],
+      },
+    },
+    // the multisampled lit scene with msaa
+    vk::ClearValue {
+      color: vk::ClearColorValue {
+        float32: [0.0, 0.0, 0.0, 1.0],
+      },
+    },
+  ];
+  let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+    .render_pass(render_pass)
+    .framebuffer(framebuffer)
+    .render_area(render_area)
+    .clear_values(&clear_values);
 
-    Ok(self.command_buffer)
+  unsafe {
+    device.cmd_begin_render_pass(
+      command_buffer,
+      &render_pass_begin_info,
+      vk::SubpassContents::INLINE,
+    );
   }
 }
@@ -0,0 +1,289 @@
+use anyhow::Error;
+use ash::vk;
+use image::RgbaImage;
+
+use crate::{
+  device::Device,
+  ecs::components::camera::RenderTarget,
+  error::ReadbackError,
+  memory::{
+    types::{BufferBlockSize, BufferMemory, BufferMemoryLocation, ImageId},
+    MemoryManager,
+  },
+  pipeline::pools::{CommandBufferType, Pools},
+};
+
+use super::{
+  framebuffer::{Framebuffer, SceneFramebuffer},
+  render_pass::RenderPasses,
+};
+
+/// Format of the offscreen image, the shaders output linear colors which are stored srgb encoded like on most windows
+pub const HEADLESS_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
+
+/// Takes the place of the swapchain without a window.
+/// Every frame is copied into a host visible buffer at the end of its command buffer
+pub struct Headless {
+  framebuffer: Framebuffer,
+  fence: vk::Fence,
+  image: ImageId,
+  readback: BufferMemory,
+  extent: vk::Extent2D,
+  graphics_queue: vk::Queue,
+  scene: SceneFramebuffer,
+}
+
+impl Headless {
+  pub fn init(
+    device: &Device,
+    memory_manager: &mut MemoryManager,
+    pools: &mut Pools,
+    render_passes: &RenderPasses,
+    extent: vk::Extent2D,
+  ) -> Result<Self, Error> {
+    let logical_device = device.get_device();
+
+    let image_info = vk::ImageCreateInfo::default()
+      .image_type(vk::ImageType::TYPE_2D)
+      .format(HEADLESS_FORMAT)
+      .extent(vk::Extent3D {
+        width: extent.width,
+        height: extent.height,
+        depth: 1,
+      })
+      .mip_levels(1)
+      .array_layers(1)
+      .samples(vk::SampleCountFlags::TYPE_1)
+      .tiling(vk::ImageTiling::OPTIMAL)
+      .usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC)
+      .sharing_mode(vk::SharingMode::EXCLUSIVE);
+    let subresource_range = vk::ImageSubresourceRange::default()
+      .aspect_mask(vk::ImageAspectFlags::COLOR)
+      .base_mip_level(0)
+      .level_count(1)
+      .base_array_layer(0)
+      .layer_count(1);
+    let image_view_info = vk::ImageViewCreateInfo::default()
+      .view_type(vk::ImageViewType::TYPE_2D)
+      .format(HEADLESS_FORMAT)
+      .subresource_range(subresource_range);
+    let image = memory_manager.create_image(&image_info, &image_view_info)?;
+
+    let size = pixel_count(extent) * 4;
+    let buffer = memory_manager.create_simple_buffer(
+      vk::BufferUsageFlags::TRANSFER_DST,
+      BufferBlockSize::Exact(size),
+      BufferMemoryLocation::GpuToCpu,
+    )?;
+    let readback = memory_manager
+      .reserve_buffer_mem(buffer, size)
+      .expect("Failed to reserve readback mem");
+
+    let command_buffer =
+      pools.create_command_buffers(logical_device, 1, CommandBufferType::Graphics)?[0];
+    let scene = SceneFramebuffer::create(logical_device, render_passes, memory_manager, extent)?;
+
+    let framebuffer = Framebuffer::create(
+      memory_manager
+        .get_vk_image(image)
+        .expect("Failed to get headless image"),
+      logical_device,
+      HEADLESS_FORMAT,
+      render_passes.output(RenderTarget::Window),
+      extent,
+      command_buffer,
+    )?;
+
+    let fence_create_info = vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED);
+    let fence = unsafe { logical_device.create_fence(&fence_create_info, None) }?;
+
+    Ok(Self {
+      framebuffer,
+      fence,
+      image,
+      readback,
+      extent,
+      graphics_queue: device.get_queues().graphics(),
+      scene,
+    })
+  }
+
+  #[inline]
+  pub fn get_extent(&self) -> vk::Extent2D {
+    self.extent
+  }
+
+  #[inline]
+  pub fn cleanup(&self, logical_device: &ash::Device) {
+    self.framebuffer.cleanup(logical_device);
+    self.scene.cleanup(logical_device);
+    unsafe {
+      logical_device.destroy_fence(self.fence, None);
+    }
+  }
+
+  #[inline]
+  pub fn wait_for_draw_start(&self, device: &ash::Device) {
+    unsafe {
+      device
+        .wait_for_fences(&[self.fence], true, u64::MAX)
+        .expect("Unable to wait for fences");
+
+      device
+        .reset_fences(&[self.fence])
+        .expect("Unable to reset Fence");
+    }
+  }
+
+  #[inline]
+  pub fn record_command_buffer_start(
+    &self,
+    device: &ash::Device,
+  ) -> Result<vk::CommandBuffer, vk::Result> {
+    self.framebuffer.start_record(device)
+  }
+
+  #[inline]
+  pub fn framebuffer(&self) -> vk::Framebuffer {
+    self.framebuffer.buffer()
+  }
+
+  /// Copies the rendered image into the readback buffer before ending the command buffer
+  pub fn record_command_buffer_end(
+    &self,
+    device: &ash::Device,
+    buffer: vk::CommandBuffer,
+    memory_manager: &MemoryManager,
+  ) -> Result<(), vk::Result> {
+    let image = memory_manager
+      .get_vk_image(self.image)
+      .expect("Failed to get headless image");
+    let readback = memory_manager
+      .get_vk_buffer(self.readback.buffer())
+      .expect("Failed to get readback buffer");
+
+    let color_layers = vk::ImageSubresourceLayers::default()
+      .aspect_mask(vk::ImageAspectFlags::COLOR)
+      .mip_level(0)
+      .base_array_layer(0)
+      .layer_count(1);
+    let rendered = vk::ImageMemoryBarrier::default()
+      .image(image)
+      .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+      .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+      .old_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+      .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+      .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+      .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+      .subresource_range(
+        vk::ImageSubresourceRange::default()
+          .aspect_mask(vk::ImageAspectFlags::COLOR)
+          .level_count(1)
+          .layer_count(1),
+      );
+    let region = vk::BufferImageCopy::default()
+      .buffer_offset(self.readback.offset() as u64)
+      .image_subresource(color_layers)
+      .image_extent(vk::Extent3D {
+        width: self.extent.width,
+        height: self.extent.height,
+        depth: 1,
+      });
+    let copied = vk::BufferMemoryBarrier::default()
+      .buffer(readback)
+      .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+      .dst_access_mask(vk::AccessFlags::HOST_READ)
+      .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+      .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+      .offset(self.readback.offset() as u64)
+      .size(self.readback.size() as u64);
+
+    unsafe {
+      device.cmd_pipeline_barrier(
+        buffer,
+        vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::DependencyFlags::empty(),
+        &[],
+        &[],
+        &[rendered],
+      );
+      device.cmd_copy_image_to_buffer(
+        buffer,
+        image,
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        readback,
+        &[region],
+      );
+      device.cmd_pipeline_barrier(
+        buffer,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::PipelineStageFlags::HOST,
+        vk::DependencyFlags::empty(),
+        &[],
+        &[copied],
+        &[],
+      );
+
+      device.end_command_buffer(buffer)
+    }
+  }
+
+  pub fn draw_frame(
+    &self,
+    logical_device: &ash::Device,
+    capture: Option<(vk::CommandBuffer, vk::Fence)>,
+  ) {
+    let command_buffer = [self.framebuffer.command_buffer()];
+    let submit_info = [vk::SubmitInfo::default().command_buffers(&command_buffer)];
+
+    unsafe {
+      logical_device
+        .queue_submit(self.graphics_queue, &submit_info, self.fence)
+        .expect("Unable to submit queue");
+
+      if let Some((capture, fence)) = capture {
+        let command_buffer = [capture];
+        let submit_info = [vk::SubmitInfo::default().command_buffers(&command_buffer)];
+        logical_device
+          .queue_submit(self.graphics_queue, &submit_info, fence)
+          .expect("Unable to submit queue");
+      }
+    }
+  }
+
+  /// Waits for the last submitted frame and returns its pixels
+  pub fn read_pixels(
+    &self,
+    logical_device: &ash::Device,
+    memory_manager: &MemoryManager,
+  ) -> Result<RgbaImage, Error> {
+    unsafe { logical_device.queue_wait_idle(self.graphics_queue) }?;
+
+    let data = memory_manager.read_buffer(&self.readback)?;
+    Ok(
+      RgbaImage::from_raw(self.extent.width, self.extent.height, data)
+        .ok_or(ReadbackError::Size)?,
+    )
+  }
+
+  #[inline]
+  pub fn image(&self) -> ImageId {
+    self.image
+  }
+
+  #[inline]
+  pub fn scene(&self) -> &SceneFramebuffer {
+    &self.scene
+  }
+
+  #[inline]
+  pub fn scene_mut(&mut self) -> &mut SceneFramebuffer {
+    &mut self.scene
+  }
+}
+
+#[inline]
+fn pixel_count(extent: vk::Extent2D) -> usize {
+  extent.width as usize * extent.height as usize
+}
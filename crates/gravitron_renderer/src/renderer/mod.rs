@@ -1,25 +1,51 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, path::PathBuf};
 
 use anyhow::Error;
 use ash::vk;
-use glam::Mat4;
-use gravitron_window::config::WindowConfig;
-use resources::lighting::{LightInfo, PointLight, SpotLight};
-use swapchain::SwapChain;
+use capture::Captures;
+use environment::Environment;
+use framebuffer::{begin_render_pass, TextureFramebuffer, HDR_IMAGES};
+use headless::HEADLESS_FORMAT;
+use image::RgbaImage;
+use log::{error, info};
+use output::{Output, OutputTarget};
+use post::{create_post_sets, is_srgb, PostProcess};
+use render_pass::RenderPasses;
+use resources::{
+  camera::CameraData,
+  cluster::{Cluster, ClusterCamera, CLUSTER_COUNT},
+  environment::EnvironmentInfo,
+  lighting::{LightInfo, PointLight, SpotLight},
+  material::GpuMaterial,
+  post_process::PostProcessInfo,
+  shadow::ShadowInfo,
+};
+use shadow::{ShadowDescriptors, ShadowMaps};
+use swapchain::select_surface_format;
+use transparent::{TransparentDraws, TransparentInstance};
 
 use crate::{
-  config::GraphicsConfig,
+  asset::watcher::FileWatcher,
+  config::{GraphicsConfig, Msaa, PostProcessConfig, PresentMode, ShadowConfig, TextureSource},
+  ecs::{
+    components::{
+      camera::{RenderTarget, Viewport},
+      skybox::Skybox,
+    },
+    resources::screenshot::Screenshots,
+  },
   memory::{
-    types::{BufferMemory, BufferMemoryLocation},
+    types::{BufferMemory, BufferMemoryLocation, ImageId},
     MemoryManager,
   },
   model::{
+    bounds::Frustum,
     model::{InstanceData, ModelHandle},
     ModelManager,
   },
   pipeline::{
     descriptor::{DescriptorHandle, DescriptorInfo, DescriptorSetHandle, DescriptorType},
-    graphics::{stage::RenderingStage, GraphicsPipelineBuilder},
+    graphics::{stage::RenderingStage, GraphicsPipeline, GraphicsPipelineBuilder},
     manager::GraphicsPipelineHandle,
     DescriptorManager,
   },
@@ -27,17 +53,25 @@ use crate::{
 
 use super::{
   device::Device,
-  error::RendererInitError,
+  error::{ReadbackError, RendererInitError},
   instance::InstanceDevice,
   memory::types::{BufferBlockSize, BufferId},
   pipeline::{manager::PipelineManager, pools::Pools},
   surface::Surface,
+  texture::{ColorSpace, SamplerDescriptor, TextureData, TextureManager},
 };
 
+mod capture;
+mod environment;
 mod framebuffer;
+mod headless;
+pub(crate) mod output;
+mod post;
 mod render_pass;
 pub mod resources;
+mod shadow;
 pub(crate) mod swapchain;
+pub(crate) mod transparent;
 
 pub const DEFAULT_TEXTURE: TextureHandle = TextureHandle(0);
 
@@ -49,14 +83,47 @@ pub const CAMERA_DESCRIPTOR: DescriptorHandle = DescriptorHandle(0);
 pub const LIGHT_INFO_DESCRIPTOR: DescriptorHandle = DescriptorHandle(1);
 pub const POINT_LIGHT_DESCRIPTOR: DescriptorHandle = DescriptorHandle(2);
 pub const SPOT_LIGHT_DESCRIPTOR: DescriptorHandle = DescriptorHandle(3);
-pub const TEXTURE_DESCRIPTOR: DescriptorHandle = DescriptorHandle(4);
+pub const MATERIAL_DESCRIPTOR: DescriptorHandle = DescriptorHandle(4);
+pub const CLUSTER_CAMERA_DESCRIPTOR: DescriptorHandle = DescriptorHandle(5);
+pub const CLUSTER_DESCRIPTOR: DescriptorHandle = DescriptorHandle(6);
+pub const LIGHT_INDEX_DESCRIPTOR: DescriptorHandle = DescriptorHandle(7);
+pub const TEXTURE_DESCRIPTOR: DescriptorHandle = DescriptorHandle(8);
 
-#[derive(Default, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+#[derive(Default, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
 pub struct TextureHandle(pub(crate) u32);
 
+/// One pass of a camera, `camera` is the index into the camera descriptor
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) struct CameraPass {
+  pub target: RenderTarget,
+  pub viewport: Viewport,
+  pub camera: u32,
+}
+
 pub struct Renderer {
-  render_pass: ash::vk::RenderPass,
-  swapchain: SwapChain,
+  render_passes: RenderPasses,
+  output: Output,
+  captures: Captures,
+  shadow_maps: ShadowMaps,
+  shadow_descriptors: ShadowDescriptors,
+  shadow_config: ShadowConfig,
+  shadow_frusta: Vec<Frustum>,
+  transparent: TransparentDraws,
+  post_process: PostProcess,
+  post_sets: [DescriptorSetHandle; HDR_IMAGES],
+  environment: Environment,
+  msaa: Msaa,
+  supported_samples: vk::SampleCountFlags,
+  pending_msaa: Option<Msaa>,
+  render_textures: HashMap<
+    TextureHandle,
+    (
+      TextureFramebuffer,
+      DescriptorSetHandle,
+      [DescriptorSetHandle; HDR_IMAGES],
+    ),
+  >,
+  camera_passes: Vec<CameraPass>,
   logical_device: ash::Device,
   draw_commands: BufferId,
   draw_count: BufferId,
@@ -65,6 +132,10 @@ pub struct Renderer {
   buffers_updated: Vec<usize>,
   shader_mem: HashMap<GraphicsPipelineHandle, (BufferMemory, BufferMemory, u32)>,
   descriptor_buffer: BufferId,
+  texture_files: Vec<(PathBuf, ImageId, ColorSpace, SamplerDescriptor)>,
+  texture_watcher: Option<FileWatcher<usize>>,
+  drawing: bool,
+  paused: bool,
 }
 
 impl Renderer {
@@ -74,29 +145,40 @@ impl Renderer {
     device: &Device,
     memory_manager: &mut MemoryManager,
     descriptor_manager: &mut DescriptorManager,
-    surface: &Surface,
-    window_config: &WindowConfig,
+    target: OutputTarget,
     pools: &mut Pools,
     config: &GraphicsConfig,
-  ) -> Result<(Self, PipelineManager), Error> {
+  ) -> Result<(Self, PipelineManager, TextureManager), Error> {
     let logical_device = device.get_device();
 
-    let format = surface
-      .get_formats(instance.get_physical_device())?
-      .first()
-      .ok_or(RendererInitError::FormatMissing)?
-      .format;
-    let render_pass = render_pass::init_render_pass(logical_device, format)?;
+    let (format, window_layout) = match target {
+      OutputTarget::Window(surface, _, config) => (
+        select_surface_format(
+          config.surface_format,
+          &surface.get_formats(instance.get_physical_device())?,
+        )
+        .ok_or(RendererInitError::FormatMissing)?
+        .format,
+        vk::ImageLayout::PRESENT_SRC_KHR,
+      ),
+      OutputTarget::Headless(_) => (HEADLESS_FORMAT, vk::ImageLayout::TRANSFER_SRC_OPTIMAL),
+    };
+    let supported_samples = supported_samples(instance);
+    let msaa = config.msaa.clamp(supported_samples);
+    let render_passes =
+      RenderPasses::init(logical_device, format, window_layout, msaa.sample_count())?;
 
-    let swapchain = SwapChain::init(
+    let output = Output::init(
+      &target,
       instance,
       device,
-      surface,
       memory_manager,
-      window_config,
       pools,
-      render_pass,
+      &render_passes,
     )?;
+    let captures = Captures::new(logical_device, memory_manager, pools)?;
+    let shadow_maps = ShadowMaps::init(logical_device, memory_manager, &config.shadows)?;
+    let transparent = TransparentDraws::init(memory_manager)?;
 
     let draw_commands = memory_manager.create_advanced_buffer(
       vk::BufferUsageFlags::INDIRECT_BUFFER,
@@ -115,21 +197,33 @@ impl Renderer {
     )?;
 
     let camera_mem = memory_manager
-      .reserve_buffer_mem(buffer, size_of::<Mat4>() * 2)
+      .reserve_buffer_mem(buffer, size_of::<CameraData>())
       .unwrap();
     let light_info_mem = memory_manager
       .reserve_buffer_mem(buffer, size_of::<LightInfo>())
       .unwrap();
     let point_light_mem = memory_manager
-      .reserve_buffer_mem(buffer, size_of::<PointLight>() * 10)
+      .reserve_buffer_mem(buffer, size_of::<PointLight>() * 16)
       .unwrap();
     let spot_light_mem = memory_manager
-      .reserve_buffer_mem(buffer, size_of::<SpotLight>() * 10)
+      .reserve_buffer_mem(buffer, size_of::<SpotLight>() * 16)
+      .unwrap();
+    let material_mem = memory_manager
+      .reserve_buffer_mem(buffer, size_of::<GpuMaterial>() * 64)
+      .unwrap();
+    let cluster_camera_mem = memory_manager
+      .reserve_buffer_mem(buffer, size_of::<ClusterCamera>() * 4)
+      .unwrap();
+    let cluster_mem = memory_manager
+      .reserve_buffer_mem(buffer, size_of::<Cluster>() * CLUSTER_COUNT as usize)
+      .unwrap();
+    let light_index_mem = memory_manager
+      .reserve_buffer_mem(buffer, size_of::<u32>() * 1024)
       .unwrap();
     let descriptor = vec![
       DescriptorInfo {
-        stage: vk::ShaderStageFlags::VERTEX,
-        r#type: DescriptorType::UniformBuffer(camera_mem),
+        stage: vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+        r#type: DescriptorType::StorageBuffer(camera_mem),
       },
       DescriptorInfo {
         stage: vk::ShaderStageFlags::FRAGMENT,
@@ -143,57 +237,230 @@ impl Renderer {
         stage: vk::ShaderStageFlags::FRAGMENT,
         r#type: DescriptorType::StorageBuffer(spot_light_mem),
       },
+      DescriptorInfo {
+        stage: vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+        r#type: DescriptorType::StorageBuffer(material_mem),
+      },
+      DescriptorInfo {
+        stage: vk::ShaderStageFlags::FRAGMENT,
+        r#type: DescriptorType::StorageBuffer(cluster_camera_mem),
+      },
+      DescriptorInfo {
+        stage: vk::ShaderStageFlags::FRAGMENT,
+        r#type: DescriptorType::StorageBuffer(cluster_mem),
+      },
+      DescriptorInfo {
+        stage: vk::ShaderStageFlags::FRAGMENT,
+        r#type: DescriptorType::StorageBuffer(light_index_mem),
+      },
     ];
     descriptor_manager
       .create_descriptor_set(descriptor, memory_manager)
       .expect("Failed to create default descriptor set");
 
     let mut textures = Vec::new();
-    for (bytes, interpolation) in &config.textures {
-      textures.push(memory_manager.create_texture_image(*interpolation, bytes)?);
+    let mut texture_files = Vec::new();
+    let mut render_textures = Vec::new();
+    for (i, source) in config.textures.iter().enumerate() {
+      match source {
+        TextureSource::Image(bytes, color_space, sampler) => {
+          textures.push(memory_manager.create_texture_image(*sampler, *color_space, bytes)?);
+        }
+        TextureSource::File(path, color_space, sampler) => {
+          let image =
+            memory_manager.create_texture(*sampler, TextureData::load_file(path, *color_space)?)?;
+          textures.push(image);
+          texture_files.push((path.clone(), image, *color_space, *sampler));
+        }
+        TextureSource::RenderTarget(width, height, interpolation) => {
+          let framebuffer = TextureFramebuffer::create(
+            logical_device,
+            format,
+            *interpolation,
+            &render_passes,
+            memory_manager,
+            vk::Extent2D {
+              width: *width,
+              height: *height,
+            },
+          )?;
+          textures.push(framebuffer.image());
+          render_textures.push((TextureHandle(i as u32), framebuffer));
+        }
+      }
     }
 
+    // the texture array is updated after bind, which has its own limits
+    let mut indexing = vk::PhysicalDeviceDescriptorIndexingProperties::default();
+    let mut properties = vk::PhysicalDeviceProperties2::default().push_next(&mut indexing);
+    unsafe {
+      instance
+        .get_instance()
+        .get_physical_device_properties2(instance.get_physical_device(), &mut properties)
+    };
+    let limits = properties.properties.limits;
+    let max_textures = config.max_textures.min(
+      limits
+        .max_per_stage_descriptor_samplers
+        .min(limits.max_per_stage_descriptor_sampled_images)
+        .min(limits.max_descriptor_set_samplers)
+        .min(limits.max_descriptor_set_sampled_images)
+        .min(indexing.max_per_stage_descriptor_update_after_bind_samplers)
+        .min(indexing.max_per_stage_descriptor_update_after_bind_sampled_images)
+        .min(indexing.max_descriptor_set_update_after_bind_samplers)
+        .min(indexing.max_descriptor_set_update_after_bind_sampled_images),
+    );
+    let texture_manager = TextureManager::new(
+      textures,
+      render_textures.iter().map(|(handle, _)| *handle).collect(),
+      max_textures,
+      output.frames_in_flight(),
+    );
+
     let descriptor = vec![DescriptorInfo {
       stage: vk::ShaderStageFlags::FRAGMENT,
-      r#type: DescriptorType::Sampler(textures),
+      r#type: DescriptorType::Sampler(texture_manager.images()),
     }];
     descriptor_manager
       .create_descriptor_set(descriptor, memory_manager)
       .expect("Failed to create default descriptor set");
 
-    let images = swapchain.attachments();
+    descriptor_manager
+      .create_descriptor_set(
+        attachment_descriptors(output.scene().attachments()),
+        memory_manager,
+      )
+      .expect("Failed to create attachment descriptor set");
+
+    let shadow_info_mem = memory_manager
+      .reserve_buffer_mem(buffer, size_of::<ShadowInfo>())
+      .unwrap();
+    let shadow_matrices_mem = memory_manager
+      .reserve_buffer_mem(
+        buffer,
+        size_of::<glam::Mat4>() * config.shadows.layers() as usize,
+      )
+      .unwrap();
     let descriptor = vec![
       DescriptorInfo {
         stage: vk::ShaderStageFlags::FRAGMENT,
-        r#type: DescriptorType::InputAttachment(images[0]),
+        r#type: DescriptorType::UniformBuffer(shadow_info_mem),
       },
       DescriptorInfo {
-        stage: vk::ShaderStageFlags::FRAGMENT,
-        r#type: DescriptorType::InputAttachment(images[1]),
+        stage: vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+        r#type: DescriptorType::StorageBuffer(shadow_matrices_mem),
       },
       DescriptorInfo {
         stage: vk::ShaderStageFlags::FRAGMENT,
-        r#type: DescriptorType::InputAttachment(images[2]),
+        r#type: DescriptorType::Sampler(vec![shadow_maps.image()]),
       },
     ];
-    descriptor_manager
+    let (shadow_set, shadow_handles) = descriptor_manager
       .create_descriptor_set(descriptor, memory_manager)
-      .expect("Failed to create attachment descriptor set");
+      .expect("Failed to create shadow descriptor set");
+    let shadow_descriptors = ShadowDescriptors {
+      set: shadow_set,
+      info: shadow_handles[0],
+      matrices: shadow_handles[1],
+    };
 
-    let mut pipeline_manager = PipelineManager::init(logical_device, render_pass, &swapchain);
+    let post_info_mem = memory_manager
+      .reserve_buffer_mem(buffer, size_of::<PostProcessInfo>())
+      .unwrap();
+    let post_sets = create_post_sets(
+      descriptor_manager,
+      memory_manager,
+      &post_info_mem,
+      output.scene().hdr_images(),
+    )
+    .expect("Failed to create post-processing descriptor set");
+
+    let environment_info_mem = memory_manager
+      .reserve_buffer_mem(buffer, size_of::<EnvironmentInfo>())
+      .unwrap();
+    let environment = Environment::init(environment_info_mem, memory_manager, descriptor_manager)?;
+
+    let render_textures = render_textures
+      .into_iter()
+      .map(|(handle, framebuffer)| {
+        let (set, _) = descriptor_manager
+          .create_descriptor_set(
+            attachment_descriptors(framebuffer.scene().attachments()),
+            memory_manager,
+          )
+          .expect("Failed to create attachment descriptor set");
+        let post_sets = create_post_sets(
+          descriptor_manager,
+          memory_manager,
+          &post_info_mem,
+          framebuffer.scene().hdr_images(),
+        )
+        .expect("Failed to create post-processing descriptor set");
+        (handle, (framebuffer, set, post_sets))
+      })
+      .collect();
+
+    let mut pipeline_manager = PipelineManager::init(
+      logical_device,
+      render_passes.main(),
+      shadow_maps.render_pass(),
+      render_passes.post(),
+      render_passes.output(RenderTarget::Window),
+      render_passes.samples(),
+    );
 
     let world = GraphicsPipelineBuilder::new()
       .add_descriptor_sets(vec![DEFAULT_DESCRIPTOR_SET, TEXTURE_DESCRIPTOR_SET]);
     pipeline_manager.build_graphics_pipeline(world, descriptor_manager);
     let light = GraphicsPipelineBuilder::new()
       .rendering_stage(RenderingStage::Light)
-      .add_descriptor_sets(vec![DEFAULT_DESCRIPTOR_SET, ATTACHMENT_DESCRIPTOR_SET]);
+      .add_descriptor_sets(vec![
+        DEFAULT_DESCRIPTOR_SET,
+        ATTACHMENT_DESCRIPTOR_SET,
+        shadow_descriptors.set,
+        environment.set(),
+      ]);
     pipeline_manager.build_graphics_pipeline(light, descriptor_manager);
+    let shadow = GraphicsPipelineBuilder::new()
+      .rendering_stage(RenderingStage::Shadow)
+      .add_descriptor_set(shadow_descriptors.set);
+    pipeline_manager.build_graphics_pipeline(shadow, descriptor_manager);
+    let transparent_pipeline = GraphicsPipelineBuilder::new()
+      .rendering_stage(RenderingStage::Transparent)
+      .add_descriptor_sets(vec![
+        DEFAULT_DESCRIPTOR_SET,
+        TEXTURE_DESCRIPTOR_SET,
+        shadow_descriptors.set,
+        environment.set(),
+      ]);
+    pipeline_manager.build_graphics_pipeline(transparent_pipeline, descriptor_manager);
+    let post_process = PostProcess::init(
+      &config.post_process,
+      post_info_mem,
+      post_sets[0],
+      !is_srgb(format),
+      &mut pipeline_manager,
+      descriptor_manager,
+    )?;
 
     Ok((
       Self {
-        render_pass,
-        swapchain,
+        render_passes,
+        output,
+        captures,
+        shadow_maps,
+        shadow_descriptors,
+        shadow_config: config.shadows.clone(),
+        shadow_frusta: Vec::new(),
+        transparent,
+        post_process,
+        post_sets,
+        environment,
+        msaa,
+        supported_samples,
+        pending_msaa: None,
+        render_textures,
+        camera_passes: Vec::new(),
         logical_device: logical_device.clone(),
         draw_commands,
         draw_count,
@@ -201,23 +468,128 @@ impl Renderer {
         buffers_updated: Vec::new(),
         shader_mem: HashMap::new(),
         descriptor_buffer: buffer,
+        texture_files,
+        texture_watcher: None,
+        drawing: false,
+        paused: false,
       },
       pipeline_manager,
+      texture_manager,
     ))
   }
 
   pub(crate) fn cleanup(&self) {
-    unsafe {
-      self
-        .logical_device
-        .destroy_render_pass(self.render_pass, None);
+    self.render_passes.cleanup(&self.logical_device);
+    for (framebuffer, _, _) in self.render_textures.values() {
+      framebuffer.cleanup(&self.logical_device);
     }
-    self.swapchain.cleanup(&self.logical_device);
+    self.output.cleanup(&self.logical_device);
+    self.captures.cleanup(&self.logical_device);
+    self.shadow_maps.cleanup(&self.logical_device);
   }
 
+  /// Nothing is recorded or drawn this frame if the output is paused or out of date
   #[inline]
-  pub(crate) fn wait_for_draw_start(&self) {
-    self.swapchain.wait_for_draw_start(&self.logical_device);
+  pub(crate) fn wait_for_draw_start(&mut self) {
+    self.drawing = !self.paused && self.output.wait_for_draw_start(&self.logical_device);
+  }
+
+  /// Whether the current frame is drawn
+  #[inline]
+  pub(crate) fn is_drawing(&self) -> bool {
+    self.drawing
+  }
+
+  #[inline]
+  pub(crate) fn frames_in_flight(&self) -> usize {
+    self.output.frames_in_flight()
+  }
+
+  /// Recreates the swapchain if the window size changed or it is out of date.
+  /// Rendering is paused while the window has no area, e.g. while it is minimized
+  #[allow(clippy::too_many_arguments)]
+  pub(crate) fn resize_output(
+    &mut self,
+    instance: &InstanceDevice,
+    device: &Device,
+    surface: &Surface,
+    pools: &mut Pools,
+    memory_manager: &mut MemoryManager,
+    descriptor_manager: &mut DescriptorManager,
+    size: vk::Extent2D,
+  ) -> Result<(), Error> {
+    let Output::Window(swapchain) = &mut self.output else {
+      return Ok(());
+    };
+
+    self.paused = size.width == 0 || size.height == 0;
+    if self.paused || !swapchain.needs_recreation(size) {
+      return Ok(());
+    }
+
+    if !swapchain.recreate(
+      instance,
+      device,
+      surface,
+      memory_manager,
+      pools,
+      &self.render_passes,
+      size,
+    )? {
+      self.paused = true;
+      return Ok(());
+    }
+
+    // the scene images kept their ids, only the views in the descriptors changed
+    let scene = swapchain.scene();
+    for image in scene.attachments().iter().chain(scene.hdr_images()) {
+      descriptor_manager.rewrite_image(*image, memory_manager);
+    }
+    self.buffers_updated.clear();
+    Ok(())
+  }
+
+  /// Reads the last frame rendered to `RenderTarget::Window` back into memory, blocks until the gpu finished it.
+  /// Only available when rendering headless
+  pub fn read_pixels(&self, memory_manager: &MemoryManager) -> Result<RgbaImage, Error> {
+    match &self.output {
+      Output::Headless(headless) => headless.read_pixels(&self.logical_device, memory_manager),
+      Output::Window(_) => Err(ReadbackError::NotHeadless.into()),
+    }
+  }
+
+  #[inline]
+  pub fn is_headless(&self) -> bool {
+    matches!(self.output, Output::Headless(_))
+  }
+
+  /// Recreates the swapchain with the mode before the next frame, does nothing when rendering headless
+  #[inline]
+  pub fn set_present_mode(&mut self, present_mode: PresentMode) {
+    if let Output::Window(swapchain) = &mut self.output {
+      swapchain.set_present_mode(present_mode);
+    }
+  }
+
+  /// Switches between `PresentMode::Fifo` and `PresentMode::Immediate`
+  #[inline]
+  pub fn set_vsync(&mut self, vsync: bool) {
+    self.set_present_mode(if vsync {
+      PresentMode::Fifo
+    } else {
+      PresentMode::Immediate
+    });
+  }
+
+  /// Whether the window waits for the vertical blank, false when rendering headless
+  pub fn vsync(&self) -> bool {
+    match &self.output {
+      Output::Window(swapchain) => matches!(
+        swapchain.present_mode(),
+        vk::PresentModeKHR::FIFO | vk::PresentModeKHR::FIFO_RELAXED
+      ),
+      Output::Headless(_) => false,
+    }
   }
 
   pub(crate) fn record_command_buffer(
@@ -227,6 +599,14 @@ impl Renderer {
     memory_manager: &mut MemoryManager,
     model_manager: &ModelManager,
   ) -> Result<(), vk::Result> {
+    if !self.drawing {
+      return Ok(());
+    }
+
+    if let Err(err) = self.post_process.update_info(memory_manager) {
+      error!("Failed to update post-processing settings: {err}");
+    }
+
     //check for invalidations
     let buffer_ids = [
       self.descriptor_buffer,
@@ -237,87 +617,464 @@ impl Renderer {
       model_manager.instance_buffer_id(),
     ];
     if memory_manager.buffer_reallocated(&buffer_ids)
+      || memory_manager.buffer_reallocated(&self.transparent.buffer_ids())
       || pipeline_manager.graphics_changed()
       || descriptor_manager.descriptor_changed()
     {
       self.buffers_updated.clear();
     }
 
-    if self
-      .buffers_updated
-      .contains(&self.swapchain.current_frame())
-    {
+    if self.buffers_updated.contains(&self.output.current_frame()) {
       return Ok(());
     }
 
     let buffer = self
-      .swapchain
-      .record_command_buffer_start(&self.logical_device, self.render_pass)?;
+      .output
+      .record_command_buffer_start(&self.logical_device)?;
 
     model_manager.record_command_buffer(memory_manager, buffer, &self.logical_device);
 
+    self.record_shadow_passes(buffer, pipeline_manager, descriptor_manager, memory_manager);
+
+    let mut targets = self
+      .render_textures
+      .keys()
+      .map(|handle| RenderTarget::Texture(*handle))
+      .collect::<Vec<_>>();
+    targets.sort();
+    targets.push(RenderTarget::Window);
+
+    let camera_passes = self.camera_passes.clone();
+    for target in targets {
+      let (framebuffer, extent, attachment_set) = match target {
+        RenderTarget::Window => (
+          self.output.scene().buffer(),
+          self.output.get_extent(),
+          ATTACHMENT_DESCRIPTOR_SET,
+        ),
+        RenderTarget::Texture(handle) => {
+          let (framebuffer, set, _) = &self.render_textures[&handle];
+          (framebuffer.scene().buffer(), framebuffer.extent(), *set)
+        }
+      };
+      let full_area = vk::Rect2D {
+        offset: vk::Offset2D::default(),
+        extent,
+      };
+
+      let mut first = true;
+      for pass in camera_passes.iter().filter(|pass| pass.target == target) {
+        let Some((offset, size)) = pass
+          .viewport
+          .physical_rect(glam::UVec2::new(extent.width, extent.height))
+        else {
+          continue;
+        };
+        let area = vk::Rect2D {
+          offset: vk::Offset2D {
+            x: offset.x as i32,
+            y: offset.y as i32,
+          },
+          extent: vk::Extent2D {
+            width: size.x,
+            height: size.y,
+          },
+        };
+
+        // the first pass clears the whole target, so uncovered areas are not left undefined
+        begin_render_pass(
+          &self.logical_device,
+          buffer,
+          self.render_passes.get(first),
+          framebuffer,
+          if first { full_area } else { area },
+        );
+        first = false;
+
+        self.record_camera_pass(
+          buffer,
+          pass.camera,
+          area,
+          attachment_set,
+          pipeline_manager,
+          descriptor_manager,
+          memory_manager,
+          model_manager,
+        );
+      }
+
+      if first {
+        begin_render_pass(
+          &self.logical_device,
+          buffer,
+          self.render_passes.get(true),
+          framebuffer,
+          full_area,
+        );
+        unsafe {
+          self
+            .logical_device
+            .cmd_next_subpass(buffer, vk::SubpassContents::INLINE);
+          self
+            .logical_device
+            .cmd_next_subpass(buffer, vk::SubpassContents::INLINE);
+          self.logical_device.cmd_end_render_pass(buffer);
+        }
+      }
+
+      let (scene, output, post_sets) = match target {
+        RenderTarget::Window => (
+          self.output.scene(),
+          self.output.framebuffer(),
+          &self.post_sets,
+        ),
+        RenderTarget::Texture(handle) => {
+          let (framebuffer, _, post_sets) = &self.render_textures[&handle];
+          (framebuffer.scene(), framebuffer.buffer(), post_sets)
+        }
+      };
+      self.post_process.record(
+        &self.logical_device,
+        buffer,
+        pipeline_manager,
+        descriptor_manager,
+        &self.render_passes,
+        target,
+        scene,
+        output,
+        post_sets,
+      );
+    }
+
+    self
+      .output
+      .record_command_buffer_end(&self.logical_device, buffer, memory_manager)?;
+
+    self.buffers_updated.push(self.output.current_frame());
+    Ok(())
+  }
+
+  #[allow(clippy::too_many_arguments)]
+  fn record_camera_pass(
+    &mut self,
+    buffer: vk::CommandBuffer,
+    camera: u32,
+    area: vk::Rect2D,
+    attachment_set: DescriptorSetHandle,
+    pipeline_manager: &PipelineManager,
+    descriptor_manager: &DescriptorManager,
+    memory_manager: &mut MemoryManager,
+    model_manager: &ModelManager,
+  ) {
+    let viewport = vk::Viewport::default()
+      .x(area.offset.x as f32)
+      .y(area.offset.y as f32)
+      .width(area.extent.width as f32)
+      .height(area.extent.height as f32)
+      .min_depth(0.0)
+      .max_depth(1.0);
+
+    unsafe {
+      self.logical_device.cmd_set_viewport(buffer, 0, &[viewport]);
+      self.logical_device.cmd_set_scissor(buffer, 0, &[area]);
+    }
+
     for pipeline in pipeline_manager.graphics_pipelines() {
       unsafe {
         pipeline.bind(buffer, &self.logical_device, descriptor_manager);
+        pipeline.push_camera(buffer, &self.logical_device, camera);
+      }
+      self.draw_indirect(buffer, pipeline, memory_manager);
+    }
 
-        let draw_commands = memory_manager.get_vk_buffer(self.draw_commands).unwrap();
-        let draw_count = memory_manager.get_vk_buffer(self.draw_count).unwrap();
-        let (cmd_mem, count_mem, _) = self.shader_mem.entry(pipeline.id()).or_insert_with(|| {
-          (
-            memory_manager
-              .reserve_buffer_mem(
-                self.draw_commands,
-                10 * std::mem::size_of::<vk::DrawIndexedIndirectCommand>(),
-              )
-              .expect("Failed to reserve draw cmd mem"),
-            memory_manager
-              .reserve_buffer_mem(self.draw_count, 4)
-              .expect("Failed to reserve draw cmd mem"),
-            0,
-          )
-        });
-        let max_draw_count = cmd_mem.size() / 20;
+    unsafe {
+      self
+        .logical_device
+        .cmd_next_subpass(buffer, vk::SubpassContents::INLINE);
 
-        self.logical_device.cmd_draw_indexed_indirect_count(
+      let light_pipeline = pipeline_manager.light_pipeline();
+      light_pipeline.bind(buffer, &self.logical_device, descriptor_manager);
+      if attachment_set != ATTACHMENT_DESCRIPTOR_SET {
+        light_pipeline.rebind_descriptor_set(
           buffer,
-          draw_commands,
-          cmd_mem.offset() as u64,
-          draw_count,
-          count_mem.offset() as u64,
-          max_draw_count as u32,
-          std::mem::size_of::<vk::DrawIndexedIndirectCommand>() as u32,
+          &self.logical_device,
+          descriptor_manager,
+          ATTACHMENT_DESCRIPTOR_SET,
+          attachment_set,
         );
       }
-    }
+      light_pipeline.push_camera(buffer, &self.logical_device, camera);
 
-    unsafe {
+      self.logical_device.cmd_draw(buffer, 3, 1, 0, 0);
       self
         .logical_device
         .cmd_next_subpass(buffer, vk::SubpassContents::INLINE);
+
+      let transparent_pipeline = pipeline_manager.transparent_pipeline();
+      transparent_pipeline.bind(buffer, &self.logical_device, descriptor_manager);
+      transparent_pipeline.push_camera(buffer, &self.logical_device, camera);
     }
+    self
+      .transparent
+      .record(&self.logical_device, buffer, memory_manager, camera);
+    // the next pass draws from the model instances again
+    model_manager.record_command_buffer(memory_manager, buffer, &self.logical_device);
 
     unsafe {
-      pipeline_manager
-        .light_pipeline()
-        .bind(buffer, &self.logical_device, descriptor_manager);
+      self.logical_device.cmd_end_render_pass(buffer);
+    }
+  }
 
-      self.logical_device.cmd_draw(buffer, 3, 1, 0, 0);
+  /// Renders the casters of all graphics pipelines into every active shadow map layer
+  fn record_shadow_passes(
+    &mut self,
+    buffer: vk::CommandBuffer,
+    pipeline_manager: &PipelineManager,
+    descriptor_manager: &DescriptorManager,
+    memory_manager: &mut MemoryManager,
+  ) {
+    self
+      .shadow_maps
+      .record_start(&self.logical_device, buffer, memory_manager);
+
+    let shadow_pipeline = pipeline_manager.shadow_pipeline();
+    for layer in self.shadow_maps.layers().to_vec() {
+      self
+        .shadow_maps
+        .begin_layer(&self.logical_device, buffer, layer);
+      unsafe {
+        shadow_pipeline.bind(buffer, &self.logical_device, descriptor_manager);
+        shadow_pipeline.push_camera(buffer, &self.logical_device, layer);
+      }
+
+      // the draws of every pipeline share the vertex and instance layout
+      for pipeline in pipeline_manager.graphics_pipelines() {
+        self.draw_indirect(buffer, pipeline, memory_manager);
+      }
+
+      unsafe {
+        self.logical_device.cmd_end_render_pass(buffer);
+      }
+    }
+  }
+
+  /// Draws the instances assigned to `pipeline` with whatever pipeline is bound
+  fn draw_indirect(
+    &mut self,
+    buffer: vk::CommandBuffer,
+    pipeline: &GraphicsPipeline,
+    memory_manager: &mut MemoryManager,
+  ) {
+    let draw_commands = memory_manager.get_vk_buffer(self.draw_commands).unwrap();
+    let draw_count = memory_manager.get_vk_buffer(self.draw_count).unwrap();
+    let (cmd_mem, count_mem, _) = self.shader_mem.entry(pipeline.id()).or_insert_with(|| {
+      (
+        memory_manager
+          .reserve_buffer_mem(
+            self.draw_commands,
+            10 * std::mem::size_of::<vk::DrawIndexedIndirectCommand>(),
+          )
+          .expect("Failed to reserve draw cmd mem"),
+        memory_manager
+          .reserve_buffer_mem(self.draw_count, 4)
+          .expect("Failed to reserve draw cmd mem"),
+        0,
+      )
+    });
+    let max_draw_count = cmd_mem.size() / 20;
+
+    unsafe {
+      self.logical_device.cmd_draw_indexed_indirect_count(
+        buffer,
+        draw_commands,
+        cmd_mem.offset() as u64,
+        draw_count,
+        count_mem.offset() as u64,
+        max_draw_count as u32,
+        std::mem::size_of::<vk::DrawIndexedIndirectCommand>() as u32,
+      );
+    }
+  }
+
+  pub(crate) fn watch_texture_files(&mut self) {
+    let mut watcher = FileWatcher::new();
+    for (i, (path, _, _, _)) in self.texture_files.iter().enumerate() {
+      watcher.watch(path, i);
+    }
+    self.texture_watcher = Some(watcher);
+  }
+
+  /// Uploads texture files changed on disk, failed reloads keep the previous texture
+  pub(crate) fn reload_textures(
+    &mut self,
+    memory_manager: &mut MemoryManager,
+    descriptor_manager: &mut DescriptorManager,
+  ) {
+    let Some(watcher) = &mut self.texture_watcher else {
+      return;
+    };
+
+    for i in watcher.poll() {
+      let (path, image, color_space, sampler) = &self.texture_files[i];
+      let result = TextureData::load_file(path, *color_space)
+        .and_then(|texture| memory_manager.replace_texture_image(*image, sampler, texture));
+
+      match result {
+        Ok(()) => {
+          descriptor_manager.rewrite_image(*image, memory_manager);
+          info!("Reloaded texture {}", path.display());
+        }
+        Err(err) => error!("Failed to reload texture {}: {err}", path.display()),
+      }
+    }
+  }
+
+  /// Re-records the command buffers if the passes changed
+  pub(crate) fn set_camera_passes(&mut self, camera_passes: Vec<CameraPass>) {
+    if self.camera_passes != camera_passes {
+      self.camera_passes = camera_passes;
+      self.buffers_updated.clear();
     }
+  }
 
+  /// The sample count in use, it might be lower than the requested one
+  #[inline]
+  pub fn msaa(&self) -> Msaa {
+    self.msaa
+  }
+
+  /// Rebuilds the scene render pass, its images and pipelines before the next frame.
+  /// Clamped to the sample counts the device supports
+  #[inline]
+  pub fn set_msaa(&mut self, msaa: Msaa) {
+    self.pending_msaa = Some(msaa);
+  }
+
+  /// Applies the sample count requested with `set_msaa`
+  pub(crate) fn apply_msaa(
+    &mut self,
+    pipeline_manager: &mut PipelineManager,
+    descriptor_manager: &mut DescriptorManager,
+    memory_manager: &mut MemoryManager,
+  ) -> Result<(), Error> {
+    let Some(msaa) = self.pending_msaa.take() else {
+      return Ok(());
+    };
+    let msaa = msaa.clamp(self.supported_samples);
+    if msaa == self.msaa {
+      return Ok(());
+    }
+
+    self.wait_for_idle();
     self
-      .swapchain
-      .record_command_buffer_end(&self.logical_device, buffer)?;
+      .render_passes
+      .set_samples(&self.logical_device, msaa.sample_count())?;
 
-    self.buffers_updated.push(self.swapchain.current_frame());
+    let output = self.output.scene_mut();
+    let scenes = std::iter::once(output).chain(
+      self
+        .render_textures
+        .values_mut()
+        .map(|(framebuffer, _, _)| framebuffer.scene_mut()),
+    );
+    for scene in scenes {
+      let extent = scene.extent();
+      scene.resize(
+        &self.logical_device,
+        &self.render_passes,
+        memory_manager,
+        extent,
+      )?;
+      // the images kept their ids, only the views in the descriptors changed
+      for image in scene.attachments().iter().chain(scene.hdr_images()) {
+        descriptor_manager.rewrite_image(*image, memory_manager);
+      }
+    }
+
+    pipeline_manager.set_scene_render_pass(
+      self.render_passes.main(),
+      self.render_passes.samples(),
+      descriptor_manager,
+    )?;
+    self.msaa = msaa;
+    self.buffers_updated.clear();
     Ok(())
   }
 
+  #[inline]
+  pub fn post_process(&self) -> &PostProcessConfig {
+    self.post_process.config()
+  }
+
+  /// Takes effect with the next frame
+  pub fn set_post_process(&mut self, config: PostProcessConfig) {
+    if self.post_process.set_config(config) {
+      self.buffers_updated.clear();
+    }
+  }
+
+  /// Custom post-processing pipelines add this set, it holds the settings (binding 0), the image
+  /// the pass reads (binding 1) and the hdr scene (binding 2)
+  #[inline]
+  pub fn post_descriptor_set(&self) -> DescriptorSetHandle {
+    self.post_process.set()
+  }
+
+  /// Regenerates the environment maps if the skybox changed
+  pub(crate) fn update_environment(
+    &mut self,
+    skybox: Option<&Skybox>,
+    memory_manager: &mut MemoryManager,
+    descriptor_manager: &mut DescriptorManager,
+  ) -> Result<(), Error> {
+    self
+      .environment
+      .update(skybox, memory_manager, descriptor_manager)
+  }
+
+  #[inline]
+  pub(crate) fn shadow_descriptors(&self) -> ShadowDescriptors {
+    self.shadow_descriptors
+  }
+
+  #[inline]
+  pub(crate) fn shadow_config(&self) -> &ShadowConfig {
+    &self.shadow_config
+  }
+
+  /// Re-records the command buffers if other shadow map layers have to be rendered,
+  /// `frusta` are the views of the layers casters are culled against
+  pub(crate) fn set_shadows(&mut self, layers: Vec<u32>, frusta: Vec<Frustum>) {
+    if self.shadow_maps.set_layers(layers) {
+      self.buffers_updated.clear();
+    }
+    self.shadow_frusta = frusta;
+  }
+
+  #[inline]
+  pub(crate) fn shadow_frusta(&self) -> &[Frustum] {
+    &self.shadow_frusta
+  }
+
+  /// The size in pixels of the given target, `None` if the target does not exist
+  pub(crate) fn target_extent(&self, target: RenderTarget) -> Option<vk::Extent2D> {
+    match target {
+      RenderTarget::Window => Some(self.output.get_extent()),
+      RenderTarget::Texture(handle) => self
+        .render_textures
+        .get(&handle)
+        .map(|(framebuffer, _, _)| framebuffer.extent()),
+    }
+  }
+
   pub(crate) fn update_draw_buffer(
     &mut self,
     memory_manager: &mut MemoryManager,
     instances: HashMap<ModelHandle, HashMap<GraphicsPipelineHandle, Vec<InstanceData>>>,
     model_manager: &mut ModelManager,
   ) {
+    self.remove_commands(memory_manager, model_manager);
+
     let cmd_new = model_manager
       .update_draw_buffer(
         self.draw_commands,
@@ -377,8 +1134,154 @@ impl Renderer {
       memory_manager.write_to_buffer_direct(self.draw_commands, write_data_slice, &write_info);
   }
 
+  /// Frees the command slots of removed models by moving the last command of the shader into
+  /// them, so the draw count does not grow with every removed model
+  fn remove_commands(&mut self, memory_manager: &mut MemoryManager, model_manager: &ModelManager) {
+    let mut freed: HashMap<GraphicsPipelineHandle, Vec<u64>> = HashMap::new();
+    self.commands.retain(|model, shaders| {
+      if model_manager.contains(*model) {
+        return true;
+      }
+      for (shader, (_, offset)) in shaders.iter() {
+        freed.entry(*shader).or_default().push(*offset);
+      }
+      false
+    });
+
+    let cmd_size = std::mem::size_of::<vk::DrawIndexedIndirectCommand>() as u64;
+    let mut write_info = Vec::new();
+    let mut write_data = Vec::new();
+
+    for (shader, mut offsets) in freed {
+      let Some((cmd_mem, count_mem, count)) = self.shader_mem.get_mut(&shader) else {
+        continue;
+      };
+
+      // from the back, so the last command is never a freed one
+      offsets.sort_unstable_by(|a, b| b.cmp(a));
+      for offset in offsets {
+        *count -= 1;
+        let last = cmd_mem.offset() as u64 + *count as u64 * cmd_size;
+        if offset == last {
+          continue;
+        }
+
+        let moved = self
+          .commands
+          .values_mut()
+          .filter_map(|shaders| shaders.get_mut(&shader))
+          .find(|(_, cmd_offset)| *cmd_offset == last);
+        if let Some((cmd, cmd_offset)) = moved {
+          *cmd_offset = offset;
+          write_info.push(vk::BufferCopy {
+            src_offset: write_data.len() as u64 * cmd_size,
+            dst_offset: offset,
+            size: cmd_size,
+          });
+          write_data.push(*cmd);
+        }
+      }
+
+      let _ = memory_manager.write_to_buffer(count_mem, &[*count]);
+    }
+
+    if !write_data.is_empty() {
+      let _ = memory_manager.write_to_buffer_direct(self.draw_commands, &write_data, &write_info);
+    }
+  }
+
+  /// `cameras` are the view matrices and blended instances in the order of the camera descriptor
+  pub(crate) fn update_transparent(
+    &mut self,
+    memory_manager: &mut MemoryManager,
+    model_manager: &ModelManager,
+    cameras: Vec<(glam::Mat4, Vec<TransparentInstance>)>,
+  ) {
+    match self
+      .transparent
+      .update(memory_manager, model_manager, cameras)
+    {
+      Ok(true) => self.buffers_updated.clear(),
+      Ok(false) => {}
+      Err(err) => error!("Failed to update transparent draws: {err}"),
+    }
+  }
+
   #[inline]
   pub(crate) fn draw_frame(&mut self) {
-    self.swapchain.draw_frame(&self.logical_device);
+    if !self.drawing {
+      return;
+    }
+
+    self.drawing = false;
+    self
+      .output
+      .draw_frame(&self.logical_device, self.captures.take_submit());
   }
+
+  /// Copies the current window image after the frame is drawn, it is saved once the gpu finished it
+  pub(crate) fn capture(
+    &mut self,
+    memory_manager: &mut MemoryManager,
+    screenshots: &mut Screenshots,
+    paths: Vec<PathBuf>,
+  ) -> Result<(), Error> {
+    let source = self.output.capture_source(memory_manager);
+    self.captures.record(
+      &self.logical_device,
+      memory_manager,
+      screenshots,
+      source,
+      paths,
+    )
+  }
+
+  #[inline]
+  pub(crate) fn wait_for_idle(&self) {
+    unsafe { self.logical_device.device_wait_idle() }.expect("Unable to wait for device idle");
+  }
+
+  /// Saves the captures the gpu finished, `wait` blocks until one is finished
+  #[inline]
+  pub(crate) fn collect_captures(
+    &mut self,
+    memory_manager: &MemoryManager,
+    screenshots: &mut Screenshots,
+    wait: bool,
+  ) {
+    self
+      .captures
+      .collect(&self.logical_device, memory_manager, screenshots, wait);
+  }
+}
+
+/// Sample counts usable for the scene, lighting every sample needs sample rate shading
+fn supported_samples(instance: &InstanceDevice) -> vk::SampleCountFlags {
+  let physical_device = instance.get_physical_device();
+  let features = unsafe {
+    instance
+      .get_instance()
+      .get_physical_device_features(physical_device)
+  };
+  if features.sample_rate_shading != vk::TRUE {
+    return vk::SampleCountFlags::TYPE_1;
+  }
+
+  let limits = unsafe {
+    instance
+      .get_instance()
+      .get_physical_device_properties(physical_device)
+  }
+  .limits;
+  limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts
+}
+
+fn attachment_descriptors(images: &[ImageId]) -> Vec<DescriptorInfo> {
+  images
+    .iter()
+    .map(|image| DescriptorInfo {
+      stage: vk::ShaderStageFlags::FRAGMENT,
+      r#type: DescriptorType::InputAttachment(*image),
+    })
+    .collect()
 }
@@ -0,0 +1,187 @@
+use anyhow::Error;
+use ash::vk;
+use gravitron_window::config::WindowConfig;
+
+use crate::{
+  config::SwapchainConfig, device::Device, instance::InstanceDevice, memory::MemoryManager,
+  pipeline::pools::Pools, surface::Surface,
+};
+
+use super::{
+  capture::CaptureSource,
+  framebuffer::SceneFramebuffer,
+  headless::{Headless, HEADLESS_FORMAT},
+  render_pass::RenderPasses,
+  swapchain::SwapChain,
+};
+
+/// What `RenderTarget::Window` is created for
+pub(crate) enum OutputTarget<'a> {
+  Window(&'a Surface, &'a WindowConfig, &'a SwapchainConfig),
+  Headless(vk::Extent2D),
+}
+
+/// The images `RenderTarget::Window` cameras render to
+pub(crate) enum Output {
+  Window(SwapChain),
+  Headless(Headless),
+}
+
+impl Output {
+  pub fn init(
+    target: &OutputTarget,
+    instance: &InstanceDevice,
+    device: &Device,
+    memory_manager: &mut MemoryManager,
+    pools: &mut Pools,
+    render_passes: &RenderPasses,
+  ) -> Result<Self, Error> {
+    Ok(match target {
+      OutputTarget::Window(surface, window_config, config) => Output::Window(SwapChain::init(
+        instance,
+        device,
+        surface,
+        memory_manager,
+        window_config,
+        config,
+        pools,
+        render_passes,
+      )?),
+      OutputTarget::Headless(extent) => Output::Headless(Headless::init(
+        device,
+        memory_manager,
+        pools,
+        render_passes,
+        *extent,
+      )?),
+    })
+  }
+
+  #[inline]
+  pub fn get_extent(&self) -> vk::Extent2D {
+    match self {
+      Output::Window(swapchain) => swapchain.get_extent(),
+      Output::Headless(headless) => headless.get_extent(),
+    }
+  }
+
+  #[inline]
+  pub fn cleanup(&self, logical_device: &ash::Device) {
+    match self {
+      Output::Window(swapchain) => swapchain.cleanup(logical_device),
+      Output::Headless(headless) => headless.cleanup(logical_device),
+    }
+  }
+
+  /// Returns false if the frame can not be drawn
+  #[inline]
+  pub fn wait_for_draw_start(&mut self, device: &ash::Device) -> bool {
+    match self {
+      Output::Window(swapchain) => swapchain.wait_for_draw_start(device),
+      Output::Headless(headless) => {
+        headless.wait_for_draw_start(device);
+        true
+      }
+    }
+  }
+
+  #[inline]
+  pub fn record_command_buffer_start(
+    &self,
+    device: &ash::Device,
+  ) -> Result<vk::CommandBuffer, vk::Result> {
+    match self {
+      Output::Window(swapchain) => swapchain.record_command_buffer_start(device),
+      Output::Headless(headless) => headless.record_command_buffer_start(device),
+    }
+  }
+
+  /// The framebuffer of the output pass
+  #[inline]
+  pub fn framebuffer(&self) -> vk::Framebuffer {
+    match self {
+      Output::Window(swapchain) => swapchain.framebuffer(),
+      Output::Headless(headless) => headless.framebuffer(),
+    }
+  }
+
+  #[inline]
+  pub fn record_command_buffer_end(
+    &self,
+    device: &ash::Device,
+    buffer: vk::CommandBuffer,
+    memory_manager: &MemoryManager,
+  ) -> Result<(), vk::Result> {
+    match self {
+      Output::Window(swapchain) => swapchain.record_command_buffer_end(device, buffer),
+      Output::Headless(headless) => {
+        headless.record_command_buffer_end(device, buffer, memory_manager)
+      }
+    }
+  }
+
+  #[inline]
+  pub fn draw_frame(
+    &mut self,
+    logical_device: &ash::Device,
+    capture: Option<(vk::CommandBuffer, vk::Fence)>,
+  ) {
+    match self {
+      Output::Window(swapchain) => swapchain.draw_frame(logical_device, capture),
+      Output::Headless(headless) => headless.draw_frame(logical_device, capture),
+    }
+  }
+
+  /// The image of the current frame and the layout the render pass leaves it in
+  pub fn capture_source(&self, memory_manager: &MemoryManager) -> CaptureSource {
+    match self {
+      Output::Window(swapchain) => CaptureSource {
+        image: swapchain.image(),
+        layout: vk::ImageLayout::PRESENT_SRC_KHR,
+        format: swapchain.format(),
+        extent: swapchain.get_extent(),
+      },
+      Output::Headless(headless) => CaptureSource {
+        image: memory_manager
+          .get_vk_image(headless.image())
+          .expect("Failed to get headless image"),
+        layout: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        format: HEADLESS_FORMAT,
+        extent: headless.get_extent(),
+      },
+    }
+  }
+
+  /// Frames that may still be executed by the gpu while the next one is recorded
+  #[inline]
+  pub fn frames_in_flight(&self) -> usize {
+    match self {
+      Output::Window(swapchain) => swapchain.frames_in_flight(),
+      Output::Headless(_) => 1,
+    }
+  }
+
+  #[inline]
+  pub fn current_frame(&self) -> usize {
+    match self {
+      Output::Window(swapchain) => swapchain.current_frame(),
+      Output::Headless(_) => 0,
+    }
+  }
+
+  #[inline]
+  pub fn scene(&self) -> &SceneFramebuffer {
+    match self {
+      Output::Window(swapchain) => swapchain.scene(),
+      Output::Headless(headless) => headless.scene(),
+    }
+  }
+
+  #[inline]
+  pub fn scene_mut(&mut self) -> &mut SceneFramebuffer {
+    match self {
+      Output::Window(swapchain) => swapchain.scene_mut(),
+      Output::Headless(headless) => headless.scene_mut(),
+    }
+  }
+}
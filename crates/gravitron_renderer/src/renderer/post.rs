@@ -0,0 +1,382 @@
+use anyhow::Error;
+use ash::vk;
+
+use crate::{
+  config::PostProcessConfig,
+  ecs::components::camera::RenderTarget,
+  error::RendererInitError,
+  memory::{
+    types::{BufferMemory, ImageId},
+    MemoryManager,
+  },
+  pipeline::{
+    descriptor::{DescriptorInfo, DescriptorSetHandle, DescriptorType},
+    graphics::{stage::RenderingStage, GraphicsPipelineBuilder},
+    manager::GraphicsPipelineHandle,
+    DescriptorManager, PipelineManager,
+  },
+};
+
+use super::{
+  framebuffer::{begin_render_pass, SceneFramebuffer, HDR_IMAGES},
+  render_pass::RenderPasses,
+  resources::post_process::PostProcessInfo,
+};
+
+/// A fullscreen pass of the post-processing chain
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum PostStep {
+  /// Keeps the colors above the bloom threshold
+  BloomThreshold,
+  /// Gaussian blur along x (0) or y (1)
+  BloomBlur(u32),
+  /// Adds the blurred bright colors to the scene
+  BloomComposite,
+  Tonemap,
+  /// The user post-processing pipeline with this index in build order
+  Custom(usize),
+  Fxaa,
+  Output,
+}
+
+/// `source` and `destination` index the hdr images of the target, the output pass writes into the target instead
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct PostPass {
+  pub step: PostStep,
+  pub source: usize,
+  pub destination: Option<usize>,
+}
+
+/// The passes from the hdr scene to the target
+pub(crate) fn post_passes(bloom: bool, custom: usize, fxaa: bool) -> Vec<PostPass> {
+  let mut steps = Vec::new();
+  if bloom {
+    steps.extend([
+      PostStep::BloomThreshold,
+      PostStep::BloomBlur(0),
+      PostStep::BloomBlur(1),
+      PostStep::BloomComposite,
+    ]);
+  }
+  steps.push(PostStep::Tonemap);
+  steps.extend((0..custom).map(PostStep::Custom));
+  if fxaa {
+    steps.push(PostStep::Fxaa);
+  }
+
+  let mut passes = Vec::new();
+  let mut source = 0;
+  for step in steps {
+    // the first image keeps the scene, the other two take turns
+    let destination = if source == 1 { 2 } else { 1 };
+    passes.push(PostPass {
+      step,
+      source,
+      destination: Some(destination),
+    });
+    source = destination;
+  }
+  passes.push(PostPass {
+    step: PostStep::Output,
+    source,
+    destination: None,
+  });
+  passes
+}
+
+/// Whether the format encodes colors as srgb when written
+pub(crate) fn is_srgb(format: vk::Format) -> bool {
+  matches!(
+    format,
+    vk::Format::R8G8B8A8_SRGB
+      | vk::Format::B8G8R8A8_SRGB
+      | vk::Format::A8B8G8R8_SRGB_PACK32
+      | vk::Format::R8G8B8_SRGB
+      | vk::Format::B8G8R8_SRGB
+  )
+}
+
+/// One post-processing descriptor set per hdr image of a target, each samples that image as the source of a pass
+pub(crate) fn create_post_sets(
+  descriptor_manager: &mut DescriptorManager,
+  memory_manager: &MemoryManager,
+  info: &BufferMemory,
+  images: &[ImageId; HDR_IMAGES],
+) -> Option<[DescriptorSetHandle; HDR_IMAGES]> {
+  let mut sets = Vec::new();
+  for source in images {
+    let descriptor = vec![
+      DescriptorInfo {
+        stage: vk::ShaderStageFlags::FRAGMENT,
+        r#type: DescriptorType::UniformBuffer(*info),
+      },
+      DescriptorInfo {
+        stage: vk::ShaderStageFlags::FRAGMENT,
+        r#type: DescriptorType::Sampler(vec![*source]),
+      },
+      DescriptorInfo {
+        stage: vk::ShaderStageFlags::FRAGMENT,
+        r#type: DescriptorType::Sampler(vec![images[0]]),
+      },
+    ];
+    let (set, _) = descriptor_manager.create_descriptor_set(descriptor, memory_manager)?;
+    sets.push(set);
+  }
+  sets.try_into().ok()
+}
+
+/// The settings and built-in pipelines of the post-processing chain
+pub(crate) struct PostProcess {
+  config: PostProcessConfig,
+  info: BufferMemory,
+  /// The layout all post-processing pipelines are built with, any of the per image sets is bound in its place
+  set: DescriptorSetHandle,
+  encode_srgb: bool,
+  changed: bool,
+  bloom_threshold: GraphicsPipelineHandle,
+  bloom_blur: GraphicsPipelineHandle,
+  bloom_composite: GraphicsPipelineHandle,
+  tonemap: GraphicsPipelineHandle,
+  fxaa: GraphicsPipelineHandle,
+}
+
+impl PostProcess {
+  /// `info` is the memory of the post-processing uniform, `set` one of the sets created by
+  /// `create_post_sets` and `encode_srgb` is set if the output format is not srgb
+  pub fn init(
+    config: &PostProcessConfig,
+    info: BufferMemory,
+    set: DescriptorSetHandle,
+    encode_srgb: bool,
+    pipeline_manager: &mut PipelineManager,
+    descriptor_manager: &DescriptorManager,
+  ) -> Result<Self, Error> {
+    let mut build = |builder: GraphicsPipelineBuilder<'_>| {
+      pipeline_manager
+        .build_graphics_pipeline(
+          builder
+            .rendering_stage(RenderingStage::PostProcess)
+            .add_descriptor_set(set),
+          descriptor_manager,
+        )
+        .ok_or(RendererInitError::Pipeline("post-processing"))
+    };
+
+    let bloom_threshold = build(GraphicsPipelineBuilder::new().fragment_shader(
+      vk_shader_macros::include_glsl!("./assets/bloom_threshold.frag"),
+    ))?;
+    let bloom_blur = build(
+      GraphicsPipelineBuilder::new()
+        .fragment_shader(vk_shader_macros::include_glsl!("./assets/bloom_blur.frag")),
+    )?;
+    let bloom_composite = build(GraphicsPipelineBuilder::new().fragment_shader(
+      vk_shader_macros::include_glsl!("./assets/bloom_composite.frag"),
+    ))?;
+    let tonemap = build(GraphicsPipelineBuilder::new())?;
+    let fxaa = build(
+      GraphicsPipelineBuilder::new()
+        .fragment_shader(vk_shader_macros::include_glsl!("./assets/fxaa.frag")),
+    )?;
+
+    let output = GraphicsPipelineBuilder::new()
+      .rendering_stage(RenderingStage::Output)
+      .add_descriptor_set(set);
+    pipeline_manager
+      .build_graphics_pipeline(output, descriptor_manager)
+      .ok_or(RendererInitError::Pipeline("output"))?;
+
+    Ok(Self {
+      config: config.clone(),
+      info,
+      set,
+      encode_srgb,
+      changed: true,
+      bloom_threshold,
+      bloom_blur,
+      bloom_composite,
+      tonemap,
+      fxaa,
+    })
+  }
+
+  #[inline]
+  pub fn set(&self) -> DescriptorSetHandle {
+    self.set
+  }
+
+  #[inline]
+  pub fn config(&self) -> &PostProcessConfig {
+    &self.config
+  }
+
+  /// Returns true if bloom or fxaa was enabled or disabled, the passes have to be recorded again then
+  pub fn set_config(&mut self, config: PostProcessConfig) -> bool {
+    let passes_changed =
+      self.config.bloom.is_some() != config.bloom.is_some() || self.config.fxaa != config.fxaa;
+    self.config = config;
+    self.changed = true;
+    passes_changed
+  }
+
+  /// Writes the settings into the uniform if they changed
+  pub fn update_info(&mut self, memory_manager: &mut MemoryManager) -> Result<(), Error> {
+    if self.changed {
+      let info = PostProcessInfo::new(&self.config, self.encode_srgb);
+      memory_manager.write_to_buffer(&self.info, &[info])?;
+      self.changed = false;
+    }
+    Ok(())
+  }
+
+  /// Runs the chain on the scene of the target, `sets` are the post-processing sets of its hdr images
+  #[allow(clippy::too_many_arguments)]
+  pub fn record(
+    &self,
+    device: &ash::Device,
+    buffer: vk::CommandBuffer,
+    pipeline_manager: &PipelineManager,
+    descriptor_manager: &DescriptorManager,
+    render_passes: &RenderPasses,
+    target: RenderTarget,
+    scene: &SceneFramebuffer,
+    output: vk::Framebuffer,
+    sets: &[DescriptorSetHandle; HDR_IMAGES],
+  ) {
+    let builtin = [
+      self.bloom_threshold,
+      self.bloom_blur,
+      self.bloom_composite,
+      self.tonemap,
+      self.fxaa,
+    ];
+    let custom = pipeline_manager
+      .post_pipelines()
+      .into_iter()
+      .filter(|pipeline| !builtin.contains(&pipeline.id()))
+      .collect::<Vec<_>>();
+
+    let area = vk::Rect2D {
+      offset: vk::Offset2D::default(),
+      extent: scene.extent(),
+    };
+    let viewport = vk::Viewport::default()
+      .width(area.extent.width as f32)
+      .height(area.extent.height as f32)
+      .min_depth(0.0)
+      .max_depth(1.0);
+
+    for pass in post_passes(self.config.bloom.is_some(), custom.len(), self.config.fxaa) {
+      let (render_pass, framebuffer) = match pass.destination {
+        Some(image) => (render_passes.post(), scene.post_buffer(image)),
+        None => (render_passes.output(target), output),
+      };
+      let pipeline = match pass.step {
+        PostStep::BloomThreshold => pipeline_manager.post_pipeline(self.bloom_threshold),
+        PostStep::BloomBlur(_) => pipeline_manager.post_pipeline(self.bloom_blur),
+        PostStep::BloomComposite => pipeline_manager.post_pipeline(self.bloom_composite),
+        PostStep::Tonemap => pipeline_manager.post_pipeline(self.tonemap),
+        PostStep::Custom(i) => custom[i],
+        PostStep::Fxaa => pipeline_manager.post_pipeline(self.fxaa),
+        PostStep::Output => pipeline_manager.output_pipeline(),
+      };
+
+      begin_render_pass(device, buffer, render_pass, framebuffer, area);
+      unsafe {
+        device.cmd_set_viewport(buffer, 0, &[viewport]);
+        device.cmd_set_scissor(buffer, 0, &[area]);
+
+        pipeline.bind(buffer, device, descriptor_manager);
+        pipeline.rebind_descriptor_set(
+          buffer,
+          device,
+          descriptor_manager,
+          self.set,
+          sets[pass.source],
+        );
+        // the blur reads its direction from the push constant
+        if let PostStep::BloomBlur(direction) = pass.step {
+          pipeline.push_camera(buffer, device, direction);
+        }
+
+        device.cmd_draw(buffer, 3, 1, 0, 0);
+        device.cmd_end_render_pass(buffer);
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use ash::vk;
+
+  use super::{is_srgb, post_passes, PostPass, PostStep};
+
+  fn steps(passes: &[PostPass]) -> Vec<PostStep> {
+    passes.iter().map(|pass| pass.step).collect()
+  }
+
+  #[test]
+  fn passes() {
+    let passes = post_passes(false, 0, false);
+    assert_eq!(steps(&passes), vec![PostStep::Tonemap, PostStep::Output]);
+    assert_eq!(passes[0].source, 0);
+    assert_eq!(passes[1].source, 1);
+    assert_eq!(passes[1].destination, None);
+
+    let passes = post_passes(true, 2, false);
+    assert_eq!(
+      steps(&passes),
+      vec![
+        PostStep::BloomThreshold,
+        PostStep::BloomBlur(0),
+        PostStep::BloomBlur(1),
+        PostStep::BloomComposite,
+        PostStep::Tonemap,
+        PostStep::Custom(0),
+        PostStep::Custom(1),
+        PostStep::Output,
+      ]
+    );
+
+    // anti-aliasing runs on the final colors
+    let passes = post_passes(false, 1, true);
+    assert_eq!(
+      steps(&passes),
+      vec![
+        PostStep::Tonemap,
+        PostStep::Custom(0),
+        PostStep::Fxaa,
+        PostStep::Output,
+      ]
+    );
+  }
+
+  #[test]
+  fn ping_pong() {
+    for (bloom, custom, fxaa) in [
+      (false, 0, false),
+      (false, 3, true),
+      (true, 0, false),
+      (true, 1, true),
+    ] {
+      let passes = post_passes(bloom, custom, fxaa);
+      assert_eq!(passes[0].source, 0);
+
+      for pair in passes.windows(2) {
+        let destination = pair[0].destination.unwrap();
+        // the scene is never overwritten and no pass reads what it writes
+        assert_ne!(destination, 0);
+        assert_ne!(destination, pair[0].source);
+        assert_eq!(pair[1].source, destination);
+      }
+      assert_eq!(passes.last().unwrap().destination, None);
+    }
+  }
+
+  #[test]
+  fn srgb() {
+    assert!(is_srgb(vk::Format::B8G8R8A8_SRGB));
+    assert!(!is_srgb(vk::Format::B8G8R8A8_UNORM));
+    assert!(!is_srgb(vk::Format::R16G16B16A16_SFLOAT));
+  }
+}
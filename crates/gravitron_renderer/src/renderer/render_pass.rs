@@ -1,12 +1,126 @@
 use ash::vk;
 
-pub fn init_render_pass(
+use crate::ecs::components::camera::RenderTarget;
+
+use super::framebuffer::HDR_FORMAT;
+
+/// The scene passes are compatible, they only differ in whether the hdr image is cleared.
+/// The post-processing passes write into one hdr image, the output passes into the output image of a target
+pub struct RenderPasses {
+  samples: vk::SampleCountFlags,
+  scene_clear: vk::RenderPass,
+  scene_load: vk::RenderPass,
+  post: vk::RenderPass,
+  window_output: vk::RenderPass,
+  texture_output: vk::RenderPass,
+}
+
+impl RenderPasses {
+  /// `window_layout` is the layout the window output is left in, presentable or ready to be copied when headless
+  pub fn init(
+    logical_device: &ash::Device,
+    format: vk::Format,
+    window_layout: vk::ImageLayout,
+    samples: vk::SampleCountFlags,
+  ) -> Result<Self, vk::Result> {
+    let texture = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+
+    Ok(Self {
+      samples,
+      scene_clear: init_render_pass(logical_device, false, samples)?,
+      scene_load: init_render_pass(logical_device, true, samples)?,
+      post: init_fullscreen_pass(logical_device, HDR_FORMAT, texture)?,
+      window_output: init_fullscreen_pass(logical_device, format, window_layout)?,
+      texture_output: init_fullscreen_pass(logical_device, format, texture)?,
+    })
+  }
+
+  /// Recreates the scene passes, scene framebuffers and pipelines have to be created again afterwards
+  pub fn set_samples(
+    &mut self,
+    logical_device: &ash::Device,
+    samples: vk::SampleCountFlags,
+  ) -> Result<(), vk::Result> {
+    let scene_clear = init_render_pass(logical_device, false, samples)?;
+    let scene_load = init_render_pass(logical_device, true, samples)?;
+    unsafe {
+      logical_device.destroy_render_pass(self.scene_clear, None);
+      logical_device.destroy_render_pass(self.scene_load, None);
+    }
+    self.scene_clear = scene_clear;
+    self.scene_load = scene_load;
+    self.samples = samples;
+    Ok(())
+  }
+
+  /// Samples per pixel of the G-Buffer, depth and lit scene
+  #[inline]
+  pub fn samples(&self) -> vk::SampleCountFlags {
+    self.samples
+  }
+
+  #[inline]
+  pub fn is_multisampled(&self) -> bool {
+    self.samples != vk::SampleCountFlags::TYPE_1
+  }
+
+  /// The render pass scene pipelines and framebuffers are created with
+  #[inline]
+  pub fn main(&self) -> vk::RenderPass {
+    self.scene_clear
+  }
+
+  /// The first pass into a target clears it, all following ones draw on top
+  #[inline]
+  pub fn get(&self, first: bool) -> vk::RenderPass {
+    if first {
+      self.scene_clear
+    } else {
+      self.scene_load
+    }
+  }
+
+  #[inline]
+  pub fn post(&self) -> vk::RenderPass {
+    self.post
+  }
+
+  /// Writes the post-processed scene into the output image of the target
+  #[inline]
+  pub fn output(&self, target: RenderTarget) -> vk::RenderPass {
+    match target {
+      RenderTarget::Window => self.window_output,
+      RenderTarget::Texture(_) => self.texture_output,
+    }
+  }
+
+  #[inline]
+  pub fn texture_output(&self) -> vk::RenderPass {
+    self.texture_output
+  }
+
+  pub fn cleanup(&self, logical_device: &ash::Device) {
+    unsafe {
+      logical_device.destroy_render_pass(self.scene_clear, None);
+      logical_device.destroy_render_pass(self.scene_load, None);
+      logical_device.destroy_render_pass(self.post, None);
+      logical_device.destroy_render_pass(self.window_output, None);
+      logical_device.destroy_render_pass(self.texture_output, None);
+    }
+  }
+}
+
+/// With multiple samples the scene is lit into a multisampled image, resolved into the hdr image by the last subpass
+fn init_render_pass(
   logical_device: &ash::Device,
-  format: vk::Format,
+  load_output: bool,
+  samples: vk::SampleCountFlags,
 ) -> Result<vk::RenderPass, vk::Result> {
+  let multisampled = samples != vk::SampleCountFlags::TYPE_1;
+
   let color = vk::AttachmentDescription::default()
     .format(vk::Format::R32G32B32A32_SFLOAT)
-    .samples(vk::SampleCountFlags::TYPE_1)
+    .samples(samples)
     .load_op(vk::AttachmentLoadOp::CLEAR)
     .store_op(vk::AttachmentStoreOp::STORE)
     .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
@@ -15,14 +129,32 @@ pub fn init_render_pass(
     .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
   let normal = color;
   let pos = color;
+  let emissive = color;
 
-  let output = color
-    .format(format)
-    .final_layout(vk::ImageLayout::PRESENT_SRC_KHR);
+  // sampled by the post-processing afterwards
+  let output = if load_output {
+    color
+      .load_op(vk::AttachmentLoadOp::LOAD)
+      .initial_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+  } else {
+    color
+  }
+  .format(HDR_FORMAT)
+  .samples(vk::SampleCountFlags::TYPE_1);
+
+  let lit = if load_output {
+    color
+      .load_op(vk::AttachmentLoadOp::LOAD)
+      .initial_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+  } else {
+    color
+  }
+  .format(HDR_FORMAT)
+  .final_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
 
   let depth = vk::AttachmentDescription::default()
     .format(vk::Format::D32_SFLOAT)
-    .samples(vk::SampleCountFlags::TYPE_1)
+    .samples(samples)
     .load_op(vk::AttachmentLoadOp::CLEAR)
     .store_op(vk::AttachmentStoreOp::STORE)
     .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
@@ -30,7 +162,10 @@ pub fn init_render_pass(
     .initial_layout(vk::ImageLayout::UNDEFINED)
     .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
 
-  let attachment = [color, normal, pos, depth, output];
+  let mut attachment = vec![color, normal, pos, emissive, depth, output];
+  if multisampled {
+    attachment.push(lit);
+  }
 
   let color_out = [
     vk::AttachmentReference::default()
@@ -42,6 +177,9 @@ pub fn init_render_pass(
     vk::AttachmentReference::default()
       .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
       .attachment(2),
+    vk::AttachmentReference::default()
+      .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+      .attachment(3),
   ];
   let color_in = [
     vk::AttachmentReference::default()
@@ -53,15 +191,30 @@ pub fn init_render_pass(
     vk::AttachmentReference::default()
       .layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
       .attachment(2),
+    vk::AttachmentReference::default()
+      .layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+      .attachment(3),
   ];
 
   let output = [vk::AttachmentReference::default()
     .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-    .attachment(4)];
+    .attachment(if multisampled { 6 } else { 5 })];
+  let resolve = [vk::AttachmentReference::default()
+    .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+    .attachment(5)];
   let depth = vk::AttachmentReference::default()
     .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
-    .attachment(3);
+    .attachment(4);
 
+  // the last subpass resolves the multisampled lit scene
+  let mut blended = vk::SubpassDescription::default()
+    .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+    .depth_stencil_attachment(&depth)
+    .color_attachments(&output);
+  if multisampled {
+    blended = blended.resolve_attachments(&resolve);
+  }
+  // G-Buffer, deferred lighting and blended meshes on top of the lit scene
   let subpass = [
     vk::SubpassDescription::default()
       .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
@@ -71,24 +224,41 @@ pub fn init_render_pass(
       .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
       .color_attachments(&output)
       .input_attachments(&color_in),
+    blended,
   ];
 
   let subpass_dependency = [
     vk::SubpassDependency::default()
       .src_subpass(vk::SUBPASS_EXTERNAL)
       .dst_subpass(0)
-      .src_stage_mask(vk::PipelineStageFlags::BOTTOM_OF_PIPE)
+      .src_stage_mask(
+        vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+          | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS
+          | vk::PipelineStageFlags::FRAGMENT_SHADER,
+      )
       .dst_stage_mask(
         vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
           | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
       )
-      .src_access_mask(vk::AccessFlags::MEMORY_READ)
+      .src_access_mask(
+        vk::AccessFlags::COLOR_ATTACHMENT_WRITE | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+      )
       .dst_access_mask(
         vk::AccessFlags::COLOR_ATTACHMENT_READ
           | vk::AccessFlags::COLOR_ATTACHMENT_WRITE
           | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+      ),
+    vk::SubpassDependency::default()
+      .src_subpass(vk::SUBPASS_EXTERNAL)
+      .dst_subpass(1)
+      .src_stage_mask(
+        vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::FRAGMENT_SHADER,
       )
-      .dependency_flags(vk::DependencyFlags::BY_REGION),
+      .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+      .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+      .dst_access_mask(
+        vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+      ),
     vk::SubpassDependency::default()
       .src_subpass(0)
       .dst_subpass(1)
@@ -107,6 +277,82 @@ pub fn init_render_pass(
       )
       .dst_access_mask(vk::AccessFlags::MEMORY_READ)
       .dependency_flags(vk::DependencyFlags::BY_REGION),
+    vk::SubpassDependency::default()
+      .src_subpass(0)
+      .dst_subpass(2)
+      .src_stage_mask(vk::PipelineStageFlags::LATE_FRAGMENT_TESTS)
+      .dst_stage_mask(vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS)
+      .src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+      .dst_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ)
+      .dependency_flags(vk::DependencyFlags::BY_REGION),
+    vk::SubpassDependency::default()
+      .src_subpass(1)
+      .dst_subpass(2)
+      .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+      .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+      .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+      .dst_access_mask(
+        vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+      )
+      .dependency_flags(vk::DependencyFlags::BY_REGION),
+    vk::SubpassDependency::default()
+      .src_subpass(2)
+      .dst_subpass(vk::SUBPASS_EXTERNAL)
+      .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+      .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+      .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+      .dst_access_mask(vk::AccessFlags::SHADER_READ),
+  ];
+
+  let render_pass_create_info = vk::RenderPassCreateInfo::default()
+    .attachments(&attachment)
+    .subpasses(&subpass)
+    .dependencies(&subpass_dependency);
+  unsafe { logical_device.create_render_pass(&render_pass_create_info, None) }
+}
+
+/// A single color attachment overwritten by a fullscreen pass sampling other images
+fn init_fullscreen_pass(
+  logical_device: &ash::Device,
+  format: vk::Format,
+  output_layout: vk::ImageLayout,
+) -> Result<vk::RenderPass, vk::Result> {
+  let attachment = [vk::AttachmentDescription::default()
+    .format(format)
+    .samples(vk::SampleCountFlags::TYPE_1)
+    .load_op(vk::AttachmentLoadOp::DONT_CARE)
+    .store_op(vk::AttachmentStoreOp::STORE)
+    .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+    .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+    .initial_layout(vk::ImageLayout::UNDEFINED)
+    .final_layout(output_layout)];
+
+  let output = [vk::AttachmentReference::default()
+    .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+    .attachment(0)];
+  let subpass = [vk::SubpassDescription::default()
+    .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+    .color_attachments(&output)];
+
+  let subpass_dependency = [
+    vk::SubpassDependency::default()
+      .src_subpass(vk::SUBPASS_EXTERNAL)
+      .dst_subpass(0)
+      .src_stage_mask(
+        vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::FRAGMENT_SHADER,
+      )
+      .dst_stage_mask(
+        vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::FRAGMENT_SHADER,
+      )
+      .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE | vk::AccessFlags::SHADER_READ)
+      .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE | vk::AccessFlags::SHADER_READ),
+    vk::SubpassDependency::default()
+      .src_subpass(0)
+      .dst_subpass(vk::SUBPASS_EXTERNAL)
+      .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+      .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+      .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+      .dst_access_mask(vk::AccessFlags::SHADER_READ),
   ];
 
   let render_pass_create_info = vk::RenderPassCreateInfo::default()
@@ -0,0 +1,8 @@
+//! All alignment is required to match the shaders alignment
+
+#[repr(C, align(16))]
+pub struct CameraData {
+  pub view_matrix: glam::Mat4,
+  pub projection_matrix: glam::Mat4,
+  pub render_layers: u32,
+}
@@ -0,0 +1,400 @@
+//! All alignment is required to match the shaders alignment
+
+use std::ops::RangeInclusive;
+
+use crate::model::bounds::BoundingSphere;
+
+use super::shadow::depth_range;
+
+/// Tiles along the width of the view
+pub const CLUSTER_X: u32 = 16;
+/// Tiles along the height of the view
+pub const CLUSTER_Y: u32 = 9;
+/// Logarithmic depth slices
+pub const CLUSTER_Z: u32 = 24;
+/// Clusters of each camera
+pub const CLUSTER_COUNT: u32 = CLUSTER_X * CLUSTER_Y * CLUSTER_Z;
+
+/// Depth range of the slices for projections without a far plane, everything further away is in the last slice
+const CLUSTER_MAX_DISTANCE: f32 = 1000.0;
+/// The first slice starts here for projections with a near plane at or behind the camera
+const CLUSTER_MIN_NEAR: f32 = 0.1;
+
+/// How view depth maps to slices for one camera
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClusterCamera {
+  pub near: f32,
+  pub scale: f32,
+}
+
+/// Lights of a cluster are `point_lights` point light indices starting at `offset`, followed by `spot_lights` spot light indices
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Cluster {
+  pub offset: u32,
+  pub point_lights: u32,
+  pub spot_lights: u32,
+}
+
+/// Clusters of every camera, the clusters of camera `i` start at `i * CLUSTER_COUNT`
+#[derive(Debug, Default)]
+pub(crate) struct LightClusters {
+  pub cameras: Vec<ClusterCamera>,
+  pub clusters: Vec<Cluster>,
+  pub indices: Vec<u32>,
+}
+
+impl ClusterCamera {
+  pub(crate) fn new(projection: glam::Mat4) -> Self {
+    let (near, far) = depth_range(projection);
+    let near = near.max(CLUSTER_MIN_NEAR);
+    let far = if far.is_finite() {
+      far
+    } else {
+      CLUSTER_MAX_DISTANCE
+    }
+    .max(near * 2.0);
+
+    Self {
+      near,
+      scale: CLUSTER_Z as f32 / (far / near).ln(),
+    }
+  }
+
+  /// Depth slice containing the view depth, depths outside of the range are clamped to the first and last slice
+  #[inline]
+  pub(crate) fn slice(&self, depth: f32) -> u32 {
+    let slice = ((depth.max(self.near) / self.near).ln() * self.scale).floor();
+    (slice as u32).min(CLUSTER_Z - 1)
+  }
+}
+
+impl LightClusters {
+  /// `cameras` are the view and projection matrices in the order of the camera descriptor
+  pub(crate) fn new(
+    cameras: &[(glam::Mat4, glam::Mat4)],
+    point_lights: &[BoundingSphere],
+    spot_lights: &[BoundingSphere],
+  ) -> Self {
+    let mut result = Self::default();
+
+    for (view, projection) in cameras {
+      let camera = ClusterCamera::new(*projection);
+      let near = depth_range(*projection).0;
+      let ranges = |lights: &[BoundingSphere]| {
+        lights
+          .iter()
+          .enumerate()
+          .filter_map(|(i, light)| {
+            light_clusters(*view, *projection, &camera, near, light)
+              .map(|ranges| (i as u32, ranges))
+          })
+          .collect::<Vec<_>>()
+      };
+      let points = ranges(point_lights);
+      let spots = ranges(spot_lights);
+
+      let mut clusters = vec![Cluster::default(); CLUSTER_COUNT as usize];
+      for (_, ranges) in &points {
+        for_each_cluster(ranges, |i| clusters[i].point_lights += 1);
+      }
+      for (_, ranges) in &spots {
+        for_each_cluster(ranges, |i| clusters[i].spot_lights += 1);
+      }
+
+      let mut offset = result.indices.len() as u32;
+      for cluster in &mut clusters {
+        cluster.offset = offset;
+        offset += cluster.point_lights + cluster.spot_lights;
+      }
+      result.indices.resize(offset as usize, 0);
+
+      let mut filled = vec![0; CLUSTER_COUNT as usize];
+      for (light, ranges) in &points {
+        for_each_cluster(ranges, |i| {
+          result.indices[(clusters[i].offset + filled[i]) as usize] = *light;
+          filled[i] += 1;
+        });
+      }
+      for (light, ranges) in &spots {
+        for_each_cluster(ranges, |i| {
+          result.indices[(clusters[i].offset + filled[i]) as usize] = *light;
+          filled[i] += 1;
+        });
+      }
+
+      result.cameras.push(camera);
+      result.clusters.extend(clusters);
+    }
+
+    result
+  }
+}
+
+#[inline]
+pub(crate) fn cluster_index(x: u32, y: u32, z: u32) -> usize {
+  ((z * CLUSTER_Y + y) * CLUSTER_X + x) as usize
+}
+
+fn for_each_cluster(ranges: &[RangeInclusive<u32>; 3], mut f: impl FnMut(usize)) {
+  for z in ranges[2].clone() {
+    for y in ranges[1].clone() {
+      for x in ranges[0].clone() {
+        f(cluster_index(x, y, z));
+      }
+    }
+  }
+}
+
+/// Tiles and slices the light can reach, `None` if it is outside of the view.
+/// `near` is the near plane of the projection, the view matrix has to keep distances
+pub(crate) fn light_clusters(
+  view: glam::Mat4,
+  projection: glam::Mat4,
+  camera: &ClusterCamera,
+  near: f32,
+  light: &BoundingSphere,
+) -> Option<[RangeInclusive<u32>; 3]> {
+  let center = view.transform_point3(light.center);
+  let radius = light.radius;
+
+  let depth_min = -center.z - radius;
+  let depth_max = -center.z + radius;
+  if depth_max < near {
+    return None;
+  }
+
+  // the part of the bounding box in front of the near plane, all corners project in front of the camera
+  let z_max = (center.z + radius).min(-near);
+  let z_min = (center.z - radius).min(z_max);
+  let mut min = glam::Vec2::splat(f32::INFINITY);
+  let mut max = glam::Vec2::splat(f32::NEG_INFINITY);
+  for x in [center.x - radius, center.x + radius] {
+    for y in [center.y - radius, center.y + radius] {
+      for z in [z_min, z_max] {
+        let ndc = projection
+          .project_point3(glam::Vec3::new(x, y, z))
+          .truncate();
+        min = min.min(ndc);
+        max = max.max(ndc);
+      }
+    }
+  }
+  if max.x < -1.0 || max.y < -1.0 || min.x > 1.0 || min.y > 1.0 {
+    return None;
+  }
+
+  let tile = |ndc: f32, count: u32| {
+    (((ndc.clamp(-1.0, 1.0) * 0.5 + 0.5) * count as f32).floor() as u32).min(count - 1)
+  };
+  Some([
+    tile(min.x, CLUSTER_X)..=tile(max.x, CLUSTER_X),
+    tile(min.y, CLUSTER_Y)..=tile(max.y, CLUSTER_Y),
+    camera.slice(depth_min)..=camera.slice(depth_max),
+  ])
+}
+
+/// Sphere containing the cone of a spot light, lights wider than about 75 degrees are bound by their range
+pub(crate) fn spot_bounds(
+  position: glam::Vec3,
+  direction: glam::Vec3,
+  angle: f32,
+  range: f32,
+) -> BoundingSphere {
+  // the rim of the cone is the furthest point from the middle of its axis
+  let radius = range * (1.25 - angle.cos()).max(0.25).sqrt();
+  if radius < range {
+    BoundingSphere {
+      center: position + direction.normalize_or_zero() * range * 0.5,
+      radius,
+    }
+  } else {
+    BoundingSphere {
+      center: position,
+      radius: range,
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::model::bounds::BoundingSphere;
+
+  use super::{
+    cluster_index, light_clusters, spot_bounds, ClusterCamera, LightClusters, CLUSTER_COUNT,
+    CLUSTER_X, CLUSTER_Y, CLUSTER_Z,
+  };
+
+  fn camera() -> (glam::Mat4, glam::Mat4) {
+    (
+      glam::Mat4::look_at_rh(
+        glam::Vec3::new(2.0, 1.0, 4.0),
+        glam::Vec3::new(0.0, 0.0, -10.0),
+        glam::Vec3::NEG_Y,
+      ),
+      glam::Mat4::perspective_rh(1.0, 16.0 / 9.0, 0.1, 200.0),
+    )
+  }
+
+  /// The cluster the light pass reads for a world position
+  fn cluster_of(view: glam::Mat4, projection: glam::Mat4, position: glam::Vec3) -> usize {
+    let camera = ClusterCamera::new(projection);
+    let view_pos = view.transform_point3(position);
+    let ndc = projection.project_point3(view_pos);
+    let tile =
+      |v: f32, count: u32| (((v * 0.5 + 0.5) * count as f32).floor() as u32).min(count - 1);
+    cluster_index(
+      tile(ndc.x, CLUSTER_X),
+      tile(ndc.y, CLUSTER_Y),
+      camera.slice(-view_pos.z),
+    )
+  }
+
+  #[test]
+  fn slices() {
+    let camera = ClusterCamera::new(glam::Mat4::perspective_rh(1.0, 1.0, 0.5, 500.0));
+    assert!((camera.near - 0.5).abs() < 1e-4);
+    assert_eq!(camera.slice(0.0), 0);
+    assert_eq!(camera.slice(0.5), 0);
+    assert_eq!(camera.slice(499.0), CLUSTER_Z - 1);
+    assert_eq!(camera.slice(10_000.0), CLUSTER_Z - 1);
+
+    let slices = (1..1000)
+      .map(|d| camera.slice(d as f32 * 0.5))
+      .collect::<Vec<_>>();
+    assert!(slices.windows(2).all(|pair| pair[0] <= pair[1]));
+
+    // every slice covers the same depth ratio
+    let ratio = (500.0f32 / 0.5).powf(1.0 / CLUSTER_Z as f32);
+    assert_eq!(camera.slice(0.5 * ratio.powi(3) * 1.01), 3);
+
+    let infinite = ClusterCamera::new(glam::Mat4::perspective_infinite_rh(1.0, 1.0, 0.1));
+    assert!(infinite.scale.is_finite());
+    let orthographic = ClusterCamera::new(glam::Mat4::orthographic_rh(
+      -1.0, 1.0, -1.0, 1.0, 0.0, 100.0,
+    ));
+    assert!(orthographic.near > 0.0);
+  }
+
+  #[test]
+  fn assignment() {
+    let (view, projection) = camera();
+    let camera = ClusterCamera::new(projection);
+    let near = 0.1;
+
+    let behind = BoundingSphere {
+      center: glam::Vec3::new(2.0, 1.0, 10.0),
+      radius: 2.0,
+    };
+    assert!(light_clusters(view, projection, &camera, near, &behind).is_none());
+
+    let aside = BoundingSphere {
+      center: view
+        .inverse()
+        .transform_point3(glam::Vec3::new(100.0, 0.0, -10.0)),
+      radius: 1.0,
+    };
+    assert!(light_clusters(view, projection, &camera, near, &aside).is_none());
+
+    // a light around the camera reaches the whole screen
+    let around = BoundingSphere {
+      center: glam::Vec3::new(2.0, 1.0, 4.0),
+      radius: 1.0,
+    };
+    let [x, y, z] = light_clusters(view, projection, &camera, near, &around).unwrap();
+    assert_eq!((x, y), (0..=CLUSTER_X - 1, 0..=CLUSTER_Y - 1));
+    assert_eq!(*z.start(), 0);
+  }
+
+  #[test]
+  fn conservative() {
+    let (view, projection) = camera();
+    let inverse = (projection * view).inverse();
+    let points = [
+      BoundingSphere {
+        center: glam::Vec3::new(0.0, 0.0, -10.0),
+        radius: 3.0,
+      },
+      BoundingSphere {
+        center: glam::Vec3::new(-4.0, 2.0, -2.0),
+        radius: 5.0,
+      },
+      BoundingSphere {
+        center: glam::Vec3::new(5.0, -1.0, 1.0),
+        radius: 2.5,
+      },
+    ];
+    let spots = [spot_bounds(
+      glam::Vec3::new(1.0, 3.0, -5.0),
+      glam::Vec3::NEG_Y,
+      0.6,
+      8.0,
+    )];
+    let clusters = LightClusters::new(&[(view, projection), (view, projection)], &points, &spots);
+    assert_eq!(clusters.cameras.len(), 2);
+    assert_eq!(clusters.clusters.len(), 2 * CLUSTER_COUNT as usize);
+
+    // every visible position inside a light finds the light in its cluster
+    let steps = 24;
+    for ix in 0..steps {
+      for iy in 0..steps {
+        for iz in 0..steps {
+          let ndc = glam::Vec3::new(
+            (ix as f32 + 0.5) / steps as f32 * 2.0 - 1.0,
+            (iy as f32 + 0.5) / steps as f32 * 2.0 - 1.0,
+            1.0 - ((iz as f32 + 0.5) / steps as f32).powi(4),
+          );
+          let position = inverse.project_point3(ndc);
+          let cluster = clusters.clusters[cluster_of(view, projection, position)];
+          let lights = &clusters.indices[cluster.offset as usize..]
+            [..(cluster.point_lights + cluster.spot_lights) as usize];
+          let (cluster_points, cluster_spots) = lights.split_at(cluster.point_lights as usize);
+
+          for (i, light) in points.iter().enumerate() {
+            if light.center.distance(position) <= light.radius {
+              assert!(cluster_points.contains(&(i as u32)));
+            }
+          }
+          for (i, light) in spots.iter().enumerate() {
+            if light.center.distance(position) <= light.radius {
+              assert!(cluster_spots.contains(&(i as u32)));
+            }
+          }
+        }
+      }
+    }
+
+    // the second camera has its own lists
+    let first = &clusters.clusters[..CLUSTER_COUNT as usize];
+    let second = &clusters.clusters[CLUSTER_COUNT as usize..];
+    assert!(first
+      .iter()
+      .zip(second)
+      .all(|(a, b)| { a.point_lights == b.point_lights && a.spot_lights == b.spot_lights }));
+    assert!(second[0].offset >= first.last().unwrap().offset);
+  }
+
+  #[test]
+  fn spot() {
+    let position = glam::Vec3::new(1.0, 2.0, 3.0);
+    let direction = glam::Vec3::new(1.0, 0.0, 1.0).normalize();
+    let angle = 0.4;
+    let range = 10.0;
+    let bounds = spot_bounds(position, direction, angle, range);
+    assert!(bounds.radius < range);
+
+    let rim = glam::Quat::from_axis_angle(direction.any_orthonormal_vector(), angle) * direction;
+    for point in [
+      position,
+      position + direction * range,
+      position + rim * range,
+    ] {
+      assert!(bounds.center.distance(point) <= bounds.radius + 1e-4);
+    }
+
+    let wide = spot_bounds(position, direction, 1.4, range);
+    assert_eq!(wide.center, position);
+    assert_eq!(wide.radius, range);
+  }
+}
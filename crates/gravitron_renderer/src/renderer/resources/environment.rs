@@ -0,0 +1,27 @@
+//! All alignment is required to match the shaders alignment
+
+#[repr(C, align(16))]
+#[derive(Debug, PartialEq)]
+pub struct EnvironmentInfo {
+  /// Scales the skybox and the image based lighting
+  pub intensity: f32,
+  /// Mip level of the prefiltered cubemap belonging to a roughness of 1
+  pub max_lod: f32,
+  /// 0 without a skybox, the flat ambient light of the directional light is used then
+  pub enabled: u32,
+}
+
+#[cfg(test)]
+mod test {
+  use std::mem::offset_of;
+
+  use super::EnvironmentInfo;
+
+  #[test]
+  fn gpu_layout() {
+    // std140 layout of the EnvironmentInfo uniform in lighting.glsl
+    assert_eq!(size_of::<EnvironmentInfo>(), 16);
+    assert_eq!(offset_of!(EnvironmentInfo, max_lod), 4);
+    assert_eq!(offset_of!(EnvironmentInfo, enabled), 8);
+  }
+}
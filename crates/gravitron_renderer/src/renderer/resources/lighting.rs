@@ -1,5 +1,8 @@
 //! All alignment is required to match the shaders alignment
 
+/// Shadow map layer of spot lights without shadows
+pub const NO_SHADOW: u32 = u32::MAX;
+
 #[repr(C)]
 pub struct LightInfo {
   pub num_point_lights: u32,
@@ -33,6 +36,8 @@ pub struct SpotLight {
   pub intensity: f32,
   pub range: f32,
   pub angle: f32,
+  /// Layer of the shadow map or `NO_SHADOW`
+  pub shadow: u32,
 }
 
 #[repr(align(16))]
@@ -1,13 +1,61 @@
-use crate::{pipeline::manager::GraphicsPipelineHandle, renderer::TextureHandle};
+use crate::{asset::Asset, pipeline::manager::GraphicsPipelineHandle, renderer::TextureHandle};
 
+/// Texture index the shaders treat as "no texture", the material value is used on its own
+pub const NO_TEXTURE: u32 = u32::MAX;
+
+/// How the alpha of the base color is used
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum AlphaMode {
+  /// Alpha is ignored
+  #[default]
+  Opaque,
+  /// Fragments with an alpha below the cutoff are discarded
+  Mask(f32),
+  /// Blended over the lit scene after the light pass, sorted back to front.
+  /// The `shader` of the material is not used and the instances do not cast shadows
+  Blend,
+}
+
+/// Shared by every `MeshRenderer` holding a handle to it, add it with `AssetServer::add`.
+/// Changes made through `AssetServer::get_mut` apply to all users in the next frame
+#[derive(Clone)]
 pub struct Material {
   pub color: glam::Vec4,
   pub texture_id: TextureHandle,
   pub metallic: f32,
   pub roughness: f32,
+  /// Tangent space normal map, should be created with `ColorSpace::Linear`
+  pub normal_texture: Option<TextureHandle>,
+  pub normal_scale: f32,
+  /// Roughness is read from the green and metallic from the blue channel, both are multiplied with the scalar values
+  pub metallic_roughness_texture: Option<TextureHandle>,
+  /// Ambient occlusion in the red channel
+  pub occlusion_texture: Option<TextureHandle>,
+  pub occlusion_strength: f32,
+  /// Linear emitted color, added after lighting
+  pub emissive: glam::Vec3,
+  /// Multiplied with `emissive`
+  pub emissive_texture: Option<TextureHandle>,
+  pub alpha_mode: AlphaMode,
+  /// Instances are batched by shader, so it can not be overridden per instance
   pub shader: GraphicsPipelineHandle,
 }
 
+/// Layout of a material in the material storage buffer
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(C, align(16))]
+pub(crate) struct GpuMaterial {
+  pub color: glam::Vec4,
+  /// metallic, roughness, normal scale and occlusion strength
+  pub factors: glam::Vec4,
+  pub emissive: glam::Vec3,
+  pub emissive_texture: u32,
+  /// base color, normal, metallic-roughness and occlusion texture
+  pub textures: glam::UVec4,
+  /// Fragments with a lower alpha are discarded
+  pub alpha_cutoff: f32,
+}
+
 impl Material {
   #[inline]
   pub fn new() -> Self {
@@ -15,6 +63,8 @@ impl Material {
   }
 }
 
+impl Asset for Material {}
+
 impl Default for Material {
   fn default() -> Self {
     Self {
@@ -22,7 +72,78 @@ impl Default for Material {
       texture_id: Default::default(),
       metallic: 0.0,
       roughness: 0.0,
+      normal_texture: None,
+      normal_scale: 1.0,
+      metallic_roughness_texture: None,
+      occlusion_texture: None,
+      occlusion_strength: 1.0,
+      emissive: glam::Vec3::ZERO,
+      emissive_texture: None,
+      alpha_mode: AlphaMode::Opaque,
       shader: Default::default(),
     }
   }
 }
+
+impl From<&Material> for GpuMaterial {
+  fn from(material: &Material) -> Self {
+    let texture = |texture: Option<TextureHandle>| texture.map_or(NO_TEXTURE, |texture| texture.0);
+
+    Self {
+      color: material.color,
+      factors: glam::Vec4::new(
+        material.metallic,
+        material.roughness,
+        material.normal_scale,
+        material.occlusion_strength,
+      ),
+      emissive: material.emissive,
+      emissive_texture: texture(material.emissive_texture),
+      textures: glam::UVec4::new(
+        material.texture_id.0,
+        texture(material.normal_texture),
+        texture(material.metallic_roughness_texture),
+        texture(material.occlusion_texture),
+      ),
+      alpha_cutoff: match material.alpha_mode {
+        AlphaMode::Mask(cutoff) => cutoff,
+        AlphaMode::Opaque | AlphaMode::Blend => 0.0,
+      },
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::mem::offset_of;
+
+  use super::{AlphaMode, GpuMaterial, Material, NO_TEXTURE};
+  use crate::renderer::TextureHandle;
+
+  #[test]
+  fn gpu_layout() {
+    // std430 layout of the Material struct in shader.vert
+    assert_eq!(size_of::<GpuMaterial>(), 80);
+    assert_eq!(offset_of!(GpuMaterial, emissive), 32);
+    assert_eq!(offset_of!(GpuMaterial, emissive_texture), 44);
+    assert_eq!(offset_of!(GpuMaterial, textures), 48);
+    assert_eq!(offset_of!(GpuMaterial, alpha_cutoff), 64);
+
+    let material = GpuMaterial::from(&Material {
+      normal_texture: Some(TextureHandle(3)),
+      ..Default::default()
+    });
+    assert_eq!(
+      material.textures,
+      glam::UVec4::new(0, 3, NO_TEXTURE, NO_TEXTURE)
+    );
+    assert_eq!(material.emissive_texture, NO_TEXTURE);
+    assert_eq!(material.alpha_cutoff, 0.0);
+
+    let masked = GpuMaterial::from(&Material {
+      alpha_mode: AlphaMode::Mask(0.5),
+      ..Default::default()
+    });
+    assert_eq!(masked.alpha_cutoff, 0.5);
+  }
+}
@@ -1,2 +1,7 @@
+pub(crate) mod camera;
+pub(crate) mod cluster;
+pub(crate) mod environment;
 pub(crate) mod lighting;
 pub mod material;
+pub(crate) mod post_process;
+pub(crate) mod shadow;
@@ -0,0 +1,62 @@
+//! All alignment is required to match the shaders alignment
+
+use crate::config::{PostProcessConfig, Tonemapping};
+
+#[repr(C, align(16))]
+#[derive(Debug, PartialEq)]
+pub struct PostProcessInfo {
+  pub exposure: f32,
+  pub tonemapping: u32,
+  pub bloom_threshold: f32,
+  pub bloom_intensity: f32,
+  pub bloom_radius: f32,
+  pub gamma: f32,
+  /// 1 if the target is not srgb, the output pass encodes the colors itself then
+  pub encode_srgb: u32,
+}
+
+impl PostProcessInfo {
+  pub(crate) fn new(config: &PostProcessConfig, encode_srgb: bool) -> Self {
+    let bloom = config.bloom.clone().unwrap_or_default();
+    Self {
+      exposure: config.exposure,
+      // matches the TONEMAP constants in tonemap.frag
+      tonemapping: match config.tonemapping {
+        Tonemapping::None => 0,
+        Tonemapping::Reinhard => 1,
+        Tonemapping::Aces => 2,
+        Tonemapping::AgX => 3,
+      },
+      bloom_threshold: bloom.threshold,
+      bloom_intensity: bloom.intensity,
+      bloom_radius: bloom.radius,
+      gamma: config.gamma,
+      encode_srgb: encode_srgb as u32,
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::mem::offset_of;
+
+  use super::PostProcessInfo;
+  use crate::config::{BloomConfig, PostProcessConfig, Tonemapping};
+
+  #[test]
+  fn gpu_layout() {
+    // std140 layout of the PostProcessInfo uniform in the post-processing shaders
+    assert_eq!(size_of::<PostProcessInfo>(), 32);
+    assert_eq!(offset_of!(PostProcessInfo, bloom_threshold), 8);
+    assert_eq!(offset_of!(PostProcessInfo, gamma), 20);
+    assert_eq!(offset_of!(PostProcessInfo, encode_srgb), 24);
+
+    let config = PostProcessConfig::default()
+      .set_tonemapping(Tonemapping::AgX)
+      .set_bloom(Some(BloomConfig::default().set_intensity(2.0)));
+    let info = PostProcessInfo::new(&config, true);
+    assert_eq!(info.tonemapping, 3);
+    assert_eq!(info.bloom_intensity, 2.0);
+    assert_eq!(info.encode_srgb, 1);
+  }
+}
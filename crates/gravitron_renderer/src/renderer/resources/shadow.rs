@@ -0,0 +1,402 @@
+//! All alignment is required to match the shaders alignment
+
+use crate::config::ShadowConfig;
+
+/// Blend between uniform (0) and logarithmic (1) cascade splits
+pub(crate) const CASCADE_SPLIT_LAMBDA: f32 = 0.8;
+/// Casters up to this far outside of a cascade towards the light still cast shadows into it
+const CASTER_MARGIN: f32 = 50.0;
+/// Near plane of spot light shadow maps
+const SPOT_NEAR: f32 = 0.05;
+/// World space offset along the normal before the shadow map is sampled
+const NORMAL_BIAS: f32 = 0.02;
+
+#[repr(C, align(16))]
+pub struct ShadowInfo {
+  pub num_cascades: u32,
+  pub pcf_radius: u32,
+  pub texel_size: f32,
+  pub normal_bias: f32,
+}
+
+impl ShadowInfo {
+  #[inline]
+  pub(crate) fn new(config: &ShadowConfig, num_cascades: u32) -> Self {
+    Self {
+      num_cascades,
+      pcf_radius: config.pcf_radius,
+      texel_size: 1.0 / config.map_size as f32,
+      normal_bias: NORMAL_BIAS,
+    }
+  }
+}
+
+/// A spot light as seen by the shadow pass
+pub(crate) struct SpotShadow {
+  pub position: glam::Vec3,
+  pub direction: glam::Vec3,
+  pub angle: f32,
+  pub range: f32,
+}
+
+/// The matrix of every shadow map layer and which layers have to be rendered this frame
+pub(crate) struct ShadowMatrices {
+  pub info: ShadowInfo,
+  pub matrices: Vec<glam::Mat4>,
+  pub layers: Vec<u32>,
+}
+
+impl ShadowMatrices {
+  /// `camera` is the view and projection matrix the cascades are fit to,
+  /// spot lights beyond `max_spot_shadows` are ignored
+  pub(crate) fn new(
+    config: &ShadowConfig,
+    camera: Option<(glam::Mat4, glam::Mat4)>,
+    directional: Option<glam::Vec3>,
+    spots: impl IntoIterator<Item = SpotShadow>,
+  ) -> Self {
+    let cascades = config.cascades();
+    let mut matrices = vec![glam::Mat4::IDENTITY; config.layers() as usize];
+    let mut layers = Vec::new();
+
+    let mut num_cascades = 0;
+    if let (Some((view, projection)), Some(direction)) = (camera, directional) {
+      let cascade_matrices = directional_cascades(
+        view,
+        projection,
+        direction,
+        cascades,
+        config.distance,
+        config.map_size,
+      );
+      num_cascades = cascade_matrices.len() as u32;
+      for (i, matrix) in cascade_matrices.into_iter().enumerate() {
+        matrices[i] = matrix;
+        layers.push(i as u32);
+      }
+    }
+
+    for (i, spot) in spots
+      .into_iter()
+      .take(config.max_spot_shadows as usize)
+      .enumerate()
+    {
+      let layer = cascades + i as u32;
+      matrices[layer as usize] =
+        spot_light_matrix(spot.position, spot.direction, spot.angle, spot.range);
+      layers.push(layer);
+    }
+
+    Self {
+      info: ShadowInfo::new(config, num_cascades),
+      matrices,
+      layers,
+    }
+  }
+}
+
+/// Far distance of each cascade, a blend of logarithmic and uniform splits between `near` and `far`
+pub(crate) fn cascade_splits(near: f32, far: f32, count: u32, lambda: f32) -> Vec<f32> {
+  (1..=count)
+    .map(|i| {
+      let t = i as f32 / count as f32;
+      let log = near * (far / near).powf(t);
+      let uniform = near + (far - near) * t;
+      lambda * log + (1.0 - lambda) * uniform
+    })
+    .collect()
+}
+
+/// Distances of the near and far plane of a projection to the camera
+pub(crate) fn depth_range(projection: glam::Mat4) -> (f32, f32) {
+  let inverse = projection.inverse();
+  (
+    -inverse.project_point3(glam::Vec3::ZERO).z,
+    -inverse.project_point3(glam::Vec3::Z).z,
+  )
+}
+
+/// World space corners of the part of the camera view between the distances `start` and `end`
+pub(crate) fn frustum_slice(
+  view: glam::Mat4,
+  projection: glam::Mat4,
+  start: f32,
+  end: f32,
+) -> [glam::Vec3; 8] {
+  let inverse = (projection * view).inverse();
+  let depth = |distance: f32| {
+    projection
+      .project_point3(glam::Vec3::new(0.0, 0.0, -distance))
+      .z
+  };
+  let (start, end) = (depth(start), depth(end));
+
+  let mut corners = [glam::Vec3::ZERO; 8];
+  for (i, (x, y)) in [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)]
+    .into_iter()
+    .enumerate()
+  {
+    corners[i] = inverse.project_point3(glam::Vec3::new(x, y, start));
+    corners[i + 4] = inverse.project_point3(glam::Vec3::new(x, y, end));
+  }
+  corners
+}
+
+/// One matrix per cascade, each covering a slice of the camera view up to `distance`
+pub(crate) fn directional_cascades(
+  view: glam::Mat4,
+  projection: glam::Mat4,
+  direction: glam::Vec3,
+  count: u32,
+  distance: f32,
+  map_size: u32,
+) -> Vec<glam::Mat4> {
+  let (near, far) = depth_range(projection);
+  let far = if far.is_finite() {
+    far.min(distance)
+  } else {
+    distance
+  };
+  if far <= near {
+    return Vec::new();
+  }
+
+  let mut start = near;
+  cascade_splits(near, far, count, CASCADE_SPLIT_LAMBDA)
+    .into_iter()
+    .map(|end| {
+      let corners = frustum_slice(view, projection, start, end);
+      start = end;
+      directional_light_matrix(direction, &corners, map_size)
+    })
+    .collect()
+}
+
+/// Orthographic view of the light containing all corners.
+/// The size only depends on the slice and the position is snapped to texels, so shadow edges do not shimmer while the camera moves
+pub(crate) fn directional_light_matrix(
+  direction: glam::Vec3,
+  corners: &[glam::Vec3; 8],
+  map_size: u32,
+) -> glam::Mat4 {
+  let direction = direction.try_normalize().unwrap_or(glam::Vec3::NEG_Y);
+  let center = corners.iter().sum::<glam::Vec3>() / 8.0;
+  let radius = corners
+    .iter()
+    .map(|corner| corner.distance(center))
+    .fold(0.0, f32::max);
+  let radius = (radius * 16.0).ceil() / 16.0;
+
+  let eye = center - direction * (radius + CASTER_MARGIN);
+  let view = glam::Mat4::look_at_rh(eye, center, up_vector(direction));
+  let mut projection = glam::Mat4::orthographic_rh(
+    -radius,
+    radius,
+    -radius,
+    radius,
+    0.0,
+    2.0 * radius + CASTER_MARGIN,
+  );
+
+  let texels = map_size as f32 / 2.0;
+  let origin = (projection * view)
+    .project_point3(glam::Vec3::ZERO)
+    .truncate()
+    * texels;
+  let offset = (origin.round() - origin) / texels;
+  projection.w_axis.x += offset.x;
+  projection.w_axis.y += offset.y;
+
+  projection * view
+}
+
+/// Perspective view from the light covering its cone
+pub(crate) fn spot_light_matrix(
+  position: glam::Vec3,
+  direction: glam::Vec3,
+  angle: f32,
+  range: f32,
+) -> glam::Mat4 {
+  let direction = direction.try_normalize().unwrap_or(glam::Vec3::NEG_Y);
+  let fov = (2.0 * angle).clamp(0.01, std::f32::consts::PI - 0.01);
+  let projection = glam::Mat4::perspective_rh(fov, 1.0, SPOT_NEAR, range.max(SPOT_NEAR * 2.0));
+  let view = glam::Mat4::look_at_rh(position, position + direction, up_vector(direction));
+  projection * view
+}
+
+#[inline]
+fn up_vector(direction: glam::Vec3) -> glam::Vec3 {
+  if direction.y.abs() > 0.99 {
+    glam::Vec3::Z
+  } else {
+    glam::Vec3::Y
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::config::ShadowConfig;
+
+  use super::{
+    cascade_splits, depth_range, directional_cascades, frustum_slice, spot_light_matrix,
+    ShadowMatrices, SpotShadow,
+  };
+
+  fn camera() -> (glam::Mat4, glam::Mat4) {
+    (
+      glam::Mat4::look_at_rh(
+        glam::Vec3::new(3.0, 2.0, 5.0),
+        glam::Vec3::new(0.0, 0.0, -10.0),
+        glam::Vec3::NEG_Y,
+      ),
+      glam::Mat4::perspective_rh(1.2, 16.0 / 9.0, 0.1, 1000.0),
+    )
+  }
+
+  #[test]
+  fn splits() {
+    let uniform = cascade_splits(0.1, 100.1, 4, 0.0);
+    for (split, expected) in uniform.iter().zip([25.1, 50.1, 75.1, 100.1]) {
+      assert!((split - expected).abs() < 1e-3);
+    }
+
+    let log = cascade_splits(1.0, 1000.0, 3, 1.0);
+    for (split, expected) in log.iter().zip([10.0, 100.0, 1000.0]) {
+      assert!((split - expected).abs() < 1e-2);
+    }
+
+    let splits = cascade_splits(0.1, 100.0, 4, 0.8);
+    assert!((splits[3] - 100.0).abs() < 1e-3);
+    assert!(splits.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(splits[0] > 0.1);
+  }
+
+  #[test]
+  fn depth() {
+    let (near, far) = depth_range(glam::Mat4::perspective_rh(1.0, 1.0, 0.5, 200.0));
+    assert!((near - 0.5).abs() < 1e-4);
+    assert!((far - 200.0).abs() < 0.1);
+
+    let (near, far) = depth_range(glam::Mat4::orthographic_rh(-1.0, 1.0, -1.0, 1.0, 2.0, 50.0));
+    assert!((near - 2.0).abs() < 1e-4);
+    assert!((far - 50.0).abs() < 1e-4);
+  }
+
+  #[test]
+  fn slice() {
+    let (view, projection) = camera();
+    let corners = frustum_slice(view, projection, 5.0, 20.0);
+
+    for (i, corner) in corners.iter().enumerate() {
+      let view_pos = view.transform_point3(*corner);
+      let expected = if i < 4 { 5.0 } else { 20.0 };
+      assert!((-view_pos.z - expected).abs() < 1e-2);
+
+      let clip = projection.project_point3(view_pos);
+      assert!((clip.x.abs() - 1.0).abs() < 1e-3);
+      assert!((clip.y.abs() - 1.0).abs() < 1e-3);
+    }
+  }
+
+  #[test]
+  fn cascades() {
+    let (view, projection) = camera();
+    let direction = glam::Vec3::new(0.3, 1.0, -0.2).normalize();
+    let matrices = directional_cascades(view, projection, direction, 4, 100.0, 2048);
+    assert_eq!(matrices.len(), 4);
+
+    let (near, _) = depth_range(projection);
+    let mut start = near;
+    for (matrix, end) in
+      matrices
+        .iter()
+        .zip(cascade_splits(near, 100.0, 4, super::CASCADE_SPLIT_LAMBDA))
+    {
+      for corner in frustum_slice(view, projection, start, end) {
+        let clip = matrix.project_point3(corner);
+        assert!(clip.x.abs() <= 1.0 && clip.y.abs() <= 1.0);
+        assert!((0.0..=1.0).contains(&clip.z));
+
+        // casters between the slice and the light are still rendered
+        let caster = matrix.project_point3(corner - direction * 10.0);
+        assert!((0.0..=1.0).contains(&caster.z));
+        assert!(caster.z < clip.z);
+      }
+      start = end;
+    }
+
+    let infinite = glam::Mat4::perspective_infinite_rh(1.2, 1.0, 0.1);
+    let matrices = directional_cascades(view, infinite, direction, 3, 100.0, 2048);
+    assert_eq!(matrices.len(), 3);
+    assert!(matrices.iter().all(|matrix| matrix.is_finite()));
+  }
+
+  #[test]
+  fn cascade_stability() {
+    let (view, projection) = camera();
+    let direction = glam::Vec3::new(0.3, 1.0, -0.2).normalize();
+    let map_size = 1024;
+
+    // the cascade is moved in whole texels when the camera moves
+    let moved = view * glam::Mat4::from_translation(glam::Vec3::new(0.013, 0.0, 0.021));
+    let a = directional_cascades(view, projection, direction, 1, 50.0, map_size)[0];
+    let b = directional_cascades(moved, projection, direction, 1, 50.0, map_size)[0];
+
+    let texels = map_size as f32 / 2.0;
+    let shift =
+      (a.project_point3(glam::Vec3::ZERO) - b.project_point3(glam::Vec3::ZERO)).truncate() * texels;
+    assert!((shift - shift.round()).abs().max_element() < 1e-2);
+  }
+
+  #[test]
+  fn spot() {
+    let position = glam::Vec3::new(1.0, 2.0, 3.0);
+    let direction = glam::Vec3::new(0.0, -1.0, 1.0).normalize();
+    let matrix = spot_light_matrix(position, direction, 0.5, 10.0);
+
+    let center = matrix.project_point3(position + direction * 5.0);
+    assert!(center.x.abs() < 1e-4 && center.y.abs() < 1e-4);
+    assert!(center.z > 0.0 && center.z < 1.0);
+
+    // the edge of the cone is on the edge of the map
+    let edge = glam::Quat::from_axis_angle(direction.any_orthonormal_vector(), 0.5) * direction;
+    let clip = matrix.project_point3(position + edge * 5.0);
+    assert!((clip.truncate().length() - 1.0).abs() < 1e-3);
+
+    let behind = matrix * (position - direction).extend(1.0);
+    assert!(behind.w < 0.0);
+
+    // straight down does not break the view matrix
+    let down = spot_light_matrix(position, glam::Vec3::NEG_Y, 0.5, 10.0);
+    assert!(down.is_finite());
+    let below = down.project_point3(position - glam::Vec3::Y * 5.0);
+    assert!(below.x.abs() < 1e-4 && below.y.abs() < 1e-4);
+  }
+
+  #[test]
+  fn layers() {
+    let config = ShadowConfig::default()
+      .set_cascades(2)
+      .set_max_spot_shadows(1);
+    let spot = |x| SpotShadow {
+      position: glam::Vec3::new(x, 0.0, 0.0),
+      direction: glam::Vec3::X,
+      angle: 0.5,
+      range: 10.0,
+    };
+
+    let shadows = ShadowMatrices::new(
+      &config,
+      Some(camera()),
+      Some(glam::Vec3::NEG_Y),
+      [spot(0.0), spot(1.0)],
+    );
+    assert_eq!(shadows.info.num_cascades, 2);
+    assert_eq!(shadows.matrices.len(), 3);
+    assert_eq!(shadows.layers, vec![0, 1, 2]);
+
+    let shadows = ShadowMatrices::new(&config, None, Some(glam::Vec3::NEG_Y), [spot(0.0)]);
+    assert_eq!(shadows.info.num_cascades, 0);
+    assert_eq!(shadows.layers, vec![2]);
+  }
+}
@@ -0,0 +1,264 @@
+use anyhow::Error;
+use ash::vk;
+
+use crate::{
+  config::ShadowConfig,
+  memory::{types::ImageId, MemoryManager},
+  pipeline::descriptor::{DescriptorHandle, DescriptorSetHandle},
+};
+
+const SHADOW_FORMAT: vk::Format = vk::Format::D32_SFLOAT;
+
+/// The shadow descriptor set and the descriptors written every frame
+#[derive(Clone, Copy)]
+pub(crate) struct ShadowDescriptors {
+  pub set: DescriptorSetHandle,
+  pub info: DescriptorHandle,
+  pub matrices: DescriptorHandle,
+}
+
+/// One depth map per layer of an image array, the cascades of the directional light come first, then the spot lights
+pub struct ShadowMaps {
+  render_pass: vk::RenderPass,
+  image: ImageId,
+  views: Vec<vk::ImageView>,
+  framebuffers: Vec<vk::Framebuffer>,
+  size: u32,
+  layers: Vec<u32>,
+}
+
+impl ShadowMaps {
+  pub fn init(
+    logical_device: &ash::Device,
+    memory_manager: &mut MemoryManager,
+    config: &ShadowConfig,
+  ) -> Result<Self, Error> {
+    let render_pass = init_render_pass(logical_device)?;
+    let size = config.map_size;
+    let layer_count = config.layers();
+
+    let image_info = vk::ImageCreateInfo::default()
+      .image_type(vk::ImageType::TYPE_2D)
+      .format(SHADOW_FORMAT)
+      .extent(vk::Extent3D {
+        width: size,
+        height: size,
+        depth: 1,
+      })
+      .mip_levels(1)
+      .array_layers(layer_count)
+      .samples(vk::SampleCountFlags::TYPE_1)
+      .tiling(vk::ImageTiling::OPTIMAL)
+      .usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED)
+      .sharing_mode(vk::SharingMode::EXCLUSIVE);
+    let image_view_info = vk::ImageViewCreateInfo::default()
+      .view_type(vk::ImageViewType::TYPE_2D_ARRAY)
+      .format(SHADOW_FORMAT)
+      .subresource_range(subresource_range(0, layer_count));
+    // positions outside of every map are lit
+    let sampler_info = vk::SamplerCreateInfo::default()
+      .mag_filter(vk::Filter::LINEAR)
+      .min_filter(vk::Filter::LINEAR)
+      .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+      .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+      .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+      .border_color(vk::BorderColor::FLOAT_OPAQUE_WHITE)
+      .compare_enable(true)
+      .compare_op(vk::CompareOp::LESS_OR_EQUAL);
+    let image =
+      memory_manager.create_sampler_image(&image_info, &image_view_info, &sampler_info)?;
+    let vk_image = memory_manager
+      .get_vk_image(image)
+      .expect("Failed to get shadow map image");
+
+    let mut views = Vec::new();
+    let mut framebuffers = Vec::new();
+    for layer in 0..layer_count {
+      let view_info = vk::ImageViewCreateInfo::default()
+        .image(vk_image)
+        .view_type(vk::ImageViewType::TYPE_2D)
+        .format(SHADOW_FORMAT)
+        .subresource_range(subresource_range(layer, 1));
+      let view = unsafe { logical_device.create_image_view(&view_info, None) }?;
+      views.push(view);
+
+      let attachments = [view];
+      let framebuffer_info = vk::FramebufferCreateInfo::default()
+        .render_pass(render_pass)
+        .attachments(&attachments)
+        .width(size)
+        .height(size)
+        .layers(1);
+      framebuffers.push(unsafe { logical_device.create_framebuffer(&framebuffer_info, None) }?);
+    }
+
+    Ok(Self {
+      render_pass,
+      image,
+      views,
+      framebuffers,
+      size,
+      layers: Vec::new(),
+    })
+  }
+
+  pub fn cleanup(&self, logical_device: &ash::Device) {
+    unsafe {
+      for framebuffer in &self.framebuffers {
+        logical_device.destroy_framebuffer(*framebuffer, None);
+      }
+      for view in &self.views {
+        logical_device.destroy_image_view(*view, None);
+      }
+      logical_device.destroy_render_pass(self.render_pass, None);
+    }
+  }
+
+  #[inline]
+  pub fn render_pass(&self) -> vk::RenderPass {
+    self.render_pass
+  }
+
+  #[inline]
+  pub fn image(&self) -> ImageId {
+    self.image
+  }
+
+  /// Layers rendered every frame, returns true if they changed
+  pub fn set_layers(&mut self, layers: Vec<u32>) -> bool {
+    let changed = self.layers != layers;
+    self.layers = layers;
+    changed
+  }
+
+  #[inline]
+  pub fn layers(&self) -> &[u32] {
+    &self.layers
+  }
+
+  /// Layers not rendered this frame are left undefined, they are never sampled.
+  /// All of them are moved into the layout the descriptor expects
+  pub fn record_start(
+    &self,
+    device: &ash::Device,
+    buffer: vk::CommandBuffer,
+    memory_manager: &MemoryManager,
+  ) {
+    let barrier = vk::ImageMemoryBarrier::default()
+      .image(
+        memory_manager
+          .get_vk_image(self.image)
+          .expect("Failed to get shadow map image"),
+      )
+      .src_access_mask(vk::AccessFlags::empty())
+      .dst_access_mask(vk::AccessFlags::SHADER_READ)
+      .old_layout(vk::ImageLayout::UNDEFINED)
+      .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+      .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+      .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+      .subresource_range(subresource_range(0, self.views.len() as u32));
+
+    unsafe {
+      device.cmd_pipeline_barrier(
+        buffer,
+        vk::PipelineStageFlags::FRAGMENT_SHADER,
+        vk::PipelineStageFlags::FRAGMENT_SHADER,
+        vk::DependencyFlags::empty(),
+        &[],
+        &[],
+        &[barrier],
+      );
+    }
+  }
+
+  /// Begins the render pass of the layer and covers the whole map with the viewport
+  pub fn begin_layer(&self, device: &ash::Device, buffer: vk::CommandBuffer, layer: u32) {
+    let area = vk::Rect2D {
+      offset: vk::Offset2D::default(),
+      extent: vk::Extent2D {
+        width: self.size,
+        height: self.size,
+      },
+    };
+    let clear_values = [vk::ClearValue {
+      depth_stencil: vk::ClearDepthStencilValue {
+        depth: 1.0,
+        stencil: 0,
+      },
+    }];
+    let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+      .render_pass(self.render_pass)
+      .framebuffer(self.framebuffers[layer as usize])
+      .render_area(area)
+      .clear_values(&clear_values);
+    let viewport = vk::Viewport::default()
+      .width(self.size as f32)
+      .height(self.size as f32)
+      .min_depth(0.0)
+      .max_depth(1.0);
+
+    unsafe {
+      device.cmd_begin_render_pass(buffer, &render_pass_begin_info, vk::SubpassContents::INLINE);
+      device.cmd_set_viewport(buffer, 0, &[viewport]);
+      device.cmd_set_scissor(buffer, 0, &[area]);
+    }
+  }
+}
+
+#[inline]
+fn subresource_range(base_layer: u32, layer_count: u32) -> vk::ImageSubresourceRange {
+  vk::ImageSubresourceRange::default()
+    .aspect_mask(vk::ImageAspectFlags::DEPTH)
+    .base_mip_level(0)
+    .level_count(1)
+    .base_array_layer(base_layer)
+    .layer_count(layer_count)
+}
+
+fn init_render_pass(logical_device: &ash::Device) -> Result<vk::RenderPass, vk::Result> {
+  let depth = vk::AttachmentDescription::default()
+    .format(SHADOW_FORMAT)
+    .samples(vk::SampleCountFlags::TYPE_1)
+    .load_op(vk::AttachmentLoadOp::CLEAR)
+    .store_op(vk::AttachmentStoreOp::STORE)
+    .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+    .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+    .initial_layout(vk::ImageLayout::UNDEFINED)
+    .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+  let attachment = [depth];
+
+  let depth = vk::AttachmentReference::default()
+    .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+    .attachment(0);
+  let subpass = [vk::SubpassDescription::default()
+    .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+    .depth_stencil_attachment(&depth)];
+
+  let subpass_dependency = [
+    vk::SubpassDependency::default()
+      .src_subpass(vk::SUBPASS_EXTERNAL)
+      .dst_subpass(0)
+      .src_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+      .dst_stage_mask(
+        vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+      )
+      .src_access_mask(vk::AccessFlags::SHADER_READ)
+      .dst_access_mask(
+        vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+          | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+      ),
+    vk::SubpassDependency::default()
+      .src_subpass(0)
+      .dst_subpass(vk::SUBPASS_EXTERNAL)
+      .src_stage_mask(vk::PipelineStageFlags::LATE_FRAGMENT_TESTS)
+      .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+      .src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+      .dst_access_mask(vk::AccessFlags::SHADER_READ),
+  ];
+
+  let render_pass_create_info = vk::RenderPassCreateInfo::default()
+    .attachments(&attachment)
+    .subpasses(&subpass)
+    .dependencies(&subpass_dependency);
+  unsafe { logical_device.create_render_pass(&render_pass_create_info, None) }
+}
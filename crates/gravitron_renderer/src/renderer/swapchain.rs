@@ -3,166 +3,217 @@ use ash::{khr, vk};
 use gravitron_window::config::WindowConfig;
 
 use crate::{
+  config::{PresentMode, SwapchainConfig},
   device::Device,
+  ecs::components::camera::RenderTarget,
+  error::RendererInitError,
   instance::InstanceDevice,
-  memory::{types::ImageId, MemoryManager},
+  memory::MemoryManager,
   pipeline::pools::{CommandBufferType, Pools},
   surface::Surface,
 };
 
-use super::framebuffer::{Framebuffer, IMAGES_PER_FRAME_BUFFER};
+use super::{
+  framebuffer::{Framebuffer, SceneFramebuffer},
+  render_pass::RenderPasses,
+};
+
+struct FrameSync {
+  available: vk::Semaphore,
+  in_flight: vk::Fence,
+}
 
 pub struct SwapChain {
   loader: khr::swapchain::Device,
   swapchain: vk::SwapchainKHR,
   framebuffers: Vec<Framebuffer>,
+  images: Vec<vk::Image>,
+  format: vk::Format,
   extent: vk::Extent2D,
+  requested_extent: vk::Extent2D,
+  config: SwapchainConfig,
+  present_mode: vk::PresentModeKHR,
+  command_buffers: Vec<vk::CommandBuffer>,
+  frames: Vec<FrameSync>,
+  images_in_flight: Vec<vk::Fence>,
+  frame: usize,
   current_image: usize,
+  out_of_date: bool,
   graphics_queue: vk::Queue,
-  attachments: [ImageId; IMAGES_PER_FRAME_BUFFER as usize],
+  scene: SceneFramebuffer,
 }
 
 impl SwapChain {
+  #[allow(clippy::too_many_arguments)]
   pub fn init(
     instance_device: &InstanceDevice,
     device: &Device,
     surfaces: &Surface,
     memory_manager: &mut MemoryManager,
     window_config: &WindowConfig,
+    config: &SwapchainConfig,
     pools: &mut Pools,
-    render_pass: vk::RenderPass,
+    render_passes: &RenderPasses,
   ) -> Result<Self, Error> {
-    let physical_device = instance_device.get_physical_device();
     let logical_device = device.get_device();
 
-    let surface_capabilities = surfaces.get_capabilities(physical_device)?;
-    let surface_format = *surfaces.get_formats(physical_device)?.first().unwrap();
-
-    let mut extent = surface_capabilities.current_extent;
-    if extent.width == u32::MAX || extent.height == u32::MAX {
-      extent.width = window_config.width;
-      extent.height = window_config.height;
-    }
-
-    let frame_buffer_count =
-      if surface_capabilities.min_image_count <= surface_capabilities.max_image_count {
-        3.max(surface_capabilities.min_image_count)
-          .min(surface_capabilities.max_image_count)
-      } else {
-        if surface_capabilities.min_image_count < 1 {
-          panic!("Inconsistent possible Swapchain ImageCounts");
-        }
-        surface_capabilities.min_image_count
-      };
-
-    let present_mode = if surfaces
-      .get_present_modes(physical_device)?
-      .contains(&vk::PresentModeKHR::MAILBOX)
-    {
-      vk::PresentModeKHR::MAILBOX
-    } else {
-      vk::PresentModeKHR::FIFO
-    };
-
-    let queue_families = [device.get_queue_families().get_graphics_q_index()];
-    let swapchain_create_info = vk::SwapchainCreateInfoKHR::default()
-      .surface(surfaces.get_surface())
-      .min_image_count(frame_buffer_count)
-      .image_format(surface_format.format)
-      .image_color_space(surface_format.color_space)
-      .image_extent(extent)
-      .image_array_layers(1)
-      .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC)
-      .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
-      .queue_family_indices(&queue_families)
-      .pre_transform(surface_capabilities.current_transform)
-      .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
-      .present_mode(present_mode);
-
     let swapchain_loader =
       khr::swapchain::Device::new(instance_device.get_instance(), logical_device);
-    let swapchain = unsafe { swapchain_loader.create_swapchain(&swapchain_create_info, None) }?;
+    let (swapchain, format, extent, present_mode) = create_swapchain(
+      &swapchain_loader,
+      instance_device,
+      device,
+      surfaces,
+      config,
+      vk::Extent2D {
+        width: window_config.width,
+        height: window_config.height,
+      },
+      vk::SwapchainKHR::null(),
+    )?;
 
     let swapchain_images = unsafe { swapchain_loader.get_swapchain_images(swapchain) }?;
 
-    let extend_3d = vk::Extent3D {
-      width: extent.width,
-      height: extent.height,
-      depth: 1,
-    };
-    let depth_image_create_info = vk::ImageCreateInfo::default()
-      .image_type(vk::ImageType::TYPE_2D)
-      .format(vk::Format::D32_SFLOAT)
-      .extent(extend_3d)
-      .mip_levels(1)
-      .array_layers(1)
-      .samples(vk::SampleCountFlags::TYPE_1)
-      .tiling(vk::ImageTiling::OPTIMAL)
-      .usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT)
-      .sharing_mode(vk::SharingMode::EXCLUSIVE)
-      .queue_family_indices(&queue_families);
-
-    let subresource_range = vk::ImageSubresourceRange::default()
-      .aspect_mask(vk::ImageAspectFlags::DEPTH)
-      .base_mip_level(0)
-      .level_count(1)
-      .base_array_layer(0)
-      .layer_count(1);
-    let depth_image_view_create_info = vk::ImageViewCreateInfo::default()
-      .view_type(vk::ImageViewType::TYPE_2D)
-      .format(vk::Format::D32_SFLOAT)
-      .subresource_range(subresource_range);
-
-    let depth_image =
-      memory_manager.create_image(&depth_image_create_info, &depth_image_view_create_info)?;
-
-    let command_buffer = pools.create_command_buffers(
+    let command_buffers = pools.create_command_buffers(
       logical_device,
       swapchain_images.len(),
       CommandBufferType::Graphics,
     )?;
 
-    let image_info = depth_image_create_info
-      .usage(vk::ImageUsageFlags::INPUT_ATTACHMENT | vk::ImageUsageFlags::COLOR_ATTACHMENT)
-      .format(vk::Format::R32G32B32A32_SFLOAT);
-
-    let subresource_range = subresource_range.aspect_mask(vk::ImageAspectFlags::COLOR);
-    let image_view_info = depth_image_view_create_info
-      .format(vk::Format::R32G32B32A32_SFLOAT)
-      .subresource_range(subresource_range);
+    let scene = SceneFramebuffer::create(logical_device, render_passes, memory_manager, extent)?;
 
-    let mut images = Vec::new();
-    for _ in 0..IMAGES_PER_FRAME_BUFFER {
-      images.push(memory_manager.create_image(&image_info, &image_view_info)?);
-    }
-    let images = [images[0], images[1], images[2]];
+    let framebuffers = create_framebuffers(
+      logical_device,
+      &swapchain_images,
+      format,
+      render_passes.output(RenderTarget::Window),
+      extent,
+      &command_buffers,
+    )?;
 
-    let mut framebuffers = Vec::new();
-    for (swapchain_image, command_buffer) in swapchain_images.into_iter().zip(command_buffer) {
-      framebuffers.push(Framebuffer::create(
-        swapchain_image,
-        logical_device,
-        surface_format.format,
-        images,
-        depth_image,
-        render_pass,
-        memory_manager,
-        extent,
-        command_buffer,
-      )?);
+    let semaphore_create_info = vk::SemaphoreCreateInfo::default();
+    let fence_create_info = vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED);
+    let mut frames = Vec::new();
+    for _ in 0..config.frames_in_flight.max(1) {
+      frames.push(FrameSync {
+        available: unsafe { logical_device.create_semaphore(&semaphore_create_info, None) }?,
+        in_flight: unsafe { logical_device.create_fence(&fence_create_info, None) }?,
+      });
     }
 
     Ok(Self {
       loader: swapchain_loader,
       swapchain,
       framebuffers,
+      images_in_flight: vec![vk::Fence::null(); swapchain_images.len()],
+      images: swapchain_images,
+      format,
       extent,
+      requested_extent: extent,
+      config: config.clone(),
+      present_mode,
+      command_buffers,
+      frames,
+      frame: 0,
       current_image: 0,
+      out_of_date: false,
       graphics_queue: device.get_queues().graphics(),
-      attachments: images,
+      scene,
     })
   }
 
+  /// Recreates the swapchain, its framebuffers and the scene images for the new window size.
+  /// Returns false if the surface has no area, e.g. while the window is minimized
+  #[allow(clippy::too_many_arguments)]
+  pub fn recreate(
+    &mut self,
+    instance_device: &InstanceDevice,
+    device: &Device,
+    surfaces: &Surface,
+    memory_manager: &mut MemoryManager,
+    pools: &mut Pools,
+    render_passes: &RenderPasses,
+    size: vk::Extent2D,
+  ) -> Result<bool, Error> {
+    let logical_device = device.get_device();
+    unsafe { logical_device.device_wait_idle() }?;
+
+    let capabilities = surfaces.get_capabilities(instance_device.get_physical_device())?;
+    let extent = swapchain_extent(&capabilities, size);
+    if extent.width == 0 || extent.height == 0 {
+      return Ok(false);
+    }
+
+    let (swapchain, format, extent, present_mode) = create_swapchain(
+      &self.loader,
+      instance_device,
+      device,
+      surfaces,
+      &self.config,
+      size,
+      self.swapchain,
+    )?;
+
+    for framebuffer in &self.framebuffers {
+      framebuffer.cleanup(logical_device);
+    }
+    unsafe {
+      self.loader.destroy_swapchain(self.swapchain, None);
+    }
+    self.swapchain = swapchain;
+    self.images = unsafe { self.loader.get_swapchain_images(swapchain) }?;
+
+    if self.command_buffers.len() < self.images.len() {
+      self.command_buffers.extend(pools.create_command_buffers(
+        logical_device,
+        self.images.len() - self.command_buffers.len(),
+        CommandBufferType::Graphics,
+      )?);
+    }
+
+    self
+      .scene
+      .resize(logical_device, render_passes, memory_manager, extent)?;
+    self.framebuffers = create_framebuffers(
+      logical_device,
+      &self.images,
+      format,
+      render_passes.output(RenderTarget::Window),
+      extent,
+      &self.command_buffers,
+    )?;
+
+    self.images_in_flight = vec![vk::Fence::null(); self.images.len()];
+    self.format = format;
+    self.extent = extent;
+    self.present_mode = present_mode;
+    self.requested_extent = size;
+    self.current_image = 0;
+    self.out_of_date = false;
+    Ok(true)
+  }
+
+  /// Whether the swapchain does not match the window anymore
+  #[inline]
+  pub fn needs_recreation(&self, size: vk::Extent2D) -> bool {
+    self.out_of_date || self.requested_extent != size
+  }
+
+  /// The swapchain is recreated with the new mode before the next frame
+  #[inline]
+  pub fn set_present_mode(&mut self, present_mode: PresentMode) {
+    if self.config.present_mode != present_mode {
+      self.config.present_mode = present_mode;
+      self.out_of_date = true;
+    }
+  }
+
+  /// The mode frames are presented with, the preferred one might not be supported
+  #[inline]
+  pub fn present_mode(&self) -> vk::PresentModeKHR {
+    self.present_mode
+  }
+
   #[inline]
   pub fn get_extent(&self) -> vk::Extent2D {
     self.extent
@@ -173,36 +224,86 @@ impl SwapChain {
     for framebuffer in &self.framebuffers {
       framebuffer.cleanup(logical_device);
     }
+    self.scene.cleanup(logical_device);
 
     unsafe {
+      for frame in &self.frames {
+        logical_device.destroy_semaphore(frame.available, None);
+        logical_device.destroy_fence(frame.in_flight, None);
+      }
       self.loader.destroy_swapchain(self.swapchain, None);
     }
   }
 
   #[inline]
-  pub fn wait_for_draw_start(&self, device: &ash::Device) {
+  pub fn scene(&self) -> &SceneFramebuffer {
+    &self.scene
+  }
+
+  #[inline]
+  pub fn scene_mut(&mut self) -> &mut SceneFramebuffer {
+    &mut self.scene
+  }
+
+  /// Acquires the image of the next frame, returns false if the swapchain is out of date and nothing can be drawn
+  pub fn wait_for_draw_start(&mut self, device: &ash::Device) -> bool {
+    let frame = &self.frames[self.frame];
     unsafe {
       device
-        .wait_for_fences(
-          &[self.framebuffers[self.current_image].begin_drawing()],
-          true,
-          u64::MAX,
-        )
+        .wait_for_fences(&[frame.in_flight], true, u64::MAX)
         .expect("Unable to wait for fences");
+    }
+
+    if self.out_of_date {
+      return false;
+    }
+
+    let image_index = match unsafe {
+      self
+        .loader
+        .acquire_next_image(self.swapchain, u64::MAX, frame.available, vk::Fence::null())
+    } {
+      Ok((image_index, suboptimal)) => {
+        // the image can still be presented, the swapchain is recreated afterwards
+        self.out_of_date = suboptimal;
+        image_index as usize
+      }
+      Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+        self.out_of_date = true;
+        return false;
+      }
+      Err(err) => panic!("Unable to acquire next image: {err}"),
+    };
+
+    unsafe {
+      let image_in_flight = self.images_in_flight[image_index];
+      if image_in_flight != vk::Fence::null() {
+        device
+          .wait_for_fences(&[image_in_flight], true, u64::MAX)
+          .expect("Unable to wait for fences");
+      }
 
       device
-        .reset_fences(&[self.framebuffers[self.current_image].begin_drawing()])
+        .reset_fences(&[frame.in_flight])
         .expect("Unable to reset Fence");
     }
+    self.images_in_flight[image_index] = frame.in_flight;
+    self.current_image = image_index;
+    true
   }
 
   #[inline]
   pub fn record_command_buffer_start(
     &self,
     device: &ash::Device,
-    render_pass: vk::RenderPass,
   ) -> Result<vk::CommandBuffer, vk::Result> {
-    self.framebuffers[self.current_image].start_record(device, render_pass, self.extent)
+    self.framebuffers[self.current_image].start_record(device)
+  }
+
+  /// The output framebuffer of the current image
+  #[inline]
+  pub fn framebuffer(&self) -> vk::Framebuffer {
+    self.framebuffers[self.current_image].buffer()
   }
 
   #[inline]
@@ -211,69 +312,341 @@ impl SwapChain {
     device: &ash::Device,
     buffer: vk::CommandBuffer,
   ) -> Result<(), vk::Result> {
-    unsafe {
-      device.cmd_end_render_pass(buffer);
-      device.end_command_buffer(buffer)
-    }
+    unsafe { device.end_command_buffer(buffer) }
   }
 
-  pub fn draw_frame(&mut self, logical_device: &ash::Device) {
-    let (image_index, _) = unsafe {
-      self
-        .loader
-        .acquire_next_image(
-          self.swapchain,
-          u64::MAX,
-          self.framebuffers[self.current_image].available(),
-          vk::Fence::null(),
-        )
-        .expect("Unable to acquire next image")
-    };
+  /// The capture is submitted after the frame and presenting waits for it
+  pub fn draw_frame(
+    &mut self,
+    logical_device: &ash::Device,
+    capture: Option<(vk::CommandBuffer, vk::Fence)>,
+  ) {
+    let frame = &self.frames[self.frame];
+    let framebuffer = &self.framebuffers[self.current_image];
 
-    let semaphore_available = [self.framebuffers[self.current_image].available()];
+    let semaphore_available = [frame.available];
     let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
-    let semaphore_render_finished = [self.framebuffers[self.current_image].finished()];
-    let command_buffer = [self.framebuffers[self.current_image].command_buffer()];
+    let semaphore_render_finished = [framebuffer.finished()];
+    let command_buffer = [framebuffer.command_buffer()];
 
     let submit_info = [vk::SubmitInfo::default()
       .wait_semaphores(&semaphore_available)
       .wait_dst_stage_mask(&wait_stages)
       .command_buffers(&command_buffer)
-      .signal_semaphores(&semaphore_render_finished)];
+      .signal_semaphores(if capture.is_some() {
+        &[]
+      } else {
+        &semaphore_render_finished
+      })];
 
     unsafe {
       logical_device
-        .queue_submit(
-          self.graphics_queue,
-          &submit_info,
-          self.framebuffers[self.current_image].begin_drawing(),
-        )
+        .queue_submit(self.graphics_queue, &submit_info, frame.in_flight)
         .expect("Unable to submit queue");
+
+      if let Some((capture, fence)) = capture {
+        let command_buffer = [capture];
+        let submit_info = [vk::SubmitInfo::default()
+          .command_buffers(&command_buffer)
+          .signal_semaphores(&semaphore_render_finished)];
+        logical_device
+          .queue_submit(self.graphics_queue, &submit_info, fence)
+          .expect("Unable to submit queue");
+      }
     }
 
     let swapchains = [self.swapchain];
-    let image_indices = [image_index];
+    let image_indices = [self.current_image as u32];
     let present_info = vk::PresentInfoKHR::default()
       .wait_semaphores(&semaphore_render_finished)
       .swapchains(&swapchains)
       .image_indices(&image_indices);
-    unsafe {
+    match unsafe {
       self
         .loader
         .queue_present(self.graphics_queue, &present_info)
-        .expect("Unable to queue present");
+    } {
+      Ok(false) => {}
+      Ok(true) | Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => self.out_of_date = true,
+      Err(err) => panic!("Unable to queue present: {err}"),
     }
 
-    self.current_image = (self.current_image + 1) % self.framebuffers.len();
+    self.frame = (self.frame + 1) % self.frames.len();
+  }
+
+  #[inline]
+  pub fn frames_in_flight(&self) -> usize {
+    self.frames.len()
   }
 
+  /// The index of the acquired image, command buffers are recorded per image
   #[inline]
   pub fn current_frame(&self) -> usize {
     self.current_image
   }
 
+  /// The swapchain image of the current frame
+  #[inline]
+  pub fn image(&self) -> vk::Image {
+    self.images[self.current_image]
+  }
+
   #[inline]
-  pub fn attachments(&self) -> &[ImageId] {
-    &self.attachments
+  pub fn format(&self) -> vk::Format {
+    self.format
+  }
+}
+
+fn create_swapchain(
+  loader: &khr::swapchain::Device,
+  instance_device: &InstanceDevice,
+  device: &Device,
+  surfaces: &Surface,
+  config: &SwapchainConfig,
+  size: vk::Extent2D,
+  old_swapchain: vk::SwapchainKHR,
+) -> Result<
+  (
+    vk::SwapchainKHR,
+    vk::Format,
+    vk::Extent2D,
+    vk::PresentModeKHR,
+  ),
+  Error,
+> {
+  let physical_device = instance_device.get_physical_device();
+
+  let surface_capabilities = surfaces.get_capabilities(physical_device)?;
+  let surface_format = select_surface_format(
+    config.surface_format,
+    &surfaces.get_formats(physical_device)?,
+  )
+  .ok_or(RendererInitError::FormatMissing)?;
+  let extent = swapchain_extent(&surface_capabilities, size);
+  let present_mode = select_present_mode(
+    config.present_mode,
+    &surfaces.get_present_modes(physical_device)?,
+  );
+
+  let queue_families = [device.get_queue_families().get_graphics_q_index()];
+  let swapchain_create_info = vk::SwapchainCreateInfoKHR::default()
+    .surface(surfaces.get_surface())
+    .min_image_count(image_count(&surface_capabilities))
+    .image_format(surface_format.format)
+    .image_color_space(surface_format.color_space)
+    .image_extent(extent)
+    .image_array_layers(1)
+    .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC)
+    .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
+    .queue_family_indices(&queue_families)
+    .pre_transform(surface_capabilities.current_transform)
+    .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
+    .present_mode(present_mode)
+    .old_swapchain(old_swapchain);
+
+  let swapchain = unsafe { loader.create_swapchain(&swapchain_create_info, None) }?;
+  Ok((swapchain, surface_format.format, extent, present_mode))
+}
+
+fn create_framebuffers(
+  logical_device: &ash::Device,
+  swapchain_images: &[vk::Image],
+  format: vk::Format,
+  render_pass: vk::RenderPass,
+  extent: vk::Extent2D,
+  command_buffers: &[vk::CommandBuffer],
+) -> Result<Vec<Framebuffer>, Error> {
+  let mut framebuffers = Vec::new();
+  for (&swapchain_image, &command_buffer) in swapchain_images.iter().zip(command_buffers) {
+    framebuffers.push(Framebuffer::create(
+      swapchain_image,
+      logical_device,
+      format,
+      render_pass,
+      extent,
+      command_buffer,
+    )?);
+  }
+  Ok(framebuffers)
+}
+
+/// The preferred format if supported, otherwise 8 bit srgb or the first supported format
+pub(crate) fn select_surface_format(
+  preferred: Option<vk::SurfaceFormatKHR>,
+  available: &[vk::SurfaceFormatKHR],
+) -> Option<vk::SurfaceFormatKHR> {
+  preferred
+    .filter(|preferred| available.contains(preferred))
+    .or_else(|| {
+      available.iter().copied().find(|format| {
+        matches!(
+          format.format,
+          vk::Format::B8G8R8A8_SRGB | vk::Format::R8G8B8A8_SRGB
+        ) && format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
+      })
+    })
+    .or_else(|| available.first().copied())
+}
+
+/// The first supported mode of the preference, FIFO has to be supported by every surface
+fn select_present_mode(
+  preferred: PresentMode,
+  available: &[vk::PresentModeKHR],
+) -> vk::PresentModeKHR {
+  preferred
+    .candidates()
+    .iter()
+    .copied()
+    .find(|mode| available.contains(mode))
+    .unwrap_or(vk::PresentModeKHR::FIFO)
+}
+
+/// The surface decides the extent, unless it leaves it to the swapchain
+fn swapchain_extent(capabilities: &vk::SurfaceCapabilitiesKHR, size: vk::Extent2D) -> vk::Extent2D {
+  if capabilities.current_extent.width != u32::MAX && capabilities.current_extent.height != u32::MAX
+  {
+    return capabilities.current_extent;
+  }
+
+  vk::Extent2D {
+    width: size.width.clamp(
+      capabilities.min_image_extent.width,
+      capabilities.max_image_extent.width,
+    ),
+    height: size.height.clamp(
+      capabilities.min_image_extent.height,
+      capabilities.max_image_extent.height,
+    ),
+  }
+}
+
+/// Triple buffering if possible, a max image count of 0 means there is no limit
+fn image_count(capabilities: &vk::SurfaceCapabilitiesKHR) -> u32 {
+  let count = 3.max(capabilities.min_image_count);
+  if capabilities.max_image_count > 0 {
+    count.min(capabilities.max_image_count)
+  } else {
+    count
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use ash::vk;
+
+  use super::{image_count, select_present_mode, select_surface_format, swapchain_extent};
+  use crate::config::PresentMode;
+
+  #[test]
+  fn extent() {
+    let size = vk::Extent2D {
+      width: 800,
+      height: 600,
+    };
+    let capabilities = vk::SurfaceCapabilitiesKHR {
+      current_extent: vk::Extent2D {
+        width: 1024,
+        height: 768,
+      },
+      ..Default::default()
+    };
+    assert_eq!(
+      swapchain_extent(&capabilities, size),
+      capabilities.current_extent
+    );
+
+    // the window size is used, limited to what the surface supports
+    let capabilities = vk::SurfaceCapabilitiesKHR {
+      current_extent: vk::Extent2D {
+        width: u32::MAX,
+        height: u32::MAX,
+      },
+      min_image_extent: vk::Extent2D {
+        width: 1,
+        height: 1,
+      },
+      max_image_extent: vk::Extent2D {
+        width: 640,
+        height: 4096,
+      },
+      ..Default::default()
+    };
+    assert_eq!(
+      swapchain_extent(&capabilities, size),
+      vk::Extent2D {
+        width: 640,
+        height: 600,
+      }
+    );
+  }
+
+  #[test]
+  fn images() {
+    let capabilities = |min_image_count, max_image_count| vk::SurfaceCapabilitiesKHR {
+      min_image_count,
+      max_image_count,
+      ..Default::default()
+    };
+    assert_eq!(image_count(&capabilities(2, 8)), 3);
+    assert_eq!(image_count(&capabilities(2, 2)), 2);
+    assert_eq!(image_count(&capabilities(4, 8)), 4);
+    assert_eq!(image_count(&capabilities(1, 0)), 3);
+  }
+
+  #[test]
+  fn present_mode() {
+    let all = [
+      vk::PresentModeKHR::FIFO,
+      vk::PresentModeKHR::FIFO_RELAXED,
+      vk::PresentModeKHR::MAILBOX,
+      vk::PresentModeKHR::IMMEDIATE,
+    ];
+    assert_eq!(
+      select_present_mode(PresentMode::FifoRelaxed, &all),
+      vk::PresentModeKHR::FIFO_RELAXED
+    );
+    assert_eq!(
+      select_present_mode(PresentMode::Immediate, &all),
+      vk::PresentModeKHR::IMMEDIATE
+    );
+
+    let fifo_mailbox = [vk::PresentModeKHR::FIFO, vk::PresentModeKHR::MAILBOX];
+    assert_eq!(
+      select_present_mode(PresentMode::Immediate, &fifo_mailbox),
+      vk::PresentModeKHR::MAILBOX
+    );
+    assert_eq!(
+      select_present_mode(PresentMode::FifoRelaxed, &fifo_mailbox),
+      vk::PresentModeKHR::FIFO
+    );
+    assert_eq!(
+      select_present_mode(PresentMode::Mailbox, &[vk::PresentModeKHR::FIFO]),
+      vk::PresentModeKHR::FIFO
+    );
+  }
+
+  #[test]
+  fn surface_format() {
+    let format = |format, color_space| vk::SurfaceFormatKHR {
+      format,
+      color_space,
+    };
+    let unorm = format(
+      vk::Format::B8G8R8A8_UNORM,
+      vk::ColorSpaceKHR::SRGB_NONLINEAR,
+    );
+    let srgb = format(vk::Format::B8G8R8A8_SRGB, vk::ColorSpaceKHR::SRGB_NONLINEAR);
+    let hdr = format(
+      vk::Format::A2B10G10R10_UNORM_PACK32,
+      vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+    );
+    let available = [unorm, srgb];
+
+    assert_eq!(select_surface_format(None, &available), Some(srgb));
+    assert_eq!(select_surface_format(Some(unorm), &available), Some(unorm));
+    // unsupported preferences fall back
+    assert_eq!(select_surface_format(Some(hdr), &available), Some(srgb));
+    assert_eq!(select_surface_format(None, &[hdr]), Some(hdr));
+    assert_eq!(select_surface_format(None, &[]), None);
   }
 }
@@ -0,0 +1,278 @@
+use anyhow::Error;
+use ash::vk;
+
+use crate::{
+  memory::{
+    error::MemoryError,
+    types::{BufferBlockSize, BufferId, BufferMemory, BufferMemoryLocation},
+    MemoryManager,
+  },
+  model::{
+    model::{InstanceData, ModelHandle},
+    ModelManager,
+  },
+};
+
+const MIN_CAPACITY: usize = 16;
+const CMD_SIZE: usize = size_of::<vk::DrawIndexedIndirectCommand>();
+
+/// A blended instance, `center` is the world space center of its bounds
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct TransparentInstance {
+  pub model: ModelHandle,
+  pub center: glam::Vec3,
+  pub data: InstanceData,
+}
+
+/// The blended instances of every camera sorted back to front and their indirect draws.
+/// Each camera has room for `capacity` instances, so the command buffers are only recorded again when it grows
+pub(crate) struct TransparentDraws {
+  instance_buffer: BufferId,
+  command_buffer: BufferId,
+  instances: BufferMemory,
+  commands: BufferMemory,
+  counts: BufferMemory,
+  capacity: usize,
+  cameras: usize,
+}
+
+impl TransparentDraws {
+  pub fn init(memory_manager: &mut MemoryManager) -> Result<Self, Error> {
+    let instance_buffer = memory_manager.create_simple_buffer(
+      vk::BufferUsageFlags::VERTEX_BUFFER,
+      BufferBlockSize::Medium,
+      BufferMemoryLocation::CpuToGpu,
+    )?;
+    let command_buffer = memory_manager.create_simple_buffer(
+      vk::BufferUsageFlags::INDIRECT_BUFFER,
+      BufferBlockSize::Small,
+      BufferMemoryLocation::CpuToGpu,
+    )?;
+
+    let capacity = MIN_CAPACITY;
+    let cameras = 1;
+    let instances = memory_manager
+      .reserve_buffer_mem(instance_buffer, capacity * size_of::<InstanceData>())
+      .ok_or(MemoryError::Reallocate)?;
+    let commands = memory_manager
+      .reserve_buffer_mem(command_buffer, capacity * CMD_SIZE)
+      .ok_or(MemoryError::Reallocate)?;
+    let counts = memory_manager
+      .reserve_buffer_mem(command_buffer, size_of::<u32>())
+      .ok_or(MemoryError::Reallocate)?;
+
+    Ok(Self {
+      instance_buffer,
+      command_buffer,
+      instances,
+      commands,
+      counts,
+      capacity,
+      cameras,
+    })
+  }
+
+  /// Sorts the instances of every camera and writes their draws, `cameras` are in the order of the camera descriptor.
+  /// Returns true if the command buffers have to be recorded again
+  pub fn update(
+    &mut self,
+    memory_manager: &mut MemoryManager,
+    model_manager: &ModelManager,
+    cameras: Vec<(glam::Mat4, Vec<TransparentInstance>)>,
+  ) -> Result<bool, Error> {
+    let needed = cameras
+      .iter()
+      .map(|(_, instances)| instances.len())
+      .max()
+      .unwrap_or_default();
+    let resized = needed > self.capacity || cameras.len() > self.cameras;
+    if resized {
+      self.capacity = self.capacity.max(needed.next_power_of_two());
+      self.cameras = self.cameras.max(cameras.len().next_power_of_two());
+
+      let slots = self.cameras * self.capacity;
+      memory_manager.resize_buffer_mem(&mut self.instances, slots * size_of::<InstanceData>())?;
+      memory_manager.resize_buffer_mem(&mut self.commands, slots * CMD_SIZE)?;
+      memory_manager.resize_buffer_mem(&mut self.counts, self.cameras * size_of::<u32>())?;
+    }
+
+    let mut instances = Vec::new();
+    let mut instance_regions = Vec::new();
+    let mut commands = Vec::new();
+    let mut command_regions = Vec::new();
+    let mut counts = Vec::new();
+    for (camera, (view, mut camera_instances)) in cameras.into_iter().enumerate() {
+      sort_back_to_front(view, &mut camera_instances);
+
+      // first_instance is relative to the instances of this camera
+      let first = camera * self.capacity;
+      let draws = batches(&camera_instances)
+        .into_iter()
+        .filter_map(|(model, start, count)| {
+          model_manager.draw_command(model, (first + start) as u32, count as u32)
+        })
+        .collect::<Vec<_>>();
+      counts.push(draws.len() as u32);
+
+      if !camera_instances.is_empty() {
+        instance_regions.push(vk::BufferCopy {
+          src_offset: (instances.len() * size_of::<InstanceData>()) as u64,
+          dst_offset: (self.instances.offset() + first * size_of::<InstanceData>()) as u64,
+          size: (camera_instances.len() * size_of::<InstanceData>()) as u64,
+        });
+        instances.extend(camera_instances.into_iter().map(|instance| instance.data));
+      }
+      if !draws.is_empty() {
+        command_regions.push(vk::BufferCopy {
+          src_offset: (commands.len() * CMD_SIZE) as u64,
+          dst_offset: (self.commands.offset() + first * CMD_SIZE) as u64,
+          size: (draws.len() * CMD_SIZE) as u64,
+        });
+        commands.extend(draws);
+      }
+    }
+
+    if !instances.is_empty() {
+      memory_manager.write_to_buffer_direct(self.instance_buffer, &instances, &instance_regions)?;
+    }
+    if !commands.is_empty() {
+      memory_manager.write_to_buffer_direct(self.command_buffer, &commands, &command_regions)?;
+    }
+    if !counts.is_empty() {
+      memory_manager.write_to_buffer(&self.counts, &counts)?;
+    }
+
+    Ok(resized)
+  }
+
+  /// Draws the instances of the camera with the bound pipeline, the instance vertex binding is replaced
+  pub fn record(
+    &self,
+    device: &ash::Device,
+    buffer: vk::CommandBuffer,
+    memory_manager: &MemoryManager,
+    camera: u32,
+  ) {
+    if camera as usize >= self.cameras {
+      return;
+    }
+
+    let instance_buffer = memory_manager.get_vk_buffer(self.instance_buffer).unwrap();
+    let command_buffer = memory_manager.get_vk_buffer(self.command_buffer).unwrap();
+    unsafe {
+      device.cmd_bind_vertex_buffers(
+        buffer,
+        1,
+        &[instance_buffer],
+        &[self.instances.offset() as u64],
+      );
+      device.cmd_draw_indexed_indirect_count(
+        buffer,
+        command_buffer,
+        (self.commands.offset() + camera as usize * self.capacity * CMD_SIZE) as u64,
+        command_buffer,
+        (self.counts.offset() + camera as usize * size_of::<u32>()) as u64,
+        self.capacity as u32,
+        CMD_SIZE as u32,
+      );
+    }
+  }
+
+  #[inline]
+  pub fn buffer_ids(&self) -> [BufferId; 2] {
+    [self.instance_buffer, self.command_buffer]
+  }
+}
+
+/// Farthest first by view depth, instances at the same depth keep their order
+pub(crate) fn sort_back_to_front(view: glam::Mat4, instances: &mut [TransparentInstance]) {
+  let depth = |instance: &TransparentInstance| -view.transform_point3(instance.center).z;
+  instances.sort_by(|a, b| depth(b).total_cmp(&depth(a)));
+}
+
+/// Runs of consecutive instances of the same model as (model, first, count), each is one instanced draw
+pub(crate) fn batches(instances: &[TransparentInstance]) -> Vec<(ModelHandle, usize, usize)> {
+  let mut batches: Vec<(ModelHandle, usize, usize)> = Vec::new();
+  for (i, instance) in instances.iter().enumerate() {
+    match batches.last_mut() {
+      Some((model, _, count)) if *model == instance.model => *count += 1,
+      _ => batches.push((instance.model, i, 1)),
+    }
+  }
+  batches
+}
+
+#[cfg(test)]
+mod test {
+  use crate::model::model::{InstanceData, ModelHandle};
+
+  use super::{batches, sort_back_to_front, TransparentInstance};
+
+  fn instance(model: u64, center: glam::Vec3, material: u32) -> TransparentInstance {
+    TransparentInstance {
+      model: ModelHandle(model),
+      center,
+      data: InstanceData::new(glam::Mat4::from_translation(center), material, 1, 0),
+    }
+  }
+
+  fn materials(instances: &[TransparentInstance]) -> Vec<u32> {
+    instances
+      .iter()
+      .map(|instance| instance.data.material)
+      .collect()
+  }
+
+  #[test]
+  fn back_to_front() {
+    let view = glam::Mat4::look_at_rh(glam::Vec3::ZERO, glam::Vec3::NEG_Z, glam::Vec3::NEG_Y);
+    let mut instances = vec![
+      instance(0, glam::Vec3::new(0.0, 0.0, -1.0), 0),
+      instance(0, glam::Vec3::new(3.0, 0.0, -5.0), 1),
+      instance(1, glam::Vec3::new(0.0, 0.0, 4.0), 2),
+      instance(1, glam::Vec3::new(0.0, -2.0, -3.0), 3),
+    ];
+    sort_back_to_front(view, &mut instances);
+    assert_eq!(materials(&instances), vec![1, 3, 0, 2]);
+
+    // the order depends on the camera
+    let behind = glam::Mat4::look_at_rh(
+      glam::Vec3::new(0.0, 0.0, 10.0),
+      glam::Vec3::ZERO,
+      glam::Vec3::NEG_Y,
+    );
+    sort_back_to_front(behind, &mut instances);
+    assert_eq!(materials(&instances), vec![1, 3, 0, 2]);
+    let opposite = glam::Mat4::look_at_rh(glam::Vec3::ZERO, glam::Vec3::Z, glam::Vec3::NEG_Y);
+    sort_back_to_front(opposite, &mut instances);
+    assert_eq!(materials(&instances), vec![2, 0, 3, 1]);
+  }
+
+  #[test]
+  fn equal_depth() {
+    let view = glam::Mat4::look_at_rh(glam::Vec3::ZERO, glam::Vec3::NEG_Z, glam::Vec3::NEG_Y);
+    let mut instances = (0..4)
+      .map(|i| instance(0, glam::Vec3::new(i as f32, 0.0, -2.0), i))
+      .collect::<Vec<_>>();
+    sort_back_to_front(view, &mut instances);
+    assert_eq!(materials(&instances), vec![0, 1, 2, 3]);
+  }
+
+  #[test]
+  fn model_batches() {
+    let instances = [0, 0, 1, 0, 0, 0, 2]
+      .into_iter()
+      .map(|model| instance(model, glam::Vec3::ZERO, 0))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      batches(&instances),
+      vec![
+        (ModelHandle(0), 0, 2),
+        (ModelHandle(1), 2, 1),
+        (ModelHandle(0), 3, 3),
+        (ModelHandle(2), 6, 1),
+      ]
+    );
+    assert!(batches(&[]).is_empty());
+  }
+}
@@ -0,0 +1,117 @@
+use std::path::Path;
+
+use anyhow::Error;
+
+use crate::{
+  asset::{Asset, AssetLoader},
+  renderer::TextureHandle,
+};
+
+use super::{loader::ImageFormat, ColorSpace, SamplerDescriptor, TextureData};
+
+/// Texture asset, uploaded to the `TextureManager` once loaded and its slot freed when the last
+/// strong handle is dropped. The texels are moved to the gpu on upload
+#[derive(Debug)]
+pub struct Texture {
+  pub(crate) data: Option<TextureData>,
+  pub(crate) sampler: SamplerDescriptor,
+  pub(crate) handle: Option<TextureHandle>,
+}
+
+/// Loads png, jpeg, tga, hdr, exr, ktx2 and dds files
+#[derive(Clone, Copy, Debug)]
+pub struct TextureLoader {
+  /// Used for 8 bit images and wins over the color space stored in KTX2 and DDS files
+  pub color_space: ColorSpace,
+  pub sampler: SamplerDescriptor,
+}
+
+impl Texture {
+  #[inline]
+  pub fn new(data: TextureData, sampler: impl Into<SamplerDescriptor>) -> Self {
+    Self {
+      data: Some(data),
+      sampler: sampler.into(),
+      handle: None,
+    }
+  }
+
+  /// The texture to use in a `Material`, `None` until the texture is uploaded
+  #[inline]
+  pub fn handle(&self) -> Option<TextureHandle> {
+    self.handle
+  }
+}
+
+impl Asset for Texture {}
+
+impl Default for TextureLoader {
+  fn default() -> Self {
+    Self {
+      color_space: ColorSpace::Srgb,
+      sampler: SamplerDescriptor::linear(),
+    }
+  }
+}
+
+impl AssetLoader for TextureLoader {
+  type Asset = Texture;
+
+  fn extensions(&self) -> &[&str] {
+    &["png", "jpg", "jpeg", "tga", "hdr", "exr", "ktx2", "dds"]
+  }
+
+  fn load(&self, data: Vec<u8>, path: &Path) -> Result<Self::Asset, Error> {
+    let texture = match path
+      .extension()
+      .and_then(|extension| ImageFormat::from_extension(&extension.to_string_lossy()))
+    {
+      Some(format) => TextureData::decode_as(&data, format, self.color_space)?,
+      None => TextureData::decode(&data, self.color_space)?,
+    };
+
+    Ok(Texture::new(texture, self.sampler))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::{io::Cursor, path::Path};
+
+  use super::TextureLoader;
+  use crate::{
+    asset::AssetLoader,
+    texture::{loader::TexelFormat, ColorSpace},
+  };
+
+  #[test]
+  fn load_png() {
+    let mut data = Cursor::new(Vec::new());
+    image::DynamicImage::from(image::RgbaImage::new(4, 2))
+      .write_to(&mut data, image::ImageFormat::Png)
+      .unwrap();
+
+    let loader = TextureLoader {
+      color_space: ColorSpace::Linear,
+      ..Default::default()
+    };
+    let texture = loader
+      .load(data.into_inner(), Path::new("texture.png"))
+      .unwrap();
+    assert!(texture.handle().is_none());
+
+    let data = texture.data.unwrap();
+    assert_eq!((data.width(), data.height()), (4, 2));
+    assert_eq!(data.format(), TexelFormat::Rgba8);
+    assert_eq!(data.color_space(), ColorSpace::Linear);
+  }
+
+  #[test]
+  fn load_invalid() {
+    let loader = TextureLoader::default();
+    assert!(loader
+      .load(vec![1, 2, 3], Path::new("texture.png"))
+      .is_err());
+    assert!(loader.load(vec![1, 2, 3], Path::new("texture")).is_err());
+  }
+}
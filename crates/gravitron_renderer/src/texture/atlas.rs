@@ -0,0 +1,272 @@
+use anyhow::Error;
+
+use crate::error::TextureError;
+
+/// Pixel rectangle inside an atlas
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AtlasRect {
+  pub x: u32,
+  pub y: u32,
+  pub width: u32,
+  pub height: u32,
+}
+
+/// Texture coordinates of a sprite inside an atlas
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UvRect {
+  pub min: glam::Vec2,
+  pub max: glam::Vec2,
+}
+
+struct Shelf {
+  y: u32,
+  height: u32,
+  x: u32,
+}
+
+/// Shelf packer, sprites are placed on the shelf wasting the least height and new shelves are
+/// opened below the last one
+pub struct AtlasPacker {
+  width: u32,
+  height: u32,
+  padding: u32,
+  shelves: Vec<Shelf>,
+}
+
+/// An RGBA image sprites are packed into, upload it with `TextureManager::add_atlas`
+pub struct TextureAtlas {
+  packer: AtlasPacker,
+  pixels: Vec<u8>,
+}
+
+impl UvRect {
+  /// Maps coordinates of the sprite in 0..1 to coordinates in the atlas
+  #[inline]
+  pub fn map(&self, uv: glam::Vec2) -> glam::Vec2 {
+    self.min + (self.max - self.min) * uv
+  }
+}
+
+impl AtlasPacker {
+  #[inline]
+  pub fn new(width: u32, height: u32) -> Self {
+    Self {
+      width,
+      height,
+      padding: 0,
+      shelves: Vec::new(),
+    }
+  }
+
+  /// Empty pixels between sprites to avoid bleeding when sampling with linear filtering
+  #[inline]
+  pub fn with_padding(mut self, padding: u32) -> Self {
+    self.padding = padding;
+    self
+  }
+
+  /// Returns `None` if there is no space left for the sprite
+  pub fn pack(&mut self, width: u32, height: u32) -> Option<AtlasRect> {
+    if width == 0 || height == 0 || width > self.width {
+      return None;
+    }
+
+    let shelf = self
+      .shelves
+      .iter_mut()
+      .filter(|shelf| shelf.height >= height && shelf.x + width <= self.width)
+      .min_by_key(|shelf| shelf.height - height);
+
+    let shelf = match shelf {
+      Some(shelf) => shelf,
+      None => {
+        let y = self
+          .shelves
+          .last()
+          .map_or(0, |shelf| shelf.y + shelf.height + self.padding);
+        if y + height > self.height {
+          return None;
+        }
+
+        self.shelves.push(Shelf { y, height, x: 0 });
+        self.shelves.last_mut().unwrap()
+      }
+    };
+
+    let rect = AtlasRect {
+      x: shelf.x,
+      y: shelf.y,
+      width,
+      height,
+    };
+    shelf.x += width + self.padding;
+    Some(rect)
+  }
+
+  pub fn uv(&self, rect: &AtlasRect) -> UvRect {
+    let size = glam::Vec2::new(self.width as f32, self.height as f32);
+    UvRect {
+      min: glam::Vec2::new(rect.x as f32, rect.y as f32) / size,
+      max: glam::Vec2::new((rect.x + rect.width) as f32, (rect.y + rect.height) as f32) / size,
+    }
+  }
+
+  #[inline]
+  pub fn width(&self) -> u32 {
+    self.width
+  }
+
+  #[inline]
+  pub fn height(&self) -> u32 {
+    self.height
+  }
+}
+
+impl TextureAtlas {
+  pub fn new(width: u32, height: u32) -> Self {
+    Self {
+      packer: AtlasPacker::new(width, height),
+      pixels: vec![0; width as usize * height as usize * 4],
+    }
+  }
+
+  #[inline]
+  pub fn with_padding(mut self, padding: u32) -> Self {
+    self.packer = self.packer.with_padding(padding);
+    self
+  }
+
+  /// `rgba` has to contain `width * height` pixels
+  pub fn add_sprite(&mut self, width: u32, height: u32, rgba: &[u8]) -> Result<UvRect, Error> {
+    let row = width as usize * 4;
+    if rgba.len() != row * height as usize {
+      return Err(TextureError::InvalidSize(width, height).into());
+    }
+    let rect = self
+      .packer
+      .pack(width, height)
+      .ok_or(TextureError::AtlasFull)?;
+
+    let atlas_row = self.packer.width() as usize * 4;
+    for (y, line) in rgba.chunks_exact(row).enumerate() {
+      let start = (rect.y as usize + y) * atlas_row + rect.x as usize * 4;
+      self.pixels[start..start + row].copy_from_slice(line);
+    }
+
+    Ok(self.packer.uv(&rect))
+  }
+
+  /// Decodes an encoded image, e.g. a png, and adds it as sprite
+  pub fn add_image(&mut self, data: &[u8]) -> Result<UvRect, Error> {
+    let image = image::load_from_memory(data)?.to_rgba8();
+    self.add_sprite(image.width(), image.height(), image.as_raw())
+  }
+
+  #[inline]
+  pub fn width(&self) -> u32 {
+    self.packer.width()
+  }
+
+  #[inline]
+  pub fn height(&self) -> u32 {
+    self.packer.height()
+  }
+
+  #[inline]
+  pub fn pixels(&self) -> &[u8] {
+    &self.pixels
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{AtlasPacker, AtlasRect, TextureAtlas};
+
+  fn overlaps(a: &AtlasRect, b: &AtlasRect) -> bool {
+    a.x < b.x + b.width && b.x < a.x + a.width && a.y < b.y + b.height && b.y < a.y + a.height
+  }
+
+  #[test]
+  fn pack_without_overlap() {
+    let mut packer = AtlasPacker::new(64, 64);
+    let sizes = [
+      (16, 16),
+      (32, 8),
+      (8, 8),
+      (16, 16),
+      (64, 8),
+      (10, 20),
+      (8, 4),
+    ];
+
+    let rects = sizes
+      .iter()
+      .map(|(width, height)| packer.pack(*width, *height).unwrap())
+      .collect::<Vec<_>>();
+
+    for (i, a) in rects.iter().enumerate() {
+      assert!(a.x + a.width <= 64 && a.y + a.height <= 64);
+      for b in &rects[i + 1..] {
+        assert!(!overlaps(a, b), "{a:?} overlaps {b:?}");
+      }
+    }
+
+    // the small sprite fills the gap next to the first shelf instead of opening a new one
+    assert_eq!(
+      rects[2],
+      AtlasRect {
+        x: 48,
+        y: 0,
+        width: 8,
+        height: 8
+      }
+    );
+  }
+
+  #[test]
+  fn full_atlas() {
+    let mut packer = AtlasPacker::new(32, 32).with_padding(2);
+    assert_eq!(packer.pack(33, 1), None);
+    assert_eq!(packer.pack(0, 4), None);
+
+    assert_eq!(packer.pack(15, 15).unwrap().x, 0);
+    assert_eq!(packer.pack(15, 15).unwrap().x, 17);
+    assert_eq!(packer.pack(15, 15).unwrap().y, 17);
+    assert_eq!(packer.pack(15, 16), None);
+    assert!(packer.pack(15, 15).is_some());
+    assert_eq!(packer.pack(1, 1), None);
+  }
+
+  #[test]
+  fn uv_rects() {
+    let mut packer = AtlasPacker::new(64, 32);
+    packer.pack(32, 32).unwrap();
+    let rect = packer.pack(16, 8).unwrap();
+    let uv = packer.uv(&rect);
+
+    assert_eq!(uv.min, glam::Vec2::new(0.5, 0.0));
+    assert_eq!(uv.max, glam::Vec2::new(0.75, 0.25));
+    assert_eq!(
+      uv.map(glam::Vec2::new(0.5, 1.0)),
+      glam::Vec2::new(0.625, 0.25)
+    );
+  }
+
+  #[test]
+  fn copy_sprite_pixels() {
+    let mut atlas = TextureAtlas::new(4, 4);
+    atlas.add_sprite(2, 2, &[1; 16]).unwrap();
+    let uv = atlas.add_sprite(1, 2, &[2, 2, 2, 2, 3, 3, 3, 3]).unwrap();
+    assert_eq!(uv.min, glam::Vec2::new(0.5, 0.0));
+
+    let pixel = |x: usize, y: usize| atlas.pixels()[(y * 4 + x) * 4];
+    assert_eq!(pixel(1, 1), 1);
+    assert_eq!(pixel(2, 0), 2);
+    assert_eq!(pixel(2, 1), 3);
+    assert_eq!(pixel(3, 0), 0);
+    assert_eq!(pixel(0, 2), 0);
+
+    assert!(atlas.add_sprite(2, 2, &[0; 4]).is_err());
+    assert!(atlas.add_sprite(4, 4, &[0; 64]).is_err());
+  }
+}
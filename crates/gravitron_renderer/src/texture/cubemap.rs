@@ -0,0 +1,540 @@
+use std::f32::consts::PI;
+
+use anyhow::Error;
+use glam::{Vec2, Vec3, Vec4};
+
+use crate::error::TextureError;
+
+/// Faces in the order vulkan expects them: +X, -X, +Y, -Y, +Z, -Z
+pub const FACES: usize = 6;
+
+/// Linear RGBA texels of the six square faces, each face is stored row by row
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cubemap {
+  size: u32,
+  faces: [Vec<Vec4>; FACES],
+}
+
+impl Cubemap {
+  /// Every face holds `size * size` texels
+  pub fn new(size: u32, faces: [Vec<Vec4>; FACES]) -> Result<Self, Error> {
+    if size == 0
+      || faces
+        .iter()
+        .any(|face| face.len() != size as usize * size as usize)
+    {
+      return Err(TextureError::InvalidSize(size, size).into());
+    }
+
+    Ok(Self { size, faces })
+  }
+
+  /// Evaluates `color` for the direction through the center of every texel
+  pub fn from_fn(size: u32, color: impl Fn(Vec3) -> Vec4) -> Self {
+    let size = size.max(1);
+    let faces = std::array::from_fn(|face| {
+      (0..size * size)
+        .map(|texel| color(texel_direction(face, size, texel % size, texel / size)))
+        .collect()
+    });
+
+    Self { size, faces }
+  }
+
+  /// Projects an equirectangular image onto a cubemap. The top row of the image is +Y and
+  /// its horizontal center faces +X, the forward direction of an unrotated camera
+  pub fn from_equirect(width: u32, height: u32, pixels: &[Vec4], size: u32) -> Result<Self, Error> {
+    if width == 0 || height == 0 || pixels.len() != width as usize * height as usize {
+      return Err(TextureError::InvalidSize(width, height).into());
+    }
+
+    Ok(Self::from_fn(size, |direction| {
+      sample_equirect(width, height, pixels, equirect_uv(direction))
+    }))
+  }
+
+  #[inline]
+  pub fn size(&self) -> u32 {
+    self.size
+  }
+
+  #[inline]
+  pub fn faces(&self) -> &[Vec<Vec4>; FACES] {
+    &self.faces
+  }
+
+  #[inline]
+  pub fn texel(&self, face: usize, x: u32, y: u32) -> Vec4 {
+    self.faces[face][(y * self.size + x) as usize]
+  }
+
+  /// Bilinear sample in the face the direction points at, texels do not blend across edges
+  pub fn sample(&self, direction: Vec3) -> Vec4 {
+    let (face, uv) = direction_to_face(direction);
+    let max = self.size as f32 - 1.0;
+    let x = (uv.x * self.size as f32 - 0.5).clamp(0.0, max);
+    let y = (uv.y * self.size as f32 - 0.5).clamp(0.0, max);
+
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(self.size - 1), (y0 + 1).min(self.size - 1));
+    let (tx, ty) = (x.fract(), y.fract());
+
+    let top = self.texel(face, x0, y0).lerp(self.texel(face, x1, y0), tx);
+    let bottom = self.texel(face, x0, y1).lerp(self.texel(face, x1, y1), tx);
+    top.lerp(bottom, ty)
+  }
+
+  /// Halves every face with a box filter, a 1x1 cubemap stays the same
+  pub fn downsample(&self) -> Self {
+    if self.size == 1 {
+      return self.clone();
+    }
+
+    let size = self.size / 2;
+    let faces = std::array::from_fn(|face| {
+      (0..size * size)
+        .map(|texel| {
+          let (x, y) = ((texel % size) * 2, (texel / size) * 2);
+          (self.texel(face, x, y)
+            + self.texel(face, x + 1, y)
+            + self.texel(face, x, y + 1)
+            + self.texel(face, x + 1, y + 1))
+            / 4.0
+        })
+        .collect()
+    });
+
+    Self { size, faces }
+  }
+
+  /// Cosine weighted average of the incoming light, the diffuse light of a white lambertian
+  /// surface facing each direction. Integrates over the cubemap downsampled to at most 16x16
+  pub fn irradiance(&self, size: u32) -> Self {
+    let mut source = self.clone();
+    while source.size > 16 {
+      source = source.downsample();
+    }
+
+    let samples = (0..FACES)
+      .flat_map(|face| {
+        let source = &source;
+        (0..source.size * source.size).map(move |texel| {
+          let (x, y) = (texel % source.size, texel / source.size);
+          (
+            texel_direction(face, source.size, x, y),
+            source.texel(face, x, y) * texel_solid_angle(source.size, x, y),
+          )
+        })
+      })
+      .collect::<Vec<_>>();
+
+    Self::from_fn(size, |normal| {
+      let mut irradiance = Vec4::ZERO;
+      for (direction, radiance) in &samples {
+        irradiance += *radiance * normal.dot(*direction).max(0.0);
+      }
+      (irradiance / PI).truncate().extend(1.0)
+    })
+  }
+
+  /// Mip chain of `levels` cubemaps starting at `size`, convolved with the GGX distribution.
+  /// Level `i` belongs to a roughness of `i / (levels - 1)`
+  pub fn prefilter(&self, size: u32, levels: u32, samples: u32) -> Vec<Self> {
+    let mut chain = vec![self.clone()];
+    while chain.last().is_some_and(|level| level.size > 1) {
+      chain.push(chain.last().unwrap().downsample());
+    }
+    let source_texel_angle = 4.0 * PI / (FACES as f32 * (self.size * self.size) as f32);
+
+    let levels = levels.max(1);
+    (0..levels)
+      .map(|level| {
+        let size = (size >> level).max(1);
+        if level == 0 {
+          return Self::from_fn(size, |direction| sample_chain(&chain, direction, 0.0));
+        }
+
+        let roughness = level as f32 / (levels - 1) as f32;
+        let alpha = roughness * roughness;
+        Self::from_fn(size, |normal| {
+          let mut color = Vec3::ZERO;
+          let mut weight = 0.0;
+          for i in 0..samples {
+            let half = importance_sample_ggx(hammersley(i, samples), normal, roughness);
+            let light = 2.0 * normal.dot(half) * half - normal;
+            let n_dot_l = normal.dot(light);
+            if n_dot_l <= 0.0 {
+              continue;
+            }
+
+            // samples of less likely directions cover more texels, so they read a smaller mip
+            let pdf = ggx_distribution(normal.dot(half).max(0.0), alpha) / 4.0;
+            let sample_angle = 1.0 / (samples as f32 * pdf + 0.0001);
+            let lod = (0.5 * (sample_angle / source_texel_angle).log2() + 1.0).max(0.0);
+
+            color += sample_chain(&chain, light, lod).truncate() * n_dot_l;
+            weight += n_dot_l;
+          }
+          (color / weight.max(0.0001)).extend(1.0)
+        })
+      })
+      .collect()
+  }
+
+  /// The faces of every cubemap one after another as half floats, how a cube image expects
+  /// its layers in a buffer copy
+  pub(crate) fn to_rgba16f(&self) -> Vec<u8> {
+    self
+      .faces
+      .iter()
+      .flatten()
+      .flat_map(|texel| texel.to_array())
+      .flat_map(|value| half::f16::from_f32(value).to_ne_bytes())
+      .collect()
+  }
+}
+
+/// Direction through `uv` on a face, `uv` goes from 0 to 1 from the top left corner
+pub fn face_direction(face: usize, uv: Vec2) -> Vec3 {
+  let Vec2 { x: s, y: t } = uv * 2.0 - 1.0;
+  match face {
+    0 => Vec3::new(1.0, -t, -s),
+    1 => Vec3::new(-1.0, -t, s),
+    2 => Vec3::new(s, 1.0, t),
+    3 => Vec3::new(s, -1.0, -t),
+    4 => Vec3::new(s, -t, 1.0),
+    _ => Vec3::new(-s, -t, -1.0),
+  }
+  .normalize()
+}
+
+/// The face a direction points at and the position on it, follows the face selection of vulkan
+pub fn direction_to_face(direction: Vec3) -> (usize, Vec2) {
+  let abs = direction.abs();
+  let (face, major, s, t) = if abs.x >= abs.y && abs.x >= abs.z {
+    if direction.x >= 0.0 {
+      (0, abs.x, -direction.z, -direction.y)
+    } else {
+      (1, abs.x, direction.z, -direction.y)
+    }
+  } else if abs.y >= abs.z {
+    if direction.y >= 0.0 {
+      (2, abs.y, direction.x, direction.z)
+    } else {
+      (3, abs.y, direction.x, -direction.z)
+    }
+  } else if direction.z >= 0.0 {
+    (4, abs.z, direction.x, -direction.y)
+  } else {
+    (5, abs.z, -direction.x, -direction.y)
+  };
+
+  let major = major.max(f32::MIN_POSITIVE);
+  (face, (Vec2::new(s, t) / major + 1.0) * 0.5)
+}
+
+/// Position of a direction in an equirectangular image
+pub fn equirect_uv(direction: Vec3) -> Vec2 {
+  let direction = direction.normalize();
+  Vec2::new(
+    0.5 + direction.z.atan2(direction.x) / (2.0 * PI),
+    direction.y.clamp(-1.0, 1.0).acos() / PI,
+  )
+}
+
+/// Inverse of `equirect_uv`
+pub fn equirect_direction(uv: Vec2) -> Vec3 {
+  let phi = (uv.x - 0.5) * 2.0 * PI;
+  let theta = uv.y * PI;
+  Vec3::new(
+    theta.sin() * phi.cos(),
+    theta.cos(),
+    theta.sin() * phi.sin(),
+  )
+}
+
+/// Scale and bias applied to F0 by the split sum approximation of the specular light, indexed
+/// by the cosine between normal and view direction along x and the roughness along y
+pub fn brdf_lut(size: u32, samples: u32) -> Vec<Vec2> {
+  let size = size.max(1);
+  (0..size * size)
+    .map(|texel| {
+      let n_dot_v = ((texel % size) as f32 + 0.5) / size as f32;
+      let roughness = ((texel / size) as f32 + 0.5) / size as f32;
+      integrate_brdf(n_dot_v, roughness, samples)
+    })
+    .collect()
+}
+
+fn integrate_brdf(n_dot_v: f32, roughness: f32, samples: u32) -> Vec2 {
+  let view = Vec3::new((1.0 - n_dot_v * n_dot_v).sqrt(), 0.0, n_dot_v);
+  let mut result = Vec2::ZERO;
+
+  for i in 0..samples {
+    let half = importance_sample_ggx(hammersley(i, samples), Vec3::Z, roughness);
+    let light = 2.0 * view.dot(half) * half - view;
+
+    let n_dot_l = light.z.max(0.0);
+    let n_dot_h = half.z.max(0.0);
+    let v_dot_h = view.dot(half).max(0.0);
+    if n_dot_l <= 0.0 {
+      continue;
+    }
+
+    let visibility = geometry_smith(n_dot_v, n_dot_l, roughness) * v_dot_h / (n_dot_h * n_dot_v);
+    let fresnel = (1.0 - v_dot_h).powi(5);
+    result += Vec2::new(1.0 - fresnel, fresnel) * visibility;
+  }
+
+  result / samples as f32
+}
+
+/// Schlick-GGX with the remapping used for image based lighting
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+  let k = roughness * roughness / 2.0;
+  let schlick = |n_dot_x: f32| n_dot_x / (n_dot_x * (1.0 - k) + k);
+  schlick(n_dot_v) * schlick(n_dot_l)
+}
+
+fn ggx_distribution(n_dot_h: f32, alpha: f32) -> f32 {
+  let alpha2 = alpha * alpha;
+  let denominator = n_dot_h * n_dot_h * (alpha2 - 1.0) + 1.0;
+  alpha2 / (PI * denominator * denominator)
+}
+
+/// Half vector around `normal` distributed like the GGX distribution
+fn importance_sample_ggx(xi: Vec2, normal: Vec3, roughness: f32) -> Vec3 {
+  let alpha = roughness * roughness;
+  let phi = 2.0 * PI * xi.x;
+  let cos_theta = ((1.0 - xi.y) / (1.0 + (alpha * alpha - 1.0) * xi.y)).sqrt();
+  let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+
+  let up = if normal.z.abs() < 0.999 {
+    Vec3::Z
+  } else {
+    Vec3::X
+  };
+  let tangent = up.cross(normal).normalize();
+  let bitangent = normal.cross(tangent);
+
+  (tangent * phi.cos() * sin_theta + bitangent * phi.sin() * sin_theta + normal * cos_theta)
+    .normalize()
+}
+
+/// Low discrepancy point set in the unit square
+fn hammersley(i: u32, count: u32) -> Vec2 {
+  Vec2::new(
+    i as f32 / count as f32,
+    i.reverse_bits() as f32 * 2.328_306_4e-10,
+  )
+}
+
+/// Blends the two mips around `lod`
+fn sample_chain(chain: &[Cubemap], direction: Vec3, lod: f32) -> Vec4 {
+  let lod = lod.min((chain.len() - 1) as f32);
+  let lower = lod.floor() as usize;
+  let upper = (lower + 1).min(chain.len() - 1);
+  chain[lower]
+    .sample(direction)
+    .lerp(chain[upper].sample(direction), lod.fract())
+}
+
+fn texel_direction(face: usize, size: u32, x: u32, y: u32) -> Vec3 {
+  face_direction(
+    face,
+    Vec2::new(
+      (x as f32 + 0.5) / size as f32,
+      (y as f32 + 0.5) / size as f32,
+    ),
+  )
+}
+
+/// Solid angle covered by a texel, texels near the edges of a face cover less
+fn texel_solid_angle(size: u32, x: u32, y: u32) -> f32 {
+  let area = |x: f32, y: f32| (x * y).atan2((x * x + y * y + 1.0).sqrt());
+  let texel = 2.0 / size as f32;
+  let x0 = x as f32 * texel - 1.0;
+  let y0 = y as f32 * texel - 1.0;
+  let (x1, y1) = (x0 + texel, y0 + texel);
+  area(x0, y0) - area(x0, y1) - area(x1, y0) + area(x1, y1)
+}
+
+/// Bilinear sample that wraps horizontally
+fn sample_equirect(width: u32, height: u32, pixels: &[Vec4], uv: Vec2) -> Vec4 {
+  let x = uv.x * width as f32 - 0.5;
+  let y = (uv.y * height as f32 - 0.5).clamp(0.0, height as f32 - 1.0);
+
+  let x0 = x.floor();
+  let (tx, ty) = (x - x0, y.fract());
+  let x0 = (x0 as i64).rem_euclid(width as i64) as usize;
+  let x1 = (x0 + 1) % width as usize;
+  let y0 = y.floor() as usize;
+  let y1 = (y0 + 1).min(height as usize - 1);
+
+  let pixel = |x: usize, y: usize| pixels[y * width as usize + x];
+  let top = pixel(x0, y0).lerp(pixel(x1, y0), tx);
+  let bottom = pixel(x0, y1).lerp(pixel(x1, y1), tx);
+  top.lerp(bottom, ty)
+}
+
+#[cfg(test)]
+mod test {
+  use glam::{Vec2, Vec3, Vec4};
+
+  use super::{
+    brdf_lut, direction_to_face, equirect_direction, equirect_uv, face_direction,
+    texel_solid_angle, Cubemap, FACES,
+  };
+
+  fn assert_close(a: Vec4, b: Vec4, epsilon: f32) {
+    assert!(a.abs_diff_eq(b, epsilon), "{a} != {b}");
+  }
+
+  #[test]
+  fn face_centers() {
+    let centers = [Vec3::X, -Vec3::X, Vec3::Y, -Vec3::Y, Vec3::Z, -Vec3::Z];
+    for (face, center) in centers.into_iter().enumerate() {
+      assert!(face_direction(face, Vec2::splat(0.5)).abs_diff_eq(center, 1e-6));
+      let (found, uv) = direction_to_face(center);
+      assert_eq!(found, face);
+      assert!(uv.abs_diff_eq(Vec2::splat(0.5), 1e-6));
+    }
+  }
+
+  #[test]
+  fn face_round_trip() {
+    for face in 0..FACES {
+      for uv in [
+        Vec2::new(0.1, 0.2),
+        Vec2::new(0.9, 0.3),
+        Vec2::new(0.25, 0.75),
+      ] {
+        let (found, found_uv) = direction_to_face(face_direction(face, uv));
+        assert_eq!(found, face);
+        assert!(found_uv.abs_diff_eq(uv, 1e-5), "{face}: {found_uv} != {uv}");
+      }
+    }
+  }
+
+  #[test]
+  fn equirect_round_trip() {
+    assert!(equirect_uv(Vec3::X).abs_diff_eq(Vec2::new(0.5, 0.5), 1e-6));
+    assert!((equirect_uv(Vec3::Y).y).abs() < 1e-6);
+    assert!((equirect_uv(-Vec3::Y).y - 1.0).abs() < 1e-6);
+
+    for uv in [
+      Vec2::new(0.1, 0.3),
+      Vec2::new(0.6, 0.5),
+      Vec2::new(0.9, 0.8),
+    ] {
+      let direction = equirect_direction(uv);
+      assert!((direction.length() - 1.0).abs() < 1e-5);
+      assert!(equirect_uv(direction).abs_diff_eq(uv, 1e-5));
+    }
+  }
+
+  #[test]
+  fn solid_angles_cover_sphere() {
+    let size = 8;
+    let total = (0..size * size)
+      .map(|texel| texel_solid_angle(size, texel % size, texel / size))
+      .sum::<f32>()
+      * FACES as f32;
+    assert!((total - 4.0 * std::f32::consts::PI).abs() < 1e-4);
+  }
+
+  #[test]
+  fn equirect_to_cubemap() {
+    let (width, height) = (64, 32);
+    // every pixel stores the direction it was projected from
+    let pixels = (0..width * height)
+      .map(|pixel| {
+        let uv = Vec2::new((pixel % width) as f32 + 0.5, (pixel / width) as f32 + 0.5)
+          / Vec2::new(width as f32, height as f32);
+        equirect_direction(uv).extend(1.0)
+      })
+      .collect::<Vec<_>>();
+
+    let cubemap = Cubemap::from_equirect(width, height, &pixels, 8).unwrap();
+    assert_eq!(cubemap.size(), 8);
+    for face in 0..FACES {
+      for (x, y) in [(3, 3), (1, 6), (6, 2)] {
+        let expected = face_direction(face, (Vec2::new(x as f32, y as f32) + 0.5) / 8.0);
+        let texel = cubemap.texel(face, x, y);
+        assert!(
+          texel.truncate().normalize().dot(expected) > 0.99,
+          "{face} {x} {y}: {texel} != {expected}"
+        );
+      }
+    }
+
+    assert!(Cubemap::from_equirect(width, height, &pixels[1..], 8).is_err());
+  }
+
+  #[test]
+  fn constant_environment() {
+    let color = Vec4::new(0.5, 1.0, 2.0, 1.0);
+    let cubemap = Cubemap::from_fn(32, |_| color);
+    assert!(cubemap
+      .faces()
+      .iter()
+      .flatten()
+      .all(|texel| *texel == color));
+    assert_close(cubemap.sample(Vec3::new(0.3, -0.2, 0.9)), color, 1e-6);
+    assert_eq!(cubemap.downsample().size(), 16);
+
+    let irradiance = cubemap.irradiance(4);
+    assert_eq!(irradiance.size(), 4);
+    for texel in irradiance.faces().iter().flatten() {
+      assert_close(*texel, color, 0.01);
+    }
+
+    let prefiltered = cubemap.prefilter(16, 5, 32);
+    assert_eq!(
+      prefiltered.iter().map(Cubemap::size).collect::<Vec<_>>(),
+      vec![16, 8, 4, 2, 1]
+    );
+    for texel in prefiltered
+      .iter()
+      .flat_map(|level| level.faces().iter().flatten())
+    {
+      assert_close(*texel, color, 1e-4);
+    }
+  }
+
+  #[test]
+  fn irradiance_faces_the_light() {
+    let cubemap = Cubemap::from_fn(16, |direction| {
+      Vec4::splat(direction.y.max(0.0)).truncate().extend(1.0)
+    });
+    let irradiance = cubemap.irradiance(4);
+    let up = irradiance.sample(Vec3::Y).x;
+    let side = irradiance.sample(Vec3::X).x;
+    let down = irradiance.sample(-Vec3::Y).x;
+    assert!(up > side && side > down, "{up} {side} {down}");
+    // texels next to -Y still see a sliver of the upper hemisphere
+    assert!(down < up * 0.05, "{up} {down}");
+  }
+
+  #[test]
+  fn lut_range() {
+    let lut = brdf_lut(8, 64);
+    assert_eq!(lut.len(), 64);
+    for value in &lut {
+      assert!(
+        value.x >= 0.0 && value.y >= 0.0 && value.x + value.y <= 1.01,
+        "{value}"
+      );
+    }
+    // smooth surfaces reflect almost everything at normal incidence
+    assert!(lut[7].x + lut[7].y > 0.9);
+  }
+
+  #[test]
+  fn half_float_faces() {
+    let cubemap = Cubemap::from_fn(2, |_| Vec4::new(1.0, 0.5, 0.25, 1.0));
+    let bytes = cubemap.to_rgba16f();
+    assert_eq!(bytes.len(), FACES * 4 * 4 * 2);
+    assert_eq!(half::f16::from_ne_bytes([bytes[2], bytes[3]]).to_f32(), 0.5);
+  }
+}
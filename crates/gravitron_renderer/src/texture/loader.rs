@@ -0,0 +1,758 @@
+use std::path::Path;
+
+use anyhow::Error;
+use ash::vk;
+
+use crate::error::TextureError;
+
+use super::mipmap;
+
+/// How the color values of a texture are stored. Albedo and emissive maps are usually sRGB,
+/// normal, roughness and other data maps linear
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+  #[default]
+  Srgb,
+  Linear,
+}
+
+/// Container format of an encoded texture
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ImageFormat {
+  Png,
+  Jpeg,
+  Tga,
+  Hdr,
+  Exr,
+  Ktx2,
+  Dds,
+}
+
+/// Layout of the texels after decoding
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TexelFormat {
+  Rgba8,
+  Rgba16Float,
+  Rgba32Float,
+  Bc1,
+  Bc2,
+  Bc3,
+  Bc4,
+  Bc5,
+  Bc6h,
+  Bc7,
+}
+
+/// Decoded texture ready for upload, `levels` holds the base level followed by pre-baked mips
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextureData {
+  width: u32,
+  height: u32,
+  format: TexelFormat,
+  color_space: ColorSpace,
+  levels: Vec<Vec<u8>>,
+}
+
+const KTX2_MAGIC: [u8; 12] = [
+  0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+const TGA_FOOTER: &[u8] = b"TRUEVISION-XFILE.\0";
+
+impl ImageFormat {
+  /// Detects the format from the magic bytes. TGA files without footer are detected from their
+  /// header, which is ambiguous, so prefer `from_extension` if the file name is known
+  pub fn detect(data: &[u8]) -> Option<Self> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+      Some(Self::Png)
+    } else if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+      Some(Self::Jpeg)
+    } else if data.starts_with(b"#?RADIANCE") || data.starts_with(b"#?RGBE") {
+      Some(Self::Hdr)
+    } else if data.starts_with(&[0x76, 0x2F, 0x31, 0x01]) {
+      Some(Self::Exr)
+    } else if data.starts_with(&KTX2_MAGIC) {
+      Some(Self::Ktx2)
+    } else if data.starts_with(b"DDS ") {
+      Some(Self::Dds)
+    } else if data.ends_with(TGA_FOOTER) || is_tga_header(data) {
+      Some(Self::Tga)
+    } else {
+      None
+    }
+  }
+
+  pub fn from_extension(extension: &str) -> Option<Self> {
+    match extension.to_ascii_lowercase().as_str() {
+      "png" => Some(Self::Png),
+      "jpg" | "jpeg" => Some(Self::Jpeg),
+      "tga" => Some(Self::Tga),
+      "hdr" => Some(Self::Hdr),
+      "exr" => Some(Self::Exr),
+      "ktx2" => Some(Self::Ktx2),
+      "dds" => Some(Self::Dds),
+      _ => None,
+    }
+  }
+}
+
+impl TexelFormat {
+  pub const ALL: [TexelFormat; 10] = [
+    Self::Rgba8,
+    Self::Rgba16Float,
+    Self::Rgba32Float,
+    Self::Bc1,
+    Self::Bc2,
+    Self::Bc3,
+    Self::Bc4,
+    Self::Bc5,
+    Self::Bc6h,
+    Self::Bc7,
+  ];
+
+  /// Formats without an sRGB variant ignore the color space
+  pub fn vk_format(self, color_space: ColorSpace) -> vk::Format {
+    let srgb = color_space == ColorSpace::Srgb;
+    match self {
+      Self::Rgba8 if srgb => vk::Format::R8G8B8A8_SRGB,
+      Self::Rgba8 => vk::Format::R8G8B8A8_UNORM,
+      Self::Rgba16Float => vk::Format::R16G16B16A16_SFLOAT,
+      Self::Rgba32Float => vk::Format::R32G32B32A32_SFLOAT,
+      Self::Bc1 if srgb => vk::Format::BC1_RGBA_SRGB_BLOCK,
+      Self::Bc1 => vk::Format::BC1_RGBA_UNORM_BLOCK,
+      Self::Bc2 if srgb => vk::Format::BC2_SRGB_BLOCK,
+      Self::Bc2 => vk::Format::BC2_UNORM_BLOCK,
+      Self::Bc3 if srgb => vk::Format::BC3_SRGB_BLOCK,
+      Self::Bc3 => vk::Format::BC3_UNORM_BLOCK,
+      Self::Bc4 => vk::Format::BC4_UNORM_BLOCK,
+      Self::Bc5 => vk::Format::BC5_UNORM_BLOCK,
+      Self::Bc6h => vk::Format::BC6H_UFLOAT_BLOCK,
+      Self::Bc7 if srgb => vk::Format::BC7_SRGB_BLOCK,
+      Self::Bc7 => vk::Format::BC7_UNORM_BLOCK,
+    }
+  }
+
+  #[inline]
+  pub fn is_compressed(self) -> bool {
+    !matches!(self, Self::Rgba8 | Self::Rgba16Float | Self::Rgba32Float)
+  }
+
+  /// Size in bytes of one pixel or one 4x4 block for compressed formats
+  pub fn block_size(self) -> usize {
+    match self {
+      Self::Rgba8 => 4,
+      Self::Rgba16Float | Self::Bc1 | Self::Bc4 => 8,
+      Self::Rgba32Float | Self::Bc2 | Self::Bc3 | Self::Bc5 | Self::Bc6h | Self::Bc7 => 16,
+    }
+  }
+
+  /// Size in bytes of an image with the given extent
+  pub fn level_size(self, width: u32, height: u32) -> usize {
+    let (width, height) = if self.is_compressed() {
+      (width.div_ceil(4), height.div_ceil(4))
+    } else {
+      (width, height)
+    };
+    width.max(1) as usize * height.max(1) as usize * self.block_size()
+  }
+
+  fn from_ktx2(format: ktx2::Format) -> Option<Self> {
+    Some(match format {
+      ktx2::Format::R8G8B8A8_UNORM | ktx2::Format::R8G8B8A8_SRGB => Self::Rgba8,
+      ktx2::Format::R16G16B16A16_SFLOAT => Self::Rgba16Float,
+      ktx2::Format::R32G32B32A32_SFLOAT => Self::Rgba32Float,
+      ktx2::Format::BC1_RGBA_UNORM_BLOCK | ktx2::Format::BC1_RGBA_SRGB_BLOCK => Self::Bc1,
+      ktx2::Format::BC2_UNORM_BLOCK | ktx2::Format::BC2_SRGB_BLOCK => Self::Bc2,
+      ktx2::Format::BC3_UNORM_BLOCK | ktx2::Format::BC3_SRGB_BLOCK => Self::Bc3,
+      ktx2::Format::BC4_UNORM_BLOCK => Self::Bc4,
+      ktx2::Format::BC5_UNORM_BLOCK => Self::Bc5,
+      ktx2::Format::BC6H_UFLOAT_BLOCK => Self::Bc6h,
+      ktx2::Format::BC7_UNORM_BLOCK | ktx2::Format::BC7_SRGB_BLOCK => Self::Bc7,
+      _ => return None,
+    })
+  }
+
+  /// The second value is true for BGRA data
+  fn from_dds(dds: &ddsfile::Dds) -> Option<(Self, bool)> {
+    use ddsfile::{D3DFormat, DxgiFormat};
+
+    if let Some(format) = dds.get_dxgi_format() {
+      return Some(match format {
+        DxgiFormat::R8G8B8A8_UNorm | DxgiFormat::R8G8B8A8_UNorm_sRGB => (Self::Rgba8, false),
+        DxgiFormat::B8G8R8A8_UNorm | DxgiFormat::B8G8R8A8_UNorm_sRGB => (Self::Rgba8, true),
+        DxgiFormat::R16G16B16A16_Float => (Self::Rgba16Float, false),
+        DxgiFormat::R32G32B32A32_Float => (Self::Rgba32Float, false),
+        DxgiFormat::BC1_UNorm | DxgiFormat::BC1_UNorm_sRGB => (Self::Bc1, false),
+        DxgiFormat::BC2_UNorm | DxgiFormat::BC2_UNorm_sRGB => (Self::Bc2, false),
+        DxgiFormat::BC3_UNorm | DxgiFormat::BC3_UNorm_sRGB => (Self::Bc3, false),
+        DxgiFormat::BC4_UNorm => (Self::Bc4, false),
+        DxgiFormat::BC5_UNorm => (Self::Bc5, false),
+        DxgiFormat::BC6H_UF16 => (Self::Bc6h, false),
+        DxgiFormat::BC7_UNorm | DxgiFormat::BC7_UNorm_sRGB => (Self::Bc7, false),
+        _ => return None,
+      });
+    }
+
+    Some(match dds.get_d3d_format()? {
+      D3DFormat::A8B8G8R8 => (Self::Rgba8, false),
+      D3DFormat::A8R8G8B8 => (Self::Rgba8, true),
+      D3DFormat::DXT1 => (Self::Bc1, false),
+      D3DFormat::DXT2 | D3DFormat::DXT3 => (Self::Bc2, false),
+      D3DFormat::DXT4 | D3DFormat::DXT5 => (Self::Bc3, false),
+      _ => return None,
+    })
+  }
+}
+
+impl TextureData {
+  /// `pixels` holds `width * height` RGBA pixels
+  pub fn from_rgba8(
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+    color_space: ColorSpace,
+  ) -> Result<Self, Error> {
+    if width == 0 || height == 0 || pixels.len() != TexelFormat::Rgba8.level_size(width, height) {
+      return Err(TextureError::InvalidSize(width, height).into());
+    }
+
+    Ok(Self {
+      width,
+      height,
+      format: TexelFormat::Rgba8,
+      color_space,
+      levels: vec![pixels],
+    })
+  }
+
+  /// `pixels` holds `width * height` linear RGBA pixels, e.g. a procedural HDR environment
+  pub fn from_rgba32f(width: u32, height: u32, pixels: Vec<f32>) -> Result<Self, Error> {
+    if width == 0 || height == 0 || pixels.len() != width as usize * height as usize * 4 {
+      return Err(TextureError::InvalidSize(width, height).into());
+    }
+
+    Ok(Self {
+      width,
+      height,
+      format: TexelFormat::Rgba32Float,
+      color_space: ColorSpace::Linear,
+      levels: vec![pixels
+        .iter()
+        .flat_map(|value| value.to_ne_bytes())
+        .collect()],
+    })
+  }
+
+  /// `pixels` holds `width * height` RGBA half floats
+  pub(crate) fn from_rgba16f(width: u32, height: u32, pixels: Vec<u8>) -> Result<Self, Error> {
+    texture_with_levels(
+      width,
+      height,
+      TexelFormat::Rgba16Float,
+      ColorSpace::Linear,
+      vec![pixels],
+    )
+  }
+
+  /// Detects the format from the data. `color_space` wins over the color space stored in
+  /// KTX2 and DDS files, HDR and EXR images are always linear
+  pub fn decode(data: &[u8], color_space: ColorSpace) -> Result<Self, Error> {
+    let format = ImageFormat::detect(data).ok_or(TextureError::UnknownFormat)?;
+    Self::decode_as(data, format, color_space)
+  }
+
+  pub fn decode_as(
+    data: &[u8],
+    format: ImageFormat,
+    color_space: ColorSpace,
+  ) -> Result<Self, Error> {
+    match format {
+      ImageFormat::Png => decode_image(data, image::ImageFormat::Png, color_space),
+      ImageFormat::Jpeg => decode_image(data, image::ImageFormat::Jpeg, color_space),
+      ImageFormat::Tga => decode_image(data, image::ImageFormat::Tga, color_space),
+      ImageFormat::Hdr => decode_float_image(data, image::ImageFormat::Hdr),
+      ImageFormat::Exr => decode_float_image(data, image::ImageFormat::OpenExr),
+      ImageFormat::Ktx2 => decode_ktx2(data, color_space),
+      ImageFormat::Dds => decode_dds(data, color_space),
+    }
+  }
+
+  /// The format is taken from the file extension, the content is used if it is unknown
+  pub fn load_file(path: impl AsRef<Path>, color_space: ColorSpace) -> Result<Self, Error> {
+    let path = path.as_ref();
+    let data = std::fs::read(path)?;
+
+    match path
+      .extension()
+      .and_then(|extension| ImageFormat::from_extension(&extension.to_string_lossy()))
+    {
+      Some(format) => Self::decode_as(&data, format, color_space),
+      None => Self::decode(&data, color_space),
+    }
+  }
+
+  /// Fills the mip chain on the cpu, returns false for formats that can not be downsampled
+  pub fn generate_mips(&mut self) -> bool {
+    let base = &self.levels[0];
+    let chain = match (self.format, self.color_space) {
+      (TexelFormat::Rgba8, ColorSpace::Srgb) => {
+        mipmap::generate_mip_chain(self.width, self.height, base)
+      }
+      (TexelFormat::Rgba8, ColorSpace::Linear) => {
+        mipmap::generate_mip_chain_linear(self.width, self.height, base)
+      }
+      (TexelFormat::Rgba32Float, _) => {
+        let floats = base
+          .chunks_exact(4)
+          .map(|value| f32::from_ne_bytes(value.try_into().unwrap()))
+          .collect::<Vec<_>>();
+        mipmap::generate_mip_chain_f32(self.width, self.height, &floats)
+          .into_iter()
+          .map(|level| level.iter().flat_map(|value| value.to_ne_bytes()).collect())
+          .collect()
+      }
+      _ => return false,
+    };
+
+    self.levels.truncate(1);
+    self.levels.extend(chain);
+    true
+  }
+
+  #[inline]
+  pub fn width(&self) -> u32 {
+    self.width
+  }
+
+  #[inline]
+  pub fn height(&self) -> u32 {
+    self.height
+  }
+
+  #[inline]
+  pub fn format(&self) -> TexelFormat {
+    self.format
+  }
+
+  #[inline]
+  pub fn color_space(&self) -> ColorSpace {
+    self.color_space
+  }
+
+  #[inline]
+  pub fn vk_format(&self) -> vk::Format {
+    self.format.vk_format(self.color_space)
+  }
+
+  #[inline]
+  pub fn levels(&self) -> &[Vec<u8>] {
+    &self.levels
+  }
+
+  /// The base level as linear colors, `None` for compressed formats
+  pub(crate) fn linear_texels(&self) -> Option<Vec<glam::Vec4>> {
+    let base = &self.levels[0];
+    Some(match (self.format, self.color_space) {
+      (TexelFormat::Rgba8, ColorSpace::Srgb) => base
+        .chunks_exact(4)
+        .map(|texel| {
+          glam::Vec4::new(
+            mipmap::srgb_to_linear(texel[0]),
+            mipmap::srgb_to_linear(texel[1]),
+            mipmap::srgb_to_linear(texel[2]),
+            texel[3] as f32 / 255.0,
+          )
+        })
+        .collect(),
+      (TexelFormat::Rgba8, ColorSpace::Linear) => base
+        .chunks_exact(4)
+        .map(|texel| glam::Vec4::from_array(std::array::from_fn(|i| texel[i] as f32 / 255.0)))
+        .collect(),
+      (TexelFormat::Rgba16Float, _) => base
+        .chunks_exact(8)
+        .map(|texel| {
+          glam::Vec4::from_array(std::array::from_fn(|i| {
+            half::f16::from_ne_bytes([texel[i * 2], texel[i * 2 + 1]]).to_f32()
+          }))
+        })
+        .collect(),
+      (TexelFormat::Rgba32Float, _) => base
+        .chunks_exact(16)
+        .map(|texel| {
+          glam::Vec4::from_array(std::array::from_fn(|i| {
+            f32::from_ne_bytes(texel[i * 4..i * 4 + 4].try_into().unwrap())
+          }))
+        })
+        .collect(),
+      _ => return None,
+    })
+  }
+
+  #[inline]
+  pub(crate) fn truncate_levels(&mut self, count: usize) {
+    self.levels.truncate(count.max(1));
+  }
+}
+
+fn is_tga_header(data: &[u8]) -> bool {
+  data.len() >= 18
+    && data[1] <= 1
+    && matches!(data[2], 1 | 2 | 3 | 9 | 10 | 11)
+    && u16::from_le_bytes([data[12], data[13]]) > 0
+    && u16::from_le_bytes([data[14], data[15]]) > 0
+    && matches!(data[16], 8 | 15 | 16 | 24 | 32)
+}
+
+fn decode_image(
+  data: &[u8],
+  format: image::ImageFormat,
+  color_space: ColorSpace,
+) -> Result<TextureData, Error> {
+  let image = image::load_from_memory_with_format(data, format)?.to_rgba8();
+  TextureData::from_rgba8(image.width(), image.height(), image.into_raw(), color_space)
+}
+
+fn decode_float_image(data: &[u8], format: image::ImageFormat) -> Result<TextureData, Error> {
+  let image = image::load_from_memory_with_format(data, format)?.to_rgba32f();
+
+  Ok(TextureData {
+    width: image.width(),
+    height: image.height(),
+    format: TexelFormat::Rgba32Float,
+    color_space: ColorSpace::Linear,
+    levels: vec![image.iter().flat_map(|value| value.to_ne_bytes()).collect()],
+  })
+}
+
+fn decode_ktx2(data: &[u8], color_space: ColorSpace) -> Result<TextureData, Error> {
+  let reader = ktx2::Reader::new(data).map_err(|err| TextureError::InvalidData(err.to_string()))?;
+  let header = reader.header();
+
+  if header.supercompression_scheme.is_some() {
+    return Err(TextureError::Unsupported("supercompressed KTX2".into()).into());
+  }
+  if header.pixel_depth > 1 || header.layer_count > 1 || header.face_count != 1 {
+    return Err(TextureError::Unsupported("KTX2 arrays, cubemaps and 3d textures".into()).into());
+  }
+  let format = header
+    .format
+    .and_then(TexelFormat::from_ktx2)
+    .ok_or_else(|| TextureError::Unsupported(format!("KTX2 format {:?}", header.format)))?;
+
+  let levels = reader
+    .levels()
+    .map(|level| level.data.to_vec())
+    .collect::<Vec<_>>();
+  texture_with_levels(
+    header.pixel_width,
+    header.pixel_height.max(1),
+    format,
+    color_space,
+    levels,
+  )
+}
+
+fn decode_dds(data: &[u8], color_space: ColorSpace) -> Result<TextureData, Error> {
+  let dds = ddsfile::Dds::read(data).map_err(|err| TextureError::InvalidData(err.to_string()))?;
+
+  if dds.get_depth() > 1 || dds.get_num_array_layers() > 1 {
+    return Err(TextureError::Unsupported("DDS arrays, cubemaps and 3d textures".into()).into());
+  }
+  let (format, bgra) = TexelFormat::from_dds(&dds).ok_or_else(|| {
+    TextureError::Unsupported(match dds.get_dxgi_format() {
+      Some(format) => format!("DDS format {format:?}"),
+      None => format!("DDS format {:?}", dds.get_d3d_format()),
+    })
+  })?;
+
+  let (width, height) = (dds.get_width(), dds.get_height());
+  let mut offset = 0;
+  let mut levels = Vec::new();
+  for level in 0..dds.get_num_mipmap_levels().max(1) {
+    let (level_width, level_height) = mipmap::mip_extent(width, height, level);
+    let size = format.level_size(level_width, level_height);
+    let Some(pixels) = dds.data.get(offset..offset + size) else {
+      return Err(TextureError::InvalidData(format!("DDS mip level {level} is truncated")).into());
+    };
+
+    let mut pixels = pixels.to_vec();
+    if bgra {
+      pixels
+        .chunks_exact_mut(4)
+        .for_each(|pixel| pixel.swap(0, 2));
+    }
+    levels.push(pixels);
+    offset += size;
+  }
+
+  texture_with_levels(width, height, format, color_space, levels)
+}
+
+fn texture_with_levels(
+  width: u32,
+  height: u32,
+  format: TexelFormat,
+  color_space: ColorSpace,
+  levels: Vec<Vec<u8>>,
+) -> Result<TextureData, Error> {
+  if width == 0 || levels.len() as u32 > mipmap::mip_levels(width, height) {
+    return Err(TextureError::InvalidSize(width, height).into());
+  }
+
+  for (level, pixels) in levels.iter().enumerate() {
+    let (level_width, level_height) = mipmap::mip_extent(width, height, level as u32);
+    if pixels.len() != format.level_size(level_width, level_height) {
+      return Err(
+        TextureError::InvalidData(format!(
+          "mip level {level} has {} bytes, expected {}",
+          pixels.len(),
+          format.level_size(level_width, level_height)
+        ))
+        .into(),
+      );
+    }
+  }
+
+  Ok(TextureData {
+    width,
+    height,
+    format,
+    color_space: if format.vk_format(ColorSpace::Srgb) == format.vk_format(ColorSpace::Linear) {
+      ColorSpace::Linear
+    } else {
+      color_space
+    },
+    levels,
+  })
+}
+
+#[cfg(test)]
+mod test {
+  use std::io::Cursor;
+
+  use ash::vk;
+
+  use super::{ColorSpace, ImageFormat, TexelFormat, TextureData, KTX2_MAGIC};
+
+  fn encode(image: image::DynamicImage, format: image::ImageFormat) -> Vec<u8> {
+    let mut data = Cursor::new(Vec::new());
+    image.write_to(&mut data, format).unwrap();
+    data.into_inner()
+  }
+
+  fn rgba_image() -> image::DynamicImage {
+    image::RgbaImage::from_fn(4, 2, |x, y| {
+      image::Rgba([x as u8 * 60, y as u8 * 200, 30, 255])
+    })
+    .into()
+  }
+
+  fn float_image() -> image::DynamicImage {
+    image::Rgb32FImage::from_fn(2, 2, |x, _| image::Rgb([x as f32 * 4.0, 0.5, 0.25])).into()
+  }
+
+  fn ktx2(format: ktx2::Format, width: u32, height: u32, levels: &[Vec<u8>]) -> Vec<u8> {
+    let index_end = ktx2::Header::LENGTH + levels.len() * ktx2::LevelIndex::LENGTH;
+    let dfd = 4u32.to_le_bytes();
+    let header = ktx2::Header {
+      format: Some(format),
+      type_size: 1,
+      pixel_width: width,
+      pixel_height: height,
+      pixel_depth: 0,
+      layer_count: 0,
+      face_count: 1,
+      level_count: levels.len() as u32,
+      supercompression_scheme: None,
+      index: ktx2::Index {
+        dfd_byte_offset: index_end as u32,
+        dfd_byte_length: dfd.len() as u32,
+        kvd_byte_offset: 0,
+        kvd_byte_length: 0,
+        sgd_byte_offset: 0,
+        sgd_byte_length: 0,
+      },
+    };
+
+    let mut data = header.as_bytes().to_vec();
+    let mut offset = (index_end + dfd.len()) as u64;
+    for level in levels {
+      let index = ktx2::LevelIndex {
+        byte_offset: offset,
+        byte_length: level.len() as u64,
+        uncompressed_byte_length: level.len() as u64,
+      };
+      data.extend(index.as_bytes());
+      offset += level.len() as u64;
+    }
+    data.extend(dfd);
+    data.extend(levels.concat());
+    data
+  }
+
+  fn dds(format: ddsfile::DxgiFormat, width: u32, height: u32, levels: u32) -> ddsfile::Dds {
+    ddsfile::Dds::new_dxgi(ddsfile::NewDxgiParams {
+      height,
+      width,
+      depth: None,
+      format,
+      mipmap_levels: Some(levels),
+      array_layers: None,
+      caps2: None,
+      is_cubemap: false,
+      resource_dimension: ddsfile::D3D10ResourceDimension::Texture2D,
+      alpha_mode: ddsfile::AlphaMode::Unknown,
+    })
+    .unwrap()
+  }
+
+  #[test]
+  fn detect_formats() {
+    let formats = [
+      (image::ImageFormat::Png, ImageFormat::Png),
+      (image::ImageFormat::Jpeg, ImageFormat::Jpeg),
+      (image::ImageFormat::Tga, ImageFormat::Tga),
+    ];
+    for (encoding, format) in formats {
+      let data = encode(rgba_image().to_rgb8().into(), encoding);
+      assert_eq!(ImageFormat::detect(&data), Some(format));
+    }
+    let hdr = encode(float_image(), image::ImageFormat::Hdr);
+    assert_eq!(ImageFormat::detect(&hdr), Some(ImageFormat::Hdr));
+    let exr = encode(float_image(), image::ImageFormat::OpenExr);
+    assert_eq!(ImageFormat::detect(&exr), Some(ImageFormat::Exr));
+    assert_eq!(ImageFormat::detect(&KTX2_MAGIC), Some(ImageFormat::Ktx2));
+    assert_eq!(ImageFormat::detect(b"DDS \0\0"), Some(ImageFormat::Dds));
+    assert_eq!(ImageFormat::detect(b"not an image"), None);
+
+    assert_eq!(ImageFormat::from_extension("JPG"), Some(ImageFormat::Jpeg));
+    assert_eq!(ImageFormat::from_extension("ktx2"), Some(ImageFormat::Ktx2));
+    assert_eq!(ImageFormat::from_extension("gif"), None);
+  }
+
+  #[test]
+  fn decode_color_images() {
+    let png = encode(rgba_image(), image::ImageFormat::Png);
+    let texture = TextureData::decode(&png, ColorSpace::Srgb).unwrap();
+    assert_eq!((texture.width(), texture.height()), (4, 2));
+    assert_eq!(texture.vk_format(), vk::Format::R8G8B8A8_SRGB);
+    assert_eq!(&texture.levels()[0][4..8], &[60, 0, 30, 255]);
+
+    let tga = encode(rgba_image(), image::ImageFormat::Tga);
+    let texture = TextureData::decode_as(&tga, ImageFormat::Tga, ColorSpace::Linear).unwrap();
+    assert_eq!(texture.vk_format(), vk::Format::R8G8B8A8_UNORM);
+    assert_eq!(texture.levels()[0], rgba_image().to_rgba8().into_raw());
+
+    let jpeg = encode(rgba_image().to_rgb8().into(), image::ImageFormat::Jpeg);
+    let texture = TextureData::decode(&jpeg, ColorSpace::Srgb).unwrap();
+    assert_eq!(texture.levels()[0].len(), 4 * 2 * 4);
+
+    assert!(TextureData::decode(b"not an image", ColorSpace::Srgb).is_err());
+    assert!(TextureData::decode(&png[..png.len() / 2], ColorSpace::Srgb).is_err());
+  }
+
+  #[test]
+  fn decode_hdr_images() {
+    for format in [image::ImageFormat::Hdr, image::ImageFormat::OpenExr] {
+      let data = encode(float_image(), format);
+      // float images are always linear
+      let texture = TextureData::decode(&data, ColorSpace::Srgb).unwrap();
+      assert_eq!(texture.format(), TexelFormat::Rgba32Float);
+      assert_eq!(texture.color_space(), ColorSpace::Linear);
+      assert_eq!(texture.vk_format(), vk::Format::R32G32B32A32_SFLOAT);
+
+      let pixel = texture.levels()[0][16..32]
+        .chunks_exact(4)
+        .map(|value| f32::from_ne_bytes(value.try_into().unwrap()))
+        .collect::<Vec<_>>();
+      assert!((pixel[0] - 4.0).abs() < 0.1, "{format:?} {pixel:?}");
+      assert!((pixel[2] - 0.25).abs() < 0.01, "{format:?} {pixel:?}");
+      assert_eq!(pixel[3], 1.0);
+    }
+  }
+
+  #[test]
+  fn linear_texels() {
+    let srgb = TextureData::from_rgba8(1, 1, vec![255, 188, 0, 51], ColorSpace::Srgb).unwrap();
+    let texel = srgb.linear_texels().unwrap()[0];
+    assert!((texel.x - 1.0).abs() < 1e-6 && (texel.y - 0.5).abs() < 0.01);
+    assert!((texel.w - 0.2).abs() < 1e-6);
+
+    let float = TextureData::from_rgba32f(1, 1, vec![4.0, 0.5, 0.25, 1.0]).unwrap();
+    assert_eq!(float.color_space(), ColorSpace::Linear);
+    assert_eq!(
+      float.linear_texels().unwrap()[0],
+      glam::Vec4::new(4.0, 0.5, 0.25, 1.0)
+    );
+    assert!(TextureData::from_rgba32f(2, 1, vec![0.0; 4]).is_err());
+  }
+
+  #[test]
+  fn decode_ktx2() {
+    let levels = vec![vec![1; 64], vec![2; 16]];
+    let data = ktx2(ktx2::Format::BC7_UNORM_BLOCK, 8, 8, &levels);
+    let texture = TextureData::decode(&data, ColorSpace::Srgb).unwrap();
+    assert_eq!(texture.format(), TexelFormat::Bc7);
+    assert_eq!(texture.levels(), &levels[..]);
+    // the requested color space wins over the one of the file
+    assert_eq!(texture.vk_format(), vk::Format::BC7_SRGB_BLOCK);
+    let texture = TextureData::decode(&data, ColorSpace::Linear).unwrap();
+    assert_eq!(texture.vk_format(), vk::Format::BC7_UNORM_BLOCK);
+
+    let data = ktx2(ktx2::Format::BC7_UNORM_BLOCK, 8, 8, &[vec![0; 48]]);
+    assert!(TextureData::decode(&data, ColorSpace::Srgb).is_err());
+    let data = ktx2(ktx2::Format::R8G8_UNORM, 1, 1, &[vec![0; 2]]);
+    assert!(TextureData::decode(&data, ColorSpace::Srgb).is_err());
+  }
+
+  #[test]
+  fn decode_dds() {
+    let mut file = dds(ddsfile::DxgiFormat::BC1_UNorm_sRGB, 8, 8, 4);
+    file.data = [vec![1; 32], vec![2; 8], vec![3; 8], vec![4; 8]].concat();
+    let mut data = Vec::new();
+    file.write(&mut data).unwrap();
+
+    let texture = TextureData::decode(&data, ColorSpace::Linear).unwrap();
+    assert_eq!(texture.format(), TexelFormat::Bc1);
+    assert_eq!(texture.vk_format(), vk::Format::BC1_RGBA_UNORM_BLOCK);
+    let sizes = texture.levels().iter().map(Vec::len).collect::<Vec<_>>();
+    assert_eq!(sizes, [32, 8, 8, 8]);
+    assert_eq!(texture.levels()[3], vec![4; 8]);
+
+    let mut file = dds(ddsfile::DxgiFormat::B8G8R8A8_UNorm, 1, 1, 1);
+    file.data = vec![1, 2, 3, 4];
+    let mut data = Vec::new();
+    file.write(&mut data).unwrap();
+    let texture = TextureData::decode(&data, ColorSpace::Srgb).unwrap();
+    assert_eq!(texture.levels()[0], vec![3, 2, 1, 4]);
+
+    data.truncate(data.len() - 1);
+    assert!(TextureData::decode(&data, ColorSpace::Srgb).is_err());
+  }
+
+  #[test]
+  fn level_sizes_and_mips() {
+    assert_eq!(TexelFormat::Rgba8.level_size(3, 5), 60);
+    assert_eq!(TexelFormat::Bc1.level_size(1, 1), 8);
+    assert_eq!(TexelFormat::Bc7.level_size(5, 9), 2 * 3 * 16);
+    assert_eq!(TexelFormat::Rgba32Float.level_size(2, 2), 64);
+
+    let mut texture = TextureData::from_rgba8(4, 4, vec![128; 64], ColorSpace::Linear).unwrap();
+    assert!(texture.generate_mips());
+    let sizes = texture.levels().iter().map(Vec::len).collect::<Vec<_>>();
+    assert_eq!(sizes, [64, 16, 4]);
+    assert!(texture.levels()[2].iter().all(|value| *value == 128));
+
+    let data = encode(float_image(), image::ImageFormat::OpenExr);
+    let mut texture = TextureData::decode(&data, ColorSpace::Linear).unwrap();
+    assert!(texture.generate_mips());
+    assert_eq!(texture.levels()[1].len(), 16);
+    let red = f32::from_ne_bytes(texture.levels()[1][0..4].try_into().unwrap());
+    assert!((red - 2.0).abs() < 0.05);
+
+    let data = ktx2(ktx2::Format::BC4_UNORM_BLOCK, 4, 4, &[vec![0; 8]]);
+    let mut texture = TextureData::decode(&data, ColorSpace::Linear).unwrap();
+    assert!(!texture.generate_mips());
+
+    assert!(TextureData::from_rgba8(2, 2, vec![0; 15], ColorSpace::Srgb).is_err());
+  }
+}
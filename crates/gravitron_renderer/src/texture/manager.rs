@@ -0,0 +1,281 @@
+use std::collections::{HashSet, VecDeque};
+
+use anyhow::Error;
+
+use crate::{
+  error::TextureError,
+  memory::{types::ImageId, MemoryManager},
+  pipeline::DescriptorManager,
+  renderer::{TextureHandle, DEFAULT_TEXTURE, TEXTURE_DESCRIPTOR},
+};
+
+use super::{atlas::TextureAtlas, slot::SlotAllocator, ColorSpace, SamplerDescriptor, TextureData};
+
+/// Owns the slots of the bindless texture array, a `TextureHandle` is the index into it.
+/// Unused slots point to the default texture
+pub struct TextureManager {
+  slots: SlotAllocator,
+  images: Vec<ImageId>,
+  render_targets: HashSet<TextureHandle>,
+  /// Slots whose descriptor element has to be written
+  changed: Vec<u32>,
+  /// Removed slots with the frame they were removed in, kept until no frame in flight uses them
+  removed: VecDeque<(u64, u32)>,
+  frame: u64,
+  frames_in_flight: u64,
+}
+
+impl TextureManager {
+  /// `textures` fill the first slots, the first one is the default texture
+  pub(crate) fn new(
+    textures: Vec<ImageId>,
+    render_targets: HashSet<TextureHandle>,
+    capacity: u32,
+    frames_in_flight: usize,
+  ) -> Self {
+    let capacity = capacity.max(textures.len() as u32);
+    let mut slots = SlotAllocator::new(capacity);
+    for _ in &textures {
+      slots.alloc();
+    }
+
+    let default = textures[DEFAULT_TEXTURE.0 as usize];
+    let mut images = textures;
+    images.resize(capacity as usize, default);
+
+    Self {
+      slots,
+      images,
+      render_targets,
+      changed: Vec::new(),
+      removed: VecDeque::new(),
+      frame: 0,
+      frames_in_flight: frames_in_flight as u64,
+    }
+  }
+
+  /// Decodes an encoded image, e.g. a png
+  pub fn add_texture(
+    &mut self,
+    memory_manager: &mut MemoryManager,
+    data: &[u8],
+    color_space: ColorSpace,
+    sampler: impl Into<SamplerDescriptor>,
+  ) -> Result<TextureHandle, Error> {
+    self.insert(memory_manager, |memory_manager| {
+      memory_manager.create_texture_image(sampler, color_space, data)
+    })
+  }
+
+  /// `pixels` holds `width * height` RGBA pixels
+  pub fn add_texture_rgba(
+    &mut self,
+    memory_manager: &mut MemoryManager,
+    width: u32,
+    height: u32,
+    pixels: &[u8],
+    color_space: ColorSpace,
+    sampler: impl Into<SamplerDescriptor>,
+  ) -> Result<TextureHandle, Error> {
+    self.insert(memory_manager, |memory_manager| {
+      memory_manager.create_texture_image_rgba(sampler, color_space, width, height, pixels)
+    })
+  }
+
+  pub fn add_texture_data(
+    &mut self,
+    memory_manager: &mut MemoryManager,
+    texture: TextureData,
+    sampler: impl Into<SamplerDescriptor>,
+  ) -> Result<TextureHandle, Error> {
+    self.insert(memory_manager, |memory_manager| {
+      memory_manager.create_texture(sampler, texture)
+    })
+  }
+
+  /// Atlases hold sRGB colors
+  #[inline]
+  pub fn add_atlas(
+    &mut self,
+    memory_manager: &mut MemoryManager,
+    atlas: &TextureAtlas,
+    sampler: impl Into<SamplerDescriptor>,
+  ) -> Result<TextureHandle, Error> {
+    self.add_texture_rgba(
+      memory_manager,
+      atlas.width(),
+      atlas.height(),
+      atlas.pixels(),
+      ColorSpace::Srgb,
+      sampler,
+    )
+  }
+
+  /// Uploads a new image for an added texture, the handle stays valid. Waits for the gpu, so
+  /// it is meant for reloading. Returns false for the default texture, render targets and free slots
+  pub fn replace_texture_data(
+    &mut self,
+    memory_manager: &mut MemoryManager,
+    handle: TextureHandle,
+    texture: TextureData,
+    sampler: impl Into<SamplerDescriptor>,
+  ) -> Result<bool, Error> {
+    if handle == DEFAULT_TEXTURE || self.render_targets.contains(&handle) || !self.contains(handle)
+    {
+      return Ok(false);
+    }
+
+    memory_manager.replace_texture_image(
+      self.images[handle.0 as usize],
+      &sampler.into(),
+      texture,
+    )?;
+    self.changed.push(handle.0);
+    Ok(true)
+  }
+
+  /// The default texture and render targets can not be removed. The image is freed once no
+  /// frame in flight can use it anymore, the handle may be reused by textures added after that
+  pub fn remove_texture(&mut self, handle: TextureHandle) -> bool {
+    if handle == DEFAULT_TEXTURE || self.render_targets.contains(&handle) || !self.contains(handle)
+    {
+      return false;
+    }
+
+    self.removed.push_back((self.frame, handle.0));
+    true
+  }
+
+  #[inline]
+  pub fn contains(&self, handle: TextureHandle) -> bool {
+    self.slots.is_allocated(handle.0) && !self.removed.iter().any(|(_, slot)| *slot == handle.0)
+  }
+
+  #[inline]
+  pub fn len(&self) -> u32 {
+    self.slots.len() - self.removed.len() as u32
+  }
+
+  #[inline]
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  #[inline]
+  pub fn capacity(&self) -> u32 {
+    self.slots.capacity()
+  }
+
+  /// One image per slot for the texture descriptor
+  #[inline]
+  pub(crate) fn images(&self) -> Vec<ImageId> {
+    self.images.clone()
+  }
+
+  /// Writes the changed elements of the texture descriptor and frees removed images
+  /// which are not used by a frame in flight anymore. `drawing` is whether a frame is recorded
+  pub(crate) fn update(
+    &mut self,
+    memory_manager: &mut MemoryManager,
+    descriptor_manager: &mut DescriptorManager,
+    drawing: bool,
+  ) -> Result<(), Error> {
+    let retired = self.retire(drawing);
+
+    for slot in self.changed.drain(..) {
+      descriptor_manager.replace_sampler(
+        TEXTURE_DESCRIPTOR,
+        slot as usize,
+        self.images[slot as usize],
+        memory_manager,
+      );
+    }
+    for image in retired {
+      memory_manager.remove_image(image)?;
+    }
+    Ok(())
+  }
+
+  /// Points removed slots to the default texture once every frame that could use them finished.
+  /// Their elements are written before the images are freed
+  fn retire(&mut self, drawing: bool) -> Vec<ImageId> {
+    // waiting for the start of a frame waits for the frame `frames_in_flight` earlier,
+    // the extra frame covers removals made before the wait
+    if drawing {
+      self.frame += 1;
+    }
+
+    let mut retired = Vec::new();
+    while let Some(&(frame, slot)) = self.removed.front() {
+      if self.frame - frame <= self.frames_in_flight {
+        break;
+      }
+      self.removed.pop_front();
+
+      let default = self.images[DEFAULT_TEXTURE.0 as usize];
+      retired.push(std::mem::replace(&mut self.images[slot as usize], default));
+      self.slots.free(slot);
+      self.changed.push(slot);
+    }
+    retired
+  }
+
+  fn insert(
+    &mut self,
+    memory_manager: &mut MemoryManager,
+    create: impl FnOnce(&mut MemoryManager) -> Result<ImageId, Error>,
+  ) -> Result<TextureHandle, Error> {
+    let slot = self
+      .slots
+      .alloc()
+      .ok_or(TextureError::NoFreeSlot(self.slots.capacity()))?;
+
+    match create(memory_manager) {
+      Ok(image) => {
+        self.images[slot as usize] = image;
+        self.changed.push(slot);
+        Ok(TextureHandle(slot))
+      }
+      Err(err) => {
+        self.slots.free(slot);
+        Err(err)
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::collections::HashSet;
+
+  use super::TextureManager;
+  use crate::{memory::types::ImageId, renderer::TextureHandle};
+
+  #[test]
+  fn removed_after_frames_in_flight() {
+    let mut manager = TextureManager::new(vec![ImageId::Sampler(0)], HashSet::new(), 4, 2);
+    let handle = TextureHandle(manager.slots.alloc().unwrap());
+    manager.images[handle.0 as usize] = ImageId::Sampler(1);
+
+    assert!(manager.remove_texture(handle));
+    assert!(!manager.contains(handle));
+    assert!(!manager.remove_texture(handle));
+    assert_eq!(manager.len(), 1);
+
+    assert!(manager.retire(true).is_empty());
+    assert!(manager.retire(true).is_empty());
+    // frames which are not drawn do not retire anything
+    assert!(manager.retire(false).is_empty());
+    assert_eq!(manager.retire(true), vec![ImageId::Sampler(1)]);
+    assert_eq!(manager.images[handle.0 as usize], ImageId::Sampler(0));
+    assert_eq!(manager.changed, vec![handle.0]);
+    assert_eq!(manager.slots.alloc(), Some(handle.0));
+  }
+
+  #[test]
+  fn default_not_removed() {
+    let mut manager = TextureManager::new(vec![ImageId::Sampler(0)], HashSet::new(), 4, 2);
+    assert!(!manager.remove_texture(TextureHandle(0)));
+    assert!(!manager.remove_texture(TextureHandle(1)));
+  }
+}
@@ -0,0 +1,207 @@
+/// Number of levels of a full mip chain down to 1x1
+#[inline]
+pub fn mip_levels(width: u32, height: u32) -> u32 {
+  32 - width.max(height).max(1).leading_zeros()
+}
+
+#[inline]
+pub fn mip_extent(width: u32, height: u32, level: u32) -> (u32, u32) {
+  (
+    width.checked_shr(level).unwrap_or(0).max(1),
+    height.checked_shr(level).unwrap_or(0).max(1),
+  )
+}
+
+/// Halves an sRGB RGBA image with a box filter, colors are averaged in linear space.
+/// Used if the device can not blit the texture format with linear filtering
+pub fn downsample_rgba(width: u32, height: u32, pixels: &[u8]) -> (u32, u32, Vec<u8>) {
+  box_filter(width, height, pixels, |samples| {
+    let mut result = [0; 4];
+    for channel in 0..3 {
+      let linear = samples
+        .iter()
+        .map(|sample| srgb_to_linear(sample[channel]))
+        .sum::<f32>()
+        / 4.0;
+      result[channel] = linear_to_srgb(linear);
+    }
+    result[3] = average_u8(samples, 3);
+    result
+  })
+}
+
+/// Halves a linear RGBA image with a box filter
+pub fn downsample_rgba_linear(width: u32, height: u32, pixels: &[u8]) -> (u32, u32, Vec<u8>) {
+  box_filter(width, height, pixels, |samples| {
+    std::array::from_fn(|channel| average_u8(samples, channel))
+  })
+}
+
+/// Halves a floating point RGBA image with a box filter
+pub fn downsample_rgba_f32(width: u32, height: u32, pixels: &[f32]) -> (u32, u32, Vec<f32>) {
+  box_filter(width, height, pixels, |samples| {
+    std::array::from_fn(|channel| samples.iter().map(|sample| sample[channel]).sum::<f32>() / 4.0)
+  })
+}
+
+/// All levels below the base level of an sRGB image
+#[inline]
+pub fn generate_mip_chain(width: u32, height: u32, pixels: &[u8]) -> Vec<Vec<u8>> {
+  mip_chain(width, height, pixels, downsample_rgba)
+}
+
+#[inline]
+pub fn generate_mip_chain_linear(width: u32, height: u32, pixels: &[u8]) -> Vec<Vec<u8>> {
+  mip_chain(width, height, pixels, downsample_rgba_linear)
+}
+
+#[inline]
+pub fn generate_mip_chain_f32(width: u32, height: u32, pixels: &[f32]) -> Vec<Vec<f32>> {
+  mip_chain(width, height, pixels, downsample_rgba_f32)
+}
+
+type Downsample<T> = fn(u32, u32, &[T]) -> (u32, u32, Vec<T>);
+
+fn mip_chain<T>(width: u32, height: u32, pixels: &[T], downsample: Downsample<T>) -> Vec<Vec<T>> {
+  let mut levels: Vec<Vec<T>> = Vec::new();
+  let (mut width, mut height) = (width, height);
+
+  for _ in 1..mip_levels(width, height) {
+    let previous = levels.last().map_or(pixels, |level| level.as_slice());
+    let (new_width, new_height, level) = downsample(width, height, previous);
+    levels.push(level);
+    width = new_width;
+    height = new_height;
+  }
+
+  levels
+}
+
+/// Averages 2x2 pixels into one, odd sizes clamp to the last row and column
+fn box_filter<T: Copy>(
+  width: u32,
+  height: u32,
+  pixels: &[T],
+  average: impl Fn([&[T]; 4]) -> [T; 4],
+) -> (u32, u32, Vec<T>) {
+  let (new_width, new_height) = mip_extent(width, height, 1);
+  let mut result = Vec::with_capacity(new_width as usize * new_height as usize * 4);
+
+  let pixel = |x: u32, y: u32| {
+    let start = (y.min(height - 1) as usize * width as usize + x.min(width - 1) as usize) * 4;
+    &pixels[start..start + 4]
+  };
+
+  for y in 0..new_height {
+    for x in 0..new_width {
+      result.extend(average([
+        pixel(x * 2, y * 2),
+        pixel(x * 2 + 1, y * 2),
+        pixel(x * 2, y * 2 + 1),
+        pixel(x * 2 + 1, y * 2 + 1),
+      ]));
+    }
+  }
+
+  (new_width, new_height, result)
+}
+
+fn average_u8(samples: [&[u8]; 4], channel: usize) -> u8 {
+  (samples
+    .iter()
+    .map(|sample| sample[channel] as f32)
+    .sum::<f32>()
+    / 4.0)
+    .round() as u8
+}
+
+pub(crate) fn srgb_to_linear(value: u8) -> f32 {
+  let value = value as f32 / 255.0;
+  if value <= 0.04045 {
+    value / 12.92
+  } else {
+    ((value + 0.055) / 1.055).powf(2.4)
+  }
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+  let value = if value <= 0.0031308 {
+    value * 12.92
+  } else {
+    1.055 * value.powf(1.0 / 2.4) - 0.055
+  };
+  (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+#[cfg(test)]
+mod test {
+  use super::{
+    downsample_rgba, downsample_rgba_f32, downsample_rgba_linear, generate_mip_chain, mip_extent,
+    mip_levels,
+  };
+
+  #[test]
+  fn mip_math() {
+    assert_eq!(mip_levels(1, 1), 1);
+    assert_eq!(mip_levels(0, 0), 1);
+    assert_eq!(mip_levels(2, 1), 2);
+    assert_eq!(mip_levels(256, 256), 9);
+    assert_eq!(mip_levels(300, 17), 9);
+    assert_eq!(mip_levels(1, 1024), 11);
+
+    assert_eq!(mip_extent(300, 17, 0), (300, 17));
+    assert_eq!(mip_extent(300, 17, 3), (37, 2));
+    assert_eq!(mip_extent(300, 17, 8), (1, 1));
+    assert_eq!(mip_extent(300, 17, 40), (1, 1));
+  }
+
+  #[test]
+  fn downsample() {
+    let solid = [200, 100, 50, 255].repeat(4);
+    assert_eq!(
+      downsample_rgba(2, 2, &solid),
+      (1, 1, vec![200, 100, 50, 255])
+    );
+
+    // black and white average to linear 0.5, not srgb 0.5
+    let checker = [
+      [0, 0, 0, 0],
+      [255, 255, 255, 255],
+      [255, 255, 255, 255],
+      [0, 0, 0, 0],
+    ]
+    .concat();
+    assert_eq!(downsample_rgba(2, 2, &checker).2, vec![188, 188, 188, 128]);
+    assert_eq!(
+      downsample_rgba_linear(2, 2, &checker).2,
+      vec![128, 128, 128, 128]
+    );
+    let floats = [[0.0, 1.0, 2.0, 1.0], [4.0, 1.0, 0.0, 1.0]]
+      .concat()
+      .repeat(2);
+    assert_eq!(
+      downsample_rgba_f32(2, 2, &floats).2,
+      vec![2.0, 1.0, 1.0, 1.0]
+    );
+
+    // odd sizes clamp to the last column
+    let row = [[10, 0, 0, 255], [10, 0, 0, 255], [250, 0, 0, 255]].concat();
+    let (width, height, pixels) = downsample_rgba(3, 1, &row);
+    assert_eq!((width, height), (1, 1));
+    assert_eq!(pixels, vec![10, 0, 0, 255]);
+  }
+
+  #[test]
+  fn mip_chain() {
+    let pixels = vec![255; 8 * 2 * 4];
+    let levels = generate_mip_chain(8, 2, &pixels);
+    let sizes = levels
+      .iter()
+      .map(|level| level.len() / 4)
+      .collect::<Vec<_>>();
+    assert_eq!(sizes, [4, 2, 1]);
+    assert!(levels.iter().flatten().all(|value| *value == 255));
+
+    assert!(generate_mip_chain(1, 1, &[0; 4]).is_empty());
+  }
+}
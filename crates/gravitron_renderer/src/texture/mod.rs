@@ -0,0 +1,14 @@
+pub mod asset;
+pub mod atlas;
+pub mod cubemap;
+pub mod loader;
+pub mod manager;
+pub mod mipmap;
+pub mod sampler;
+pub mod slot;
+
+pub use asset::{Texture, TextureLoader};
+pub use cubemap::Cubemap;
+pub use loader::{ColorSpace, TextureData};
+pub use manager::TextureManager;
+pub use sampler::SamplerDescriptor;
@@ -0,0 +1,171 @@
+use ash::vk;
+
+/// How a texture is sampled, a `vk::Filter` converts into a descriptor using it for all filters
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SamplerDescriptor {
+  pub address_mode_u: vk::SamplerAddressMode,
+  pub address_mode_v: vk::SamplerAddressMode,
+  pub address_mode_w: vk::SamplerAddressMode,
+  pub mag_filter: vk::Filter,
+  pub min_filter: vk::Filter,
+  pub mipmap_mode: vk::SamplerMipmapMode,
+  /// Clamped to the device limit, `None` disables anisotropic filtering
+  pub anisotropy: Option<f32>,
+  pub lod_bias: f32,
+  /// Generates the full mip chain on upload
+  pub mipmaps: bool,
+}
+
+impl SamplerDescriptor {
+  pub fn new(filter: vk::Filter) -> Self {
+    let mipmap_mode = if filter == vk::Filter::NEAREST {
+      vk::SamplerMipmapMode::NEAREST
+    } else {
+      vk::SamplerMipmapMode::LINEAR
+    };
+
+    Self {
+      address_mode_u: vk::SamplerAddressMode::REPEAT,
+      address_mode_v: vk::SamplerAddressMode::REPEAT,
+      address_mode_w: vk::SamplerAddressMode::REPEAT,
+      mag_filter: filter,
+      min_filter: filter,
+      mipmap_mode,
+      anisotropy: None,
+      lod_bias: 0.0,
+      mipmaps: true,
+    }
+  }
+
+  #[inline]
+  pub fn linear() -> Self {
+    Self::new(vk::Filter::LINEAR)
+  }
+
+  #[inline]
+  pub fn nearest() -> Self {
+    Self::new(vk::Filter::NEAREST)
+  }
+
+  /// Uses `mode` for all axes
+  #[inline]
+  pub fn with_address_mode(self, mode: vk::SamplerAddressMode) -> Self {
+    self.with_address_modes(mode, mode, mode)
+  }
+
+  #[inline]
+  pub fn with_address_modes(
+    mut self,
+    u: vk::SamplerAddressMode,
+    v: vk::SamplerAddressMode,
+    w: vk::SamplerAddressMode,
+  ) -> Self {
+    self.address_mode_u = u;
+    self.address_mode_v = v;
+    self.address_mode_w = w;
+    self
+  }
+
+  #[inline]
+  pub fn with_filters(
+    mut self,
+    mag_filter: vk::Filter,
+    min_filter: vk::Filter,
+    mipmap_mode: vk::SamplerMipmapMode,
+  ) -> Self {
+    self.mag_filter = mag_filter;
+    self.min_filter = min_filter;
+    self.mipmap_mode = mipmap_mode;
+    self
+  }
+
+  #[inline]
+  pub fn with_anisotropy(mut self, anisotropy: f32) -> Self {
+    self.anisotropy = Some(anisotropy);
+    self
+  }
+
+  #[inline]
+  pub fn with_lod_bias(mut self, lod_bias: f32) -> Self {
+    self.lod_bias = lod_bias;
+    self
+  }
+
+  #[inline]
+  pub fn without_mipmaps(mut self) -> Self {
+    self.mipmaps = false;
+    self
+  }
+
+  /// `max_anisotropy` is the device limit, 0 if anisotropic filtering is not supported
+  pub(crate) fn create_info(
+    &self,
+    mip_levels: u32,
+    max_anisotropy: f32,
+  ) -> vk::SamplerCreateInfo<'static> {
+    let anisotropy = self
+      .anisotropy
+      .map_or(1.0, |anisotropy| anisotropy.min(max_anisotropy));
+
+    vk::SamplerCreateInfo::default()
+      .address_mode_u(self.address_mode_u)
+      .address_mode_v(self.address_mode_v)
+      .address_mode_w(self.address_mode_w)
+      .mag_filter(self.mag_filter)
+      .min_filter(self.min_filter)
+      .mipmap_mode(self.mipmap_mode)
+      .anisotropy_enable(anisotropy > 1.0)
+      .max_anisotropy(anisotropy.max(1.0))
+      .mip_lod_bias(self.lod_bias)
+      .min_lod(0.0)
+      .max_lod(mip_levels as f32)
+  }
+}
+
+impl Default for SamplerDescriptor {
+  #[inline]
+  fn default() -> Self {
+    Self::linear()
+  }
+}
+
+impl From<vk::Filter> for SamplerDescriptor {
+  #[inline]
+  fn from(filter: vk::Filter) -> Self {
+    Self::new(filter)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use ash::vk;
+
+  use super::SamplerDescriptor;
+
+  #[test]
+  fn sampler_create_info() {
+    let descriptor = SamplerDescriptor::from(vk::Filter::NEAREST)
+      .with_address_mode(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+      .with_lod_bias(-0.5);
+    let info = descriptor.create_info(9, 16.0);
+    assert_eq!(info.min_filter, vk::Filter::NEAREST);
+    assert_eq!(info.mipmap_mode, vk::SamplerMipmapMode::NEAREST);
+    assert_eq!(info.address_mode_v, vk::SamplerAddressMode::CLAMP_TO_EDGE);
+    assert_eq!(info.anisotropy_enable, vk::FALSE);
+    assert_eq!(info.mip_lod_bias, -0.5);
+    assert_eq!(info.max_lod, 9.0);
+
+    let info = SamplerDescriptor::linear()
+      .with_anisotropy(32.0)
+      .create_info(1, 16.0);
+    assert_eq!(info.anisotropy_enable, vk::TRUE);
+    assert_eq!(info.max_anisotropy, 16.0);
+
+    // unsupported by the device
+    let info = SamplerDescriptor::linear()
+      .with_anisotropy(8.0)
+      .create_info(1, 0.0);
+    assert_eq!(info.anisotropy_enable, vk::FALSE);
+    assert_eq!(info.max_anisotropy, 1.0);
+  }
+}
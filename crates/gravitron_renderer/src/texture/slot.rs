@@ -0,0 +1,117 @@
+use std::collections::BTreeSet;
+
+/// Hands out indices below a fixed capacity, freed indices are reused lowest first
+#[derive(Debug, Clone)]
+pub struct SlotAllocator {
+  capacity: u32,
+  next: u32,
+  free: BTreeSet<u32>,
+}
+
+impl SlotAllocator {
+  #[inline]
+  pub fn new(capacity: u32) -> Self {
+    Self {
+      capacity,
+      next: 0,
+      free: BTreeSet::new(),
+    }
+  }
+
+  pub fn alloc(&mut self) -> Option<u32> {
+    if let Some(slot) = self.free.pop_first() {
+      return Some(slot);
+    }
+
+    if self.next < self.capacity {
+      self.next += 1;
+      Some(self.next - 1)
+    } else {
+      None
+    }
+  }
+
+  /// Returns false if the slot was not allocated
+  pub fn free(&mut self, slot: u32) -> bool {
+    if !self.is_allocated(slot) {
+      return false;
+    }
+
+    if slot + 1 == self.next {
+      // shrink instead of remembering trailing slots
+      self.next -= 1;
+      while self.next > 0 && self.free.remove(&(self.next - 1)) {
+        self.next -= 1;
+      }
+    } else {
+      self.free.insert(slot);
+    }
+    true
+  }
+
+  #[inline]
+  pub fn is_allocated(&self, slot: u32) -> bool {
+    slot < self.next && !self.free.contains(&slot)
+  }
+
+  #[inline]
+  pub fn len(&self) -> u32 {
+    self.next - self.free.len() as u32
+  }
+
+  #[inline]
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  #[inline]
+  pub fn capacity(&self) -> u32 {
+    self.capacity
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::SlotAllocator;
+
+  #[test]
+  fn reuse_freed_slots() {
+    let mut slots = SlotAllocator::new(4);
+    assert_eq!(slots.alloc(), Some(0));
+    assert_eq!(slots.alloc(), Some(1));
+    assert_eq!(slots.alloc(), Some(2));
+
+    assert!(slots.free(1));
+    assert!(!slots.free(1));
+    assert!(!slots.free(3));
+    assert!(!slots.is_allocated(1));
+    assert_eq!(slots.len(), 2);
+
+    assert_eq!(slots.alloc(), Some(1));
+    assert_eq!(slots.alloc(), Some(3));
+    assert_eq!(slots.alloc(), None);
+    assert_eq!(slots.len(), 4);
+  }
+
+  #[test]
+  fn lowest_slot_first() {
+    let mut slots = SlotAllocator::new(8);
+    for _ in 0..6 {
+      slots.alloc();
+    }
+    slots.free(4);
+    slots.free(1);
+    slots.free(5);
+
+    assert_eq!(slots.alloc(), Some(1));
+    assert_eq!(slots.alloc(), Some(4));
+    assert_eq!(slots.alloc(), Some(5));
+    assert_eq!(slots.alloc(), Some(6));
+
+    for slot in 0..7 {
+      assert!(slots.free(slot));
+    }
+    assert!(slots.is_empty());
+    assert_eq!(slots.alloc(), Some(0));
+  }
+}
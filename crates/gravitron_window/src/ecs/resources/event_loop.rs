@@ -4,7 +4,7 @@ use std::{
 };
 
 use gravitron_utils::thread::Signal;
-use winit::{event::WindowEvent, window::Window};
+use winit::{dpi::PhysicalSize, event::WindowEvent, window::Window};
 
 use crate::{config::WindowConfig, window::WindowHandler};
 
@@ -12,6 +12,7 @@ pub struct EventLoop {
   _thread: JoinHandle<()>,
   receiver: Receiver<WindowEvent>,
   events: Vec<WindowEvent>,
+  size: PhysicalSize<u32>,
   #[cfg(target_os = "linux")]
   wayland: bool,
 }
@@ -40,6 +41,7 @@ impl EventLoop {
     });
 
     let window = ready_signal_clone.wait();
+    let size = window.inner_size();
     #[cfg(target_os = "linux")]
     let wayland = wayland_signal_clone.wait();
 
@@ -48,6 +50,7 @@ impl EventLoop {
         _thread: thread,
         receiver,
         events: Vec::new(),
+        size,
         #[cfg(target_os = "linux")]
         wayland,
       },
@@ -58,6 +61,12 @@ impl EventLoop {
   #[inline]
   pub(crate) fn update_events(&mut self) {
     self.events = self.receiver.try_iter().collect();
+
+    for event in &self.events {
+      if let WindowEvent::Resized(size) = event {
+        self.size = *size;
+      }
+    }
   }
 
   #[inline]
@@ -65,6 +74,12 @@ impl EventLoop {
     &self.events
   }
 
+  /// The current inner size of the window in physical pixels
+  #[inline]
+  pub fn size(&self) -> PhysicalSize<u32> {
+    self.size
+  }
+
   #[cfg(target_os = "linux")]
   #[inline]
   pub fn wayland(&self) -> bool {
@@ -1,6 +1,7 @@
 use gravitron_components::ComponentPlugin;
 use gravitron_plugin::{
   app::{App, Running},
+  config::AppConfig,
   manager::PluginManager,
   Plugin,
 };
@@ -15,6 +16,7 @@ pub struct GravitronBuilder {
 pub struct Gravitron {
   plugin_manager: PluginManager,
   app: App<Running>,
+  initialized: bool,
 }
 
 impl Gravitron {
@@ -25,15 +27,45 @@ impl Gravitron {
 
   pub fn run(mut self) -> ! {
     info!("Running Gravitron");
-    self.app.run_init();
+    self.init();
     self.app.run_main();
 
+    self.shutdown();
+    std::process::exit(0);
+  }
+
+  /// Runs the given number of frames without waiting between them, e.g. to render headless.
+  /// Returns false if a shutdown was requested
+  pub fn run_frames(&mut self, frames: u32) -> bool {
+    self.init();
+
+    let fps = self.app.config::<AppConfig>().unwrap().fps;
+    (0..frames).all(|_| self.app.run_frame(1.0 / fps as f32))
+  }
+
+  #[inline]
+  pub fn get_resource<R: 'static>(&self) -> Option<&R> {
+    self.app.get_resource()
+  }
+
+  #[inline]
+  pub fn get_resource_mut<R: 'static>(&mut self) -> Option<&mut R> {
+    self.app.get_resource_mut()
+  }
+
+  /// Cleans up all plugins without exiting the process
+  pub fn shutdown(self) {
     info!("Cleaning up Gravitron");
     let mut app = self.app.run_cleanup();
 
     self.plugin_manager.cleanup(&mut app);
+  }
 
-    std::process::exit(0);
+  fn init(&mut self) {
+    if !self.initialized {
+      self.app.run_init();
+      self.initialized = true;
+    }
   }
 }
 
@@ -43,6 +75,11 @@ impl GravitronBuilder {
     Self::default()
   }
 
+  /// Leaves out the window plugin, `RendererConfig::enable_headless` has to be set by a plugin
+  pub fn headless() -> Self {
+    Self::with_window(false)
+  }
+
   #[inline]
   pub fn add_plugin(&mut self, plugin: impl Plugin) {
     self.plugin_manager.add_plugin(plugin);
@@ -55,22 +92,29 @@ impl GravitronBuilder {
     Gravitron {
       plugin_manager: self.plugin_manager,
       app,
+      initialized: false,
     }
   }
-}
 
-impl Default for GravitronBuilder {
-  fn default() -> Self {
-    env_logger::init();
+  fn with_window(window: bool) -> Self {
+    let _ = env_logger::try_init();
 
     info!("Creating PluginManager");
     let mut plugin_manager = PluginManager::new();
 
     info!("Adding default plugins");
     plugin_manager.add_plugin(ComponentPlugin);
-    plugin_manager.add_plugin(WindowPlugin);
+    if window {
+      plugin_manager.add_plugin(WindowPlugin);
+    }
     plugin_manager.add_plugin(RendererPlugin);
 
     Self { plugin_manager }
   }
 }
+
+impl Default for GravitronBuilder {
+  fn default() -> Self {
+    Self::with_window(true)
+  }
+}
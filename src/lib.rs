@@ -6,6 +6,7 @@ pub mod resources;
 pub use ecs::Id;
 
 pub use glam as math;
+pub use gravitron_renderer::image;
 pub use log;
 
 pub use gravitron_utils as utils;
@@ -4,7 +4,7 @@ use gravitron::{
   components::{
     camera::CameraBuilder,
     lighting::{DirectionalLight, PointLight, SpotLight},
-    renderer::MeshRenderer,
+    renderer::{MaterialOverride, MeshRenderer},
     transform::Transform,
   },
   ecs::{
@@ -23,6 +23,7 @@ use gravitron::{
     ComponentPlugin, Plugin, RendererConfig, RendererPlugin,
   },
   resources::{
+    asset::{AssetServer, Handle},
     engine_commands::EngineCommands,
     engine_info::EngineInfo,
     input::Input,
@@ -34,6 +35,7 @@ use gravitron::{
       include_glsl, DescriptorManager, PipelineManager,
     },
     renderer::{resources::material::Material, TextureHandle, DEFAULT_DESCRIPTOR_SET},
+    texture::ColorSpace,
   },
   window::winit::keyboard::KeyCode,
   Id,
@@ -54,6 +56,10 @@ pub struct Marker {
 #[derive(Component)]
 pub struct Center;
 
+/// Shared by all cubes spawned in `test`
+#[derive(Default)]
+pub struct CubeMaterial(Option<Handle<Material>>);
+
 struct Game;
 
 impl Plugin for Game {
@@ -66,6 +72,7 @@ impl Plugin for Game {
     builder.add_main_system(test4);
 
     builder.add_resource(Id::default());
+    builder.add_resource(CubeMaterial::default());
     builder.add_resource(false);
 
     let texture = builder
@@ -74,6 +81,7 @@ impl Plugin for Game {
       .graphics
       .add_texture(
         include_bytes!("../testing/image.png").to_vec(),
+        ColorSpace::Srgb,
         Filter::NEAREST,
       );
     builder.add_resource(texture);
@@ -84,15 +92,23 @@ impl Plugin for Game {
   }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn init(
   cmds: &mut Commands,
   mut id: ResMut<Id>,
   mut pipeline_manager: ResMut<PipelineManager>,
   mut descriptor_manager: ResMut<DescriptorManager>,
   mut memory_manager: ResMut<MemoryManager>,
+  mut asset_server: ResMut<AssetServer>,
+  mut cube_material: ResMut<CubeMaterial>,
+  texture: Res<TextureHandle>,
 ) {
   let image = memory_manager
-    .create_texture_image(Filter::NEAREST, include_bytes!("../testing/image.png"))
+    .create_texture_image(
+      Filter::NEAREST,
+      ColorSpace::Srgb,
+      include_bytes!("../testing/image.png"),
+    )
     .unwrap();
 
   let set = descriptor_manager
@@ -114,18 +130,24 @@ fn init(
     .build_graphics_pipeline(testing, descriptor_manager.deref())
     .unwrap();
 
+  cube_material.0 = Some(asset_server.add(Material {
+    texture_id: *texture,
+    ..Default::default()
+  }));
+
   let mut transform = Transform::default();
   transform.set_position(math::Vec3::new(5.0, 0.0, 0.0));
   cmds.create_entity((
     MeshRenderer {
-      model_id: CUBE_MODEL,
-      material: Material {
+      model_id: CUBE_MODEL.into(),
+      material: asset_server.add(Material {
         color: math::Vec4::new(1.0, 1.0, 0.0, 1.0),
         metallic: 1.0,
         roughness: 0.5,
         ..Default::default()
-      },
+      }),
     },
+    MaterialOverride::new().with_roughness(0.1),
     transform,
     Marker::default(),
   ));
@@ -133,23 +155,19 @@ fn init(
   transform.set_position(math::Vec3::new(0.0, 0.0, 0.0));
   *id = cmds.create_entity((
     MeshRenderer {
-      model_id: CUBE_MODEL,
-      material: Material {
+      model_id: CUBE_MODEL.into(),
+      material: asset_server.add(Material {
         shader: testing,
         ..Default::default()
-      },
+      }),
     },
     transform,
     Center,
   ));
 
-  let mut camera_transform = Transform::default();
-  camera_transform.set_rotation(
-    0.0,
-    std::f32::consts::FRAC_PI_4 * 3.0,
-    -std::f32::consts::FRAC_PI_4,
-  );
-  camera_transform.set_position(math::Vec3::new(10.0, 10.0, 10.0));
+  let camera_transform = Transform::default()
+    .with_position(math::Vec3::new(10.0, 10.0, 10.0))
+    .looking_at(math::Vec3::ZERO, math::Vec3::Y);
   cmds.create_entity((CameraBuilder::new().build(), camera_transform));
 
   let mut dl_t = Transform::default();
@@ -160,6 +178,7 @@ fn init(
       intensity: 1.0,
       ambient_color: glam::Vec3::new(1.0, 1.0, 1.0),
       ambient_intensity: 0.1,
+      cast_shadows: true,
     },
     dl_t,
     Marker::default(),
@@ -184,6 +203,7 @@ fn init(
       intensity: 1.0,
       range: 1.0,
       angle: 1.0,
+      cast_shadows: false,
     },
     t,
   ));
@@ -192,7 +212,7 @@ fn init(
 fn test(
   cmd: &mut Commands,
   info: Res<EngineInfo>,
-  texture: Res<TextureHandle>,
+  cube_material: Res<CubeMaterial>,
   q: Query<(&mut Transform, &mut Marker)>,
   id: Res<Id>,
 ) {
@@ -203,12 +223,12 @@ fn test(
     t.set_position(pos);
     m.t += 0.5 * info.delta_time();
   }
+  let Some(material) = cube_material.0.clone() else {
+    return;
+  };
   let renderer = MeshRenderer {
-    model_id: CUBE_MODEL,
-    material: Material {
-      texture_id: *texture,
-      ..Default::default()
-    },
+    model_id: CUBE_MODEL.into(),
+    material,
   };
   cmd.create_child(*id, (Transform::default(), Marker::default(), renderer));
 }
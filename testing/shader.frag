@@ -5,16 +5,17 @@ layout (set=1, binding=0) uniform sampler2D tex;
 layout (location = 0) out vec4 color_out;
 layout (location = 1) out vec4 normal_out;
 layout (location = 2) out vec4 pos_out;
+layout (location = 3) out vec4 emissive_out;
 
 layout (location = 0) in vec4 color_in;
 layout (location = 1) in vec3 normal;
 layout (location = 2) in vec2 uv;
 layout (location = 3) in vec3 world_pos;
-layout (location = 5) in float metallic;
-layout (location = 6) in float roughness;
+layout (location = 5) in vec4 material;
 
 void main() {
   color_out = vec4(10.0);
-  normal_out = vec4(normal, metallic);
-  pos_out = vec4(world_pos, roughness);
-}
+  normal_out = vec4(normal, material.x);
+  pos_out = vec4(world_pos, material.y);
+  emissive_out = vec4(0.0, 0.0, 0.0, 1.0);
+}
\ No newline at end of file